@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS bot_resources
+(
+    id UUID NOT NULL PRIMARY KEY,
+    bot varchar(64) NOT NULL,
+    server varchar(255) NOT NULL,
+    uri text NOT NULL
+);
+
+CREATE UNIQUE INDEX ON bot_resources (bot, server, uri);
@@ -0,0 +1,18 @@
+-- Add migration script here
+DO $$ BEGIN
+    CREATE TYPE mcp_type AS ENUM ('stream', 'sse');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+CREATE TABLE IF NOT EXISTS mcp_servers
+(
+    id UUID NOT NULL PRIMARY KEY,
+    name varchar(64) NOT NULL,
+    description text NOT NULL,
+    url text NOT NULL,
+    mcp_type mcp_type NOT NULL
+);
+CREATE UNIQUE INDEX ON mcp_servers (name);
+
+ALTER TABLE knowledge_bases ADD COLUMN description text;
+ALTER TABLE knowledge_bases ADD COLUMN num_results int NOT NULL DEFAULT 3;
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE mcp_servers ADD COLUMN include_tools text[] NOT NULL DEFAULT '{}';
+ALTER TABLE mcp_servers ADD COLUMN exclude_tools text[] NOT NULL DEFAULT '{}';
+ALTER TABLE mcp_servers ADD COLUMN tool_descriptions jsonb NOT NULL DEFAULT '{}';
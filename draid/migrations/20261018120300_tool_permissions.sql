@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS tool_permissions
+(
+    id UUID NOT NULL PRIMARY KEY,
+    tool varchar(255) NOT NULL,
+    role role_type,
+    username_id UUID references users(id) ON DELETE CASCADE,
+    CHECK (role IS NOT NULL OR username_id IS NOT NULL)
+);
+CREATE INDEX ON tool_permissions (tool);
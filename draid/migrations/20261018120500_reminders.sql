@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS reminders
+(
+    id UUID NOT NULL PRIMARY KEY,
+    username_id UUID NOT NULL references users(id) ON DELETE CASCADE,
+    message TEXT NOT NULL,
+    -- first occurrence, recurrences are expanded from here in the reminder's timezone
+    starts_at TIMESTAMPTZ NOT NULL,
+    due_at TIMESTAMPTZ NOT NULL,
+    rrule TEXT,
+    timezone TEXT NOT NULL,
+    fired_at TIMESTAMPTZ,
+    done BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX reminders_user_index ON reminders(username_id);
+CREATE INDEX reminders_due_index ON reminders(due_at) WHERE NOT done;
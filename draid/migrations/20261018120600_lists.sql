@@ -0,0 +1,20 @@
+-- Add migration script here
+-- lists are shared by everyone in the household, ie every user of this instance
+CREATE TABLE IF NOT EXISTS lists
+(
+    id UUID NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE TABLE IF NOT EXISTS list_items
+(
+    id UUID NOT NULL PRIMARY KEY,
+    list_id UUID NOT NULL references lists(id),
+    name TEXT NOT NULL,
+    quantity TEXT,
+    checked BOOLEAN NOT NULL DEFAULT FALSE,
+    added_by UUID references users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX list_items_list_index ON list_items(list_id);
@@ -0,0 +1,13 @@
+-- Add migration script here
+-- shared by the household like lists
+CREATE TABLE IF NOT EXISTS pantry_items
+(
+    id UUID NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL,
+    quantity DOUBLE PRECISION NOT NULL,
+    unit TEXT,
+    location TEXT,
+    expires_on DATE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX pantry_items_expiry_index ON pantry_items(expires_on);
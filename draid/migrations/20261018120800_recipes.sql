@@ -0,0 +1,26 @@
+-- Add migration script here
+-- structured recipes, shared by the household. the recipe text stays in the recipes knowledge base
+CREATE TABLE IF NOT EXISTS recipes
+(
+    id UUID NOT NULL PRIMARY KEY,
+    title TEXT NOT NULL,
+    servings INTEGER,
+    steps TEXT[] NOT NULL DEFAULT '{}',
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    -- hash of the ingested document or the url of an imported page
+    source TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX recipes_source_index ON recipes(source);
+CREATE TABLE IF NOT EXISTS recipe_ingredients
+(
+    id UUID NOT NULL PRIMARY KEY,
+    recipe_id UUID NOT NULL references recipes(id),
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    quantity DOUBLE PRECISION,
+    unit TEXT,
+    -- eg "finely chopped"
+    note TEXT
+);
+CREATE INDEX recipe_ingredients_recipe_index ON recipe_ingredients(recipe_id);
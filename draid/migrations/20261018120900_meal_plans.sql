@@ -0,0 +1,25 @@
+-- Add migration script here
+-- preparation and cooking time, so plans can keep to a time budget
+ALTER TABLE recipes ADD COLUMN IF NOT EXISTS total_minutes INTEGER;
+-- one plan per week for the household, weeks start on monday
+CREATE TABLE IF NOT EXISTS meal_plans
+(
+    id UUID NOT NULL PRIMARY KEY,
+    week_start DATE NOT NULL UNIQUE,
+    dinners INTEGER NOT NULL,
+    restrictions TEXT[] NOT NULL DEFAULT '{}',
+    max_minutes INTEGER,
+    servings INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE TABLE IF NOT EXISTS meal_plan_entries
+(
+    id UUID NOT NULL PRIMARY KEY,
+    plan_id UUID NOT NULL references meal_plans(id),
+    day DATE NOT NULL,
+    -- NULL for meals that aren't in the recipe box, eg "leftovers"
+    recipe_id UUID references recipes(id),
+    title TEXT NOT NULL,
+    servings INTEGER
+);
+CREATE INDEX meal_plan_entries_plan_index ON meal_plan_entries(plan_id);
@@ -0,0 +1,31 @@
+-- Add migration script here
+-- chores are shared by the household and rotate between the users assigned to them
+CREATE TABLE IF NOT EXISTS chores
+(
+    id UUID NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    -- done once every this many days, eg 1 for daily and 7 for weekly
+    frequency_days INTEGER NOT NULL,
+    -- effort, earned by whoever does the chore
+    points INTEGER NOT NULL DEFAULT 1,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE TABLE IF NOT EXISTS chore_members
+(
+    chore_id UUID NOT NULL references chores(id),
+    username_id UUID NOT NULL references users(id) ON DELETE CASCADE,
+    -- order of the rotation
+    position INTEGER NOT NULL,
+    PRIMARY KEY (chore_id, username_id)
+);
+CREATE TABLE IF NOT EXISTS chore_completions
+(
+    id UUID NOT NULL PRIMARY KEY,
+    chore_id UUID NOT NULL references chores(id),
+    username_id UUID NOT NULL references users(id) ON DELETE CASCADE,
+    -- points when it was done, so changing a chore's effort doesn't rewrite history
+    points INTEGER NOT NULL,
+    completed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX chore_completions_chore_index ON chore_completions(chore_id, completed_at);
+CREATE INDEX chore_completions_user_index ON chore_completions(username_id, completed_at);
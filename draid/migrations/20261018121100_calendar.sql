@@ -0,0 +1,38 @@
+-- Add migration script here
+-- the household calendar, shared by every user
+CREATE TABLE IF NOT EXISTS events
+(
+    id UUID NOT NULL PRIMARY KEY,
+    -- iCalendar UID, kept so that importing a calendar again updates its events
+    uid TEXT NOT NULL UNIQUE,
+    title TEXT NOT NULL,
+    description TEXT,
+    location TEXT,
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    all_day BOOLEAN NOT NULL DEFAULT FALSE,
+    rrule TEXT,
+    -- occurrences of a recurring event that were cancelled
+    exdates TIMESTAMPTZ[] NOT NULL DEFAULT '{}',
+    -- recurrences are expanded in this timezone
+    timezone TEXT NOT NULL,
+    created_by UUID references users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX events_starts_at_index ON events(starts_at);
+-- events without attendees are for the whole household
+CREATE TABLE IF NOT EXISTS event_attendees
+(
+    event_id UUID NOT NULL references events(id),
+    username_id UUID NOT NULL references users(id) ON DELETE CASCADE,
+    PRIMARY KEY (event_id, username_id)
+);
+CREATE INDEX event_attendees_user_index ON event_attendees(username_id);
+-- secret tokens in the per user .ics feed urls, calendar apps can't log in
+CREATE TABLE IF NOT EXISTS calendar_feeds
+(
+    username_id UUID NOT NULL PRIMARY KEY references users(id) ON DELETE CASCADE,
+    token TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
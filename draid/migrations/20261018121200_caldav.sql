@@ -0,0 +1,29 @@
+-- Add migration script here
+-- a user's calendar on a CalDAV server, eg Radicale or Nextcloud, synced with the household calendar
+CREATE TABLE IF NOT EXISTS caldav_accounts
+(
+    id UUID NOT NULL PRIMARY KEY,
+    username_id UUID NOT NULL UNIQUE references users(id) ON DELETE CASCADE,
+    -- the calendar collection, eg https://cloud.example.com/remote.php/dav/calendars/sam/personal/
+    url TEXT NOT NULL,
+    username TEXT NOT NULL,
+    -- sent with basic auth so it can't be hashed, an app password is best
+    password TEXT NOT NULL,
+    -- events found on a shared calendar are for the whole household, on a personal one for its owner
+    shared BOOLEAN NOT NULL DEFAULT FALSE,
+    last_synced_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+-- where each event is stored on the server and its ETag when it was last synced
+CREATE TABLE IF NOT EXISTS caldav_links
+(
+    account_id UUID NOT NULL references caldav_accounts(id) ON DELETE CASCADE,
+    href TEXT NOT NULL,
+    -- cleared when the event is deleted here, so it gets deleted on the server too
+    event_id UUID references events(id) ON DELETE SET NULL,
+    etag TEXT,
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (account_id, href)
+);
+CREATE INDEX caldav_links_event_index ON caldav_links(event_id);
@@ -0,0 +1,14 @@
+-- Add migration script here
+-- keys for the OpenAI compatible api, so other tools can talk to the bots as a user
+CREATE TABLE IF NOT EXISTS api_keys
+(
+    id UUID NOT NULL PRIMARY KEY,
+    username_id UUID NOT NULL references users(id) ON DELETE CASCADE,
+    -- what the key is for, eg open webui
+    name TEXT NOT NULL,
+    -- sha256 of the key, the key itself is only shown when it's created
+    key_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ
+);
+CREATE INDEX api_keys_username_index ON api_keys(username_id);
@@ -1,75 +1,182 @@
-use futures::{FutureExt, SinkExt, StreamExt, future::BoxFuture};
+use futures::channel::mpsc;
+use futures::{FutureExt, SinkExt, StreamExt, future::BoxFuture, stream::BoxStream};
 use poem::{
     Error, IntoResponse, Result, handler,
     http::StatusCode,
     web::websocket::{Message, WebSocket, WebSocketStream},
     web::{Data, Form, Multipart, Path, Query as WsQuery},
 };
-use poem_openapi::{OpenApi, payload::Json};
+use poem_openapi::{
+    OpenApi,
+    param::Query,
+    payload::{Binary, EventStream, Json, PlainText},
+};
 use sqlx::PgPool;
 use std::sync::Arc;
-use tracing::{Instrument, Level, info, span};
 use uuid::Uuid;
 
+use crate::audio::{
+    AudioClient, MAX_RECORDING_BYTES, Utterance, VoiceEvent, send_event, voice_turn,
+};
 use crate::auth::{UserIdentification, create_token};
+use crate::caldav::{CalDavClient, SyncSummary, sync};
+use crate::calendar::{
+    Occurrence, UPCOMING_DAYS, create_event, import_calendar, new_feed_token, upcoming, user_feed,
+    validate_event,
+};
+use crate::chat::{chat_response, chat_turn};
+use crate::chat_protocol::{AnswerStreams, chat_session, uses_envelopes};
+use crate::chores::{ChoreStatus, MemberScore, chore_statuses, scores, validate_chore};
+use crate::config::{KB, MCP};
 use crate::dbtracing::{HistogramIncrement, SpanToolUse, get_histogram, get_tool_use};
 use crate::embedding::{EmbeddingClient, get_embeddings, ingest_content};
-use crate::llm::{Bot, chat_with_tools};
+use crate::lists::{ItemChange, add_item, find_list, list_with_items};
+use crate::llm::{Bot, DiscardTokens, TokenSink, WebSocketToken};
+use crate::mcp_tools::McpServer;
+use crate::mcp_tools::McpServers;
+use crate::meal_plans::{ShoppingItem, plan_meals, planned_meal, shopping_list, week_start};
 use crate::models::{
-    AuthRequest, AuthResponse, Bots, LLMError, MessageResponse, NoData, PromptKb, ResponseStatus,
-    SessionQuery, SessionResponse, StatusResponse, SuccessResponse, UploadResponse, UsersResponse,
+    AudioResponse, AuthRequest, AuthResponse, Bots, CalendarFeed, CalendarResponse, ChatError,
+    ChatEvent, ChatQuery, ChatRequest, ChatResponse, LLMError, McpPrompt, McpResource,
+    MessageResponse, NewApiKey, NoData, PromptKb, QuickActionRequest, QuickActionResponse,
+    ResponseStatus, SessionQuery, SessionResponse, SpeakRequest, StatusResponse, SuccessResponse,
+    ToolDescription, TranscriptionResponse, UploadResponse, UsersResponse,
+};
+use crate::notifications::{Notification, Notifications};
+use crate::openai::{api_key_hash, new_api_key};
+use crate::pantry::{EXPIRING_SOON_DAYS, RECIPES_KB, stock_item};
+use crate::psql_api_keys::{ApiKey, ApiKeyRequest, create_api_key, delete_api_key, get_api_keys};
+use crate::psql_caldav::{
+    CalDavAccount, CalDavAccountRequest, delete_caldav_account, get_caldav_account,
+    get_user_caldav_credentials, set_caldav_account,
+};
+use crate::psql_calendar::{
+    Event, EventRequest, EventUpdate, delete_event, get_event, get_feed_user, set_feed_token,
+    update_event,
+};
+use crate::psql_chores::{
+    Chore, ChoreCompletion, ChoreCompletionRequest, ChoreRequest, ChoreUpdate, create_chore,
+    create_chore_completion, delete_chore, delete_chore_completion, get_chore, get_chores,
+    update_chore,
+};
+use crate::psql_lists::{
+    List, ListItem, ListItemRequest, ListItemUpdate, ListRequest, ListWithItems, create_list,
+    delete_list, delete_list_item, get_list, get_lists, rename_list, update_list_item,
+};
+use crate::psql_mcp::{
+    BotResource, BotResourceRequest, McpServerDB, attach_bot_resource, create_mcp_server,
+    delete_mcp_server, detach_bot_resource, get_bot_resources, get_mcp_servers, update_mcp_server,
+};
+use crate::psql_meal_plans::{
+    MealPlan, MealPlanEntry, MealPlanEntryRequest, MealPlanEntryUpdate, MealPlanRequest,
+    create_meal_plan_entry, delete_meal_plan, delete_meal_plan_entry, get_meal_plan,
+    get_meal_plan_for_week, update_meal_plan_entry,
+};
+use crate::psql_memory::PsqlMemory;
+use crate::psql_pantry::{
+    PantryItem, PantryItemRequest, delete_pantry_item, get_expiring_pantry_items, get_pantry_items,
+};
+use crate::psql_recipes::{
+    Recipe, RecipeRequest, RecipeSummary, create_recipe, delete_recipe, find_recipe_by_source,
+    get_recipe, get_recipes,
+};
+use crate::psql_reminders::{
+    Reminder, ReminderRequest, create_reminder, delete_reminder, get_reminders,
+};
+use crate::psql_tools::{
+    ToolPermission, ToolPermissionRequest, create_tool_permission, delete_tool_permission,
+    get_tool_permissions,
 };
-use crate::psql_memory::{PsqlMemory, write_ai_message, write_human_message};
 use crate::psql_users;
 use crate::psql_users::Role;
 use crate::psql_vectors::{
-    KnowledgeBase, get_docs_with_similar_content, get_knowledge_base, get_knowledge_bases,
+    KnowledgeBase, KnowledgeBaseUpdate, delete_knowledge_base, get_docs_with_similar_content,
+    get_knowledge_base, get_knowledge_bases, update_knowledge_base, write_knowledge_base,
 };
-use poem::error::InternalServerError;
+use crate::recipes::{ingest_recipe, recipes_from_html};
+use crate::reminders::validate_rrule;
+use crate::secrets::Secrets;
+use crate::tool_manager::ToolManager;
+use poem::error::{BadRequest, InternalServerError};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
 
+//clients speak the envelope protocol, see chat_protocol, unless they ask for
+//the original one with v=1. it takes a bare prompt and closes the socket after
+//each answer
 fn handle_chat_session(
     bot_ref: &Arc<Bot>,
     session_id: Uuid,
-    user_id: Uuid,
+    envelopes: bool,
+    user: &UserIdentification,
     pool: &PgPool,
+    servers: &Arc<McpServers>,
+    answers: &Arc<AnswerStreams>,
 ) -> impl Fn(WebSocketStream) -> BoxFuture<'static, Result<()>> + Send + Sync + 'static {
     let bot = bot_ref.clone(); //its weird I need so many clones...but they are cheap (on Arcs)
     let pool = pool.clone();
+    let servers = servers.clone();
+    let user = user.clone();
+    let answers = answers.clone();
     move |mut socket: WebSocketStream| -> BoxFuture<'static, Result<()>> {
         let bot = bot.clone();
         let pool = pool.clone();
+        let servers = servers.clone();
+        let user = user.clone();
+        let answers = answers.clone();
         async move {
+            if envelopes {
+                let (outgoing, incoming) = socket.split();
+                let user_id = user.id;
+                //the answers carry on in the background, so they get their own clones
+                return chat_session(
+                    incoming,
+                    outgoing,
+                    &answers,
+                    user_id,
+                    |prompt, mut tokens| {
+                        let bot = bot.clone();
+                        let pool = pool.clone();
+                        let servers = servers.clone();
+                        let user = user.clone();
+                        async move {
+                            chat_turn(
+                                &bot,
+                                &mut tokens,
+                                &prompt,
+                                session_id,
+                                &user,
+                                &pool,
+                                &servers,
+                            )
+                            .await
+                        }
+                    },
+                )
+                .await
+                .map_err(|e| InternalServerError(LLMError { msg: e.to_string() }));
+            }
             while let Some(Ok(Message::Text(prompt))) = &mut socket.next().await {
                 // recreate each request.  This is as "performant" as
                 // standard rest request was in previous approach
-                let psql_memory = PsqlMemory::new(100, session_id, user_id, pool.clone());
-                let messages = psql_memory.messages().await.map_err(InternalServerError)?;
-                write_human_message(prompt.clone(), &psql_memory)
-                    .await
-                    .map_err(InternalServerError)?;
-                let span_id = Uuid::new_v4().to_string();
-                //chat_with_tools produces each token in the stream to the websocket
-                let full_message = chat_with_tools(&bot, &mut socket, &messages, &prompt, &span_id)
-                    .instrument(span!(
-                        Level::INFO,
-                        "chat_with_tools",
-                        endpoint = "query",
-                        tool_use = false
-                    ))
-                    .await
-                    .map_err(|e| {
-                        let e_str = e.to_string();
-                        info!(
-                            tool_use = false,
-                            endpoint = "query",
-                            span_id,
-                            message = &e_str
-                        );
-                        InternalServerError(LLMError { msg: e_str })
-                    })?;
-                write_ai_message(full_message.message, full_message.reasoning, &psql_memory)
-                    .await
-                    .map_err(InternalServerError)?;
+                let mut tokens = LeavableSocket {
+                    socket: &mut socket,
+                    gone: false,
+                };
+                chat_turn(
+                    &bot,
+                    &mut tokens,
+                    prompt,
+                    session_id,
+                    &user,
+                    &pool,
+                    &servers,
+                )
+                .await
+                .map_err(|e| InternalServerError(LLMError { msg: e.to_string() }))?;
+                if tokens.gone {
+                    break;
+                }
                 socket
                     .send(Message::Close(None))
                     .await
@@ -81,42 +188,238 @@ fn handle_chat_session(
     }
 }
 
+//sends the tokens while the client is there. if it goes away the answer is
+//still finished and saved
+struct LeavableSocket<'a> {
+    socket: &'a mut WebSocketStream,
+    gone: bool,
+}
+
+#[async_trait::async_trait]
+impl TokenSink for LeavableSocket<'_> {
+    async fn send_token(&mut self, token: WebSocketToken) -> anyhow::Result<()> {
+        if !self.gone && self.socket.send_token(token).await.is_err() {
+            self.gone = true;
+        }
+        Ok(())
+    }
+}
+
+//the bot if the user has its role, like the websockets check
+fn permitted_bot(bots: &Bots, name: &str, user: &UserIdentification) -> Result<Arc<Bot>> {
+    let bot = bots
+        .get(name)
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+    if !bots
+        .permitted(&user.roles)
+        .into_iter()
+        .any(|permitted| Arc::ptr_eq(permitted, bot))
+    {
+        return Err(Error::from_status(StatusCode::FORBIDDEN));
+    }
+    Ok(bot.clone())
+}
+
+//sends each token as an event as it's generated. if the client has gone the
+//answer is still finished and saved
+struct ChatEventSink(mpsc::UnboundedSender<ChatEvent>);
+
+#[async_trait::async_trait]
+impl TokenSink for ChatEventSink {
+    async fn send_token(&mut self, token: WebSocketToken) -> anyhow::Result<()> {
+        let _ = self.0.unbounded_send(ChatEvent::Token(token));
+        Ok(())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 #[poem_grants::protect("Role::Tutor", ty = "crate::psql_users::Role")]
 #[handler]
 pub async fn tutor_ws_handler(
-    WsQuery(SessionQuery { session_id }): WsQuery<SessionQuery>,
+    WsQuery(ChatQuery { session_id, v }): WsQuery<ChatQuery>,
     Data(bot): Data<&Arc<Bots>>,
     Data(pool): Data<&PgPool>,
+    Data(servers): Data<&Arc<McpServers>>,
+    Data(answers): Data<&Arc<AnswerStreams>>,
     Data(user): Data<&UserIdentification>, //attached from auth middleware
     ws: WebSocket,
 ) -> Result<poem::Response> {
     let ws_upgrade = ws.on_upgrade(handle_chat_session(
         &bot.tutor_bot,
         session_id,
-        user.id,
+        uses_envelopes(v).map_err(|msg| BadRequest(NoData { msg }))?,
+        user,
         pool,
+        servers,
+        answers,
     ));
     Ok(ws_upgrade.into_response())
 }
 
+#[allow(clippy::too_many_arguments)]
 #[poem_grants::protect("Role::Helper", ty = "crate::psql_users::Role")]
 #[handler]
 pub async fn helper_ws_handler(
-    WsQuery(SessionQuery { session_id }): WsQuery<SessionQuery>,
+    WsQuery(ChatQuery { session_id, v }): WsQuery<ChatQuery>,
     Data(bot): Data<&Arc<Bots>>,
     Data(pool): Data<&PgPool>,
+    Data(servers): Data<&Arc<McpServers>>,
+    Data(answers): Data<&Arc<AnswerStreams>>,
     Data(user): Data<&UserIdentification>, //attached from auth middleware
     ws: WebSocket,
 ) -> Result<poem::Response> {
     let ws_upgrade = ws.on_upgrade(handle_chat_session(
         &bot.helper_bot,
         session_id,
-        user.id,
+        uses_envelopes(v).map_err(|msg| BadRequest(NoData { msg }))?,
+        user,
+        pool,
+        servers,
+        answers,
+    ));
+    Ok(ws_upgrade.into_response())
+}
+
+//the client sends the audio of an utterance as binary messages, then a text
+//message to say it's complete. the answer comes back as tokens, with the audio
+//of each sentence as a binary message as soon as it's synthesized
+fn handle_voice_session(
+    bot_ref: &Arc<Bot>,
+    session_id: Uuid,
+    user: &UserIdentification,
+    pool: &PgPool,
+    servers: &Arc<McpServers>,
+    audio: &Arc<AudioClient>,
+) -> impl Fn(WebSocketStream) -> BoxFuture<'static, Result<()>> + Send + Sync + 'static {
+    let bot = bot_ref.clone();
+    let pool = pool.clone();
+    let servers = servers.clone();
+    let user = user.clone();
+    let audio = audio.clone();
+    move |socket: WebSocketStream| -> BoxFuture<'static, Result<()>> {
+        let bot = bot.clone();
+        let pool = pool.clone();
+        let servers = servers.clone();
+        let user = user.clone();
+        let audio = audio.clone();
+        async move {
+            let (sink, mut stream) = socket.split();
+            //tokens and speech are sent from different tasks
+            let sink = Arc::new(tokio::sync::Mutex::new(sink));
+            let mut recording = Vec::new();
+            //an utterance over the limit is dropped once it's complete
+            let mut too_long = false;
+            while let Some(Ok(message)) = stream.next().await {
+                match message {
+                    Message::Binary(chunk) => {
+                        if too_long || recording.len() + chunk.len() > MAX_RECORDING_BYTES {
+                            too_long = true;
+                            recording = Vec::new();
+                        } else {
+                            recording.extend(chunk);
+                        }
+                    }
+                    Message::Text(text) => {
+                        let recording = std::mem::take(&mut recording);
+                        let utterance = if std::mem::take(&mut too_long) {
+                            Err(format!(
+                                "The recording is over the limit of {} bytes",
+                                MAX_RECORDING_BYTES
+                            ))
+                        } else {
+                            serde_json::from_str::<Utterance>(&text)
+                                .map_err(|e| format!("Invalid utterance: {}", e))
+                        };
+                        let utterance = match utterance {
+                            Ok(utterance) => utterance,
+                            Err(message) => {
+                                send_event(&sink, &VoiceEvent::Error { message })
+                                    .await
+                                    .map_err(|e| {
+                                        InternalServerError(LLMError { msg: e.to_string() })
+                                    })?;
+                                continue;
+                            }
+                        };
+                        voice_turn(
+                            &bot, &audio, recording, utterance, &sink, session_id, &user, &pool,
+                            &servers,
+                        )
+                        .await
+                        .map_err(|e| InternalServerError(LLMError { msg: e.to_string() }))?;
+                    }
+                    Message::Close(_) => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[poem_grants::protect("Role::Helper", ty = "crate::psql_users::Role")]
+#[handler]
+pub async fn voice_ws_handler(
+    WsQuery(SessionQuery { session_id }): WsQuery<SessionQuery>,
+    Data(bot): Data<&Arc<Bots>>,
+    Data(pool): Data<&PgPool>,
+    Data(servers): Data<&Arc<McpServers>>,
+    Data(audio): Data<&Arc<AudioClient>>,
+    Data(user): Data<&UserIdentification>, //attached from auth middleware
+    ws: WebSocket,
+) -> Result<poem::Response> {
+    let ws_upgrade = ws.on_upgrade(handle_voice_session(
+        &bot.helper_bot,
+        session_id,
+        user,
         pool,
+        servers,
+        audio,
     ));
     Ok(ws_upgrade.into_response())
 }
 
+//pushes reminders and other events for the user until the client disconnects
+#[poem_grants::protect(
+    any("Role::Admin", "Role::Tutor", "Role::Helper"),
+    ty = "crate::psql_users::Role"
+)]
+#[handler]
+pub async fn notifications_ws_handler(
+    Data(notifications): Data<&Arc<Notifications>>,
+    Data(user): Data<&UserIdentification>, //attached from auth middleware
+    ws: WebSocket,
+) -> Result<poem::Response> {
+    let mut receiver = notifications.subscribe(user.id);
+    let ws_upgrade = ws.on_upgrade(move |mut socket| async move {
+        loop {
+            tokio::select! {
+                notification = receiver.recv() => {
+                    let notification = match notification {
+                        Ok(notification) => notification,
+                        //a slow client missed some, keep going with the newest
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    };
+                    let Ok(text) = serde_json::to_string(&notification) else {
+                        continue;
+                    };
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                message = socket.next() => match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                }
+            }
+        }
+    });
+    Ok(ws_upgrade.into_response())
+}
+
 async fn similar_content(
     kb_id: i64,
     prompt: Json<PromptKb>,
@@ -132,6 +435,62 @@ async fn similar_content(
     Ok(Json(result))
 }
 
+async fn refresh_permissions(tool_manager: &ToolManager) -> Result<()> {
+    tool_manager
+        .refresh_permissions()
+        .await
+        .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))
+}
+
+async fn refresh_tools(tool_manager: &ToolManager) -> Result<()> {
+    tool_manager
+        .refresh()
+        .await
+        .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))
+}
+
+fn validate_timezone(user: &psql_users::UserRequest) -> Result<()> {
+    if let Some(timezone) = &user.timezone {
+        timezone.parse::<chrono_tz::Tz>().map_err(|e| {
+            BadRequest(NoData {
+                msg: format!("Invalid timezone: {}", e),
+            })
+        })?;
+    }
+    Ok(())
+}
+
+fn not_found(e: sqlx::Error) -> Error {
+    match e {
+        sqlx::Error::RowNotFound => Error::from_status(StatusCode::NOT_FOUND),
+        e => InternalServerError(e),
+    }
+}
+
+//a name or link that already exists is the client's mistake, not the server's
+fn conflict(e: sqlx::Error) -> Error {
+    match e.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => {
+            Error::from_status(StatusCode::CONFLICT)
+        }
+        _ => not_found(e),
+    }
+}
+
+//lets the rest of the household see the change straight away
+fn list_changed(notifications: &Notifications, list_id: &Uuid, user: &UserIdentification) {
+    notifications.broadcast(Notification::ListChanged {
+        list_id: *list_id,
+        changed_by: user.id,
+    });
+}
+
+fn bot_exists(bots: &Bots, bot: &str) -> Result<()> {
+    bots.get(bot)
+        .map(|_| ())
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))
+}
+
 pub struct Api;
 #[poem_grants::open_api]
 #[OpenApi]
@@ -143,6 +502,7 @@ impl Api {
         user: Json<psql_users::UserRequest>,
         Data(pool): Data<&PgPool>,
     ) -> Result<SuccessResponse> {
+        validate_timezone(&user)?;
         psql_users::create_user(&user, pool)
             .await
             .map_err(InternalServerError)?;
@@ -173,6 +533,7 @@ impl Api {
         user: Json<psql_users::UserRequest>,
         Data(pool): Data<&PgPool>,
     ) -> Result<SuccessResponse> {
+        validate_timezone(&user)?;
         psql_users::patch_user(&id, &user, pool)
             .await
             .map_err(InternalServerError)?;
@@ -358,6 +719,61 @@ impl Api {
         ))
     }
 
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/knowledge_base", method = "post")]
+    async fn create_kb(
+        &self,
+        kb: Json<KB>,
+        Data(pool): Data<&PgPool>,
+        Data(tool_manager): Data<&Arc<ToolManager>>,
+    ) -> Result<Json<KnowledgeBase>> {
+        write_knowledge_base(&kb, pool).await.map_err(conflict)?;
+        refresh_tools(tool_manager).await?;
+        let kb = get_knowledge_base(&kb.name, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(kb))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/knowledge_base/:kb", method = "patch")]
+    async fn update_kb(
+        &self,
+        Path(kb): Path<String>,
+        update: Json<KnowledgeBaseUpdate>,
+        Data(pool): Data<&PgPool>,
+        Data(tool_manager): Data<&Arc<ToolManager>>,
+    ) -> Result<SuccessResponse> {
+        if !update_knowledge_base(&kb, &update, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        refresh_tools(tool_manager).await?;
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/knowledge_base/:kb", method = "delete")]
+    async fn delete_kb(
+        &self,
+        Path(kb): Path<String>,
+        Data(pool): Data<&PgPool>,
+        Data(tool_manager): Data<&Arc<ToolManager>>,
+    ) -> Result<SuccessResponse> {
+        let KnowledgeBase { id, .. } = get_knowledge_base(&kb, pool).await.map_err(not_found)?;
+        delete_knowledge_base(id, pool)
+            .await
+            .map_err(InternalServerError)?;
+        refresh_tools(tool_manager).await?;
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
     #[protect("Role::Admin", ty = "crate::psql_users::Role")]
     #[oai(path = "/knowledge_base/:kb/ingest", method = "post")]
     async fn upload_file(
@@ -366,6 +782,7 @@ impl Api {
         mut multipart: Multipart,
         Data(client): Data<&Arc<EmbeddingClient>>,
         Data(pool): Data<&PgPool>,
+        Data(bots): Data<&Arc<Bots>>,
     ) -> Result<Json<UploadResponse>> {
         let KnowledgeBase { id, .. } = get_knowledge_base(&kb, pool)
             .await
@@ -383,9 +800,27 @@ impl Api {
             return Err(Error::from_status(StatusCode::BAD_REQUEST)); // Bad input? *Thrownness* into error.
         }
         let data_size = data.len();
+        let content = String::from_utf8_lossy(&data).to_string();
         let hash = ingest_content(id, pool, data, client)
             .await
             .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?;
+        //recipes are also structured so they can be scaled and searched by ingredient.
+        //that takes a call to the llm, so it happens after the upload is answered. the
+        //document is ingested either way, so a failed extraction isn't an error
+        if kb == RECIPES_KB {
+            let bot = bots.helper_bot.clone();
+            let pool = pool.clone();
+            let hash = hash.clone();
+            tokio::spawn(async move {
+                if let Err(e) = ingest_recipe(&bot, &content, &hash, &pool).await {
+                    info!(
+                        tool_use = false,
+                        endpoint = "upload_file",
+                        message = format!("Failed to extract recipe from {}: {}", hash, e)
+                    );
+                }
+            });
+        }
         let resp = UploadResponse {
             filename: filename.unwrap_or("anonymous_sprawl".to_string()),
             size: data_size,
@@ -394,4 +829,1409 @@ impl Api {
 
         Ok(Json(resp)) // Serializes to JSON; spec gen handles the rest
     }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/mcp/resources", method = "get")]
+    async fn get_mcp_resources(
+        &self,
+        Data(servers): Data<&Arc<McpServers>>,
+    ) -> Result<Json<Vec<McpResource>>> {
+        let resources = servers
+            .list_resources()
+            .await
+            .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?;
+        Ok(Json(resources))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/bot/:bot/resources", method = "get")]
+    async fn get_resources_for_bot(
+        &self,
+        Path(bot): Path<String>,
+        Data(bots): Data<&Arc<Bots>>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<BotResource>>> {
+        bot_exists(bots, &bot)?;
+        let resources = get_bot_resources(&bot, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(resources))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/bot/:bot/resources", method = "post")]
+    async fn attach_resource_to_bot(
+        &self,
+        Path(bot): Path<String>,
+        resource: Json<BotResourceRequest>,
+        Data(bots): Data<&Arc<Bots>>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<BotResource>> {
+        bot_exists(bots, &bot)?;
+        let resource = attach_bot_resource(&bot, &resource, pool)
+            .await
+            .map_err(conflict)?;
+        Ok(Json(resource))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/bot/:bot/resources/:id", method = "delete")]
+    async fn detach_resource_from_bot(
+        &self,
+        Path(bot): Path<String>,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !detach_bot_resource(&bot, &id, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/mcp/prompts", method = "get")]
+    async fn get_quick_actions(
+        &self,
+        Data(servers): Data<&Arc<McpServers>>,
+    ) -> Result<Json<Vec<McpPrompt>>> {
+        let prompts = servers
+            .list_prompts()
+            .await
+            .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?;
+        Ok(Json(prompts))
+    }
+
+    //expands the prompt template and runs it as the next turn of the session
+    #[allow(clippy::too_many_arguments)]
+    #[protect(any("Role::Tutor", "Role::Helper"), ty = "crate::psql_users::Role")]
+    #[oai(path = "/mcp/prompts/:server/:prompt", method = "post")]
+    async fn run_quick_action(
+        &self,
+        Path(server): Path<String>,
+        Path(prompt): Path<String>,
+        Json(request): Json<QuickActionRequest>,
+        Data(bots): Data<&Arc<Bots>>,
+        Data(pool): Data<&PgPool>,
+        Data(servers): Data<&Arc<McpServers>>,
+        Data(user): Data<&UserIdentification>,
+    ) -> Result<Json<QuickActionResponse>> {
+        let bot = permitted_bot(bots, &request.bot, user)?;
+        let prompt = servers
+            .expand_prompt(&server, &prompt, request.arguments)
+            .await
+            .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?;
+        let full_message = chat_turn(
+            &bot,
+            &mut DiscardTokens,
+            &prompt,
+            request.session_id,
+            user,
+            pool,
+            servers,
+        )
+        .await
+        .map_err(|e| InternalServerError(LLMError { msg: e.to_string() }))?;
+        let response = chat_response(&bot, full_message, pool)
+            .await
+            .map_err(|e| InternalServerError(LLMError { msg: e.to_string() }))?;
+        Ok(Json(QuickActionResponse { prompt, response }))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/mcp", method = "get")]
+    async fn get_mcps(&self, Data(pool): Data<&PgPool>) -> Result<Json<Vec<McpServerDB>>> {
+        let servers = get_mcp_servers(pool).await.map_err(InternalServerError)?;
+        Ok(Json(servers))
+    }
+
+    //connect before saving so a bad url is reported instead of silently skipped
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/mcp", method = "post")]
+    async fn create_mcp(
+        &self,
+        mcp: Json<MCP>,
+        Data(pool): Data<&PgPool>,
+        Data(tool_manager): Data<&Arc<ToolManager>>,
+    ) -> Result<Json<McpServerDB>> {
+        let server = McpServer::connect(mcp.0.clone())
+            .await
+            .map_err(|e| BadRequest(NoData { msg: e.to_string() }))?;
+        let server_db = create_mcp_server(&mcp, pool).await.map_err(conflict)?;
+        tool_manager.mcp_servers.insert(server);
+        refresh_tools(tool_manager).await?;
+        Ok(Json(server_db))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/mcp/:id", method = "put")]
+    async fn update_mcp(
+        &self,
+        Path(id): Path<Uuid>,
+        mcp: Json<MCP>,
+        Data(pool): Data<&PgPool>,
+        Data(tool_manager): Data<&Arc<ToolManager>>,
+    ) -> Result<SuccessResponse> {
+        let server = McpServer::connect(mcp.0.clone())
+            .await
+            .map_err(|e| BadRequest(NoData { msg: e.to_string() }))?;
+        if !update_mcp_server(&id, &mcp, pool).await.map_err(conflict)? {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        tool_manager.mcp_servers.insert(server);
+        refresh_tools(tool_manager).await?;
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/mcp/:id", method = "delete")]
+    async fn delete_mcp(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+        Data(tool_manager): Data<&Arc<ToolManager>>,
+    ) -> Result<SuccessResponse> {
+        if !delete_mcp_server(&id, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        refresh_tools(tool_manager).await?;
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/tool", method = "get")]
+    async fn get_tools(
+        &self,
+        Data(tool_manager): Data<&Arc<ToolManager>>,
+    ) -> Result<Json<Vec<ToolDescription>>> {
+        let tools = tool_manager
+            .helper_tools
+            .snapshot()
+            .iter()
+            .map(|tool| ToolDescription {
+                name: tool.name().clone(),
+                description: tool.description().clone(),
+            })
+            .collect();
+        Ok(Json(tools))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/tool/permission", method = "get")]
+    async fn get_permissions(
+        &self,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<ToolPermission>>> {
+        let permissions = get_tool_permissions(pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(permissions))
+    }
+
+    //a tool without permissions is available to every helper user, while the
+    //tutor only gets the tools granted to its user or one of their roles
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/tool/permission", method = "post")]
+    async fn create_permission(
+        &self,
+        permission: Json<ToolPermissionRequest>,
+        Data(pool): Data<&PgPool>,
+        Data(tool_manager): Data<&Arc<ToolManager>>,
+    ) -> Result<Json<ToolPermission>> {
+        if permission.role.is_none() && permission.username_id.is_none() {
+            return Err(Error::from_status(StatusCode::BAD_REQUEST));
+        }
+        let permission = create_tool_permission(&permission, pool)
+            .await
+            .map_err(InternalServerError)?;
+        refresh_permissions(tool_manager).await?;
+        Ok(Json(permission))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/tool/permission/:id", method = "delete")]
+    async fn delete_permission(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+        Data(tool_manager): Data<&Arc<ToolManager>>,
+    ) -> Result<SuccessResponse> {
+        delete_tool_permission(&id, pool)
+            .await
+            .map_err(InternalServerError)?;
+        refresh_permissions(tool_manager).await?;
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/reminder", method = "get")]
+    async fn get_reminders(
+        &self,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<Reminder>>> {
+        let reminders = get_reminders(&user.id, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(reminders))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/reminder", method = "post")]
+    async fn create_reminder(
+        &self,
+        reminder: Json<ReminderRequest>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Reminder>> {
+        if let Some(rrule) = &reminder.rrule {
+            validate_rrule(rrule, reminder.due_at, user.timezone)
+                .map_err(|e| BadRequest(NoData { msg: e.to_string() }))?;
+        }
+        let reminder = create_reminder(
+            &user.id,
+            &reminder.message,
+            &reminder.due_at,
+            reminder.rrule.as_deref(),
+            user.timezone.name(),
+            pool,
+        )
+        .await
+        .map_err(InternalServerError)?;
+        Ok(Json(reminder))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/reminder/:id", method = "delete")]
+    async fn delete_reminder(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !delete_reminder(&id, &user.id, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/list", method = "get")]
+    async fn get_lists(&self, Data(pool): Data<&PgPool>) -> Result<Json<Vec<List>>> {
+        let lists = get_lists(pool).await.map_err(InternalServerError)?;
+        Ok(Json(lists))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/list", method = "post")]
+    async fn create_list(
+        &self,
+        list: Json<ListRequest>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+        Data(notifications): Data<&Arc<Notifications>>,
+    ) -> Result<Json<List>> {
+        //a list with a similar name would be found instead of the new one
+        if find_list(&list.name, pool)
+            .await
+            .map_err(InternalServerError)?
+            .is_some()
+        {
+            return Err(Error::from_status(StatusCode::CONFLICT));
+        }
+        let list = create_list(list.name.trim(), pool)
+            .await
+            .map_err(conflict)?;
+        list_changed(notifications, &list.id, user);
+        Ok(Json(list))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/list/:id", method = "get")]
+    async fn get_list(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<ListWithItems>> {
+        let list = get_list(&id, pool).await.map_err(not_found)?;
+        let list = list_with_items(list, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(list))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/list/:id", method = "patch")]
+    async fn rename_list(
+        &self,
+        Path(id): Path<Uuid>,
+        list: Json<ListRequest>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+        Data(notifications): Data<&Arc<Notifications>>,
+    ) -> Result<Json<List>> {
+        let list = rename_list(&id, &list.name, pool).await.map_err(conflict)?;
+        list_changed(notifications, &list.id, user);
+        Ok(Json(list))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/list/:id", method = "delete")]
+    async fn delete_list(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+        Data(notifications): Data<&Arc<Notifications>>,
+    ) -> Result<SuccessResponse> {
+        delete_list(&id, pool).await.map_err(InternalServerError)?;
+        list_changed(notifications, &id, user);
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/list/:id/item", method = "post")]
+    async fn add_list_item(
+        &self,
+        Path(id): Path<Uuid>,
+        item: Json<ListItemRequest>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+        Data(notifications): Data<&Arc<Notifications>>,
+    ) -> Result<Json<ListItem>> {
+        get_list(&id, pool).await.map_err(not_found)?;
+        //an item already on the list is returned instead of added twice
+        let (item, change) = add_item(&id, &item, &user.id, pool)
+            .await
+            .map_err(InternalServerError)?;
+        if change != ItemChange::Unchanged {
+            list_changed(notifications, &id, user);
+        }
+        Ok(Json(item))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/list/item/:id", method = "patch")]
+    async fn update_list_item(
+        &self,
+        Path(id): Path<Uuid>,
+        update: Json<ListItemUpdate>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+        Data(notifications): Data<&Arc<Notifications>>,
+    ) -> Result<Json<ListItem>> {
+        let item = update_list_item(&id, &update, pool)
+            .await
+            .map_err(not_found)?;
+        list_changed(notifications, &item.list_id, user);
+        Ok(Json(item))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/list/item/:id", method = "delete")]
+    async fn delete_list_item(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+        Data(notifications): Data<&Arc<Notifications>>,
+    ) -> Result<SuccessResponse> {
+        let list_id = delete_list_item(&id, pool)
+            .await
+            .map_err(InternalServerError)?
+            .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+        list_changed(notifications, &list_id, user);
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/pantry", method = "get")]
+    async fn get_pantry(&self, Data(pool): Data<&PgPool>) -> Result<Json<Vec<PantryItem>>> {
+        let items = get_pantry_items(pool).await.map_err(InternalServerError)?;
+        Ok(Json(items))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/pantry/expiring", method = "get")]
+    async fn get_expiring_pantry(
+        &self,
+        Query(days): Query<Option<i64>>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<PantryItem>>> {
+        let today = chrono::Utc::now()
+            .with_timezone(&user.timezone)
+            .date_naive();
+        let before = today
+            .checked_add_signed(chrono::Duration::days(days.unwrap_or(EXPIRING_SOON_DAYS)))
+            .ok_or_else(|| Error::from_status(StatusCode::BAD_REQUEST))?;
+        let items = get_expiring_pantry_items(&before, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(items))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/pantry", method = "post")]
+    async fn stock_pantry(
+        &self,
+        item: Json<PantryItemRequest>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<PantryItem>> {
+        //stocking something already in the pantry adds to it
+        let item = stock_item(&item, pool)
+            .await
+            .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?
+            .map_err(|msg| BadRequest(NoData { msg }))?;
+        Ok(Json(item))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/pantry/:id", method = "delete")]
+    async fn delete_pantry_item(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !delete_pantry_item(&id, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/recipe", method = "get")]
+    async fn get_recipes(&self, Data(pool): Data<&PgPool>) -> Result<Json<Vec<RecipeSummary>>> {
+        let recipes = get_recipes(pool).await.map_err(InternalServerError)?;
+        Ok(Json(recipes))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/recipe/:id", method = "get")]
+    async fn get_recipe(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Recipe>> {
+        let recipe = get_recipe(&id, pool).await.map_err(not_found)?;
+        Ok(Json(recipe))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/recipe", method = "post")]
+    async fn create_recipe(
+        &self,
+        recipe: Json<RecipeRequest>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Recipe>> {
+        let id = create_recipe(&recipe, None, pool)
+            .await
+            .map_err(InternalServerError)?;
+        let recipe = get_recipe(&id, pool).await.map_err(InternalServerError)?;
+        Ok(Json(recipe))
+    }
+
+    //saved recipe pages, read from their schema.org Recipe JSON-LD
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/recipe/import", method = "post")]
+    async fn import_recipes(
+        &self,
+        mut multipart: Multipart,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<Recipe>>> {
+        let mut html = String::new();
+        while let Some(field) = multipart.next_field().await? {
+            if field.name() == Some("file") {
+                html = field.text().await?;
+            }
+        }
+        let found = recipes_from_html(&html);
+        if found.is_empty() {
+            return Err(BadRequest(NoData {
+                msg: "No schema.org Recipe found in the file".to_string(),
+            }));
+        }
+        let mut recipes = vec![];
+        for (recipe, url) in found {
+            //importing a page again gives back the recipe it was imported as
+            let existing = match url.as_deref() {
+                Some(url) => find_recipe_by_source(url, pool)
+                    .await
+                    .map_err(InternalServerError)?,
+                None => None,
+            };
+            let id = match existing {
+                Some(id) => id,
+                None => create_recipe(&recipe, url.as_deref(), pool)
+                    .await
+                    .map_err(InternalServerError)?,
+            };
+            recipes.push(get_recipe(&id, pool).await.map_err(InternalServerError)?);
+        }
+        Ok(Json(recipes))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/recipe/:id", method = "delete")]
+    async fn delete_recipe(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !delete_recipe(&id, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/meal_plan", method = "get")]
+    async fn get_meal_plan(
+        &self,
+        Query(week): Query<Option<chrono::NaiveDate>>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<MealPlan>> {
+        let today = chrono::Utc::now()
+            .with_timezone(&user.timezone)
+            .date_naive();
+        let plan = get_meal_plan_for_week(&week_start(week.unwrap_or(today)), pool)
+            .await
+            .map_err(InternalServerError)?
+            .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+        Ok(Json(plan))
+    }
+
+    //proposes dinners from the recipe box, replacing any plan for that week
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/meal_plan", method = "post")]
+    async fn plan_meals(
+        &self,
+        request: Json<MealPlanRequest>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<MealPlan>> {
+        let today = chrono::Utc::now()
+            .with_timezone(&user.timezone)
+            .date_naive();
+        let plan = plan_meals(&request, today, pool)
+            .await
+            .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?
+            .map_err(|msg| BadRequest(NoData { msg }))?;
+        Ok(Json(plan))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/meal_plan/:id", method = "delete")]
+    async fn delete_meal_plan(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !delete_meal_plan(&id, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/meal_plan/:id/entry", method = "post")]
+    async fn create_meal_plan_entry(
+        &self,
+        Path(id): Path<Uuid>,
+        entry: Json<MealPlanEntryRequest>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<MealPlanEntry>> {
+        get_meal_plan(&id, pool).await.map_err(not_found)?;
+        let meal = planned_meal(&entry, pool)
+            .await
+            .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?
+            .map_err(|msg| BadRequest(NoData { msg }))?;
+        let entry = create_meal_plan_entry(&id, &meal, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(entry))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/meal_plan/entry/:id", method = "patch")]
+    async fn update_meal_plan_entry(
+        &self,
+        Path(id): Path<Uuid>,
+        update: Json<MealPlanEntryUpdate>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<MealPlanEntry>> {
+        let mut update = update.0;
+        //a new recipe brings its title unless another one is given
+        if let Some(recipe_id) = update.recipe_id
+            && update.title.is_none()
+        {
+            let recipe = get_recipe(&recipe_id, pool).await.map_err(|_| {
+                BadRequest(NoData {
+                    msg: format!("No recipe with id {}", recipe_id),
+                })
+            })?;
+            update.title = Some(recipe.title);
+        }
+        let entry = update_meal_plan_entry(&id, &update, pool)
+            .await
+            .map_err(not_found)?;
+        Ok(Json(entry))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/meal_plan/entry/:id", method = "delete")]
+    async fn delete_meal_plan_entry(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !delete_meal_plan_entry(&id, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    //ingredients of the planned recipes less what the pantry has
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/meal_plan/:id/shopping_list", method = "get")]
+    async fn get_meal_plan_shopping_list(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<ShoppingItem>>> {
+        let plan = get_meal_plan(&id, pool).await.map_err(not_found)?;
+        let items = shopping_list(&plan, pool)
+            .await
+            .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?;
+        Ok(Json(items))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/chore", method = "get")]
+    async fn get_chores(&self, Data(pool): Data<&PgPool>) -> Result<Json<Vec<Chore>>> {
+        let chores = get_chores(pool).await.map_err(InternalServerError)?;
+        Ok(Json(chores))
+    }
+
+    //when each chore is due and whose turn it is
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/chore/status", method = "get")]
+    async fn get_chore_statuses(
+        &self,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<ChoreStatus>>> {
+        let statuses = chore_statuses(user.timezone, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(statuses))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/chore/scores", method = "get")]
+    async fn get_chore_scores(
+        &self,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<MemberScore>>> {
+        let scores = scores(user.timezone, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(scores))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/chore", method = "post")]
+    async fn create_chore(
+        &self,
+        chore: Json<ChoreRequest>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Chore>> {
+        validate_chore(
+            Some(chore.frequency_days),
+            Some(chore.points),
+            Some(&chore.members),
+            pool,
+        )
+        .await
+        .map_err(InternalServerError)?
+        .map_err(|msg| BadRequest(NoData { msg }))?;
+        let id = create_chore(&chore, pool).await.map_err(conflict)?;
+        let chore = get_chore(&id, pool).await.map_err(InternalServerError)?;
+        Ok(Json(chore))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/chore/:id", method = "patch")]
+    async fn update_chore(
+        &self,
+        Path(id): Path<Uuid>,
+        update: Json<ChoreUpdate>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Chore>> {
+        validate_chore(
+            update.frequency_days,
+            update.points,
+            update.members.as_deref(),
+            pool,
+        )
+        .await
+        .map_err(InternalServerError)?
+        .map_err(|msg| BadRequest(NoData { msg }))?;
+        if !update_chore(&id, &update, pool).await.map_err(conflict)? {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        let chore = get_chore(&id, pool).await.map_err(InternalServerError)?;
+        Ok(Json(chore))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/chore/:id", method = "delete")]
+    async fn delete_chore(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !delete_chore(&id, pool).await.map_err(InternalServerError)? {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    //records a chore done by someone, eg when they forgot to tell the helper
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/chore/:id/completion", method = "post")]
+    async fn create_chore_completion(
+        &self,
+        Path(id): Path<Uuid>,
+        completion: Json<ChoreCompletionRequest>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<ChoreCompletion>> {
+        validate_chore(None, None, Some(&[completion.username_id]), pool)
+            .await
+            .map_err(InternalServerError)?
+            .map_err(|msg| BadRequest(NoData { msg }))?;
+        let completion = create_chore_completion(&id, &completion.username_id, pool)
+            .await
+            .map_err(not_found)?;
+        Ok(Json(completion))
+    }
+
+    #[protect("Role::Admin", ty = "crate::psql_users::Role")]
+    #[oai(path = "/chore/completion/:id", method = "delete")]
+    async fn delete_chore_completion(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !delete_chore_completion(&id, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    //occurrences of events between two times, the next week by default
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar", method = "get")]
+    async fn get_calendar(
+        &self,
+        Query(from): Query<Option<chrono::DateTime<chrono::Utc>>>,
+        Query(until): Query<Option<chrono::DateTime<chrono::Utc>>>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<Occurrence>>> {
+        let from = from.unwrap_or_else(chrono::Utc::now);
+        let until = until.unwrap_or(from + chrono::Duration::days(UPCOMING_DAYS));
+        let occurrences = upcoming(from, until, None, user.timezone, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(occurrences))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar/event/:id", method = "get")]
+    async fn get_event(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Event>> {
+        let event = get_event(&id, pool).await.map_err(not_found)?;
+        Ok(Json(event))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar/event", method = "post")]
+    async fn create_event(
+        &self,
+        event: Json<EventRequest>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Event>> {
+        let id = create_event(&event, user.timezone, &user.id, pool)
+            .await
+            .map_err(InternalServerError)?
+            .map_err(|msg| BadRequest(NoData { msg }))?;
+        let event = get_event(&id, pool).await.map_err(InternalServerError)?;
+        Ok(Json(event))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar/event/:id", method = "patch")]
+    async fn update_event(
+        &self,
+        Path(id): Path<Uuid>,
+        update: Json<EventUpdate>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Event>> {
+        let event = get_event(&id, pool).await.map_err(not_found)?;
+        validate_event(
+            update.starts_at.unwrap_or(event.starts_at),
+            update.ends_at.unwrap_or(event.ends_at),
+            update.rrule.as_deref(),
+            update.timezone.as_deref().unwrap_or(&event.timezone),
+            update.attendees.as_deref(),
+            pool,
+        )
+        .await
+        .map_err(InternalServerError)?
+        .map_err(|msg| BadRequest(NoData { msg }))?;
+        if !update_event(&id, &update, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        let event = get_event(&id, pool).await.map_err(InternalServerError)?;
+        Ok(Json(event))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar/event/:id", method = "delete")]
+    async fn delete_event(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !delete_event(&id, pool).await.map_err(InternalServerError)? {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    //.ics files, events already imported are updated by their UID
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar/import", method = "post")]
+    async fn import_calendar(
+        &self,
+        mut multipart: Multipart,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<Event>>> {
+        let mut calendar = String::new();
+        while let Some(field) = multipart.next_field().await? {
+            if field.name() == Some("file") {
+                calendar = field.text().await?;
+            }
+        }
+        let ids = import_calendar(&calendar, user.timezone, &user.id, pool)
+            .await
+            .map_err(InternalServerError)?;
+        if ids.is_empty() {
+            return Err(BadRequest(NoData {
+                msg: "No events found in the file".to_string(),
+            }));
+        }
+        let mut events = vec![];
+        for id in ids {
+            events.push(get_event(&id, pool).await.map_err(InternalServerError)?);
+        }
+        Ok(Json(events))
+    }
+
+    //a new feed url for the user's calendar app, the old one stops working
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar/feed", method = "post")]
+    async fn create_calendar_feed(
+        &self,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<CalendarFeed>> {
+        let token = new_feed_token();
+        set_feed_token(&user.id, &token, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(CalendarFeed {
+            url: format!("/calendar/feed/{}.ics", token),
+        }))
+    }
+
+    //not protected, calendar apps can't log in so the token in the url is the secret
+    #[oai(path = "/calendar/feed/:token", method = "get")]
+    async fn get_calendar_feed(
+        &self,
+        Path(token): Path<String>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<CalendarResponse> {
+        let username_id = get_feed_user(token.trim_end_matches(".ics"), pool)
+            .await
+            .map_err(InternalServerError)?
+            .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+        let calendar = user_feed(&username_id, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(CalendarResponse::Calendar(PlainText(calendar)))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar/caldav", method = "get")]
+    async fn get_caldav_account(
+        &self,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<CalDavAccount>> {
+        let account = get_caldav_account(&user.id, pool)
+            .await
+            .map_err(InternalServerError)?
+            .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+        Ok(Json(account))
+    }
+
+    //the user's calendar on a CalDAV server, synced in the background from then on
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar/caldav", method = "put")]
+    async fn set_caldav_account(
+        &self,
+        account: Json<CalDavAccountRequest>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+        Data(secrets): Data<&Arc<Secrets>>,
+    ) -> Result<Json<CalDavAccount>> {
+        CalDavClient::new(&account.url, &account.username, &account.password)
+            .map_err(|e| BadRequest(NoData { msg: e.to_string() }))?;
+        let password = secrets
+            .seal(&account.password)
+            .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?;
+        let account = set_caldav_account(&user.id, &account, &password, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(account))
+    }
+
+    //events already synced stay on the calendar
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar/caldav", method = "delete")]
+    async fn delete_caldav_account(
+        &self,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !delete_caldav_account(&user.id, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    //syncs straight away rather than waiting for the background sync
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/calendar/caldav/sync", method = "post")]
+    async fn sync_caldav_account(
+        &self,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+        Data(secrets): Data<&Arc<Secrets>>,
+    ) -> Result<Json<SyncSummary>> {
+        let account = get_user_caldav_credentials(&user.id, pool)
+            .await
+            .map_err(InternalServerError)?
+            .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+        let summary = sync(&account, secrets, pool)
+            .await
+            .map_err(InternalServerError)?
+            .map_err(|msg| BadRequest(NoData { msg }))?;
+        Ok(Json(summary))
+    }
+
+    //speech to text. the form's file field holds the audio, its name's extension
+    //tells the server the format, and an optional language field the language
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/audio/transcribe", method = "post")]
+    async fn transcribe(
+        &self,
+        mut multipart: Multipart,
+        Data(audio): Data<&Arc<AudioClient>>,
+    ) -> Result<Json<TranscriptionResponse>> {
+        let mut filename = None;
+        let mut language = None;
+        let mut data = Vec::new();
+        while let Some(field) = multipart.next_field().await? {
+            match field.name() {
+                Some("file") => {
+                    filename = field.file_name().map(String::from);
+                    data = field.bytes().await?;
+                }
+                Some("language") => language = Some(field.text().await?),
+                _ => {}
+            }
+        }
+        if data.is_empty() {
+            return Err(BadRequest(NoData {
+                msg: "No audio in the request".to_string(),
+            }));
+        }
+        let text = audio
+            .transcribe(
+                data,
+                filename.as_deref().unwrap_or("audio.wav"),
+                language.as_deref(),
+            )
+            .await
+            .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?;
+        Ok(Json(TranscriptionResponse { text }))
+    }
+
+    //text to speech, as mp3
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/audio/speak", method = "post")]
+    async fn speak(
+        &self,
+        request: Json<SpeakRequest>,
+        Data(audio): Data<&Arc<AudioClient>>,
+    ) -> Result<AudioResponse> {
+        if request.text.trim().is_empty() {
+            return Err(BadRequest(NoData {
+                msg: "Nothing to say".to_string(),
+            }));
+        }
+        let speech = audio
+            .speak(&request.text, request.voice.as_deref())
+            .await
+            .map_err(|e| InternalServerError(NoData { msg: e.to_string() }))?;
+        Ok(AudioResponse::Audio(Binary(speech)))
+    }
+
+    //a key for OpenAI compatible clients to use the bots as this user
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/api_keys", method = "post")]
+    async fn create_api_key(
+        &self,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+        Json(request): Json<ApiKeyRequest>,
+    ) -> Result<Json<NewApiKey>> {
+        if request.name.trim().is_empty() {
+            return Err(BadRequest(NoData {
+                msg: "API key name is required".to_string(),
+            }));
+        }
+        let key = new_api_key();
+        let api_key = create_api_key(&user.id, request.name.trim(), &api_key_hash(&key), pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(NewApiKey {
+            id: api_key.id,
+            name: api_key.name,
+            key,
+        }))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/api_keys", method = "get")]
+    async fn get_api_keys(
+        &self,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<Json<Vec<ApiKey>>> {
+        let api_keys = get_api_keys(&user.id, pool)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Json(api_keys))
+    }
+
+    #[protect(
+        any("Role::Admin", "Role::Tutor", "Role::Helper"),
+        ty = "crate::psql_users::Role"
+    )]
+    #[oai(path = "/api_keys/:id", method = "delete")]
+    async fn delete_api_key(
+        &self,
+        Path(id): Path<Uuid>,
+        Data(user): Data<&UserIdentification>,
+        Data(pool): Data<&PgPool>,
+    ) -> Result<SuccessResponse> {
+        if !delete_api_key(&id, &user.id, pool)
+            .await
+            .map_err(InternalServerError)?
+        {
+            return Err(Error::from_status(StatusCode::NOT_FOUND));
+        }
+        Ok(SuccessResponse::Success(Json(StatusResponse {
+            status: ResponseStatus::Success,
+        })))
+    }
+
+    //the whole answer at once, for scripts and clients without websockets
+    #[allow(clippy::too_many_arguments)]
+    #[protect(any("Role::Tutor", "Role::Helper"), ty = "crate::psql_users::Role")]
+    #[oai(path = "/chat/:bot", method = "post")]
+    async fn chat(
+        &self,
+        Path(bot): Path<String>,
+        Json(request): Json<ChatRequest>,
+        Data(bots): Data<&Arc<Bots>>,
+        Data(pool): Data<&PgPool>,
+        Data(servers): Data<&Arc<McpServers>>,
+        Data(user): Data<&UserIdentification>,
+    ) -> Result<Json<ChatResponse>> {
+        let bot = permitted_bot(bots, &bot, user)?;
+        let full_message = chat_turn(
+            &bot,
+            &mut DiscardTokens,
+            &request.message,
+            request.session_id,
+            user,
+            pool,
+            servers,
+        )
+        .await
+        .map_err(|e| InternalServerError(LLMError { msg: e.to_string() }))?;
+        let response = chat_response(&bot, full_message, pool)
+            .await
+            .map_err(|e| InternalServerError(LLMError { msg: e.to_string() }))?;
+        Ok(Json(response))
+    }
+
+    //tokens as server-sent events, ending with the whole answer or an error
+    #[allow(clippy::too_many_arguments)]
+    #[protect(any("Role::Tutor", "Role::Helper"), ty = "crate::psql_users::Role")]
+    #[oai(path = "/chat/:bot/stream", method = "post")]
+    async fn chat_stream(
+        &self,
+        Path(bot): Path<String>,
+        Json(request): Json<ChatRequest>,
+        Data(bots): Data<&Arc<Bots>>,
+        Data(pool): Data<&PgPool>,
+        Data(servers): Data<&Arc<McpServers>>,
+        Data(user): Data<&UserIdentification>,
+    ) -> Result<EventStream<BoxStream<'static, ChatEvent>>> {
+        let bot = permitted_bot(bots, &bot, user)?;
+        let (sender, receiver) = mpsc::unbounded();
+        let mut sink = ChatEventSink(sender.clone());
+        let pool = pool.clone();
+        let servers = servers.clone();
+        let user = user.clone();
+        tokio::spawn(async move {
+            let result = match chat_turn(
+                &bot,
+                &mut sink,
+                &request.message,
+                request.session_id,
+                &user,
+                &pool,
+                &servers,
+            )
+            .await
+            {
+                Ok(full_message) => chat_response(&bot, full_message, &pool).await,
+                Err(e) => Err(e),
+            };
+            let event = match result {
+                Ok(response) => ChatEvent::Done(response),
+                Err(e) => {
+                    info!(
+                        tool_use = false,
+                        endpoint = "chat_stream",
+                        message = format!("Error in chat stream: {}", e)
+                    );
+                    ChatEvent::Error(ChatError { msg: e.to_string() })
+                }
+            };
+            //the client may have gone already
+            let _ = sender.unbounded_send(event);
+        });
+        Ok(EventStream::new(receiver.boxed()).keep_alive(std::time::Duration::from_secs(15)))
+    }
 }
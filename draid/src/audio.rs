@@ -0,0 +1,505 @@
+//speech to text and text to speech through OpenAI compatible audio APIs, eg a
+//whisper.cpp server and Kokoro or Piper
+use crate::auth::UserIdentification;
+use crate::chat::chat_turn;
+use crate::llm::{Bot, TokenSink, WebSocketToken};
+use crate::mcp_tools::McpServers;
+use futures::{Sink, SinkExt};
+use poem::web::websocket::Message;
+use reqwest::Client;
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tokio::sync::{Mutex, mpsc};
+use uuid::Uuid;
+
+//speech is always mp3, each sentence is a file of its own that plays by itself
+pub const SPEECH_FORMAT: &str = "mp3";
+
+//shorter sentences are spoken together with the next, so "Sure." doesn't
+//become its own request
+const MIN_SENTENCE_CHARS: usize = 20;
+
+//the most audio an utterance may have, the upload limit of OpenAI's transcriptions
+pub const MAX_RECORDING_BYTES: usize = 25 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct Transcription {
+    text: String,
+}
+
+pub struct AudioClient {
+    client: Client,
+    transcription_endpoint: String,
+    transcription_model: String,
+    speech_endpoint: String,
+    speech_model: String,
+    voice: String,
+}
+
+impl AudioClient {
+    pub fn new(
+        transcription_endpoint: &str,
+        transcription_model: String,
+        speech_endpoint: &str,
+        speech_model: String,
+        voice: String,
+    ) -> Self {
+        Self {
+            client: Client::new(),
+            transcription_endpoint: transcription_endpoint.trim_end_matches('/').to_string(),
+            transcription_model,
+            speech_endpoint: speech_endpoint.trim_end_matches('/').to_string(),
+            speech_model,
+            voice,
+        }
+    }
+
+    //the filename's extension tells the server what format the audio is in
+    pub async fn transcribe(
+        &self,
+        audio: Vec<u8>,
+        filename: &str,
+        language: Option<&str>,
+    ) -> anyhow::Result<String> {
+        let mut form = Form::new()
+            .part("file", Part::bytes(audio).file_name(filename.to_string()))
+            .text("model", self.transcription_model.clone())
+            .text("response_format", "json");
+        if let Some(language) = language {
+            form = form.text("language", language.to_string());
+        }
+        let transcription: Transcription = self
+            .client
+            .post(format!(
+                "{}/v1/audio/transcriptions",
+                self.transcription_endpoint
+            ))
+            .multipart(form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(transcription.text.trim().to_string())
+    }
+
+    pub async fn speak(&self, text: &str, voice: Option<&str>) -> anyhow::Result<Vec<u8>> {
+        let audio = self
+            .client
+            .post(format!("{}/v1/audio/speech", self.speech_endpoint))
+            .json(&serde_json::json!({
+                "model": self.speech_model,
+                "input": text,
+                "voice": voice.unwrap_or(&self.voice),
+                "response_format": SPEECH_FORMAT,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(audio.to_vec())
+    }
+}
+
+//markdown the model writes that shouldn't be read out
+fn speakable(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            line.trim_start()
+                .trim_start_matches(['-', '#', '>'])
+                .replace(['*', '`', '#', '_'], "")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+//splits streamed text into sentences, so each can be spoken while the rest of
+//the answer is still being generated
+#[derive(Default)]
+pub struct SentenceChunker {
+    buffer: String,
+}
+
+impl SentenceChunker {
+    //the sentences completed by these tokens
+    pub fn push(&mut self, tokens: &str) -> Vec<String> {
+        self.buffer.push_str(tokens);
+        let mut sentences = vec![];
+        let mut start = 0;
+        let mut chars = self.buffer.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            //the end of a sentence is only known once the next character arrives
+            let Some((next_index, next)) = chars.peek().copied() else {
+                break;
+            };
+            let ends_sentence = c == '\n' || (matches!(c, '.' | '!' | '?') && next.is_whitespace());
+            if !ends_sentence {
+                continue;
+            }
+            let sentence = speakable(&self.buffer[start..=index]);
+            if c == '\n' && sentence.is_empty() {
+                start = next_index;
+            } else if c == '\n' || sentence.len() >= MIN_SENTENCE_CHARS {
+                sentences.push(sentence);
+                start = next_index;
+            }
+        }
+        self.buffer.drain(..start);
+        sentences
+    }
+
+    //whatever is left once the answer is complete
+    pub fn finish(&mut self) -> Option<String> {
+        let sentence = speakable(&self.buffer);
+        self.buffer.clear();
+        (!sentence.is_empty()).then_some(sentence)
+    }
+}
+
+//messages of the voice websocket besides the tokens and the audio
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum VoiceEvent {
+    //what the user was heard saying
+    Transcript { text: String },
+    //the answer and its audio are complete
+    Done,
+    //something went wrong, eg the answer couldn't be spoken and is only sent as text
+    Error { message: String },
+}
+
+//sent by the client after the audio of an utterance
+#[derive(Deserialize)]
+pub struct Utterance {
+    #[serde(default)]
+    pub filename: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub voice: Option<String>,
+}
+
+async fn send_message<S>(sink: &Mutex<S>, message: Message) -> anyhow::Result<()>
+where
+    S: Sink<Message> + Unpin + Send,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    sink.lock().await.send(message).await?;
+    Ok(())
+}
+
+pub async fn send_event<S>(sink: &Mutex<S>, event: &VoiceEvent) -> anyhow::Result<()>
+where
+    S: Sink<Message> + Unpin + Send,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    send_message(sink, Message::Text(serde_json::to_string(event)?)).await
+}
+
+//sends the tokens on and hands each finished sentence to the speaker
+pub struct SpokenTokens<S> {
+    sink: Arc<Mutex<S>>,
+    chunker: SentenceChunker,
+    sentences: mpsc::UnboundedSender<String>,
+}
+
+#[async_trait::async_trait]
+impl<S> TokenSink for SpokenTokens<S>
+where
+    S: Sink<Message> + Unpin + Send,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    async fn send_token(&mut self, token: WebSocketToken) -> anyhow::Result<()> {
+        if let Some(tokens) = token.message() {
+            //the speaker only stops once the client is gone, the text is sent regardless
+            for sentence in self.chunker.push(tokens) {
+                let _ = self.sentences.send(sentence);
+            }
+        }
+        send_message(&self.sink, Message::Text(serde_json::to_string(&token)?)).await
+    }
+}
+
+//synthesizes the sentences in order, sending each as a binary message, once
+//speech fails the rest of the answer is only sent as text
+async fn speak_sentences<S>(
+    audio: Arc<AudioClient>,
+    voice: Option<String>,
+    mut sentences: mpsc::UnboundedReceiver<String>,
+    sink: Arc<Mutex<S>>,
+) where
+    S: Sink<Message> + Unpin + Send,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    let mut speaking = true;
+    while let Some(sentence) = sentences.recv().await {
+        if !speaking {
+            continue;
+        }
+        let sent = match audio.speak(&sentence, voice.as_deref()).await {
+            Ok(speech) => send_message(&sink, Message::Binary(speech)).await,
+            Err(e) => {
+                speaking = false;
+                let message = format!("The answer couldn't be spoken: {}", e);
+                send_event(&sink, &VoiceEvent::Error { message }).await
+            }
+        };
+        //the client is gone, the answer is still generated and saved
+        if sent.is_err() {
+            speaking = false;
+        }
+    }
+}
+
+//streams the answer's tokens and its speech, sentence by sentence
+pub async fn speak_answer<S, F>(
+    audio: &Arc<AudioClient>,
+    voice: Option<String>,
+    sink: &Arc<Mutex<S>>,
+    answer: impl FnOnce(SpokenTokens<S>) -> F,
+) -> anyhow::Result<()>
+where
+    S: Sink<Message> + Unpin + Send + 'static,
+    S::Error: std::error::Error + Send + Sync + 'static,
+    F: Future<Output = anyhow::Result<SpokenTokens<S>>>,
+{
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let speaker = tokio::spawn(speak_sentences(
+        audio.clone(),
+        voice,
+        receiver,
+        sink.clone(),
+    ));
+    let tokens = SpokenTokens {
+        sink: sink.clone(),
+        chunker: SentenceChunker::default(),
+        sentences: sender,
+    };
+    let result = answer(tokens).await.map(|mut tokens| {
+        if let Some(rest) = tokens.chunker.finish() {
+            let _ = tokens.sentences.send(rest);
+        }
+    });
+    //the speaker finishes once the tokens, and with them the sender, are dropped
+    speaker.await?;
+    result?;
+    send_event(sink, &VoiceEvent::Done).await
+}
+
+//one spoken exchange: the recording is transcribed, answered by the bot and the
+//answer is sent back as tokens and speech
+#[allow(clippy::too_many_arguments)]
+pub async fn voice_turn<S>(
+    bot: &Bot,
+    audio: &Arc<AudioClient>,
+    recording: Vec<u8>,
+    utterance: Utterance,
+    sink: &Arc<Mutex<S>>,
+    session_id: Uuid,
+    user: &UserIdentification,
+    pool: &PgPool,
+    servers: &McpServers,
+) -> anyhow::Result<()>
+where
+    S: Sink<Message> + Unpin + Send + 'static,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    let filename = utterance.filename.as_deref().unwrap_or("audio.wav");
+    let text = audio
+        .transcribe(recording, filename, utterance.language.as_deref())
+        .await?;
+    send_event(sink, &VoiceEvent::Transcript { text: text.clone() }).await?;
+    //nothing was said
+    if text.is_empty() {
+        return send_event(sink, &VoiceEvent::Done).await;
+    }
+    speak_answer(audio, utterance.voice, sink, async |mut tokens| {
+        chat_turn(bot, &mut tokens, &text, session_id, user, pool, servers).await?;
+        Ok(tokens)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AudioClient, SentenceChunker, speak_answer, speakable};
+    use crate::llm::{TokenSink, WebSocketToken};
+    use futures::StreamExt;
+    use poem::listener::{Acceptor, Listener, TcpListener};
+    use poem::web::{Json, Multipart, websocket::Message};
+    use poem::{Route, Server, handler, http::StatusCode};
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    #[handler]
+    async fn create_transcription(mut multipart: Multipart) -> poem::Result<Json<Value>> {
+        let mut fields = vec![];
+        while let Some(field) = multipart.next_field().await? {
+            let name = field.name().unwrap_or_default().to_string();
+            let value = match field.file_name() {
+                Some(filename) => filename.to_string(),
+                None => field.text().await?,
+            };
+            fields.push(format!("{}={}", name, value));
+        }
+        Ok(Json(json!({ "text": format!(" {} ", fields.join(" ")) })))
+    }
+
+    #[handler]
+    fn create_speech(Json(request): Json<Value>) -> poem::Result<Vec<u8>> {
+        if request["response_format"] != "mp3" {
+            return Err(poem::Error::from_status(StatusCode::BAD_REQUEST));
+        }
+        //a voice the speech server can't speak with
+        if request["voice"] == "broken" {
+            return Err(poem::Error::from_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+        Ok(format!(
+            "{}:{}",
+            request["voice"].as_str().unwrap(),
+            request["input"].as_str().unwrap()
+        )
+        .into_bytes())
+    }
+
+    //a mock of the OpenAI audio API
+    async fn mock_audio_server() -> Arc<AudioClient> {
+        let app = Route::new()
+            .at("/v1/audio/transcriptions", create_transcription)
+            .at("/v1/audio/speech", create_speech);
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let address = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+        let url = format!("http://{}/", address);
+        Arc::new(AudioClient::new(
+            &url,
+            "whisper-1".to_string(),
+            &url,
+            "kokoro".to_string(),
+            "af_heart".to_string(),
+        ))
+    }
+
+    #[test]
+    fn it_splits_sentences() {
+        let mut chunker = SentenceChunker::default();
+        assert!(chunker.push("Sure. The lights").is_empty());
+        assert!(chunker.push(" are on now.").is_empty());
+        assert_eq!(
+            chunker.push(" Anything else?"),
+            vec!["Sure. The lights are on now."]
+        );
+        assert_eq!(
+            chunker.push("\n- **milk**\nVersion 1.5 is out"),
+            vec!["Anything else?", "milk"]
+        );
+        assert_eq!(chunker.finish(), Some("Version 1.5 is out".to_string()));
+        assert_eq!(chunker.finish(), None);
+        assert_eq!(speakable("## Today\n* `rain` later"), "Today rain later");
+    }
+
+    #[tokio::test]
+    async fn it_transcribes_and_speaks() {
+        let audio = mock_audio_server().await;
+        let text = audio
+            .transcribe(vec![1, 2, 3], "clip.webm", Some("en"))
+            .await
+            .unwrap();
+        assert_eq!(
+            text,
+            "file=clip.webm model=whisper-1 response_format=json language=en"
+        );
+        assert_eq!(audio.speak("Hello", None).await.unwrap(), b"af_heart:Hello");
+        assert_eq!(
+            audio.speak("Hello", Some("bm_george")).await.unwrap(),
+            b"bm_george:Hello"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_speaks_before_the_answer_ends() {
+        let audio = mock_audio_server().await;
+        let (sender, mut receiver) = futures::channel::mpsc::unbounded();
+        let sink = Arc::new(Mutex::new(sender));
+        let (release, held) = tokio::sync::oneshot::channel::<()>();
+        let answer = tokio::spawn({
+            let audio = audio.clone();
+            let sink = sink.clone();
+            async move {
+                speak_answer(&audio, None, &sink, async move |mut tokens| {
+                    for text in ["The garage door ", "is open. ", "Shall I close it?"] {
+                        tokens.send_token(WebSocketToken::answer(text)).await?;
+                    }
+                    //holds the end of the answer back until the first audio arrived
+                    let _ = held.await;
+                    Ok(tokens)
+                })
+                .await
+                .unwrap();
+            }
+        });
+        let mut texts = 0;
+        loop {
+            match receiver.next().await.unwrap() {
+                Message::Text(_) => texts += 1,
+                Message::Binary(speech) => {
+                    assert_eq!(speech, b"af_heart:The garage door is open.");
+                    break;
+                }
+                _ => panic!("unexpected message"),
+            }
+        }
+        assert_eq!(texts, 3);
+        release.send(()).unwrap();
+        answer.await.unwrap();
+        let Message::Binary(speech) = receiver.next().await.unwrap() else {
+            panic!("expected speech");
+        };
+        assert_eq!(speech, b"af_heart:Shall I close it?");
+        let Message::Text(done) = receiver.next().await.unwrap() else {
+            panic!("expected done");
+        };
+        assert_eq!(done, r#"{"type":"done"}"#);
+    }
+
+    #[tokio::test]
+    async fn it_falls_back_to_text_when_speech_fails() {
+        let audio = mock_audio_server().await;
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        let sink = Arc::new(Mutex::new(sender));
+        let voice = Some("broken".to_string());
+        speak_answer(&audio, voice, &sink, async move |mut tokens| {
+            for text in ["The garage door is open. ", "Shall I close it?"] {
+                tokens.send_token(WebSocketToken::answer(text)).await?;
+            }
+            Ok(tokens)
+        })
+        .await
+        .unwrap();
+        drop(sink);
+        let messages = receiver.collect::<Vec<_>>().await;
+        let texts = messages
+            .iter()
+            .map(|message| match message {
+                Message::Text(text) => serde_json::from_str::<Value>(text).unwrap(),
+                _ => panic!("expected only text"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts.len(), 4);
+        assert_eq!(
+            texts.iter().filter(|text| text["type"] == "error").count(),
+            1
+        );
+        assert_eq!(texts[3], json!({"type": "done"}));
+    }
+}
@@ -1,6 +1,10 @@
-use crate::psql_users::get_user;
+use crate::openai::{api_key_hash, openai_error};
+use crate::psql_api_keys::use_api_key;
+use crate::psql_users::{Role, UserResponse, get_user};
+use chrono_tz::Tz;
 use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use poem::error::InternalServerError;
+use poem::http::StatusCode;
 use poem::http::header::AUTHORIZATION;
 use poem::{Endpoint, Middleware, Request, Result};
 use serde::{Deserialize, Serialize};
@@ -15,6 +19,24 @@ use std::time::{SystemTime, UNIX_EPOCH};
 pub struct UserIdentification {
     pub username: String,
     pub id: Uuid,
+    pub roles: Vec<Role>,
+    pub timezone: Tz,
+}
+
+//timezones are validated when saved, but fall back to UTC rather than locking the user out
+fn user_timezone(timezone: &str) -> Tz {
+    timezone.parse().unwrap_or(Tz::UTC)
+}
+
+impl From<&UserResponse> for UserIdentification {
+    fn from(user: &UserResponse) -> Self {
+        Self {
+            username: user.username.clone(),
+            id: user.id,
+            roles: user.roles.clone(),
+            timezone: user_timezone(&user.timezone),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -104,10 +126,8 @@ impl<E: Endpoint> Endpoint for JwtMiddlewareImpl<E> {
                 .await
                 .map_err(InternalServerError)?;
 
-            req.extensions_mut().insert(UserIdentification {
-                username: calling_user.username,
-                id: calling_user.id,
-            });
+            req.extensions_mut()
+                .insert(UserIdentification::from(&calling_user));
             // Attach permissions to request for `poem-grants`
             req.attach(calling_user.roles);
         }
@@ -160,10 +180,56 @@ impl<E: Endpoint> Endpoint for WSMiddlewareImpl<E> {
         let calling_user = get_user(&token_data.claims.sub, pool)
             .await
             .map_err(InternalServerError)?;
-        req.extensions_mut().insert(UserIdentification {
-            username: calling_user.username,
-            id: calling_user.id,
-        });
+        req.extensions_mut()
+            .insert(UserIdentification::from(&calling_user));
+        // Attach permissions to request for `poem-grants`
+        req.attach(calling_user.roles);
+        // call the next endpoint.
+        self.ep.call(req).await
+    }
+}
+
+/// A middleware that identifies the user from an API key in the Authorization header,
+/// rejecting the request without one
+pub struct ApiKeyMiddleware;
+
+impl<E: Endpoint> Middleware<E> for ApiKeyMiddleware {
+    type Output = ApiKeyMiddlewareImpl<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ApiKeyMiddlewareImpl { ep }
+    }
+}
+
+/// The new endpoint type generated by the ApiKeyMiddleware.
+pub struct ApiKeyMiddlewareImpl<E> {
+    ep: E,
+}
+
+impl<E: Endpoint> Endpoint for ApiKeyMiddlewareImpl<E> {
+    type Output = E::Output;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let pool = req.data::<PgPool>().ok_or_else(|| {
+            InternalServerError(AuthError {
+                msg: "Could not get database connection".to_string(),
+            })
+        })?;
+        let key = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| openai_error(StatusCode::UNAUTHORIZED, "Missing API key"))?;
+        let username = use_api_key(&api_key_hash(key), pool)
+            .await
+            .map_err(InternalServerError)?
+            .ok_or_else(|| openai_error(StatusCode::UNAUTHORIZED, "Incorrect API key provided"))?;
+        let calling_user = get_user(&username, pool)
+            .await
+            .map_err(InternalServerError)?;
+        req.extensions_mut()
+            .insert(UserIdentification::from(&calling_user));
         // Attach permissions to request for `poem-grants`
         req.attach(calling_user.roles);
         // call the next endpoint.
@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::fmt;
+
+//deeper nesting than anyone types by hand, but well short of the stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum CalcError {
+    UnexpectedCharacter(char, usize),
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    UnknownVariable(String),
+    UnknownFunction(String),
+    WrongArgumentCount {
+        name: String,
+        expected: String,
+        got: usize,
+    },
+    DivisionByZero,
+    NotANumber,
+    TooDeep,
+}
+
+impl std::error::Error for CalcError {}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalcError::UnexpectedCharacter(c, position) => {
+                write!(f, "Unexpected character '{}' at position {}", c, position)
+            }
+            CalcError::UnexpectedToken(token) => write!(f, "Unexpected '{}'", token),
+            CalcError::UnexpectedEnd => write!(f, "Expression ended unexpectedly"),
+            CalcError::UnknownVariable(name) => write!(f, "Unknown variable '{}'", name),
+            CalcError::UnknownFunction(name) => write!(f, "Unknown function '{}'", name),
+            CalcError::WrongArgumentCount {
+                name,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Function '{}' expects {} arguments but got {}",
+                name, expected, got
+            ),
+            CalcError::DivisionByZero => write!(f, "Division by zero"),
+            CalcError::NotANumber => write!(f, "Result is not a real number"),
+            CalcError::TooDeep => write!(f, "Expression is nested too deeply"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    Percent,
+    Comma,
+    LeftParen,
+    RightParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Caret => write!(f, "^"),
+            Token::Percent => write!(f, "%"),
+            Token::Comma => write!(f, ","),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, CalcError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' | '\t' | '\n' => i += 1,
+            '0'..='9' | '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                //scientific notation, eg 1.5e3
+                if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let literal: String = chars[start..i].iter().collect();
+                let number = literal
+                    .parse::<f64>()
+                    .map_err(|_| CalcError::UnexpectedToken(literal))?;
+                tokens.push(Token::Number(number));
+            }
+            //thousands separators, eg 1,000, are ambiguous with function arguments so aren't supported
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            '+' => {
+                tokens.push(Token::Plus);
+                i += 1;
+            }
+            '-' => {
+                tokens.push(Token::Minus);
+                i += 1;
+            }
+            '*' | '×' => {
+                tokens.push(Token::Star);
+                i += 1;
+            }
+            '/' | '÷' => {
+                tokens.push(Token::Slash);
+                i += 1;
+            }
+            '^' => {
+                tokens.push(Token::Caret);
+                i += 1;
+            }
+            '%' => {
+                tokens.push(Token::Percent);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::LeftParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RightParen);
+                i += 1;
+            }
+            _ => return Err(CalcError::UnexpectedCharacter(c, i)),
+        }
+    }
+    Ok(tokens)
+}
+
+//a value that came from a trailing %, so that "a + b%" can mean "a plus b percent of a"
+struct Operand {
+    value: f64,
+    is_percent: bool,
+}
+
+impl Operand {
+    fn value(value: f64) -> Self {
+        Self {
+            value,
+            is_percent: false,
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+    variables: &'a HashMap<String, f64>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+    fn expect(&mut self, expected: Token) -> Result<(), CalcError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(CalcError::UnexpectedToken(token.to_string())),
+            None => Err(CalcError::UnexpectedEnd),
+        }
+    }
+
+    // additive := multiplicative (('+' | '-') multiplicative)*
+    fn additive(&mut self) -> Result<Operand, CalcError> {
+        let mut left = self.multiplicative()?;
+        while let Some(token) = self.peek() {
+            let is_plus = match token {
+                Token::Plus => true,
+                Token::Minus => false,
+                _ => break,
+            };
+            self.next();
+            let right = self.multiplicative()?;
+            //100 + 10% is 110, like on a pocket calculator
+            let right = if right.is_percent {
+                left.value * right.value
+            } else {
+                right.value
+            };
+            left = Operand::value(if is_plus {
+                left.value + right
+            } else {
+                left.value - right
+            });
+        }
+        Ok(left)
+    }
+
+    // multiplicative := unary (('*' | '/' | implicit) unary)*
+    fn multiplicative(&mut self) -> Result<Operand, CalcError> {
+        let mut left = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Star) => {
+                    self.next();
+                    let right = self.unary()?;
+                    left = Operand::value(left.value * right.value);
+                }
+                Some(Token::Slash) => {
+                    self.next();
+                    let right = self.unary()?;
+                    if right.value == 0.0 {
+                        return Err(CalcError::DivisionByZero);
+                    }
+                    left = Operand::value(left.value / right.value);
+                }
+                //implicit multiplication, eg 2(3 + 4) or 2pi
+                Some(Token::LeftParen) | Some(Token::Ident(_)) => {
+                    let right = self.unary()?;
+                    left = Operand::value(left.value * right.value);
+                }
+                _ => break,
+            }
+        }
+        Ok(left)
+    }
+
+    //every nested expression passes through unary, so it bounds the recursion
+    fn unary(&mut self) -> Result<Operand, CalcError> {
+        if self.depth == MAX_DEPTH {
+            return Err(CalcError::TooDeep);
+        }
+        self.depth += 1;
+        let operand = self.signed();
+        self.depth -= 1;
+        operand
+    }
+
+    // unary := ('-' | '+') unary | power
+    fn signed(&mut self) -> Result<Operand, CalcError> {
+        match self.peek() {
+            Some(Token::Minus) => {
+                self.next();
+                let operand = self.unary()?;
+                Ok(Operand {
+                    value: -operand.value,
+                    is_percent: operand.is_percent,
+                })
+            }
+            Some(Token::Plus) => {
+                self.next();
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    // power := postfix ('^' unary)?, right associative
+    fn power(&mut self) -> Result<Operand, CalcError> {
+        let base = self.postfix()?;
+        if let Some(Token::Caret) = self.peek() {
+            self.next();
+            let exponent = self.unary()?;
+            return Ok(Operand::value(base.value.powf(exponent.value)));
+        }
+        Ok(base)
+    }
+
+    // postfix := primary '%'?
+    fn postfix(&mut self) -> Result<Operand, CalcError> {
+        let value = self.primary()?;
+        if let Some(Token::Percent) = self.peek() {
+            self.next();
+            return Ok(Operand {
+                value: value / 100.0,
+                is_percent: true,
+            });
+        }
+        Ok(Operand::value(value))
+    }
+
+    // primary := number | ident | ident '(' arguments ')' | '(' additive ')'
+    fn primary(&mut self) -> Result<f64, CalcError> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::LeftParen) => {
+                let value = self.additive()?.value;
+                self.expect(Token::RightParen)?;
+                Ok(value)
+            }
+            Some(Token::Ident(name)) => {
+                if let Some(Token::LeftParen) = self.peek() {
+                    self.next();
+                    let arguments = self.arguments()?;
+                    call_function(&name, &arguments)
+                } else {
+                    self.variable(&name)
+                }
+            }
+            Some(token) => Err(CalcError::UnexpectedToken(token.to_string())),
+            None => Err(CalcError::UnexpectedEnd),
+        }
+    }
+
+    fn arguments(&mut self) -> Result<Vec<f64>, CalcError> {
+        let mut arguments = Vec::new();
+        if let Some(Token::RightParen) = self.peek() {
+            self.next();
+            return Ok(arguments);
+        }
+        loop {
+            arguments.push(self.additive()?.value);
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RightParen) => return Ok(arguments),
+                Some(token) => return Err(CalcError::UnexpectedToken(token.to_string())),
+                None => return Err(CalcError::UnexpectedEnd),
+            }
+        }
+    }
+
+    fn variable(&self, name: &str) -> Result<f64, CalcError> {
+        if let Some(value) = self.variables.get(name) {
+            return Ok(*value);
+        }
+        match name {
+            "pi" => Ok(std::f64::consts::PI),
+            "e" => Ok(std::f64::consts::E),
+            _ => Err(CalcError::UnknownVariable(name.to_string())),
+        }
+    }
+}
+
+fn check_arguments(name: &str, arguments: &[f64], expected: usize) -> Result<(), CalcError> {
+    if arguments.len() != expected {
+        return Err(CalcError::WrongArgumentCount {
+            name: name.to_string(),
+            expected: expected.to_string(),
+            got: arguments.len(),
+        });
+    }
+    Ok(())
+}
+
+fn call_function(name: &str, arguments: &[f64]) -> Result<f64, CalcError> {
+    match name {
+        "sqrt" | "abs" | "floor" | "ceil" | "ln" | "log" | "exp" => {
+            check_arguments(name, arguments, 1)?;
+            let x = arguments[0];
+            Ok(match name {
+                "sqrt" => x.sqrt(),
+                "abs" => x.abs(),
+                "floor" => x.floor(),
+                "ceil" => x.ceil(),
+                "ln" => x.ln(),
+                "log" => x.log10(),
+                _ => x.exp(),
+            })
+        }
+        "pow" => {
+            check_arguments(name, arguments, 2)?;
+            Ok(arguments[0].powf(arguments[1]))
+        }
+        //round(x) or round(x, digits)
+        "round" => match arguments {
+            [x] => Ok(x.round()),
+            [x, digits] => {
+                let factor = 10f64.powi(*digits as i32);
+                Ok((x * factor).round() / factor)
+            }
+            _ => Err(CalcError::WrongArgumentCount {
+                name: name.to_string(),
+                expected: "1 or 2".to_string(),
+                got: arguments.len(),
+            }),
+        },
+        "min" | "max" => {
+            if arguments.is_empty() {
+                return Err(CalcError::WrongArgumentCount {
+                    name: name.to_string(),
+                    expected: "at least 1".to_string(),
+                    got: 0,
+                });
+            }
+            let fold = if name == "min" { f64::min } else { f64::max };
+            Ok(arguments[1..]
+                .iter()
+                .fold(arguments[0], |acc, x| fold(acc, *x)))
+        }
+        _ => Err(CalcError::UnknownFunction(name.to_string())),
+    }
+}
+
+pub fn evaluate(expression: &str, variables: &HashMap<String, f64>) -> Result<f64, CalcError> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        depth: 0,
+        variables,
+    };
+    let result = parser.additive()?.value;
+    if let Some(token) = parser.peek() {
+        return Err(CalcError::UnexpectedToken(token.to_string()));
+    }
+    if !result.is_finite() {
+        return Err(CalcError::NotANumber);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CalcError, evaluate};
+    use std::collections::HashMap;
+
+    fn eval(expression: &str) -> Result<f64, CalcError> {
+        evaluate(expression, &HashMap::new())
+    }
+
+    #[test]
+    fn it_respects_operator_precedence() {
+        assert_eq!(eval("2 + 3 * 4").unwrap(), 14.0);
+        assert_eq!(eval("(2 + 3) * 4").unwrap(), 20.0);
+        assert_eq!(eval("2 ^ 3 ^ 2").unwrap(), 512.0);
+        assert_eq!(eval("-2 ^ 2").unwrap(), -4.0);
+        assert_eq!(eval("10 - 4 - 3").unwrap(), 3.0);
+    }
+
+    #[test]
+    fn it_evaluates_functions() {
+        assert_eq!(eval("sqrt(16)").unwrap(), 4.0);
+        assert_eq!(eval("pow(2, 10)").unwrap(), 1024.0);
+        assert_eq!(eval("round(2.345, 2)").unwrap(), 2.35);
+        assert_eq!(eval("min(3, 1, 2) + max(3, 1, 2)").unwrap(), 4.0);
+    }
+
+    #[test]
+    fn it_evaluates_percentages() {
+        assert_eq!(eval("20% * 50").unwrap(), 10.0);
+        assert_eq!(eval("100 + 10%").unwrap(), 110.0);
+        assert_eq!(eval("80 - 25%").unwrap(), 60.0);
+    }
+
+    #[test]
+    fn it_evaluates_compound_interest() {
+        let result = eval("round(1000 * (1 + 5% / 12) ^ (12 * 10), 2)").unwrap();
+        assert_eq!(result, 1647.01);
+    }
+
+    #[test]
+    fn it_uses_variables() {
+        let variables = HashMap::from([("total".to_string(), 87.5), ("people".to_string(), 4.0)]);
+        assert_eq!(
+            evaluate("total * (1 + 18%) / people", &variables).unwrap(),
+            25.8125
+        );
+    }
+
+    #[test]
+    fn it_supports_implicit_multiplication() {
+        assert_eq!(eval("2(3 + 4)").unwrap(), 14.0);
+    }
+
+    #[test]
+    fn it_returns_errors() {
+        assert_eq!(eval("1 / 0"), Err(CalcError::DivisionByZero));
+        assert_eq!(eval("sqrt(-1)"), Err(CalcError::NotANumber));
+        assert_eq!(eval("2 +"), Err(CalcError::UnexpectedEnd));
+        assert_eq!(eval("(2 + 3"), Err(CalcError::UnexpectedEnd));
+        assert_eq!(eval("2 $ 3"), Err(CalcError::UnexpectedCharacter('$', 2)));
+        assert_eq!(
+            eval("cups * 2"),
+            Err(CalcError::UnknownVariable("cups".to_string()))
+        );
+        assert_eq!(
+            eval("sin(1)"),
+            Err(CalcError::UnknownFunction("sin".to_string()))
+        );
+        assert!(matches!(
+            eval("pow(2)"),
+            Err(CalcError::WrongArgumentCount { .. })
+        ));
+        assert_eq!(eval(&"(".repeat(100_000)), Err(CalcError::TooDeep));
+        assert_eq!(eval(&"-".repeat(100_000)), Err(CalcError::TooDeep));
+        assert_eq!(eval(&"2^".repeat(100_000)), Err(CalcError::TooDeep));
+        assert_eq!(
+            eval(&format!("{}1{}", "(".repeat(50), ")".repeat(50))),
+            Ok(1.0)
+        );
+    }
+}
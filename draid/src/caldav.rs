@@ -0,0 +1,743 @@
+//two way sync of the household calendar with CalDAV servers, eg Radicale or Nextcloud
+use crate::calendar::{event_data, find_user, ics_event};
+use crate::ics::{parse_calendar, patch_calendar, write_calendar};
+use crate::psql_caldav::{
+    CalDavCredentials, CalDavLink, delete_caldav_link, get_caldav_credentials, get_caldav_links,
+    get_unlinked_events, set_caldav_link, set_caldav_password, set_caldav_status,
+};
+use crate::psql_calendar::{delete_event, get_event, get_event_by_uid, upsert_event};
+use crate::psql_users::{UserResponse, get_all_users};
+use crate::secrets::{Secrets, is_sealed};
+use chrono::Utc;
+use chrono_tz::Tz;
+use poem_openapi::Object;
+use quick_xml::NsReader;
+use quick_xml::events::Event as XmlEvent;
+use quick_xml::name::{Namespace, ResolveResult};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url, header};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::info;
+use uuid::Uuid;
+
+//how often every account is synced in the background
+const SYNC_INTERVAL: Duration = Duration::from_secs(300);
+
+const PROPFIND: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:getetag/><d:resourcetype/></d:prop></d:propfind>"#;
+
+//an event stored on the server
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resource {
+    pub href: String,
+    pub etag: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    //new or changed on the server
+    Pull {
+        href: String,
+        etag: String,
+    },
+    //changed here
+    Push {
+        event_id: Uuid,
+        href: String,
+        etag: String,
+    },
+    //deleted on the server
+    DeleteHere {
+        event_id: Uuid,
+        href: String,
+    },
+    //deleted here
+    DeleteThere {
+        href: String,
+        etag: String,
+    },
+    //deleted on both
+    Forget {
+        href: String,
+    },
+}
+
+#[derive(Serialize, Object, Default, Debug, PartialEq)]
+pub struct SyncSummary {
+    pub pulled: u32,
+    pub pushed: u32,
+    pub deleted_here: u32,
+    pub deleted_there: u32,
+    //events changed on both sides, the server's copy is kept
+    pub conflicts: u32,
+    //events that couldn't be synced, they are tried again next time
+    pub failed: u32,
+}
+
+pub enum WriteResult {
+    //with the new ETag, if the server gave one
+    Written(Option<String>),
+    //the If-Match or If-None-Match precondition failed
+    Conflict,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Href,
+    Etag,
+}
+
+//hrefs and ETags of the calendar objects in a PROPFIND multistatus, the
+//collection itself is left out
+fn parse_multistatus(xml: &str) -> anyhow::Result<Vec<Resource>> {
+    let mut reader = NsReader::from_str(xml);
+    let mut resources = vec![];
+    let mut href = String::new();
+    let mut etag = String::new();
+    let mut collection = false;
+    let mut field: Option<Field> = None;
+    loop {
+        match reader.read_resolved_event()? {
+            (ResolveResult::Bound(Namespace(b"DAV:")), XmlEvent::Start(element)) => {
+                match element.local_name().as_ref() {
+                    b"response" => {
+                        href.clear();
+                        etag.clear();
+                        collection = false;
+                    }
+                    b"href" => field = Some(Field::Href),
+                    b"getetag" => field = Some(Field::Etag),
+                    b"collection" => collection = true,
+                    _ => {}
+                }
+            }
+            (ResolveResult::Bound(Namespace(b"DAV:")), XmlEvent::Empty(element))
+                if element.local_name().as_ref() == b"collection" =>
+            {
+                collection = true
+            }
+            (_, XmlEvent::Text(text)) => match field {
+                Some(Field::Href) => href.push_str(&text.unescape()?),
+                Some(Field::Etag) => etag.push_str(&text.unescape()?),
+                None => {}
+            },
+            (ResolveResult::Bound(Namespace(b"DAV:")), XmlEvent::End(element)) => {
+                field = None;
+                if element.local_name().as_ref() == b"response"
+                    && !collection
+                    && !href.trim().is_empty()
+                    && !etag.trim().is_empty()
+                {
+                    resources.push(Resource {
+                        href: href.trim().to_string(),
+                        etag: etag.trim().to_string(),
+                    });
+                }
+            }
+            (_, XmlEvent::Eof) => break,
+            _ => {}
+        }
+    }
+    Ok(resources)
+}
+
+fn response_etag(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(String::from)
+}
+
+pub struct CalDavClient {
+    client: Client,
+    url: Url,
+    username: String,
+    password: String,
+}
+
+impl CalDavClient {
+    pub fn new(url: &str, username: &str, password: &str) -> anyhow::Result<Self> {
+        //hrefs are resolved against the collection, which needs its trailing slash
+        let url = match url.ends_with('/') {
+            true => Url::parse(url)?,
+            false => Url::parse(&format!("{}/", url))?,
+        };
+        Ok(Self {
+            client: Client::new(),
+            url,
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    fn request(&self, method: Method, href: &str) -> anyhow::Result<RequestBuilder> {
+        Ok(self
+            .client
+            .request(method, self.url.join(href)?)
+            .basic_auth(&self.username, Some(&self.password)))
+    }
+
+    //some servers give whole urls, others paths
+    fn path(&self, href: &str) -> anyhow::Result<String> {
+        Ok(self.url.join(href)?.path().to_string())
+    }
+
+    //where a new event is stored, named after its uid
+    pub fn href(&self, uid: &str) -> anyhow::Result<String> {
+        let name: String = uid
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() || "-_.@".contains(c) {
+                true => c,
+                false => '-',
+            })
+            .collect();
+        self.path(&format!("{}.ics", name))
+    }
+
+    pub async fn list(&self) -> anyhow::Result<Vec<Resource>> {
+        let response = self
+            .request(Method::from_bytes(b"PROPFIND")?, "")?
+            .header("Depth", "1")
+            .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(PROPFIND)
+            .send()
+            .await?
+            .error_for_status()?;
+        parse_multistatus(&response.text().await?)?
+            .into_iter()
+            .map(|resource| {
+                Ok(Resource {
+                    href: self.path(&resource.href)?,
+                    etag: resource.etag,
+                })
+            })
+            .collect()
+    }
+
+    //the calendar object and its ETag
+    pub async fn get(&self, href: &str) -> anyhow::Result<(String, Option<String>)> {
+        let response = self
+            .request(Method::GET, href)?
+            .send()
+            .await?
+            .error_for_status()?;
+        let etag = response_etag(&response);
+        Ok((response.text().await?, etag))
+    }
+
+    //without an ETag the event must not be on the server yet, with one it must
+    //not have changed since
+    pub async fn put(
+        &self,
+        href: &str,
+        calendar: String,
+        etag: Option<&str>,
+    ) -> anyhow::Result<WriteResult> {
+        let request = self
+            .request(Method::PUT, href)?
+            .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+            .body(calendar);
+        let request = match etag {
+            Some(etag) => request.header(header::IF_MATCH, etag),
+            None => request.header(header::IF_NONE_MATCH, "*"),
+        };
+        let response = request.send().await?;
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Ok(WriteResult::Conflict);
+        }
+        let response = response.error_for_status()?;
+        Ok(WriteResult::Written(response_etag(&response)))
+    }
+
+    //an event already gone counts as deleted
+    pub async fn delete(&self, href: &str, etag: &str) -> anyhow::Result<WriteResult> {
+        let response = self
+            .request(Method::DELETE, href)?
+            .header(header::IF_MATCH, etag)
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::PRECONDITION_FAILED => Ok(WriteResult::Conflict),
+            StatusCode::NOT_FOUND => Ok(WriteResult::Written(None)),
+            _ => {
+                response.error_for_status()?;
+                Ok(WriteResult::Written(None))
+            }
+        }
+    }
+}
+
+//what brings the server and the calendar back in line. an ETag that isn't the
+//one from the last sync means the event changed on the server, and when it
+//changed on both sides the server's copy wins since that's what is on phones
+fn plan(remote: &[Resource], links: &[CalDavLink]) -> Vec<Action> {
+    let linked: HashMap<&str, &CalDavLink> = links
+        .iter()
+        .map(|link| (link.href.as_str(), link))
+        .collect();
+    let mut actions: Vec<Action> = remote
+        .iter()
+        .filter_map(|resource| {
+            let href = resource.href.clone();
+            let etag = resource.etag.clone();
+            let Some(link) = linked.get(resource.href.as_str()) else {
+                return Some(Action::Pull { href, etag });
+            };
+            let remote_changed = link.etag.as_deref() != Some(resource.etag.as_str());
+            match link.event_id {
+                _ if remote_changed => Some(Action::Pull { href, etag }),
+                None => Some(Action::DeleteThere { href, etag }),
+                Some(event_id) if link.local_changed => Some(Action::Push {
+                    event_id,
+                    href,
+                    etag,
+                }),
+                Some(_) => None,
+            }
+        })
+        .collect();
+    let hrefs: HashSet<&str> = remote
+        .iter()
+        .map(|resource| resource.href.as_str())
+        .collect();
+    actions.extend(
+        links
+            .iter()
+            .filter(|link| !hrefs.contains(link.href.as_str()))
+            .map(|link| match link.event_id {
+                Some(event_id) => Action::DeleteHere {
+                    event_id,
+                    href: link.href.clone(),
+                },
+                None => Action::Forget {
+                    href: link.href.clone(),
+                },
+            }),
+    );
+    actions
+}
+
+struct AccountSync<'a> {
+    client: CalDavClient,
+    account: &'a CalDavCredentials,
+    users: Vec<UserResponse>,
+    timezone: Tz,
+    pool: &'a PgPool,
+    summary: SyncSummary,
+}
+
+impl AccountSync<'_> {
+    //events without attendees stay as they are here, or are for the owner of a
+    //personal calendar when they're new
+    async fn pull(&mut self, href: &str, etag: Option<&str>) -> anyhow::Result<()> {
+        let (calendar, new_etag) = self.client.get(href).await?;
+        //changes to single occurrences are left out, they stay on the server
+        let mut events = parse_calendar(&calendar, self.timezone);
+        if events.len() > 1 {
+            return Err(anyhow::anyhow!(
+                "{} holds {} events rather than one",
+                href,
+                events.len()
+            ));
+        }
+        let event = events
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("No event at {}", href))?;
+        let mut event = event_data(event, &self.users);
+        if event.attendees.is_empty() {
+            event.attendees = match get_event_by_uid(&event.uid, self.pool).await? {
+                Some(existing) => existing
+                    .attendees
+                    .iter()
+                    .map(|attendee| attendee.username_id)
+                    .collect(),
+                None if !self.account.shared => vec![self.account.username_id],
+                None => vec![],
+            };
+        }
+        let event_id = upsert_event(&event, Some(&self.account.username_id), self.pool).await?;
+        let etag = new_etag.as_deref().or(etag);
+        set_caldav_link(&self.account.id, href, &event_id, etag, self.pool).await?;
+        self.summary.pulled += 1;
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        event_id: &Uuid,
+        href: &str,
+        etag: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let event = ics_event(&get_event(event_id, self.pool).await?);
+        //an edit is written into the server's copy so what the calendar doesn't
+        //keep isn't lost. if that copy changed since, the put is a conflict
+        let patched = match etag {
+            Some(_) => {
+                let (original, _) = self.client.get(href).await?;
+                patch_calendar(&original, &event, Utc::now(), |name| {
+                    find_user(name, &self.users)
+                        .ok()
+                        .map(|user| user.username.clone())
+                })
+            }
+            None => None,
+        };
+        let calendar = patched.unwrap_or_else(|| write_calendar("Household", &[event], Utc::now()));
+        match self.client.put(href, calendar, etag).await? {
+            WriteResult::Written(etag) => {
+                set_caldav_link(&self.account.id, href, event_id, etag.as_deref(), self.pool)
+                    .await?;
+                self.summary.pushed += 1;
+            }
+            WriteResult::Conflict => {
+                self.summary.conflicts += 1;
+                self.pull(href, None).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn apply(&mut self, action: &Action) -> anyhow::Result<()> {
+        match action {
+            Action::Pull { href, etag } => self.pull(href, Some(etag)).await,
+            Action::Push {
+                event_id,
+                href,
+                etag,
+            } => self.write(event_id, href, Some(etag)).await,
+            Action::DeleteHere { event_id, href } => {
+                delete_caldav_link(&self.account.id, href, self.pool).await?;
+                delete_event(event_id, self.pool).await?;
+                self.summary.deleted_here += 1;
+                Ok(())
+            }
+            Action::DeleteThere { href, etag } => {
+                match self.client.delete(href, etag).await? {
+                    WriteResult::Written(_) => {
+                        delete_caldav_link(&self.account.id, href, self.pool).await?;
+                        self.summary.deleted_there += 1;
+                    }
+                    //changed on the server after it was deleted here, so it comes back
+                    WriteResult::Conflict => {
+                        self.summary.conflicts += 1;
+                        self.pull(href, None).await?;
+                    }
+                }
+                Ok(())
+            }
+            Action::Forget { href } => {
+                delete_caldav_link(&self.account.id, href, self.pool).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+//one event failing doesn't stop the rest, it is tried again next time
+pub async fn sync_account(
+    account: &CalDavCredentials,
+    secrets: &Secrets,
+    pool: &PgPool,
+) -> anyhow::Result<SyncSummary> {
+    let password = secrets.open(&account.password)?;
+    //passwords saved before they were encrypted are encrypted now
+    if !is_sealed(&account.password) {
+        set_caldav_password(&account.id, &secrets.seal(&password)?, pool).await?;
+    }
+    let client = CalDavClient::new(&account.url, &account.username, &password)?;
+    let remote = client.list().await?;
+    let links = get_caldav_links(&account.id, pool).await?;
+    let users = get_all_users(pool).await?;
+    let timezone = users
+        .iter()
+        .find(|user| user.id == account.username_id)
+        .and_then(|user| user.timezone.parse().ok())
+        .unwrap_or(Tz::UTC);
+    let mut sync = AccountSync {
+        client,
+        account,
+        users,
+        timezone,
+        pool,
+        summary: SyncSummary::default(),
+    };
+    for action in plan(&remote, &links) {
+        if let Err(e) = sync.apply(&action).await {
+            info!(
+                tool_use = false,
+                endpoint = "caldav",
+                span_id = account.id.to_string(),
+                message = format!("Failed to sync {:?}: {}", action, e)
+            );
+            sync.summary.failed += 1;
+        }
+    }
+    //after pulling, so events already on the server are matched by uid
+    for event_id in get_unlinked_events(&account.id, &account.username_id, pool).await? {
+        let result = match get_event(&event_id, pool).await {
+            Ok(event) => match sync.client.href(&event.uid) {
+                Ok(href) => sync.write(&event_id, &href, None).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            info!(
+                tool_use = false,
+                endpoint = "caldav",
+                span_id = account.id.to_string(),
+                message = format!("Failed to push event {}: {}", event_id, e)
+            );
+            sync.summary.failed += 1;
+        }
+    }
+    Ok(sync.summary)
+}
+
+//the outcome is kept on the account so the user can see why a sync failed
+pub async fn sync(
+    account: &CalDavCredentials,
+    secrets: &Secrets,
+    pool: &PgPool,
+) -> sqlx::Result<Result<SyncSummary, String>> {
+    let result = sync_account(account, secrets, pool)
+        .await
+        .map_err(|e| e.to_string());
+    set_caldav_status(&account.id, result.as_ref().err().map(String::as_str), pool).await?;
+    info!(
+        tool_use = false,
+        endpoint = "caldav",
+        span_id = account.id.to_string(),
+        message = format!(
+            "synced calendar of user {}: {:?}",
+            account.username_id, result
+        )
+    );
+    Ok(result)
+}
+
+//syncs every account in the background for as long as the server runs
+pub fn run_caldav_sync(pool: &PgPool, secrets: &Arc<Secrets>) -> JoinHandle<()> {
+    let pool = pool.clone();
+    let secrets = secrets.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SYNC_INTERVAL);
+        loop {
+            interval.tick().await;
+            let accounts = match get_caldav_credentials(&pool).await {
+                Ok(accounts) => accounts,
+                Err(e) => {
+                    info!(
+                        tool_use = false,
+                        endpoint = "caldav",
+                        message = format!("Failed to get CalDAV accounts: {}", e)
+                    );
+                    continue;
+                }
+            };
+            for account in accounts {
+                if let Err(e) = sync(&account, &secrets, &pool).await {
+                    info!(
+                        tool_use = false,
+                        endpoint = "caldav",
+                        span_id = account.id.to_string(),
+                        message = format!("Failed to sync CalDAV account: {}", e)
+                    );
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, CalDavClient, Resource, WriteResult, parse_multistatus, plan};
+    use crate::psql_caldav::CalDavLink;
+    use uuid::Uuid;
+
+    fn resource(href: &str, etag: &str) -> Resource {
+        Resource {
+            href: href.to_string(),
+            etag: etag.to_string(),
+        }
+    }
+
+    fn link(href: &str, event_id: Option<Uuid>, etag: &str, local_changed: bool) -> CalDavLink {
+        CalDavLink {
+            href: href.to_string(),
+            event_id,
+            etag: Some(etag.to_string()),
+            local_changed,
+        }
+    }
+
+    #[test]
+    fn it_reads_a_multistatus() {
+        let xml = r#"<?xml version='1.0' encoding='utf-8'?>
+<multistatus xmlns="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <response>
+    <href>/sam/calendar/</href>
+    <propstat>
+      <prop><resourcetype><collection /><C:calendar /></resourcetype><getetag>"c0"</getetag></prop>
+      <status>HTTP/1.1 200 OK</status>
+    </propstat>
+  </response>
+  <response>
+    <href>/sam/calendar/soccer.ics</href>
+    <propstat>
+      <prop><resourcetype /><getetag>&quot;e1&quot;</getetag></prop>
+      <status>HTTP/1.1 200 OK</status>
+    </propstat>
+  </response>
+  <d:response xmlns:d="DAV:">
+    <d:href>/sam/calendar/dentist.ics</d:href>
+    <d:propstat><d:prop><d:getetag>"e2"</d:getetag></d:prop></d:propstat>
+  </d:response>
+</multistatus>"#;
+        assert_eq!(
+            parse_multistatus(xml).unwrap(),
+            vec![
+                resource("/sam/calendar/soccer.ics", "\"e1\""),
+                resource("/sam/calendar/dentist.ics", "\"e2\"")
+            ]
+        );
+    }
+
+    #[test]
+    fn it_names_new_events_after_their_uid() {
+        let client = CalDavClient::new("http://localhost:5232/sam/calendar", "sam", "").unwrap();
+        assert_eq!(
+            client.href("1234@draid").unwrap(),
+            "/sam/calendar/1234@draid.ics"
+        );
+        assert_eq!(client.href("a/b c").unwrap(), "/sam/calendar/a-b-c.ics");
+    }
+
+    #[test]
+    fn it_plans_a_sync() {
+        let unchanged = Uuid::new_v4();
+        let edited = Uuid::new_v4();
+        let both = Uuid::new_v4();
+        let gone = Uuid::new_v4();
+        let remote = vec![
+            resource("/new.ics", "n"),
+            resource("/unchanged.ics", "u"),
+            resource("/edited.ics", "e"),
+            resource("/both.ics", "b2"),
+            resource("/deleted.ics", "d"),
+        ];
+        let links = vec![
+            link("/unchanged.ics", Some(unchanged), "u", false),
+            link("/edited.ics", Some(edited), "e", true),
+            link("/both.ics", Some(both), "b1", true),
+            link("/deleted.ics", None, "d", false),
+            link("/gone.ics", Some(gone), "g", false),
+            link("/forgotten.ics", None, "f", false),
+        ];
+        assert_eq!(
+            plan(&remote, &links),
+            vec![
+                Action::Pull {
+                    href: "/new.ics".to_string(),
+                    etag: "n".to_string()
+                },
+                Action::Push {
+                    event_id: edited,
+                    href: "/edited.ics".to_string(),
+                    etag: "e".to_string()
+                },
+                Action::Pull {
+                    href: "/both.ics".to_string(),
+                    etag: "b2".to_string()
+                },
+                Action::DeleteThere {
+                    href: "/deleted.ics".to_string(),
+                    etag: "d".to_string()
+                },
+                Action::DeleteHere {
+                    event_id: gone,
+                    href: "/gone.ics".to_string()
+                },
+                Action::Forget {
+                    href: "/forgotten.ics".to_string()
+                },
+            ]
+        );
+    }
+
+    //needs a CalDAV server with an empty calendar, eg
+    //docker run -p 5232:5232 tomsquest/docker-radicale, then
+    //CALDAV_TEST_URL=http://localhost:5232/test/calendar/ cargo test -- --ignored
+    #[tokio::test]
+    #[ignore]
+    async fn it_writes_to_a_caldav_server() {
+        let url = std::env::var("CALDAV_TEST_URL").unwrap();
+        let username = std::env::var("CALDAV_TEST_USERNAME").unwrap_or_else(|_| "test".to_string());
+        let password = std::env::var("CALDAV_TEST_PASSWORD").unwrap_or_default();
+        let client = CalDavClient::new(&url, &username, &password).unwrap();
+        let href = client.href(&format!("{}@draid", Uuid::new_v4())).unwrap();
+        let calendar = |title: &str| {
+            format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//draid//test//EN\r\nBEGIN:VEVENT\r\n\
+                UID:{}\r\nDTSTAMP:20261020T000000Z\r\nDTSTART:20261020T090000Z\r\n\
+                DTEND:20261020T100000Z\r\nSUMMARY:{}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+                href.rsplit('/').next().unwrap().trim_end_matches(".ics"),
+                title
+            )
+        };
+        assert!(matches!(
+            client.put(&href, calendar("Soccer"), None).await.unwrap(),
+            WriteResult::Written(_)
+        ));
+        //already there
+        assert!(matches!(
+            client.put(&href, calendar("Soccer"), None).await.unwrap(),
+            WriteResult::Conflict
+        ));
+        let listed = client.list().await.unwrap();
+        let etag = listed
+            .iter()
+            .find(|resource| resource.href == href)
+            .map(|resource| resource.etag.clone())
+            .unwrap();
+        let (text, _) = client.get(&href).await.unwrap();
+        assert!(text.contains("SUMMARY:Soccer"));
+        assert!(matches!(
+            client
+                .put(&href, calendar("Tennis"), Some(&etag))
+                .await
+                .unwrap(),
+            WriteResult::Written(_)
+        ));
+        //the etag changed with the edit
+        assert!(matches!(
+            client.delete(&href, &etag).await.unwrap(),
+            WriteResult::Conflict
+        ));
+        let etag = client
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|resource| resource.href == href)
+            .map(|resource| resource.etag)
+            .unwrap();
+        assert!(matches!(
+            client.delete(&href, &etag).await.unwrap(),
+            WriteResult::Written(_)
+        ));
+        assert!(
+            client
+                .list()
+                .await
+                .unwrap()
+                .iter()
+                .all(|resource| resource.href != href)
+        );
+    }
+}
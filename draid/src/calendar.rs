@@ -0,0 +1,742 @@
+use crate::dates::{localize, parse_datetime};
+use crate::fuzzy::best_match;
+use crate::ics::{IcsEvent, parse_calendar, write_calendar};
+use crate::psql_calendar::{
+    Event, EventData, EventRequest, get_event, get_events_between, get_user_events, upsert_event,
+};
+use crate::psql_users::{UserResponse, get_all_users};
+use crate::reminders::{parse_rrule, validate_rrule};
+use crate::tools::{ToolContext, ToolOutput, TypedTool, typed_tool};
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime, Utc};
+use chrono_tz::Tz;
+use poem_openapi::Object;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use tracing::info;
+use uuid::Uuid;
+
+//recurring events are expanded at most this many times per query
+const MAX_OCCURRENCES: u16 = 1000;
+//length of events given only a start
+const DEFAULT_EVENT_MINUTES: i64 = 60;
+//upcoming events are listed this far ahead unless asked otherwise
+pub const UPCOMING_DAYS: i64 = 7;
+
+//start and end
+type Span = (DateTime<Utc>, DateTime<Utc>);
+
+#[derive(Serialize, Object, Clone, Debug, PartialEq)]
+pub struct Occurrence {
+    pub event_id: Uuid,
+    pub title: String,
+    //in the user's timezone
+    pub starts_at: DateTime<FixedOffset>,
+    pub ends_at: DateTime<FixedOffset>,
+    pub all_day: bool,
+    pub location: Option<String>,
+    pub recurring: bool,
+    //nobody means the whole household
+    pub attendees: Vec<String>,
+}
+
+#[derive(Serialize, Object, Clone, Debug, PartialEq)]
+pub struct Period {
+    pub starts_at: DateTime<FixedOffset>,
+    pub ends_at: DateTime<FixedOffset>,
+}
+
+#[derive(Serialize, Object, Clone, Debug, PartialEq)]
+pub struct FreeBusy {
+    pub username: String,
+    pub busy: Vec<Period>,
+    pub free: Vec<Period>,
+}
+
+fn event_timezone(event: &Event) -> Tz {
+    event.timezone.parse().unwrap_or(Tz::UTC)
+}
+
+fn local(time: DateTime<Utc>, timezone: Tz) -> DateTime<FixedOffset> {
+    time.with_timezone(&timezone).fixed_offset()
+}
+
+//events without attendees are for everyone
+fn attends(event: &Event, username_id: &Uuid) -> bool {
+    event.attendees.is_empty()
+        || event
+            .attendees
+            .iter()
+            .any(|attendee| attendee.username_id == *username_id)
+}
+
+//start and end of each occurrence overlapping the range
+pub fn occurrences(event: &Event, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Span> {
+    let length = event.ends_at - event.starts_at;
+    let overlaps =
+        |start: &DateTime<Utc>| *start < to && (*start + length > from || *start >= from);
+    let single = || {
+        if overlaps(&event.starts_at) {
+            vec![(event.starts_at, event.ends_at)]
+        } else {
+            vec![]
+        }
+    };
+    let Some(rrule) = &event.rrule else {
+        return single();
+    };
+    let timezone = rrule::Tz::from(event_timezone(event));
+    let set = match parse_rrule(rrule)
+        .and_then(|rrule| rrule.build(event.starts_at.with_timezone(&timezone)))
+    {
+        Ok(set) => set,
+        //rules are checked when saved, but a bad one shouldn't hide the event
+        Err(_) => return single(),
+    };
+    event
+        .exdates
+        .iter()
+        .fold(set, |set, exdate| {
+            set.exdate(exdate.with_timezone(&timezone))
+        })
+        .after((from - length).with_timezone(&timezone))
+        .before(to.with_timezone(&timezone))
+        .all(MAX_OCCURRENCES)
+        .dates
+        .into_iter()
+        .map(|start| start.with_timezone(&Utc))
+        .filter(overlaps)
+        .map(|start| (start, start + length))
+        .collect()
+}
+
+//everything on between two times, soonest first. with an attendee, only their
+//events and the household's
+pub async fn upcoming(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    attendee: Option<&Uuid>,
+    timezone: Tz,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<Occurrence>> {
+    let events = get_events_between(&from, &to, pool).await?;
+    let mut upcoming: Vec<Occurrence> = events
+        .iter()
+        .filter(|event| attendee.is_none_or(|attendee| attends(event, attendee)))
+        .flat_map(|event| {
+            occurrences(event, from, to)
+                .into_iter()
+                .map(|(starts_at, ends_at)| Occurrence {
+                    event_id: event.id,
+                    title: event.title.clone(),
+                    starts_at: local(starts_at, timezone),
+                    ends_at: local(ends_at, timezone),
+                    all_day: event.all_day,
+                    location: event.location.clone(),
+                    recurring: event.rrule.is_some(),
+                    attendees: event
+                        .attendees
+                        .iter()
+                        .map(|attendee| attendee.username.clone())
+                        .collect(),
+                })
+        })
+        .collect();
+    upcoming.sort_by_key(|occurrence| occurrence.starts_at);
+    Ok(upcoming)
+}
+
+//periods joined where they overlap or touch
+fn merge(mut periods: Vec<Span>) -> Vec<Span> {
+    periods.sort();
+    let mut merged: Vec<Span> = vec![];
+    for (start, end) in periods {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+//the gaps between merged busy periods
+fn gaps(busy: &[Span], from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Span> {
+    let mut free = vec![];
+    let mut cursor = from;
+    for (start, end) in busy {
+        if *start > cursor {
+            free.push((cursor, *start));
+        }
+        cursor = cursor.max(*end);
+    }
+    if cursor < to {
+        free.push((cursor, to));
+    }
+    free
+}
+
+//busy and free periods of a user between two times. all day events, like
+//birthdays, don't make anyone busy
+fn free_busy(
+    events: &[Event],
+    username_id: &Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> (Vec<Span>, Vec<Span>) {
+    let busy = merge(
+        events
+            .iter()
+            .filter(|event| !event.all_day && attends(event, username_id))
+            .flat_map(|event| occurrences(event, from, to))
+            .map(|(start, end)| (start.max(from), end.min(to)))
+            .collect(),
+    );
+    let free = gaps(&busy, from, to);
+    (busy, free)
+}
+
+pub fn ics_event(event: &Event) -> IcsEvent {
+    IcsEvent {
+        uid: event.uid.clone(),
+        title: event.title.clone(),
+        description: event.description.clone(),
+        location: event.location.clone(),
+        starts_at: event.starts_at,
+        ends_at: event.ends_at,
+        all_day: event.all_day,
+        rrule: event.rrule.clone(),
+        exdates: event.exdates.clone(),
+        timezone: event_timezone(event),
+        attendees: event
+            .attendees
+            .iter()
+            .map(|attendee| attendee.username.clone())
+            .collect(),
+    }
+}
+
+//the user's events and the household's, for subscribing to from a calendar app
+pub async fn user_feed(username_id: &Uuid, pool: &PgPool) -> sqlx::Result<String> {
+    let events: Vec<IcsEvent> = get_user_events(username_id, pool)
+        .await?
+        .iter()
+        .map(ics_event)
+        .collect();
+    Ok(write_calendar("Household", &events, Utc::now()))
+}
+
+//long enough not to be guessed
+pub fn new_feed_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+//attendees are matched to users by name, anyone else is left out
+pub fn event_data(event: IcsEvent, users: &[UserResponse]) -> EventData {
+    let rrule = match event.rrule {
+        Some(rrule) if validate_rrule(&rrule, event.starts_at, event.timezone).is_err() => {
+            info!(
+                tool_use = false,
+                endpoint = "calendar",
+                message = format!("Ignoring invalid rrule {} of event {}", rrule, event.uid)
+            );
+            None
+        }
+        rrule => rrule,
+    };
+    let attendees = event
+        .attendees
+        .iter()
+        .filter_map(|name| find_user(name, users).ok())
+        .map(|user| user.id)
+        .collect();
+    EventData {
+        uid: event.uid,
+        title: event.title,
+        description: event.description,
+        location: event.location,
+        starts_at: event.starts_at,
+        ends_at: event.ends_at,
+        all_day: event.all_day,
+        rrule,
+        exdates: event.exdates,
+        timezone: event.timezone.name().to_string(),
+        attendees,
+    }
+}
+
+pub async fn import_calendar(
+    text: &str,
+    timezone: Tz,
+    created_by: &Uuid,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<Uuid>> {
+    let users = get_all_users(pool).await?;
+    let mut ids = vec![];
+    for event in parse_calendar(text, timezone) {
+        let event = event_data(event, &users);
+        ids.push(upsert_event(&event, Some(created_by), pool).await?);
+    }
+    Ok(ids)
+}
+
+pub fn find_user<'a>(name: &str, users: &'a [UserResponse]) -> Result<&'a UserResponse, String> {
+    best_match(name, users.iter().map(|user| user.username.as_str()))
+        .map(|index| &users[index])
+        .ok_or_else(|| format!("No one called {}", name))
+}
+
+//the error is for an event that can't be saved as given
+pub async fn validate_event(
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    rrule: Option<&str>,
+    timezone: &str,
+    attendees: Option<&[Uuid]>,
+    pool: &PgPool,
+) -> sqlx::Result<Result<(), String>> {
+    if ends_at < starts_at {
+        return Ok(Err("An event can't end before it starts".to_string()));
+    }
+    let timezone = match timezone.parse::<Tz>() {
+        Ok(timezone) => timezone,
+        Err(e) => return Ok(Err(format!("Invalid timezone: {}", e))),
+    };
+    if let Some(rrule) = rrule
+        && let Err(e) = validate_rrule(rrule, starts_at, timezone)
+    {
+        return Ok(Err(format!("Invalid rrule: {}", e)));
+    }
+    if let Some(attendees) = attendees {
+        let users: HashSet<Uuid> = get_all_users(pool)
+            .await?
+            .into_iter()
+            .map(|user| user.id)
+            .collect();
+        if let Some(unknown) = attendees.iter().find(|attendee| !users.contains(attendee)) {
+            return Ok(Err(format!("No user with id {}", unknown)));
+        }
+    }
+    Ok(Ok(()))
+}
+
+pub async fn create_event(
+    request: &EventRequest,
+    timezone: Tz,
+    created_by: &Uuid,
+    pool: &PgPool,
+) -> sqlx::Result<Result<Uuid, String>> {
+    if let Err(e) = validate_event(
+        request.starts_at,
+        request.ends_at,
+        request.rrule.as_deref(),
+        request.timezone.as_deref().unwrap_or(timezone.name()),
+        Some(&request.attendees),
+        pool,
+    )
+    .await?
+    {
+        return Ok(Err(e));
+    }
+    let event = EventData {
+        uid: format!("{}@draid", Uuid::new_v4()),
+        title: request.title.clone(),
+        description: request.description.clone(),
+        location: request.location.clone(),
+        starts_at: request.starts_at,
+        ends_at: request.ends_at,
+        all_day: request.all_day,
+        rrule: request.rrule.clone(),
+        exdates: vec![],
+        timezone: request
+            .timezone
+            .clone()
+            .unwrap_or_else(|| timezone.name().to_string()),
+        attendees: request.attendees.clone(),
+    };
+    Ok(Ok(upsert_event(&event, Some(created_by), pool).await?))
+}
+
+fn parse_time(text: &str, context: &ToolContext) -> Result<DateTime<Utc>, String> {
+    let now = Utc::now().with_timezone(&context.timezone);
+    parse_datetime(text, now)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| e.to_string())
+}
+
+//a range is from now for a week unless given
+fn parse_range(
+    from: Option<&str>,
+    until: Option<&str>,
+    context: &ToolContext,
+) -> Result<Span, String> {
+    let from = match from {
+        Some(from) => parse_time(from, context)?,
+        None => Utc::now(),
+    };
+    let to = match until {
+        Some(until) => parse_time(until, context)?,
+        None => from + Duration::days(UPCOMING_DAYS),
+    };
+    if to <= from {
+        return Err("The end of the range has to be after its start".to_string());
+    }
+    Ok((from, to))
+}
+
+#[derive(Clone)]
+pub struct UpcomingEventsTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl UpcomingEventsTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "upcoming_events".to_string(),
+            description: "List what's on the household calendar between two times, eg \
+                from \"saturday\" until \"monday\" for the weekend. \
+                Defaults to the next 7 days"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UpcomingEventsArgs {
+    /// Start of the range, eg "tomorrow" or "2026-11-01 09:00". Defaults to now
+    #[serde(default)]
+    from: Option<String>,
+    /// End of the range, not included, eg "monday"
+    #[serde(default)]
+    until: Option<String>,
+    /// Username to list events for, leaving out events only for others
+    #[serde(default)]
+    who: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for UpcomingEventsTool {
+    type Args = UpcomingEventsArgs;
+    type Output = ToolOutput<Vec<Occurrence>>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: UpcomingEventsArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let (from, to) = match parse_range(args.from.as_deref(), args.until.as_deref(), context) {
+            Ok(range) => range,
+            Err(e) => return Ok(ToolOutput::Error(e)),
+        };
+        let attendee = match args.who {
+            Some(who) => match find_user(&who, &get_all_users(&self.pool).await?) {
+                Ok(user) => Some(user.id),
+                Err(e) => return Ok(ToolOutput::Error(e)),
+            },
+            None => None,
+        };
+        Ok(ToolOutput::Result(
+            upcoming(from, to, attendee.as_ref(), context.timezone, &self.pool).await?,
+        ))
+    }
+}
+typed_tool!(UpcomingEventsTool);
+
+#[derive(Clone)]
+pub struct FreeBusyTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl FreeBusyTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "free_busy".to_string(),
+            description: "Find when household members are busy or free between two times, \
+                eg to find a time that suits everyone"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct FreeBusyArgs {
+    /// Start of the range, eg "tomorrow 9am"
+    from: String,
+    /// End of the range, eg "tomorrow 5pm"
+    until: String,
+    /// Usernames to check. Defaults to the user
+    #[serde(default)]
+    who: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for FreeBusyTool {
+    type Args = FreeBusyArgs;
+    type Output = ToolOutput<Vec<FreeBusy>>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: FreeBusyArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let (from, to) = match parse_range(Some(&args.from), Some(&args.until), context) {
+            Ok(range) => range,
+            Err(e) => return Ok(ToolOutput::Error(e)),
+        };
+        let users = get_all_users(&self.pool).await?;
+        let mut who = vec![];
+        for name in &args.who {
+            match find_user(name, &users) {
+                Ok(user) => who.push(user),
+                Err(e) => return Ok(ToolOutput::Error(e)),
+            }
+        }
+        if who.is_empty() {
+            who.extend(users.iter().find(|user| user.id == context.user_id));
+        }
+        let events = get_events_between(&from, &to, &self.pool).await?;
+        let period = |(start, end): Span| Period {
+            starts_at: local(start, context.timezone),
+            ends_at: local(end, context.timezone),
+        };
+        Ok(ToolOutput::Result(
+            who.iter()
+                .map(|user| {
+                    let (busy, free) = free_busy(&events, &user.id, from, to);
+                    FreeBusy {
+                        username: user.username.clone(),
+                        busy: busy.into_iter().map(period).collect(),
+                        free: free.into_iter().map(period).collect(),
+                    }
+                })
+                .collect(),
+        ))
+    }
+}
+typed_tool!(FreeBusyTool);
+
+#[derive(Clone)]
+pub struct CreateEventTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl CreateEventTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "create_event".to_string(),
+            description: "Add an event to the household calendar. Use an rrule for \
+                recurring events, eg FREQ=WEEKLY;BYDAY=SA for every Saturday"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateEventArgs {
+    title: String,
+    /// When it starts, eg "saturday at 10am" or "2026-11-01 09:00"
+    start: String,
+    /// When it ends. Defaults to an hour after it starts, or the same day for all day events
+    #[serde(default)]
+    end: Option<String>,
+    /// Length in minutes, instead of an end
+    #[serde(default)]
+    duration_minutes: Option<i64>,
+    /// Whether it takes whole days, eg a birthday or a trip
+    #[serde(default)]
+    all_day: bool,
+    #[serde(default)]
+    location: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    /// RFC 5545 recurrence rule for recurring events, eg FREQ=MONTHLY;BYMONTHDAY=1
+    #[serde(default)]
+    rrule: Option<String>,
+    /// Usernames of who it is for. Leave out for the whole household
+    #[serde(default)]
+    attendees: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for CreateEventTool {
+    type Args = CreateEventArgs;
+    type Output = ToolOutput<Event>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: CreateEventArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let timezone = context.timezone;
+        let now = Utc::now().with_timezone(&timezone);
+        let parse = |text: &str| parse_datetime(text, now).map_err(|e| e.to_string());
+        let times = parse(&args.start).and_then(|start| {
+            let end = args.end.as_deref().map(parse).transpose()?;
+            if args.all_day {
+                //all day events end at midnight after their last day
+                let last = end.unwrap_or(start).date_naive() + Duration::days(1);
+                let midnight = |date: chrono::NaiveDate| {
+                    localize(&timezone, date.and_time(NaiveTime::MIN))
+                        .map(|time| time.with_timezone(&Utc))
+                        .map_err(|e| e.to_string())
+                };
+                return Ok((midnight(start.date_naive())?, midnight(last)?));
+            }
+            let end = match (end, args.duration_minutes) {
+                (Some(end), _) => end,
+                (None, Some(minutes)) => Duration::try_minutes(minutes)
+                    .filter(|duration| *duration >= Duration::zero())
+                    .and_then(|duration| start.checked_add_signed(duration))
+                    .ok_or_else(|| format!("An event can't last {} minutes", minutes))?,
+                (None, None) => start + Duration::minutes(DEFAULT_EVENT_MINUTES),
+            };
+            Ok((start.with_timezone(&Utc), end.with_timezone(&Utc)))
+        });
+        let (starts_at, ends_at) = match times {
+            Ok(times) => times,
+            Err(e) => return Ok(ToolOutput::Error(e)),
+        };
+        let users = get_all_users(&self.pool).await?;
+        let mut attendees = vec![];
+        for name in &args.attendees {
+            match find_user(name, &users) {
+                Ok(user) => attendees.push(user.id),
+                Err(e) => return Ok(ToolOutput::Error(e)),
+            }
+        }
+        let request = EventRequest {
+            title: args.title,
+            description: args.description,
+            location: args.location,
+            starts_at,
+            ends_at,
+            all_day: args.all_day,
+            rrule: args.rrule,
+            timezone: None,
+            attendees,
+        };
+        Ok(
+            match create_event(&request, timezone, &context.user_id, &self.pool).await? {
+                Ok(id) => ToolOutput::Result(get_event(&id, &self.pool).await?),
+                Err(e) => ToolOutput::Error(e),
+            },
+        )
+    }
+}
+typed_tool!(CreateEventTool);
+
+#[cfg(test)]
+mod tests {
+    use super::{free_busy, gaps, merge, occurrences};
+    use crate::psql_calendar::{Event, EventAttendee};
+    use chrono::{DateTime, TimeZone, Utc};
+    use uuid::Uuid;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap()
+    }
+
+    fn event(starts_at: DateTime<Utc>, hours: i64, rrule: Option<&str>) -> Event {
+        Event {
+            id: Uuid::new_v4(),
+            uid: "event".to_string(),
+            title: "Event".to_string(),
+            description: None,
+            location: None,
+            starts_at,
+            ends_at: starts_at + chrono::Duration::hours(hours),
+            all_day: false,
+            rrule: rrule.map(String::from),
+            exdates: vec![],
+            timezone: "America/New_York".to_string(),
+            attendees: vec![],
+        }
+    }
+
+    #[test]
+    fn it_expands_recurring_events_in_range() {
+        //saturdays at 9am in new york, across the end of daylight saving on nov 1st
+        let mut soccer = event(at(17, 13), 2, Some("FREQ=WEEKLY;BYDAY=SA"));
+        soccer.exdates = vec![at(31, 13)];
+        let found = occurrences(
+            &soccer,
+            at(24, 14),
+            Utc.with_ymd_and_hms(2026, 11, 10, 0, 0, 0).unwrap(),
+        );
+        assert_eq!(
+            found.iter().map(|(start, _)| *start).collect::<Vec<_>>(),
+            vec![
+                at(24, 13),
+                Utc.with_ymd_and_hms(2026, 11, 7, 14, 0, 0).unwrap()
+            ]
+        );
+        assert!(occurrences(&event(at(20, 9), 1, None), at(21, 0), at(22, 0)).is_empty());
+        assert_eq!(
+            occurrences(&event(at(20, 9), 1, None), at(20, 0), at(21, 0)),
+            vec![(at(20, 9), at(20, 10))]
+        );
+    }
+
+    #[test]
+    fn it_merges_busy_periods() {
+        let busy = merge(vec![
+            (at(20, 13), at(20, 14)),
+            (at(20, 9), at(20, 11)),
+            (at(20, 10), at(20, 12)),
+            (at(20, 12), at(20, 13)),
+            (at(20, 16), at(20, 17)),
+        ]);
+        assert_eq!(
+            busy,
+            vec![(at(20, 9), at(20, 14)), (at(20, 16), at(20, 17))]
+        );
+        assert_eq!(
+            gaps(&busy, at(20, 8), at(20, 18)),
+            vec![
+                (at(20, 8), at(20, 9)),
+                (at(20, 14), at(20, 16)),
+                (at(20, 17), at(20, 18))
+            ]
+        );
+    }
+
+    #[test]
+    fn it_finds_free_time_for_attendees() {
+        let sam = Uuid::new_v4();
+        let mut dentist = event(at(20, 10), 1, None);
+        dentist.attendees = vec![EventAttendee {
+            username_id: Uuid::new_v4(),
+            username: "alex".to_string(),
+        }];
+        let dinner = event(at(20, 17), 2, None);
+        let mut birthday = event(at(20, 0), 24, None);
+        birthday.all_day = true;
+        let (busy, free) = free_busy(&[dentist, dinner, birthday], &sam, at(20, 8), at(20, 18));
+        assert_eq!(busy, vec![(at(20, 17), at(20, 18))]);
+        assert_eq!(free, vec![(at(20, 8), at(20, 17))]);
+    }
+}
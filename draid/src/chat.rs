@@ -0,0 +1,185 @@
+use crate::auth::UserIdentification;
+use crate::llm::{Bot, FullMessage, TokenSink, ToolCallTrace, chat_with_tools};
+use crate::mcp_tools::McpServers;
+use crate::models::{ChatResponse, Source, SourceKind};
+use crate::psql_mcp::{BotResource, get_bot_resources};
+use crate::psql_memory::{MessageResult, PsqlMemory, write_ai_message, write_human_message};
+use crate::psql_vectors::get_knowledge_bases;
+use crate::tools::ToolContext;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, Level, info, span};
+use uuid::Uuid;
+
+//messages of the session the bot sees
+const HISTORY_MESSAGES: usize = 100;
+
+async fn answer<S: TokenSink + ?Sized>(
+    bot: &Bot,
+    tx: &mut S,
+    messages: &[MessageResult],
+    prompt: &str,
+    session_id: Uuid,
+    user: &UserIdentification,
+) -> anyhow::Result<FullMessage> {
+    let context = ToolContext {
+        user_id: user.id,
+        roles: user.roles.clone(),
+        session_id,
+        bot_name: bot.name().to_string(),
+        span_id: Uuid::new_v4().to_string(),
+        cancellation_token: CancellationToken::new(),
+        timezone: user.timezone,
+    };
+    let span_id = context.span_id.clone();
+    //stops any tool calls still running for this turn when it fails, or when it's
+    //dropped because the client went away or stopped the answer
+    let _cancel_tools = context.cancellation_token.clone().drop_guard();
+    //chat_with_tools produces each token in the stream to tx
+    chat_with_tools(bot, tx, messages, prompt, &context)
+        .instrument(span!(
+            Level::INFO,
+            "chat_with_tools",
+            endpoint = "query",
+            tool_use = false
+        ))
+        .await
+        .inspect_err(|e| {
+            info!(
+                tool_use = false,
+                endpoint = "query",
+                span_id,
+                message = e.to_string()
+            );
+        })
+}
+
+//one exchange with a bot, whichever way the user reached it. the bot gets the
+//session's history and its resources, and the prompt and answer are saved
+pub async fn chat_turn<S: TokenSink + ?Sized>(
+    bot: &Bot,
+    tx: &mut S,
+    prompt: &str,
+    session_id: Uuid,
+    user: &UserIdentification,
+    pool: &PgPool,
+    servers: &McpServers,
+) -> anyhow::Result<FullMessage> {
+    let psql_memory = PsqlMemory::new(HISTORY_MESSAGES, session_id, user.id, pool.clone());
+    let resources = get_bot_resources(bot.name(), pool).await?;
+    let mut messages = servers.resource_context(&resources).await;
+    messages.extend(psql_memory.messages().await?);
+    write_human_message(prompt.to_string(), &psql_memory).await?;
+    let full_message = answer(bot, tx, &messages, prompt, session_id, user).await?;
+    write_ai_message(
+        full_message.message.clone(),
+        full_message.reasoning.clone(),
+        &psql_memory,
+    )
+    .await?;
+    Ok(full_message)
+}
+
+//an exchange where the caller keeps the history, nothing is saved. its session
+//is the same for each of the user's exchanges with the bot, so a confirmation
+//asked for in one request can be given in the next
+pub async fn chat_once<S: TokenSink + ?Sized>(
+    bot: &Bot,
+    tx: &mut S,
+    history: Vec<MessageResult>,
+    prompt: &str,
+    user: &UserIdentification,
+    pool: &PgPool,
+    servers: &McpServers,
+) -> anyhow::Result<FullMessage> {
+    let resources = get_bot_resources(bot.name(), pool).await?;
+    let mut messages = servers.resource_context(&resources).await;
+    messages.extend(history);
+    let session_id = Uuid::new_v5(&user.id, bot.name().as_bytes());
+    answer(bot, tx, &messages, prompt, session_id, user).await
+}
+
+//where the answer came from: the bot's resources, and the passages from any
+//knowledge base it searched
+fn sources(
+    resources: &[BotResource],
+    tool_calls: &[ToolCallTrace],
+    knowledge_bases: &[String],
+) -> Vec<Source> {
+    let resources = resources.iter().map(|resource| Source {
+        kind: SourceKind::Resource,
+        name: resource.uri.clone(),
+        passages: vec![],
+    });
+    let searches = tool_calls
+        .iter()
+        .filter(|tool_call| knowledge_bases.contains(&tool_call.name))
+        .map(|tool_call| Source {
+            kind: SourceKind::KnowledgeBase,
+            name: tool_call.name.clone(),
+            passages: tool_call.result["result"]
+                .as_array()
+                .map(|passages| {
+                    passages
+                        .iter()
+                        .filter_map(|passage| passage.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        });
+    resources.chain(searches).collect()
+}
+
+//the full message with how the bot arrived at it
+pub async fn chat_response(
+    bot: &Bot,
+    full_message: FullMessage,
+    pool: &PgPool,
+) -> anyhow::Result<ChatResponse> {
+    let resources = get_bot_resources(bot.name(), pool).await?;
+    let knowledge_bases: Vec<String> = get_knowledge_bases(pool)
+        .await?
+        .into_iter()
+        .map(|kb| kb.name)
+        .collect();
+    Ok(ChatResponse {
+        sources: sources(&resources, &full_message.tool_calls, &knowledge_bases),
+        message: full_message.message,
+        reasoning: full_message.reasoning,
+        tool_calls: full_message.tool_calls,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn it_collects_sources() {
+        let resources = vec![BotResource {
+            id: Uuid::new_v4(),
+            bot: "helper".to_string(),
+            server: "files".to_string(),
+            uri: "file:///house-rules.md".to_string(),
+        }];
+        let tool_calls = vec![
+            ToolCallTrace {
+                name: "recipes".to_string(),
+                arguments: json!({"content": "pasta"}),
+                result: json!({"result": ["boil the pasta", "drain it"]}),
+            },
+            ToolCallTrace {
+                name: "calculator".to_string(),
+                arguments: json!({"expression": "1+1"}),
+                result: json!({"result": 2}),
+            },
+        ];
+        let sources = sources(&resources, &tool_calls, &["recipes".to_string()]);
+        assert_eq!(sources.len(), 2);
+        assert!(matches!(sources[0].kind, SourceKind::Resource));
+        assert_eq!(sources[0].name, "file:///house-rules.md");
+        assert!(matches!(sources[1].kind, SourceKind::KnowledgeBase));
+        assert_eq!(sources[1].passages, vec!["boil the pasta", "drain it"]);
+    }
+}
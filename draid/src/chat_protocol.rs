@@ -0,0 +1,807 @@
+use crate::llm::{FullMessage, TokenSink, ToolCallTrace, WebSocketToken};
+use chrono::{DateTime, Duration, Utc};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use poem::web::websocket::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::watch;
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::info;
+use uuid::Uuid;
+
+//the chat websocket's protocol: json envelopes in both directions, with the
+//socket kept open across turns. the id of a prompt is on everything sent for it,
+//and each event of an answer has an event_id to resume from
+pub const PROTOCOL_VERSION: u32 = 2;
+
+//the original protocol is still spoken by clients that ask for version 1
+pub fn uses_envelopes(version: u32) -> Result<bool, String> {
+    match version {
+        1 => Ok(false),
+        PROTOCOL_VERSION => Ok(true),
+        _ => Err(format!("Unknown protocol version {}", version)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ClientEnvelope {
+    Prompt {
+        id: String,
+        text: String,
+    },
+    //stops the answer to the prompt with the id
+    Stop {
+        id: String,
+    },
+    //agrees to an action the bot asked to confirm
+    Approve {
+        id: String,
+        confirmation: String,
+    },
+    //replays the events of an answer after the last one received, then continues
+    Resume {
+        id: String,
+        last_event_id: Option<usize>,
+    },
+    Ping {
+        id: String,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ServerEnvelope {
+    Hello {
+        version: u32,
+    },
+    Token {
+        id: String,
+        #[serde(flatten)]
+        token: WebSocketToken,
+    },
+    Done {
+        id: String,
+        message: String,
+        reasoning: String,
+        tool_calls: Vec<ToolCallTrace>,
+        //the answer was stopped, so it isn't saved
+        stopped: bool,
+    },
+    Error {
+        id: Option<String>,
+        message: String,
+    },
+    Pong {
+        id: String,
+    },
+}
+
+impl ServerEnvelope {
+    fn done(id: String, full_message: FullMessage) -> Self {
+        Self::Done {
+            id,
+            message: full_message.message,
+            reasoning: full_message.reasoning,
+            tool_calls: full_message.tool_calls,
+            stopped: false,
+        }
+    }
+
+    fn stopped(id: String) -> Self {
+        Self::Done {
+            id,
+            message: "".to_string(),
+            reasoning: "".to_string(),
+            tool_calls: vec![],
+            stopped: true,
+        }
+    }
+
+    fn error(id: Option<String>, message: impl ToString) -> Self {
+        Self::Error {
+            id,
+            message: message.to_string(),
+        }
+    }
+}
+
+//how long a finished answer can still be resumed
+const RESUMABLE_MINUTES: i64 = 10;
+//an answer still going after this long is stopped, eg a tool that hangs
+const ANSWER_MINUTES: i64 = 30;
+//answers kept for each user, finished ones are dropped first to make room
+const MAX_ANSWERS_PER_USER: usize = 20;
+const EVICTION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+struct AnswerState {
+    //serialized envelopes, the event id of each is its position counting from 1
+    events: Vec<String>,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+//the events of one answer, kept apart from the connection that asked for it so
+//the answer is generated and saved even if the client goes away
+struct Answer {
+    state: Mutex<AnswerState>,
+    //the number of events and whether the answer is finished
+    progress: watch::Sender<(usize, bool)>,
+    task: Mutex<Option<AbortHandle>>,
+}
+
+impl Answer {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(AnswerState {
+                events: vec![],
+                started_at: Utc::now(),
+                finished_at: None,
+            }),
+            progress: watch::Sender::new((0, false)),
+            task: Mutex::new(None),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, AnswerState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    //events after the answer finished, eg tokens of a stopped answer, are dropped
+    fn push(&self, envelope: &ServerEnvelope, finish: bool) {
+        let mut state = self.state();
+        if state.finished_at.is_some() {
+            return;
+        }
+        let mut value = serde_json::to_value(envelope).unwrap_or_default();
+        value["event_id"] = (state.events.len() + 1).into();
+        state.events.push(value.to_string());
+        if finish {
+            state.finished_at = Some(Utc::now());
+        }
+        self.progress.send_replace((state.events.len(), finish));
+    }
+
+    //aborting the task drops the generation, which cancels any tools it called
+    fn cancel(&self, envelope: &ServerEnvelope) {
+        if let Some(task) = self.task.lock().ok().and_then(|mut task| task.take()) {
+            task.abort();
+        }
+        self.push(envelope, true);
+    }
+
+    fn stop(&self, id: String) {
+        self.cancel(&ServerEnvelope::stopped(id));
+    }
+
+    fn is_finished(&self) -> bool {
+        self.state().finished_at.is_some()
+    }
+
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.state()
+            .finished_at
+            .is_some_and(|finished_at| now - finished_at >= Duration::minutes(RESUMABLE_MINUTES))
+    }
+
+    fn is_hung(&self, now: DateTime<Utc>) -> bool {
+        let state = self.state();
+        state.finished_at.is_none() && now - state.started_at >= Duration::minutes(ANSWER_MINUTES)
+    }
+
+    //waits for events after the first `from`, returning them and whether the
+    //answer is finished
+    async fn events_after(&self, from: usize) -> (Vec<String>, bool) {
+        let mut progress = self.progress.subscribe();
+        let finished = progress
+            .wait_for(|(count, finished)| *count > from || *finished)
+            .await
+            .map(|progress| progress.1)
+            //the sender lives as long as the answer
+            .unwrap_or(true);
+        let state = self.state();
+        let events = state.events.get(from..).unwrap_or_default().to_vec();
+        (events, finished)
+    }
+}
+
+//wraps each token in an envelope for the prompt it answers
+pub struct EnvelopeTokens {
+    id: String,
+    answer: Arc<Answer>,
+}
+
+#[async_trait::async_trait]
+impl TokenSink for EnvelopeTokens {
+    async fn send_token(&mut self, token: WebSocketToken) -> anyhow::Result<()> {
+        let envelope = ServerEnvelope::Token {
+            id: self.id.clone(),
+            token,
+        };
+        self.answer.push(&envelope, false);
+        Ok(())
+    }
+}
+
+type AnswerMap = HashMap<(Uuid, String), Arc<Answer>>;
+
+//drops expired answers, and stops and drops hung ones
+fn evict(answers: &mut AnswerMap, now: DateTime<Utc>) {
+    answers.retain(|(_, id), answer| {
+        if answer.is_hung(now) {
+            let error = ServerEnvelope::error(Some(id.clone()), "The answer took too long");
+            answer.cancel(&error);
+            return false;
+        }
+        !answer.is_expired(now)
+    });
+}
+
+//makes room for another answer of the user by dropping their oldest finished
+//answers, false if all of them are still in progress
+fn make_room(answers: &mut AnswerMap, user_id: Uuid) -> bool {
+    let mut finished: Vec<(DateTime<Utc>, String)> = answers
+        .iter()
+        .filter(|((user, _), _)| *user == user_id)
+        .filter_map(|((_, id), answer)| answer.state().finished_at.map(|at| (at, id.clone())))
+        .collect();
+    finished.sort();
+    let mut count = answers.keys().filter(|(user, _)| *user == user_id).count();
+    for (_, id) in finished {
+        if count < MAX_ANSWERS_PER_USER {
+            break;
+        }
+        answers.remove(&(user_id, id));
+        count -= 1;
+    }
+    count < MAX_ANSWERS_PER_USER
+}
+
+//answers in progress and recently finished, by user and message id
+pub struct AnswerStreams {
+    answers: Mutex<AnswerMap>,
+}
+
+impl AnswerStreams {
+    pub fn new() -> Self {
+        Self {
+            answers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn answers(&self) -> MutexGuard<'_, AnswerMap> {
+        self.answers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn get(&self, user_id: Uuid, id: &str) -> Option<Arc<Answer>> {
+        self.answers().get(&(user_id, id.to_string())).cloned()
+    }
+
+    fn evict(&self, now: DateTime<Utc>) {
+        evict(&mut self.answers(), now);
+    }
+
+    //generates the answer in the background, an error if it can't be started
+    fn start<F>(
+        &self,
+        user_id: Uuid,
+        id: &str,
+        answer: impl FnOnce(EnvelopeTokens) -> F,
+    ) -> Result<Arc<Answer>, &'static str>
+    where
+        F: Future<Output = anyhow::Result<FullMessage>> + Send + 'static,
+    {
+        let mut answers = self.answers();
+        evict(&mut answers, Utc::now());
+        let key = (user_id, id.to_string());
+        if answers.contains_key(&key) {
+            return Err("The id is already in use");
+        }
+        if !make_room(&mut answers, user_id) {
+            return Err("Too many answers in progress, stop one first");
+        }
+        let buffer = Arc::new(Answer::new());
+        let tokens = EnvelopeTokens {
+            id: id.to_string(),
+            answer: buffer.clone(),
+        };
+        let generation = answer(tokens);
+        let task = tokio::spawn({
+            let buffer = buffer.clone();
+            let id = id.to_string();
+            async move {
+                let envelope = match generation.await {
+                    Ok(full_message) => ServerEnvelope::done(id, full_message),
+                    Err(e) => {
+                        info!(
+                            tool_use = false,
+                            endpoint = "chat_session",
+                            message = format!("Error in chat session: {}", e)
+                        );
+                        ServerEnvelope::error(Some(id), e)
+                    }
+                };
+                buffer.push(&envelope, true);
+            }
+        });
+        if let Ok(mut handle) = buffer.task.lock() {
+            *handle = Some(task.abort_handle());
+        }
+        answers.insert(key, buffer.clone());
+        Ok(buffer)
+    }
+}
+
+//drops old answers in the background for as long as the server runs, so
+//answers nobody comes back for don't pile up
+pub fn run_eviction(answers: &Arc<AnswerStreams>) -> JoinHandle<()> {
+    let answers = answers.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EVICTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            answers.evict(Utc::now());
+        }
+    })
+}
+
+enum Incoming {
+    Envelope(ClientEnvelope),
+    Invalid(String),
+    Ignored,
+    Closed,
+}
+
+fn parse(message: Option<Result<Message, impl std::fmt::Display>>) -> Incoming {
+    match message {
+        Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+            Ok(envelope) => Incoming::Envelope(envelope),
+            Err(e) => Incoming::Invalid(e.to_string()),
+        },
+        Some(Ok(Message::Binary(_))) => Incoming::Invalid("Expected a JSON envelope".to_string()),
+        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => Incoming::Closed,
+        Some(Ok(_)) => Incoming::Ignored,
+    }
+}
+
+async fn send<O>(outgoing: &mut O, envelope: &ServerEnvelope) -> anyhow::Result<()>
+where
+    O: Sink<Message> + Unpin,
+    O::Error: std::error::Error + Send + Sync + 'static,
+{
+    outgoing
+        .send(Message::Text(serde_json::to_string(envelope)?))
+        .await?;
+    Ok(())
+}
+
+//what the bot is asked for each envelope that starts a turn
+fn prompt_text(envelope: ClientEnvelope) -> Option<(String, String)> {
+    match envelope {
+        ClientEnvelope::Prompt { id, text } => Some((id, text)),
+        ClientEnvelope::Approve { id, confirmation } => Some((
+            id,
+            format!("Yes, go ahead. The confirmation is {}", confirmation),
+        )),
+        _ => None,
+    }
+}
+
+fn envelope_id(envelope: &ClientEnvelope) -> &str {
+    match envelope {
+        ClientEnvelope::Prompt { id, .. }
+        | ClientEnvelope::Stop { id }
+        | ClientEnvelope::Approve { id, .. }
+        | ClientEnvelope::Resume { id, .. }
+        | ClientEnvelope::Ping { id } => id,
+    }
+}
+
+//answers prompts one at a time until the client closes the socket. while an
+//answer streams the client can still ping, or stop it. if the socket closes the
+//answer carries on, and a client can resume it from the last event it received
+pub async fn chat_session<I, O, E, F>(
+    mut incoming: I,
+    mut outgoing: O,
+    answers: &AnswerStreams,
+    user_id: Uuid,
+    mut answer: impl FnMut(String, EnvelopeTokens) -> F,
+) -> anyhow::Result<()>
+where
+    I: Stream<Item = Result<Message, E>> + Unpin,
+    E: std::fmt::Display,
+    O: Sink<Message> + Unpin,
+    O::Error: std::error::Error + Send + Sync + 'static,
+    F: Future<Output = anyhow::Result<FullMessage>> + Send + 'static,
+{
+    send(
+        &mut outgoing,
+        &ServerEnvelope::Hello {
+            version: PROTOCOL_VERSION,
+        },
+    )
+    .await?;
+    loop {
+        let envelope = match parse(incoming.next().await) {
+            Incoming::Envelope(envelope) => envelope,
+            Incoming::Invalid(e) => {
+                send(&mut outgoing, &ServerEnvelope::error(None, e)).await?;
+                continue;
+            }
+            Incoming::Ignored => continue,
+            Incoming::Closed => return Ok(()),
+        };
+        let (id, current, mut next_event) = match envelope {
+            ClientEnvelope::Ping { id } => {
+                send(&mut outgoing, &ServerEnvelope::Pong { id }).await?;
+                continue;
+            }
+            ClientEnvelope::Stop { id } => {
+                match answers.get(user_id, &id) {
+                    Some(current) if !current.is_finished() => current.stop(id),
+                    _ => {
+                        let error = ServerEnvelope::error(Some(id), "Nothing is being answered");
+                        send(&mut outgoing, &error).await?;
+                    }
+                }
+                continue;
+            }
+            ClientEnvelope::Resume { id, last_event_id } => match answers.get(user_id, &id) {
+                Some(current) => (id, current, last_event_id.unwrap_or(0)),
+                None => {
+                    let error = ServerEnvelope::error(Some(id), "There is no answer to resume");
+                    send(&mut outgoing, &error).await?;
+                    continue;
+                }
+            },
+            envelope => {
+                let Some((id, prompt)) = prompt_text(envelope) else {
+                    continue;
+                };
+                let started = answers.start(user_id, &id, |tokens| answer(prompt, tokens));
+                match started {
+                    Ok(current) => (id, current, 0),
+                    Err(e) => {
+                        let error = ServerEnvelope::error(Some(id), e);
+                        send(&mut outgoing, &error).await?;
+                        continue;
+                    }
+                }
+            }
+        };
+        loop {
+            tokio::select! {
+                (events, finished) = current.events_after(next_event) => {
+                    next_event += events.len();
+                    for event in events {
+                        outgoing.send(Message::Text(event)).await?;
+                    }
+                    if finished {
+                        break;
+                    }
+                }
+                message = incoming.next() => match parse(message) {
+                    Incoming::Envelope(ClientEnvelope::Stop { id: stop_id }) if stop_id == id => {
+                        //the stopped event is sent like any other
+                        current.stop(stop_id);
+                    }
+                    Incoming::Envelope(ClientEnvelope::Ping { id }) => {
+                        send(&mut outgoing, &ServerEnvelope::Pong { id }).await?;
+                    }
+                    Incoming::Envelope(envelope) => {
+                        let error = ServerEnvelope::error(
+                            Some(envelope_id(&envelope).to_string()),
+                            format!("Still answering {}, wait for it or stop it", id),
+                        );
+                        send(&mut outgoing, &error).await?;
+                    }
+                    Incoming::Invalid(e) => {
+                        send(&mut outgoing, &ServerEnvelope::error(None, e)).await?;
+                    }
+                    Incoming::Ignored => (),
+                    //the answer carries on without the client
+                    Incoming::Closed => return Ok(()),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AnswerStreams, EnvelopeTokens, MAX_ANSWERS_PER_USER, chat_session, uses_envelopes,
+    };
+    use crate::llm::{FullMessage, TokenSink, WebSocketToken};
+    use crate::models::ChatQuery;
+    use chrono::{Duration, Utc};
+    use futures::channel::mpsc;
+    use futures::{SinkExt, StreamExt};
+    use poem::web::websocket::Message;
+    use serde_json::{Value, json};
+    use std::convert::Infallible;
+    use std::sync::Arc;
+    use tokio::sync::Notify;
+    use tokio_util::sync::CancellationToken;
+    use uuid::Uuid;
+
+    type Client = mpsc::UnboundedSender<Result<Message, Infallible>>;
+    type Server = mpsc::UnboundedReceiver<Message>;
+
+    fn full_message(message: &str) -> FullMessage {
+        FullMessage {
+            message: message.to_string(),
+            reasoning: "".to_string(),
+            tool_calls: vec![],
+        }
+    }
+
+    //a session where the answer is the prompt, sent once `release` is notified
+    //if the prompt is "wait"
+    fn connect(
+        answers: &Arc<AnswerStreams>,
+        user_id: Uuid,
+        release: &Arc<Notify>,
+    ) -> (Client, Server, tokio::task::JoinHandle<anyhow::Result<()>>) {
+        let (client, incoming) = mpsc::unbounded();
+        let (outgoing, server) = mpsc::unbounded();
+        let answers = answers.clone();
+        let release = release.clone();
+        let session = tokio::spawn(async move {
+            chat_session(
+                incoming,
+                outgoing,
+                &answers,
+                user_id,
+                |prompt: String, mut tokens: EnvelopeTokens| {
+                    let release = release.clone();
+                    async move {
+                        tokens.send_token(WebSocketToken::answer("one ")).await?;
+                        if prompt == "wait" {
+                            release.notified().await;
+                        }
+                        tokens.send_token(WebSocketToken::answer("two")).await?;
+                        Ok(full_message(&prompt))
+                    }
+                },
+            )
+            .await
+        });
+        (client, server, session)
+    }
+
+    async fn send(client: &mut Client, value: Value) {
+        client
+            .send(Ok(Message::Text(value.to_string())))
+            .await
+            .unwrap();
+    }
+
+    async fn next_envelope(server: &mut Server) -> Value {
+        match server.next().await {
+            Some(Message::Text(text)) => serde_json::from_str(&text).unwrap(),
+            _ => panic!("expected an envelope"),
+        }
+    }
+
+    #[test]
+    fn it_speaks_envelopes_unless_asked_for_v1() {
+        let session_id = Uuid::new_v4();
+        let query: ChatQuery = serde_json::from_value(json!({"session_id": session_id})).unwrap();
+        assert_eq!(uses_envelopes(query.v), Ok(true));
+        let query: ChatQuery =
+            serde_json::from_value(json!({"session_id": session_id, "v": 1})).unwrap();
+        assert_eq!(uses_envelopes(query.v), Ok(false));
+        assert!(uses_envelopes(3).is_err());
+    }
+
+    #[tokio::test]
+    async fn it_answers_turns_on_one_socket() {
+        let answers = Arc::new(AnswerStreams::new());
+        let (mut client, mut server, session) =
+            connect(&answers, Uuid::new_v4(), &Arc::new(Notify::new()));
+        assert_eq!(
+            next_envelope(&mut server).await,
+            json!({"type": "hello", "version": 2})
+        );
+        send(&mut client, json!({"type": "ping", "id": "p1"})).await;
+        assert_eq!(
+            next_envelope(&mut server).await,
+            json!({"type": "pong", "id": "p1"})
+        );
+        for (id, text) in [("m1", "first"), ("m2", "second")] {
+            send(
+                &mut client,
+                json!({"type": "prompt", "id": id, "text": text}),
+            )
+            .await;
+            assert_eq!(
+                next_envelope(&mut server).await,
+                json!({"type": "token", "id": id, "tokenType": "Message", "tokens": "one ", "event_id": 1})
+            );
+            assert_eq!(next_envelope(&mut server).await["event_id"], 2);
+            let done = next_envelope(&mut server).await;
+            assert_eq!(done["type"], "done");
+            assert_eq!(done["id"], id);
+            assert_eq!(done["message"], text);
+            assert_eq!(done["stopped"], false);
+            assert_eq!(done["event_id"], 3);
+        }
+        send(
+            &mut client,
+            json!({"type": "approve", "id": "m3", "confirmation": "ab12"}),
+        )
+        .await;
+        next_envelope(&mut server).await;
+        next_envelope(&mut server).await;
+        let done = next_envelope(&mut server).await;
+        assert_eq!(done["message"], "Yes, go ahead. The confirmation is ab12");
+        //ids can't be reused while the answer can still be resumed
+        send(
+            &mut client,
+            json!({"type": "prompt", "id": "m1", "text": "again"}),
+        )
+        .await;
+        assert_eq!(next_envelope(&mut server).await["type"], "error");
+        client
+            .send(Ok(Message::Text("hi".to_string())))
+            .await
+            .unwrap();
+        let error = next_envelope(&mut server).await;
+        assert_eq!(error["type"], "error");
+        assert_eq!(error["id"], Value::Null);
+        client.send(Ok(Message::Close(None))).await.unwrap();
+        session.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_stops_an_answer() {
+        let answers = Arc::new(AnswerStreams::new());
+        let release = Arc::new(Notify::new());
+        let (mut client, mut server, session) = connect(&answers, Uuid::new_v4(), &release);
+        next_envelope(&mut server).await;
+        send(
+            &mut client,
+            json!({"type": "prompt", "id": "m1", "text": "wait"}),
+        )
+        .await;
+        assert_eq!(next_envelope(&mut server).await["type"], "token");
+        send(
+            &mut client,
+            json!({"type": "prompt", "id": "m2", "text": "hi"}),
+        )
+        .await;
+        let busy = next_envelope(&mut server).await;
+        assert_eq!(busy["type"], "error");
+        assert_eq!(busy["id"], "m2");
+        send(&mut client, json!({"type": "stop", "id": "m1"})).await;
+        let done = next_envelope(&mut server).await;
+        assert_eq!(done["type"], "done");
+        assert_eq!(done["stopped"], true);
+        //nothing more is sent for a stopped answer
+        release.notify_one();
+        send(&mut client, json!({"type": "ping", "id": "p1"})).await;
+        assert_eq!(next_envelope(&mut server).await["type"], "pong");
+        drop(client);
+        session.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_resumes_after_a_disconnect() {
+        let answers = Arc::new(AnswerStreams::new());
+        let user_id = Uuid::new_v4();
+        let release = Arc::new(Notify::new());
+        let (mut client, mut server, session) = connect(&answers, user_id, &release);
+        next_envelope(&mut server).await;
+        send(
+            &mut client,
+            json!({"type": "prompt", "id": "m1", "text": "wait"}),
+        )
+        .await;
+        assert_eq!(next_envelope(&mut server).await["event_id"], 1);
+        //the phone went to sleep
+        drop(client);
+        session.await.unwrap().unwrap();
+        //the answer finishes with no one listening
+        release.notify_one();
+        let answer = answers.get(user_id, "m1").unwrap();
+        let (events, finished) = answer.events_after(2).await;
+        assert!(finished);
+        assert_eq!(events.len(), 1);
+
+        let (mut client, mut server, session) = connect(&answers, user_id, &release);
+        next_envelope(&mut server).await;
+        send(
+            &mut client,
+            json!({"type": "resume", "id": "m1", "last_event_id": 1}),
+        )
+        .await;
+        let token = next_envelope(&mut server).await;
+        assert_eq!(token["tokens"], "two");
+        assert_eq!(token["event_id"], 2);
+        let done = next_envelope(&mut server).await;
+        assert_eq!(done["type"], "done");
+        assert_eq!(done["message"], "wait");
+        //other users can't resume it
+        let (mut other, mut other_server, other_session) =
+            connect(&answers, Uuid::new_v4(), &release);
+        next_envelope(&mut other_server).await;
+        send(&mut other, json!({"type": "resume", "id": "m1"})).await;
+        assert_eq!(next_envelope(&mut other_server).await["type"], "error");
+        drop(other);
+        other_session.await.unwrap().unwrap();
+        drop(client);
+        session.await.unwrap().unwrap();
+    }
+
+    //an answer that never finishes, holding a guard like the one cancelling the
+    //tools of a turn
+    fn start_hung(answers: &AnswerStreams, user_id: Uuid, id: &str) -> CancellationToken {
+        let tools = CancellationToken::new();
+        let guard = tools.clone().drop_guard();
+        answers
+            .start(user_id, id, |mut tokens| async move {
+                let _guard = guard;
+                tokens.send_token(WebSocketToken::answer("one ")).await?;
+                std::future::pending::<()>().await;
+                Ok(full_message("never"))
+            })
+            .unwrap();
+        tools
+    }
+
+    async fn cancelled(tools: &CancellationToken) -> bool {
+        tokio::time::timeout(std::time::Duration::from_secs(1), tools.cancelled())
+            .await
+            .is_ok()
+    }
+
+    #[tokio::test]
+    async fn it_cancels_the_tools_of_stopped_and_hung_answers() {
+        let answers = AnswerStreams::new();
+        let user_id = Uuid::new_v4();
+        let stopped = start_hung(&answers, user_id, "m1");
+        let answer = answers.get(user_id, "m1").unwrap();
+        answer.events_after(0).await;
+        answer.stop("m1".to_string());
+        assert!(cancelled(&stopped).await);
+
+        let hung = start_hung(&answers, user_id, "m2");
+        let answer = answers.get(user_id, "m2").unwrap();
+        answer.events_after(0).await;
+        answers.evict(Utc::now() + Duration::minutes(1));
+        assert!(answers.get(user_id, "m2").is_some());
+        answers.evict(Utc::now() + Duration::minutes(31));
+        assert!(cancelled(&hung).await);
+        assert!(answers.get(user_id, "m2").is_none());
+        let (events, finished) = answer.events_after(1).await;
+        assert!(finished);
+        let error: Value = serde_json::from_str(&events[0]).unwrap();
+        assert_eq!(error["type"], "error");
+        assert_eq!(error["id"], "m2");
+    }
+
+    #[tokio::test]
+    async fn it_caps_answers_per_user() {
+        let answers = AnswerStreams::new();
+        let user_id = Uuid::new_v4();
+        for index in 0..MAX_ANSWERS_PER_USER {
+            start_hung(&answers, user_id, &format!("m{}", index));
+        }
+        let started = answers.start(user_id, "more", |_| async { Ok(full_message("more")) });
+        assert!(started.is_err());
+        //other users aren't held up
+        start_hung(&answers, Uuid::new_v4(), "m0");
+        //a finished answer makes room
+        answers.get(user_id, "m3").unwrap().stop("m3".to_string());
+        let started = answers.start(user_id, "more", |_| async { Ok(full_message("more")) });
+        assert!(started.is_ok());
+        assert!(answers.get(user_id, "m3").is_none());
+        assert!(answers.get(user_id, "m4").is_some());
+    }
+}
@@ -0,0 +1,547 @@
+use crate::fuzzy::best_match;
+use crate::psql_chores::{
+    Chore, ChoreCompletion, ChoreMember, LastDone, create_chore_completion, get_chore_completions,
+    get_chores, get_last_done,
+};
+use crate::psql_users::get_all_users;
+use crate::tools::{ToolContext, ToolOutput, TypedTool, typed_tool};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use poem_openapi::Object;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use uuid::Uuid;
+
+//about ten years, chores less often than that aren't chores
+const MAX_FREQUENCY_DAYS: i32 = 3650;
+const MAX_POINTS: i32 = 1000;
+
+#[derive(Serialize, Object, Clone, Debug, PartialEq)]
+pub struct ChoreStatus {
+    pub chore_id: Uuid,
+    pub chore: String,
+    pub frequency_days: i32,
+    pub points: i32,
+    //chores that were never done are due straight away
+    pub due_on: NaiveDate,
+    pub overdue: bool,
+    //none when nobody is assigned
+    pub turn: Option<String>,
+    pub last_done_by: Option<String>,
+    pub last_done_on: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Object, Clone, Debug, PartialEq)]
+pub struct MemberScore {
+    pub username_id: Uuid,
+    pub username: String,
+    pub points: i32,
+    //since monday
+    pub points_this_week: i32,
+    pub chores_done: i32,
+    //days in a row with a chore done
+    pub streak: i32,
+}
+
+fn today(timezone: Tz) -> NaiveDate {
+    Utc::now().with_timezone(&timezone).date_naive()
+}
+
+//whoever has gone longest without doing the chore, so doing it out of turn moves
+//you to the back. members who never did it go first, in rotation order
+fn whose_turn<'a>(
+    members: &'a [ChoreMember],
+    last_done: &HashMap<Uuid, DateTime<Utc>>,
+) -> Option<&'a ChoreMember> {
+    members
+        .iter()
+        .min_by_key(|member| last_done.get(&member.username_id))
+}
+
+fn chore_status(
+    chore: &Chore,
+    last_done: &[LastDone],
+    timezone: Tz,
+    today: NaiveDate,
+) -> ChoreStatus {
+    let done: Vec<&LastDone> = last_done
+        .iter()
+        .filter(|done| done.chore_id == chore.id)
+        .collect();
+    let by_member: HashMap<Uuid, DateTime<Utc>> = done
+        .iter()
+        .map(|done| (done.username_id, done.completed_at))
+        .collect();
+    let last = done.iter().max_by_key(|done| done.completed_at);
+    let last_done_on = last.map(|done| done.completed_at.with_timezone(&timezone).date_naive());
+    let due_on = match last_done_on {
+        //chores saved before the frequency was capped can be due past the last date
+        Some(day) => day
+            .checked_add_signed(Duration::days(chore.frequency_days as i64))
+            .unwrap_or(NaiveDate::MAX),
+        None => today,
+    };
+    ChoreStatus {
+        chore_id: chore.id,
+        chore: chore.name.clone(),
+        frequency_days: chore.frequency_days,
+        points: chore.points,
+        due_on,
+        overdue: due_on < today,
+        turn: whose_turn(&chore.members, &by_member).map(|member| member.username.clone()),
+        last_done_by: last.map(|done| done.username.clone()),
+        last_done_on,
+    }
+}
+
+pub async fn chore_statuses(timezone: Tz, pool: &PgPool) -> sqlx::Result<Vec<ChoreStatus>> {
+    let last_done = get_last_done(pool).await?;
+    let today = today(timezone);
+    Ok(get_chores(pool)
+        .await?
+        .iter()
+        .map(|chore| chore_status(chore, &last_done, timezone, today))
+        .collect())
+}
+
+//consecutive days with a chore done up to today. today doesn't break the
+//streak until it is over
+fn streak(days: &BTreeSet<NaiveDate>, today: NaiveDate) -> i32 {
+    let mut day = if days.contains(&today) {
+        today
+    } else {
+        today - Duration::days(1)
+    };
+    let mut streak = 0;
+    while days.contains(&day) {
+        streak += 1;
+        day -= Duration::days(1);
+    }
+    streak
+}
+
+//best first
+fn member_scores(
+    members: &[ChoreMember],
+    completions: &[ChoreCompletion],
+    timezone: Tz,
+    today: NaiveDate,
+) -> Vec<MemberScore> {
+    let week_start = crate::meal_plans::week_start(today);
+    let mut scores: Vec<MemberScore> = members
+        .iter()
+        .map(|member| {
+            let done: Vec<(NaiveDate, i32)> = completions
+                .iter()
+                .filter(|completion| completion.username_id == member.username_id)
+                .map(|completion| {
+                    let day = completion.completed_at.with_timezone(&timezone);
+                    (day.date_naive(), completion.points)
+                })
+                .collect();
+            let days: BTreeSet<NaiveDate> = done.iter().map(|(day, _)| *day).collect();
+            MemberScore {
+                username_id: member.username_id,
+                username: member.username.clone(),
+                points: done
+                    .iter()
+                    .fold(0, |sum: i32, (_, points)| sum.saturating_add(*points)),
+                points_this_week: done
+                    .iter()
+                    .filter(|(day, _)| *day >= week_start)
+                    .fold(0, |sum: i32, (_, points)| sum.saturating_add(*points)),
+                chores_done: done.len() as i32,
+                streak: streak(&days, today),
+            }
+        })
+        .collect();
+    scores.sort_by(|a, b| {
+        b.points
+            .cmp(&a.points)
+            .then_with(|| a.username.cmp(&b.username))
+    });
+    scores
+}
+
+//everyone taking turns at a chore, and anyone else who has done one
+pub async fn scores(timezone: Tz, pool: &PgPool) -> sqlx::Result<Vec<MemberScore>> {
+    let chores = get_chores(pool).await?;
+    let completions = get_chore_completions(pool).await?;
+    let mut scored: HashSet<Uuid> = completions
+        .iter()
+        .map(|completion| completion.username_id)
+        .collect();
+    scored.extend(
+        chores
+            .iter()
+            .flat_map(|chore| chore.members.iter().map(|member| member.username_id)),
+    );
+    let members: Vec<ChoreMember> = get_all_users(pool)
+        .await?
+        .into_iter()
+        .filter(|user| scored.contains(&user.id))
+        .map(|user| ChoreMember {
+            username_id: user.id,
+            username: user.username,
+        })
+        .collect();
+    Ok(member_scores(
+        &members,
+        &completions,
+        timezone,
+        today(timezone),
+    ))
+}
+
+//the error is for a chore that can't be saved as given
+pub async fn validate_chore(
+    frequency_days: Option<i32>,
+    points: Option<i32>,
+    members: Option<&[Uuid]>,
+    pool: &PgPool,
+) -> sqlx::Result<Result<(), String>> {
+    if frequency_days.is_some_and(|days| days < 1) {
+        return Ok(Err("A chore is done at most once a day".to_string()));
+    }
+    if frequency_days.is_some_and(|days| days > MAX_FREQUENCY_DAYS) {
+        return Ok(Err(format!(
+            "A chore is done at least once every {} days",
+            MAX_FREQUENCY_DAYS
+        )));
+    }
+    if points.is_some_and(|points| points < 0) {
+        return Ok(Err("Points can't be negative".to_string()));
+    }
+    if points.is_some_and(|points| points > MAX_POINTS) {
+        return Ok(Err(format!(
+            "A chore is worth at most {} points",
+            MAX_POINTS
+        )));
+    }
+    if let Some(members) = members {
+        let users: HashSet<Uuid> = get_all_users(pool)
+            .await?
+            .into_iter()
+            .map(|user| user.id)
+            .collect();
+        if let Some(unknown) = members.iter().find(|member| !users.contains(member)) {
+            return Ok(Err(format!("No user with id {}", unknown)));
+        }
+        if members.iter().collect::<HashSet<_>>().len() < members.len() {
+            return Ok(Err("A member can only be in the rotation once".to_string()));
+        }
+    }
+    Ok(Ok(()))
+}
+
+#[derive(Clone)]
+pub struct ReadChoresTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl ReadChoresTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "read_chores".to_string(),
+            description: "Read the household chores: when each is next due and whose turn \
+                it is, eg to answer \"whose turn is it to take out the trash?\""
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ReadChoresArgs {
+    /// Name of one chore to read. Leave out to read them all
+    #[serde(default)]
+    chore: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for ReadChoresTool {
+    type Args = ReadChoresArgs;
+    type Output = ToolOutput<Vec<ChoreStatus>>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: ReadChoresArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let statuses = chore_statuses(context.timezone, &self.pool).await?;
+        let Some(chore) = args.chore else {
+            return Ok(ToolOutput::Result(statuses));
+        };
+        Ok(
+            match best_match(&chore, statuses.iter().map(|status| status.chore.as_str())) {
+                Some(index) => ToolOutput::Result(vec![statuses[index].clone()]),
+                None => ToolOutput::Error(format!("No chore called {}", chore)),
+            },
+        )
+    }
+}
+typed_tool!(ReadChoresTool);
+
+#[derive(Clone)]
+pub struct CompleteChoreTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl CompleteChoreTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "complete_chore".to_string(),
+            description: "Record that a chore was done, eg \"I did the dishes\", earning its \
+                points. It is the user's chore unless someone else is named"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CompleteChoreArgs {
+    /// Name of the chore, eg "dishes"
+    chore: String,
+    /// Username of whoever did it, if not the user
+    #[serde(default)]
+    who: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ChoreDone {
+    chore: String,
+    done_by: String,
+    points: i32,
+    total_points: i32,
+    streak: i32,
+    next_turn: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for CompleteChoreTool {
+    type Args = CompleteChoreArgs;
+    type Output = ToolOutput<ChoreDone>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: CompleteChoreArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let chores = get_chores(&self.pool).await?;
+        let Some(index) = best_match(&args.chore, chores.iter().map(|chore| chore.name.as_str()))
+        else {
+            return Ok(ToolOutput::Error(format!("No chore called {}", args.chore)));
+        };
+        let chore = &chores[index];
+        let username_id = match args.who {
+            Some(who) => {
+                let users = get_all_users(&self.pool).await?;
+                match best_match(&who, users.iter().map(|user| user.username.as_str())) {
+                    Some(index) => users[index].id,
+                    None => return Ok(ToolOutput::Error(format!("No one called {}", who))),
+                }
+            }
+            None => context.user_id,
+        };
+        let completion = create_chore_completion(&chore.id, &username_id, &self.pool).await?;
+        let score = scores(context.timezone, &self.pool)
+            .await?
+            .into_iter()
+            .find(|score| score.username_id == username_id);
+        let status = chore_status(
+            chore,
+            &get_last_done(&self.pool).await?,
+            context.timezone,
+            today(context.timezone),
+        );
+        Ok(ToolOutput::Result(ChoreDone {
+            chore: chore.name.clone(),
+            done_by: score
+                .as_ref()
+                .map(|score| score.username.clone())
+                .unwrap_or_default(),
+            points: completion.points,
+            total_points: score.as_ref().map_or(0, |score| score.points),
+            streak: score.as_ref().map_or(0, |score| score.streak),
+            next_turn: status.turn,
+        }))
+    }
+}
+typed_tool!(CompleteChoreTool);
+
+#[derive(Clone)]
+pub struct ChoreScoresTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl ChoreScoresTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "chore_scores".to_string(),
+            description: "Read everyone's chore points, points this week and streak of days \
+                in a row with a chore done"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ChoreScoresArgs {}
+
+#[async_trait::async_trait]
+impl TypedTool for ChoreScoresTool {
+    type Args = ChoreScoresArgs;
+    type Output = ToolOutput<Vec<MemberScore>>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        _args: ChoreScoresArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        Ok(ToolOutput::Result(
+            scores(context.timezone, &self.pool).await?,
+        ))
+    }
+}
+typed_tool!(ChoreScoresTool);
+
+#[cfg(test)]
+mod tests {
+    use super::{chore_status, member_scores, streak, whose_turn};
+    use crate::psql_chores::{Chore, ChoreCompletion, ChoreMember, LastDone};
+    use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+    use chrono_tz::Tz;
+    use std::collections::{BTreeSet, HashMap};
+    use uuid::Uuid;
+
+    fn member(username: &str) -> ChoreMember {
+        ChoreMember {
+            username_id: Uuid::new_v4(),
+            username: username.to_string(),
+        }
+    }
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap()
+    }
+
+    #[test]
+    fn it_rotates_to_whoever_waited_longest() {
+        let members = [member("ana"), member("ben"), member("cy")];
+        assert_eq!(whose_turn(&members, &HashMap::new()), Some(&members[0]));
+        let last_done = HashMap::from([
+            (members[0].username_id, at(10, 8)),
+            (members[1].username_id, at(12, 8)),
+        ]);
+        assert_eq!(whose_turn(&members, &last_done), Some(&members[2]));
+        let last_done = HashMap::from([
+            (members[0].username_id, at(10, 8)),
+            (members[1].username_id, at(12, 8)),
+            (members[2].username_id, at(11, 8)),
+        ]);
+        assert_eq!(whose_turn(&members, &last_done), Some(&members[0]));
+        assert_eq!(whose_turn(&[], &last_done), None);
+    }
+
+    #[test]
+    fn it_works_out_when_a_chore_is_due() {
+        let members = vec![member("ana"), member("ben")];
+        let chore = Chore {
+            id: Uuid::new_v4(),
+            name: "trash".to_string(),
+            frequency_days: 3,
+            points: 2,
+            members: members.clone(),
+        };
+        let status = chore_status(&chore, &[], Tz::UTC, date(19));
+        assert_eq!((status.due_on, status.overdue), (date(19), false));
+        assert_eq!(status.turn.as_deref(), Some("ana"));
+        //late on the 14th in new york is still the 14th there
+        let last_done = [LastDone {
+            chore_id: chore.id,
+            username_id: members[0].username_id,
+            username: "ana".to_string(),
+            completed_at: at(15, 2),
+        }];
+        let status = chore_status(&chore, &last_done, Tz::America__New_York, date(19));
+        assert_eq!(status.last_done_on, Some(date(14)));
+        assert_eq!((status.due_on, status.overdue), (date(17), true));
+        assert_eq!(status.turn.as_deref(), Some("ben"));
+        assert_eq!(status.last_done_by.as_deref(), Some("ana"));
+        let chore = Chore {
+            frequency_days: i32::MAX,
+            ..chore
+        };
+        let status = chore_status(&chore, &last_done, Tz::UTC, date(19));
+        assert_eq!((status.due_on, status.overdue), (NaiveDate::MAX, false));
+    }
+
+    #[test]
+    fn it_counts_streaks_up_to_today() {
+        let days = BTreeSet::from([date(14), date(16), date(17), date(18)]);
+        assert_eq!(streak(&days, date(18)), 3);
+        assert_eq!(streak(&days, date(19)), 3);
+        assert_eq!(streak(&days, date(20)), 0);
+    }
+
+    #[test]
+    fn it_adds_up_points() {
+        let members = [member("ana"), member("ben")];
+        let completion = |member: &ChoreMember, day: u32, points: i32| ChoreCompletion {
+            id: Uuid::new_v4(),
+            chore_id: Uuid::new_v4(),
+            username_id: member.username_id,
+            points,
+            completed_at: at(day, 12),
+        };
+        let completions = [
+            completion(&members[1], 17, 3),
+            completion(&members[1], 18, 1),
+            completion(&members[0], 19, 2),
+            completion(&members[0], 19, 1),
+        ];
+        let scores = member_scores(&members, &completions, Tz::UTC, date(19));
+        let scores: Vec<(&str, i32, i32, i32, i32)> = scores
+            .iter()
+            .map(|score| {
+                (
+                    score.username.as_str(),
+                    score.points,
+                    score.points_this_week,
+                    score.chores_done,
+                    score.streak,
+                )
+            })
+            .collect();
+        assert_eq!(scores, vec![("ben", 4, 0, 2, 2), ("ana", 3, 3, 2, 1)]);
+    }
+}
@@ -1,28 +1,190 @@
-use serde::Deserialize;
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Object)]
 pub struct KB {
     pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
     pub num_results: i32,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "lowercase"))]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, sqlx::Type, Enum)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "mcp_type", rename_all = "lowercase")]
+#[oai(rename_all = "lowercase")]
 pub enum MCPType {
     STREAM,
     SSE,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Object)]
 pub struct MCP {
     pub name: String,
     pub description: String,
     pub url: String,
     pub mcp_type: MCPType,
+    //glob patterns (* and ?) on the tool name; empty means every tool is included
+    #[serde(default)]
+    #[oai(default)]
+    pub include_tools: Vec<String>,
+    #[serde(default)]
+    #[oai(default)]
+    pub exclude_tools: Vec<String>,
+    //tool name to description, replaces the description the server provides
+    #[serde(default)]
+    #[oai(default)]
+    pub tool_descriptions: HashMap<String, String>,
+}
+
+fn default_home_assistant_domains() -> Vec<String> {
+    [
+        "light",
+        "switch",
+        "fan",
+        "cover",
+        "climate",
+        "media_player",
+        "scene",
+        "script",
+        "lock",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+fn default_home_assistant_confirm() -> Vec<String> {
+    [
+        "lock.unlock",
+        "lock.open",
+        "alarm_control_panel.alarm_disarm",
+        //covers include garage doors
+        "cover.open_cover",
+        "cover.toggle",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct HomeAssistant {
+    pub url: String,
+    //long-lived access token, made on the user's profile page in Home Assistant
+    pub token: String,
+    //domains whose services can be called, every entity can still be read
+    #[serde(default = "default_home_assistant_domains")]
+    pub domains: Vec<String>,
+    //glob patterns on domain.service for changes the user has to confirm first
+    #[serde(default = "default_home_assistant_confirm")]
+    pub confirm: Vec<String>,
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_client_id() -> String {
+    "draid".to_string()
+}
+
+fn default_mqtt_schema() -> serde_json::Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MqttAction {
+    //send a command to the topic
+    Publish,
+    //the last message seen on the topic, usually the retained state
+    Read,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct MqttTool {
+    pub name: String,
+    pub description: String,
+    pub action: MqttAction,
+    //{{name}} is replaced by the argument, read topics can also use the + and # wildcards
+    pub topic: String,
+    //json schema of the arguments
+    #[serde(default = "default_mqtt_schema")]
+    pub schema: serde_json::Value,
+    //template of the published json, a string that is only "{{name}}" keeps the
+    //argument's type, or leaves its field out when the argument isn't given. a plain
+    //string payload is sent as is, eg ON for Tasmota
+    #[serde(default)]
+    pub payload: serde_json::Value,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Mqtt {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    //brokers drop the older connection when two clients share an id
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub tools: Vec<MqttTool>,
+}
+
+//satellites on other machines need it set to an address they can reach
+fn default_wyoming_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_wyoming_port() -> u16 {
+    10800
+}
+
+fn default_wyoming_languages() -> Vec<String> {
+    vec!["en".to_string()]
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct WyomingSatellite {
+    //the satellite_id or device_id sent in the event context
+    pub name: String,
+    //ip the satellite connects from, for clients that don't send a context
+    #[serde(default)]
+    pub address: Option<String>,
+    //the household member the satellite talks as
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Wyoming {
+    #[serde(default = "default_wyoming_address")]
+    pub address: String,
+    #[serde(default = "default_wyoming_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub satellites: Vec<WyomingSatellite>,
+    //user for satellites that aren't listed, they are refused without one
+    #[serde(default)]
+    pub default_username: Option<String>,
+    #[serde(default = "default_wyoming_languages")]
+    pub languages: Vec<String>,
 }
 
+//kb and mcp only seed the database on startup, afterwards they are managed through the api
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub kb: Vec<KB>,
     pub mcp: Vec<MCP>,
+    #[serde(default)]
+    pub home_assistant: Option<HomeAssistant>,
+    #[serde(default)]
+    pub mqtt: Option<Mqtt>,
+    #[serde(default)]
+    pub wyoming: Option<Wyoming>,
 }
@@ -0,0 +1,148 @@
+//changes that need the user's go-ahead, eg unlocking a door. the tool asks in
+//one turn and the change is only made when the confirmation is passed back in
+//a later turn of the same chat, after the user has replied
+use crate::tools::ToolContext;
+use chrono::{DateTime, Duration, Utc};
+use std::{collections::HashMap, sync::Mutex};
+use uuid::Uuid;
+
+//how long the user has to confirm
+const CONFIRMATION_MINUTES: i64 = 5;
+
+struct Pending {
+    action: String,
+    user_id: Uuid,
+    session_id: Uuid,
+    span_id: String,
+    created_at: DateTime<Utc>,
+}
+
+pub struct Confirmations {
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl Confirmations {
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    //returns the code the action is confirmed with
+    pub fn request(&self, action: &str, context: &ToolContext) -> String {
+        let mut pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Utc::now();
+        pending.retain(|_, request| {
+            now - request.created_at < Duration::minutes(CONFIRMATION_MINUTES)
+        });
+        let code = Uuid::new_v4().simple().to_string()[..8].to_string();
+        pending.insert(
+            code.clone(),
+            Pending {
+                action: action.to_string(),
+                user_id: context.user_id,
+                session_id: context.session_id,
+                span_id: context.span_id.clone(),
+                created_at: now,
+            },
+        );
+        code
+    }
+
+    //a code can only be used once, by the user it was asked of
+    pub fn confirm(&self, code: &str, action: &str, context: &ToolContext) -> Result<(), String> {
+        let mut pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(request) = pending.remove(code) else {
+            return Err("Unknown confirmation, ask the user again".to_string());
+        };
+        if Utc::now() - request.created_at >= Duration::minutes(CONFIRMATION_MINUTES) {
+            return Err("The confirmation expired, ask the user again".to_string());
+        }
+        if request.action != action
+            || request.user_id != context.user_id
+            || request.session_id != context.session_id
+        {
+            return Err("The confirmation is for something else, ask the user again".to_string());
+        }
+        if request.span_id == context.span_id {
+            pending.insert(code.to_string(), request);
+            return Err("The user has to confirm in their reply first".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Confirmations;
+    use crate::tools::ToolContext;
+    use tokio_util::sync::CancellationToken;
+    use uuid::Uuid;
+
+    fn context() -> ToolContext {
+        ToolContext {
+            user_id: Uuid::new_v4(),
+            roles: vec![],
+            session_id: Uuid::new_v4(),
+            bot_name: "helper".to_string(),
+            span_id: Uuid::new_v4().to_string(),
+            cancellation_token: CancellationToken::new(),
+            timezone: chrono_tz::UTC,
+        }
+    }
+
+    fn next_turn(context: &ToolContext) -> ToolContext {
+        ToolContext {
+            span_id: Uuid::new_v4().to_string(),
+            ..context.clone()
+        }
+    }
+
+    #[test]
+    fn it_confirms_in_a_later_turn() {
+        let confirmations = Confirmations::new();
+        let asked = context();
+        let code = confirmations.request("lock.unlock lock.front_door", &asked);
+        assert!(
+            confirmations
+                .confirm(&code, "lock.unlock lock.front_door", &asked)
+                .is_err()
+        );
+        let replied = next_turn(&asked);
+        assert!(
+            confirmations
+                .confirm(&code, "lock.unlock lock.front_door", &replied)
+                .is_ok()
+        );
+        //only once
+        assert!(
+            confirmations
+                .confirm(&code, "lock.unlock lock.front_door", &replied)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn it_refuses_other_actions_and_users() {
+        let confirmations = Confirmations::new();
+        let asked = context();
+        let code = confirmations.request("lock.unlock lock.front_door", &asked);
+        assert!(
+            confirmations
+                .confirm(&code, "lock.unlock lock.back_door", &next_turn(&asked))
+                .is_err()
+        );
+        let code = confirmations.request("lock.unlock lock.front_door", &asked);
+        assert!(
+            confirmations
+                .confirm(&code, "lock.unlock lock.front_door", &context())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,444 @@
+use chrono::{
+    DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday,
+};
+use chrono_tz::Tz;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum DateError {
+    Unrecognized(String),
+    NonexistentTime(NaiveDateTime),
+    OutOfRange,
+}
+
+impl std::error::Error for DateError {}
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateError::Unrecognized(text) => write!(
+                f,
+                "Could not understand the date '{}', try a form like 2026-03-03, \
+                'next friday at 5pm' or 'in 3 days'",
+                text
+            ),
+            DateError::NonexistentTime(time) => {
+                write!(f, "{} does not exist in this timezone", time)
+            }
+            DateError::OutOfRange => write!(f, "Date is out of range"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DurationUnit {
+    Minutes,
+    Hours,
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+impl DurationUnit {
+    fn parse(word: &str) -> Option<Self> {
+        match word.trim_end_matches('s') {
+            "min" | "minute" => Some(DurationUnit::Minutes),
+            "h" | "hr" | "hour" => Some(DurationUnit::Hours),
+            "day" => Some(DurationUnit::Days),
+            "week" | "wk" => Some(DurationUnit::Weeks),
+            "month" => Some(DurationUnit::Months),
+            "year" | "yr" => Some(DurationUnit::Years),
+            _ => None,
+        }
+    }
+}
+
+//months and years are calendar based, so Jan 31 + 1 month is Feb 28
+pub fn add_duration(
+    datetime: DateTime<Tz>,
+    amount: i64,
+    unit: DurationUnit,
+) -> Result<DateTime<Tz>, DateError> {
+    let months = match unit {
+        DurationUnit::Minutes => return checked_add(datetime, Duration::try_minutes(amount)),
+        DurationUnit::Hours => return checked_add(datetime, Duration::try_hours(amount)),
+        DurationUnit::Days => return add_days(datetime, amount),
+        DurationUnit::Weeks => return add_days(datetime, amount.saturating_mul(7)),
+        DurationUnit::Months => amount,
+        DurationUnit::Years => amount.saturating_mul(12),
+    };
+    let months =
+        Months::new(u32::try_from(months.unsigned_abs()).map_err(|_| DateError::OutOfRange)?);
+    let naive = datetime.naive_local();
+    let naive = if amount < 0 {
+        naive.checked_sub_months(months)
+    } else {
+        naive.checked_add_months(months)
+    }
+    .ok_or(DateError::OutOfRange)?;
+    localize(&datetime.timezone(), naive)
+}
+
+fn checked_add(
+    datetime: DateTime<Tz>,
+    duration: Option<Duration>,
+) -> Result<DateTime<Tz>, DateError> {
+    duration
+        .and_then(|duration| datetime.checked_add_signed(duration))
+        .ok_or(DateError::OutOfRange)
+}
+
+//days keep the wall clock time across daylight saving changes
+fn add_days(datetime: DateTime<Tz>, days: i64) -> Result<DateTime<Tz>, DateError> {
+    let naive = Duration::try_days(days)
+        .and_then(|days| datetime.naive_local().checked_add_signed(days))
+        .ok_or(DateError::OutOfRange)?;
+    localize(&datetime.timezone(), naive)
+}
+
+pub fn localize(timezone: &Tz, naive: NaiveDateTime) -> Result<DateTime<Tz>, DateError> {
+    timezone
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or(DateError::NonexistentTime(naive))
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_month(word: &str) -> Option<u32> {
+    let months = [
+        "january",
+        "february",
+        "march",
+        "april",
+        "may",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ];
+    months
+        .iter()
+        .position(|month| word == *month || (word.len() >= 3 && month.starts_with(word)))
+        .map(|index| index as u32 + 1)
+}
+
+//"3rd" -> 3
+fn parse_day(word: &str) -> Option<u32> {
+    let digits = word
+        .trim_end_matches("st")
+        .trim_end_matches("nd")
+        .trim_end_matches("rd")
+        .trim_end_matches("th");
+    digits.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+fn parse_amount(word: &str) -> Option<i64> {
+    match word {
+        "a" | "an" | "one" => Some(1),
+        "two" => Some(2),
+        "three" => Some(3),
+        "four" => Some(4),
+        "five" => Some(5),
+        "ten" => Some(10),
+        "fifteen" => Some(15),
+        "twenty" => Some(20),
+        "thirty" => Some(30),
+        _ => word.parse().ok(),
+    }
+}
+
+//nth weekday of a month, or the last one when n is 0
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u8) -> Option<NaiveDate> {
+    if n == 0 {
+        let next_month = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)?
+        };
+        let last = next_month.pred_opt()?;
+        let back = (7 + last.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+        return last.checked_sub_signed(Duration::days(back as i64));
+    }
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, n)
+}
+
+fn holiday(name: &str, year: i32) -> Option<NaiveDate> {
+    match name {
+        "new year's day" | "new years day" | "new year" | "new years" => {
+            NaiveDate::from_ymd_opt(year, 1, 1)
+        }
+        "valentine's day" | "valentines day" | "valentine's" | "valentines" => {
+            NaiveDate::from_ymd_opt(year, 2, 14)
+        }
+        "mother's day" | "mothers day" => nth_weekday(year, 5, Weekday::Sun, 2),
+        "memorial day" => nth_weekday(year, 5, Weekday::Mon, 0),
+        "father's day" | "fathers day" => nth_weekday(year, 6, Weekday::Sun, 3),
+        "independence day" | "fourth of july" | "4th of july" => {
+            NaiveDate::from_ymd_opt(year, 7, 4)
+        }
+        "labor day" => nth_weekday(year, 9, Weekday::Mon, 1),
+        "halloween" => NaiveDate::from_ymd_opt(year, 10, 31),
+        "thanksgiving" | "thanksgiving day" => nth_weekday(year, 11, Weekday::Thu, 4),
+        "christmas eve" => NaiveDate::from_ymd_opt(year, 12, 24),
+        "christmas" | "christmas day" | "xmas" => NaiveDate::from_ymd_opt(year, 12, 25),
+        "new year's eve" | "new years eve" => NaiveDate::from_ymd_opt(year, 12, 31),
+        _ => None,
+    }
+}
+
+//dates given without a year mean the next time they come around
+fn upcoming(
+    today: NaiveDate,
+    date_in_year: impl Fn(i32) -> Option<NaiveDate>,
+) -> Option<NaiveDate> {
+    date_in_year(today.year())
+        .filter(|date| *date >= today)
+        .or_else(|| date_in_year(today.year() + 1))
+}
+
+//"5pm", "5:30 pm", "17:30", "noon"
+fn parse_time(words: &[&str]) -> Option<NaiveTime> {
+    let text = words.join("");
+    match text.as_str() {
+        "noon" | "midday" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+    let (text, meridiem) = if let Some(text) = text.strip_suffix("am") {
+        (text, Some(false))
+    } else if let Some(text) = text.strip_suffix("pm") {
+        (text, Some(true))
+    } else {
+        (text.as_str(), None)
+    };
+    let (hour, minute) = match text.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        //a bare number is only a time with am/pm, otherwise it is a day or year
+        None if meridiem.is_some() => (text.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+    let hour = match meridiem {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(true) => hour % 12 + 12,
+        Some(false) => hour % 12,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn parse_date(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    let text = words.join(" ");
+    match words {
+        [] | ["today"] | ["tonight"] => return Some(today),
+        ["tomorrow"] => return today.succ_opt(),
+        ["yesterday"] => return today.pred_opt(),
+        ["day", "after", "tomorrow"] => return today.checked_add_signed(Duration::days(2)),
+        ["next", "week"] => return today.checked_add_signed(Duration::weeks(1)),
+        ["next", "month"] => return today.checked_add_months(Months::new(1)),
+        ["next", "year"] => return today.checked_add_months(Months::new(12)),
+        _ => {}
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+        return Some(date);
+    }
+    if let Some(date) = upcoming(today, |year| holiday(&text, year)) {
+        return Some(date);
+    }
+    //weekdays: "friday" and "this friday" are the next one including today,
+    //"next friday" is strictly after today and "last friday" strictly before
+    let (modifier, rest) = match words {
+        [modifier @ ("this" | "next" | "last" | "on"), rest @ ..] => (Some(*modifier), rest),
+        rest => (None, rest),
+    };
+    if let [weekday] = rest
+        && let Some(weekday) = parse_weekday(weekday)
+    {
+        let ahead =
+            (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+        let offset = match modifier {
+            Some("next") if ahead == 0 => 7,
+            Some("last") => ahead as i64 - 7,
+            _ => ahead as i64,
+        };
+        return today.checked_add_signed(Duration::days(offset));
+    }
+    //"march 3rd", "3 march", "march 3 2027", "the 3rd of march"
+    let words: Vec<&str> = words
+        .iter()
+        .copied()
+        .filter(|word| !matches!(*word, "the" | "of" | "on"))
+        .collect();
+    let (month, day, year) = match words.as_slice() {
+        [a, b] => match (parse_month(a), parse_day(b), parse_day(a), parse_month(b)) {
+            (Some(month), Some(day), _, _) | (_, _, Some(day), Some(month)) => (month, day, None),
+            _ => return None,
+        },
+        [a, b, year] => {
+            let year: i32 = year.parse().ok()?;
+            match (parse_month(a), parse_day(b), parse_day(a), parse_month(b)) {
+                (Some(month), Some(day), _, _) | (_, _, Some(day), Some(month)) => {
+                    (month, day, Some(year))
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    match year {
+        Some(year) => NaiveDate::from_ymd_opt(year, month, day),
+        None => upcoming(today, |year| NaiveDate::from_ymd_opt(year, month, day)),
+    }
+}
+
+//relative amounts: "in 40 minutes", "3 days ago", "2 weeks from now"
+fn parse_relative(words: &[&str], now: DateTime<Tz>) -> Option<Result<DateTime<Tz>, DateError>> {
+    let (amount, unit, sign) = match words {
+        ["in", amount, unit] => (amount, unit, 1),
+        [amount, unit, "ago"] => (amount, unit, -1),
+        [amount, unit, "from", "now"] => (amount, unit, 1),
+        _ => return None,
+    };
+    let amount = parse_amount(amount)?;
+    let unit = DurationUnit::parse(unit)?;
+    Some(add_duration(now, sign * amount, unit))
+}
+
+//understands the kinds of dates people say to an assistant, relative to now in the user's
+//timezone. a date without a time is midnight, a time without a date is the next time
+//the clock shows it
+pub fn parse_datetime(text: &str, now: DateTime<Tz>) -> Result<DateTime<Tz>, DateError> {
+    let unrecognized = || DateError::Unrecognized(text.to_string());
+    let trimmed = text.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(trimmed) {
+        return Ok(datetime.with_timezone(&now.timezone()));
+    }
+    for format in [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(trimmed, format) {
+            return localize(&now.timezone(), naive);
+        }
+    }
+    let lower = trimmed.to_lowercase().replace(',', " ");
+    let words: Vec<&str> = lower.split_whitespace().collect();
+    if words == ["now"] || words == ["right", "now"] {
+        return Ok(now);
+    }
+    if let Some(datetime) = parse_relative(&words, now) {
+        return datetime;
+    }
+    //split off a trailing time of day, "tomorrow at 5 pm", "friday 17:30"
+    let (date_words, time) = match words.iter().position(|word| *word == "at") {
+        Some(at) => (
+            &words[..at],
+            Some(parse_time(&words[at + 1..]).ok_or_else(unrecognized)?),
+        ),
+        None => (1..=2)
+            .filter(|n| *n <= words.len())
+            .find_map(|n| {
+                parse_time(&words[words.len() - n..])
+                    .map(|time| (&words[..words.len() - n], Some(time)))
+            })
+            .unwrap_or((&words[..], None)),
+    };
+    let today = now.date_naive();
+    let date = parse_date(date_words, today).ok_or_else(unrecognized)?;
+    let datetime = localize(
+        &now.timezone(),
+        date.and_time(time.unwrap_or(NaiveTime::MIN)),
+    )?;
+    if date_words.is_empty() && datetime < now {
+        return add_days(datetime, 1);
+    }
+    Ok(datetime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DateError, DurationUnit, add_duration, parse_datetime};
+    use chrono::{DateTime, TimeZone};
+    use chrono_tz::Tz;
+
+    //a wednesday afternoon
+    fn now() -> DateTime<Tz> {
+        chrono_tz::America::New_York
+            .with_ymd_and_hms(2026, 10, 14, 15, 30, 0)
+            .unwrap()
+    }
+
+    fn parse(text: &str) -> String {
+        parse_datetime(text, now())
+            .unwrap()
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    #[test]
+    fn it_parses_relative_dates() {
+        assert_eq!(parse("in 40 minutes"), "2026-10-14 16:10");
+        assert_eq!(parse("tomorrow at 5pm"), "2026-10-15 17:00");
+        assert_eq!(parse("3 days ago"), "2026-10-11 15:30");
+        assert_eq!(parse("in a week"), "2026-10-21 15:30");
+    }
+
+    #[test]
+    fn it_parses_weekdays() {
+        assert_eq!(parse("friday"), "2026-10-16 00:00");
+        assert_eq!(parse("wednesday"), "2026-10-14 00:00");
+        assert_eq!(parse("next wednesday"), "2026-10-21 00:00");
+        assert_eq!(parse("last monday 9:15 am"), "2026-10-12 09:15");
+    }
+
+    #[test]
+    fn it_parses_calendar_dates_and_holidays() {
+        assert_eq!(parse("March 3rd"), "2027-03-03 00:00");
+        assert_eq!(parse("the 31st of december"), "2026-12-31 00:00");
+        assert_eq!(parse("4 july 2025"), "2025-07-04 00:00");
+        assert_eq!(parse("thanksgiving"), "2026-11-26 00:00");
+        assert_eq!(parse("memorial day"), "2027-05-31 00:00");
+        assert_eq!(parse("2026-12-01 08:00"), "2026-12-01 08:00");
+    }
+
+    #[test]
+    fn it_rolls_past_times_to_tomorrow() {
+        assert_eq!(parse("at 9am"), "2026-10-15 09:00");
+        assert_eq!(parse("6pm"), "2026-10-14 18:00");
+    }
+
+    #[test]
+    fn it_rejects_unknown_dates() {
+        assert!(matches!(
+            parse_datetime("whenever", now()),
+            Err(DateError::Unrecognized(_))
+        ));
+        assert!(parse_datetime("tomorrow at teatime", now()).is_err());
+    }
+
+    #[test]
+    fn it_adds_calendar_months() {
+        let january = chrono_tz::UTC
+            .with_ymd_and_hms(2026, 1, 31, 12, 0, 0)
+            .unwrap();
+        let result = add_duration(january, 1, DurationUnit::Months).unwrap();
+        assert_eq!(result.format("%Y-%m-%d").to_string(), "2026-02-28");
+    }
+}
@@ -0,0 +1,100 @@
+//loose matching of names people say, eg "the oat milks" finds "Oat milk"
+
+//how similar two normalized names must be to count as the same thing
+pub const MATCH_THRESHOLD: f64 = 0.8;
+
+fn singular(word: &str) -> String {
+    if word.len() > 4 && word.ends_with("ies") {
+        format!("{}y", &word[..word.len() - 3])
+    } else if word.len() > 4
+        && (word.ends_with("ches")
+            || word.ends_with("shes")
+            || word.ends_with("oes")
+            || word.ends_with("xes"))
+    {
+        word[..word.len() - 2].to_string()
+    } else if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+        word[..word.len() - 1].to_string()
+    } else {
+        word.to_string()
+    }
+}
+
+//lowercase, no punctuation or articles, singular words
+pub fn normalize(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "a" | "an" | "the" | "some" | "of"))
+        .map(singular)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+//1.0 for identical names after normalizing, 0.0 for nothing in common
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = normalize(a).chars().collect();
+    let b: Vec<char> = normalize(b).chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+//index of the most similar candidate, if any is similar enough
+pub fn best_match<'a, I>(query: &str, candidates: I) -> Option<usize>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    candidates
+        .into_iter()
+        .map(|candidate| similarity(query, candidate))
+        .enumerate()
+        .filter(|(_, score)| *score >= MATCH_THRESHOLD)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{best_match, normalize, similarity};
+
+    #[test]
+    fn it_normalizes_names() {
+        assert_eq!(normalize("The Oat-Milks"), "oat milk");
+        assert_eq!(normalize("groceries"), "grocery");
+        assert_eq!(normalize("2 boxes of tomatoes"), "2 box tomato");
+        assert_eq!(normalize("glass"), "glass");
+    }
+
+    #[test]
+    fn it_scores_similar_names() {
+        assert_eq!(similarity("Bananas", "banana"), 1.0);
+        assert!(similarity("oat milk", "oatmilk") > 0.8);
+        assert!(similarity("oat milk", "almond milk") < 0.8);
+    }
+
+    #[test]
+    fn it_finds_the_best_match() {
+        let items = ["eggs", "oat milk", "bread"];
+        assert_eq!(best_match("Oat milks", items), Some(1));
+        assert_eq!(best_match("egg", items), Some(0));
+        assert_eq!(best_match("butter", items), None);
+    }
+}
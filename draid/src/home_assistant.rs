@@ -0,0 +1,922 @@
+//smart home control through the Home Assistant REST API and a long-lived token
+use crate::config::HomeAssistant;
+use crate::confirmations::Confirmations;
+use crate::fuzzy::{MATCH_THRESHOLD, best_match, normalize, similarity};
+use crate::tools::{Tool, ToolContext, ToolOutput, TypedTool, glob_match, typed_tool};
+use reqwest::Client;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+//areas aren't in the REST API, but templates can read them
+const AREAS_TEMPLATE: &str = "{% set ns = namespace(areas=[]) %}\
+    {% for area in areas() %}\
+    {% set ns.areas = ns.areas + [{'id': area, 'name': area_name(area), \
+    'entities': area_entities(area)}] %}\
+    {% endfor %}\
+    {{ ns.areas | tojson }}";
+
+//attributes that don't help answer questions about a device
+const HIDDEN_ATTRIBUTES: [&str; 4] = [
+    "friendly_name",
+    "icon",
+    "entity_picture",
+    "supported_features",
+];
+
+//devices named in a service tool's description, the rest can still be used
+const DESCRIBED_ENTITIES: usize = 30;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct State {
+    pub entity_id: String,
+    pub state: String,
+    #[serde(default)]
+    pub attributes: Map<String, Value>,
+    #[serde(default)]
+    pub last_changed: Option<String>,
+}
+
+impl State {
+    fn name(&self) -> &str {
+        self.attributes
+            .get("friendly_name")
+            .and_then(Value::as_str)
+            .unwrap_or(&self.entity_id)
+    }
+    fn domain(&self) -> &str {
+        self.entity_id
+            .split_once('.')
+            .map_or(self.entity_id.as_str(), |(domain, _)| domain)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct ServiceField {
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    example: Option<Value>,
+    #[serde(default)]
+    selector: Option<Value>,
+    //sections like advanced_fields hold more fields
+    #[serde(default)]
+    fields: HashMap<String, ServiceField>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct Service {
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    fields: HashMap<String, ServiceField>,
+}
+
+impl Service {
+    //fields of sections are flattened, they are sent alongside the others
+    fn fields(&self) -> BTreeMap<&str, &ServiceField> {
+        let mut fields = BTreeMap::new();
+        for (name, field) in &self.fields {
+            if field.fields.is_empty() {
+                fields.insert(name.as_str(), field);
+            }
+            fields.extend(
+                field
+                    .fields
+                    .iter()
+                    .map(|(name, field)| (name.as_str(), field)),
+            );
+        }
+        fields
+    }
+}
+
+#[derive(Deserialize)]
+struct DomainServices {
+    domain: String,
+    services: HashMap<String, Service>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Area {
+    pub id: String,
+    pub name: String,
+    pub entities: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct EntityState {
+    pub entity_id: String,
+    pub name: String,
+    pub state: String,
+    pub area: Option<String>,
+    pub attributes: Map<String, Value>,
+    pub last_changed: Option<String>,
+}
+
+fn entity_state(state: &State, areas: &[Area]) -> EntityState {
+    EntityState {
+        entity_id: state.entity_id.clone(),
+        name: state.name().to_string(),
+        state: state.state.clone(),
+        area: areas
+            .iter()
+            .find(|area| area.entities.contains(&state.entity_id))
+            .map(|area| area.name.clone()),
+        attributes: state
+            .attributes
+            .iter()
+            .filter(|(key, _)| !HIDDEN_ATTRIBUTES.contains(&key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect(),
+        last_changed: state.last_changed.clone(),
+    }
+}
+
+//an entity id, or the name people use for it
+fn find_entity<'a>(entity: &str, states: &'a [State]) -> Option<&'a State> {
+    states
+        .iter()
+        .find(|state| state.entity_id == entity)
+        .or_else(|| best_match(entity, states.iter().map(State::name)).map(|index| &states[index]))
+}
+
+//eg "garage door" finds "Garage Door" and "Garage door battery"
+fn matches_search(state: &State, search: &str) -> bool {
+    let search = normalize(search);
+    normalize(state.name()).contains(&search)
+        || normalize(&state.entity_id).contains(&search)
+        || similarity(state.name(), &search) >= MATCH_THRESHOLD
+}
+
+pub struct HomeAssistantClient {
+    client: Client,
+    url: String,
+    token: String,
+}
+
+impl HomeAssistantClient {
+    pub fn new(url: &str, token: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> anyhow::Result<T> {
+        Ok(self
+            .client
+            .get(format!("{}{}", self.url, path))
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    pub async fn states(&self) -> anyhow::Result<Vec<State>> {
+        self.get("/api/states").await
+    }
+
+    pub async fn state(&self, entity_id: &str) -> anyhow::Result<State> {
+        self.get(&format!("/api/states/{}", entity_id)).await
+    }
+
+    //domain to its services
+    pub async fn services(&self) -> anyhow::Result<HashMap<String, HashMap<String, Service>>> {
+        let domains: Vec<DomainServices> = self.get("/api/services").await?;
+        Ok(domains
+            .into_iter()
+            .map(|domain| (domain.domain, domain.services))
+            .collect())
+    }
+
+    pub async fn areas(&self) -> anyhow::Result<Vec<Area>> {
+        let text = self
+            .client
+            .post(format!("{}/api/template", self.url))
+            .bearer_auth(&self.token)
+            .json(&json!({ "template": AREAS_TEMPLATE }))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    //returns the states that changed while the service ran
+    pub async fn call_service(
+        &self,
+        domain: &str,
+        service: &str,
+        data: &Map<String, Value>,
+    ) -> anyhow::Result<Vec<State>> {
+        Ok(self
+            .client
+            .post(format!("{}/api/services/{}/{}", self.url, domain, service))
+            .bearer_auth(&self.token)
+            .json(data)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+//discovers the entities and services, and makes a tool for the services of
+//each allowed domain
+pub async fn get_tools(
+    config: &HomeAssistant,
+    confirmations: &Arc<Confirmations>,
+) -> anyhow::Result<Vec<Arc<dyn Tool + Send + Sync>>> {
+    let client = Arc::new(HomeAssistantClient::new(&config.url, &config.token));
+    let states = client.states().await?;
+    let services = client.services().await?;
+    let mut tools: Vec<Arc<dyn Tool + Send + Sync>> = vec![
+        Arc::new(StatesTool::new(client.clone())),
+        Arc::new(AreasTool::new(client.clone())),
+    ];
+    for domain in &config.domains {
+        let Some(domain_services) = services.get(domain) else {
+            continue;
+        };
+        let entities: Vec<&State> = states
+            .iter()
+            .filter(|state| state.domain() == domain)
+            .collect();
+        tools.push(Arc::new(ServiceTool::new(
+            domain,
+            domain_services,
+            &entities,
+            config,
+            client.clone(),
+            confirmations.clone(),
+        )));
+    }
+    Ok(tools)
+}
+
+#[derive(Clone)]
+pub struct StatesTool {
+    name: String,
+    description: String,
+    client: Arc<HomeAssistantClient>,
+}
+
+impl StatesTool {
+    pub fn new(client: Arc<HomeAssistantClient>) -> Self {
+        Self {
+            name: "ha_states".to_string(),
+            description: "Read the current state of smart home devices and sensors, eg \
+                whether the garage door is open or the temperature upstairs"
+                .to_string(),
+            client,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct StatesArgs {
+    /// Device name or entity id to look for, eg "garage door"
+    #[serde(default)]
+    search: Option<String>,
+    /// Only devices of this domain, eg light, lock or sensor
+    #[serde(default)]
+    domain: Option<String>,
+    /// Only devices in this area, eg "kitchen"
+    #[serde(default)]
+    area: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for StatesTool {
+    type Args = StatesArgs;
+    type Output = ToolOutput<Vec<EntityState>>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(&self, args: StatesArgs, _context: &ToolContext) -> anyhow::Result<Self::Output> {
+        let states = self.client.states().await?;
+        let areas = self.client.areas().await?;
+        let area = match &args.area {
+            Some(area) => match best_match(area, areas.iter().map(|area| area.name.as_str())) {
+                Some(index) => Some(&areas[index]),
+                None => return Ok(ToolOutput::Error(format!("No area called {}", area))),
+            },
+            None => None,
+        };
+        Ok(ToolOutput::Result(
+            states
+                .iter()
+                .filter(|state| {
+                    args.domain
+                        .as_ref()
+                        .is_none_or(|domain| state.domain() == domain)
+                })
+                .filter(|state| area.is_none_or(|area| area.entities.contains(&state.entity_id)))
+                .filter(|state| {
+                    args.search
+                        .as_ref()
+                        .is_none_or(|search| matches_search(state, search))
+                })
+                .map(|state| entity_state(state, &areas))
+                .collect(),
+        ))
+    }
+}
+typed_tool!(StatesTool);
+
+#[derive(Clone)]
+pub struct AreasTool {
+    name: String,
+    description: String,
+    client: Arc<HomeAssistantClient>,
+}
+
+impl AreasTool {
+    pub fn new(client: Arc<HomeAssistantClient>) -> Self {
+        Self {
+            name: "ha_areas".to_string(),
+            description: "List the areas of the home, eg rooms, and the entity ids of the \
+                devices in each"
+                .to_string(),
+            client,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct AreasArgs {}
+
+#[async_trait::async_trait]
+impl TypedTool for AreasTool {
+    type Args = AreasArgs;
+    type Output = ToolOutput<Vec<Area>>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(&self, _args: AreasArgs, _context: &ToolContext) -> anyhow::Result<Self::Output> {
+        Ok(ToolOutput::Result(self.client.areas().await?))
+    }
+}
+typed_tool!(AreasTool);
+
+//keys of service data that pick which devices a service acts on
+const TARGETS: [&str; 5] = ["entity_id", "area_id", "device_id", "label_id", "floor_id"];
+
+#[derive(Deserialize)]
+struct ServiceArgs {
+    service: String,
+    entity: String,
+    #[serde(default)]
+    data: Map<String, Value>,
+    #[serde(default)]
+    confirmation: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceOutcome {
+    //states of the devices afterwards
+    Done(Vec<EntityState>),
+    NeedsConfirmation {
+        question: String,
+        confirmation: String,
+    },
+}
+
+//the json schema type of a field, from its selector
+fn field_schema(field: &ServiceField) -> Value {
+    let mut schema = Map::new();
+    if let Some(selector) = field.selector.as_ref().and_then(Value::as_object) {
+        match selector.keys().next().map(String::as_str) {
+            Some("number") => {
+                schema.insert("type".to_string(), json!("number"));
+            }
+            Some("boolean") => {
+                schema.insert("type".to_string(), json!("boolean"));
+            }
+            Some("text") | Some("time") | Some("entity") => {
+                schema.insert("type".to_string(), json!("string"));
+            }
+            Some("select") => {
+                let options: Vec<Value> = selector["select"]["options"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|option| option.get("value").unwrap_or(option).clone())
+                    .collect();
+                schema.insert("type".to_string(), json!("string"));
+                if !options.is_empty() {
+                    schema.insert("enum".to_string(), Value::Array(options));
+                }
+            }
+            Some("color_rgb") => {
+                schema.insert("type".to_string(), json!("array"));
+                schema.insert("items".to_string(), json!({ "type": "integer" }));
+            }
+            _ => {}
+        }
+    }
+    let description = match (&field.description, &field.example) {
+        (Some(description), Some(example)) => Some(format!("{} Eg {}", description, example)),
+        (Some(description), None) => Some(description.clone()),
+        (None, Some(example)) => Some(format!("Eg {}", example)),
+        (None, None) => None,
+    };
+    if let Some(description) = description {
+        schema.insert("description".to_string(), json!(description));
+    }
+    Value::Object(schema)
+}
+
+//calls the services of one domain, eg ha_light for light.turn_on
+pub struct ServiceTool {
+    name: String,
+    description: String,
+    domain: String,
+    services: BTreeMap<String, Service>,
+    confirm: Vec<String>,
+    client: Arc<HomeAssistantClient>,
+    confirmations: Arc<Confirmations>,
+}
+
+impl ServiceTool {
+    pub fn new(
+        domain: &str,
+        services: &HashMap<String, Service>,
+        entities: &[&State],
+        config: &HomeAssistant,
+        client: Arc<HomeAssistantClient>,
+        confirmations: Arc<Confirmations>,
+    ) -> Self {
+        let names: Vec<&str> = entities
+            .iter()
+            .take(DESCRIBED_ENTITIES)
+            .map(|state| state.name())
+            .collect();
+        Self {
+            name: format!("ha_{}", domain),
+            description: format!(
+                "Control {} devices in the home. Devices: {}",
+                domain,
+                names.join(", ")
+            ),
+            domain: domain.to_string(),
+            services: services
+                .iter()
+                .map(|(name, service)| (name.clone(), service.clone()))
+                .collect(),
+            confirm: config.confirm.clone(),
+            client,
+            confirmations,
+        }
+    }
+
+    fn needs_confirmation(&self, service: &str) -> bool {
+        let name = format!("{}.{}", self.domain, service);
+        self.confirm
+            .iter()
+            .any(|pattern| glob_match(pattern, &name))
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for ServiceTool {
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    fn parameters(&self) -> Value {
+        let services: Vec<String> = self
+            .services
+            .iter()
+            .map(|(name, service)| match &service.description {
+                Some(description) => format!("{}: {}", name, description),
+                None => name.clone(),
+            })
+            .collect();
+        let fields: Map<String, Value> = self
+            .services
+            .values()
+            .flat_map(|service| service.fields().into_iter())
+            .map(|(name, field)| (name.to_string(), field_schema(field)))
+            .collect();
+        json!({
+            "type": "object",
+            "properties": {
+                "service": {
+                    "type": "string",
+                    "enum": self.services.keys().collect::<Vec<_>>(),
+                    "description": services.join("\n"),
+                },
+                "entity": {
+                    "type": "string",
+                    "description": "Name or entity id of the device",
+                },
+                "data": {
+                    "type": "object",
+                    "description": "Options for the service",
+                    "properties": fields,
+                },
+                "confirmation": {
+                    "type": "string",
+                    "description": "Only once the user has agreed, the confirmation from the \
+                        answer asking them",
+                },
+            },
+            "required": ["service", "entity"],
+        })
+    }
+    async fn invoke(&self, args: String, context: &ToolContext) -> anyhow::Result<Value> {
+        let args: ServiceArgs = serde_json::from_str(&args)
+            .map_err(|e| anyhow::anyhow!("Invalid arguments for tool {}: {}", self.name, e))?;
+        Ok(serde_json::to_value(self.call(args, context).await?)?)
+    }
+}
+
+impl ServiceTool {
+    async fn call(
+        &self,
+        args: ServiceArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<ToolOutput<ServiceOutcome>> {
+        if !self.services.contains_key(&args.service) {
+            return Ok(ToolOutput::Error(format!(
+                "{} has no service {}",
+                self.domain, args.service
+            )));
+        }
+        //the service only acts on the device that was found, so the model can't
+        //widen it to a whole area, device or label
+        let mut data = args.data;
+        for target in TARGETS {
+            data.remove(target);
+        }
+        let states: Vec<State> = self
+            .client
+            .states()
+            .await?
+            .into_iter()
+            .filter(|state| state.domain() == self.domain)
+            .collect();
+        let Some(entity) = find_entity(&args.entity, &states) else {
+            return Ok(ToolOutput::Error(format!(
+                "No {} called {}",
+                self.domain, args.entity
+            )));
+        };
+        if self.needs_confirmation(&args.service) {
+            let action = format!(
+                "{}.{} {} {}",
+                self.domain,
+                args.service,
+                entity.entity_id,
+                Value::Object(data.clone())
+            );
+            match &args.confirmation {
+                None => {
+                    return Ok(ToolOutput::Result(ServiceOutcome::NeedsConfirmation {
+                        question: format!(
+                            "Ask the user whether to {} {}. Only if they agree, call again \
+                                with this confirmation",
+                            args.service.replace('_', " "),
+                            entity.name()
+                        ),
+                        confirmation: self.confirmations.request(&action, context),
+                    }));
+                }
+                Some(code) => {
+                    if let Err(e) = self.confirmations.confirm(code, &action, context) {
+                        return Ok(ToolOutput::Error(e));
+                    }
+                }
+            }
+        }
+        data.insert("entity_id".to_string(), json!(entity.entity_id));
+        let mut changed = self
+            .client
+            .call_service(&self.domain, &args.service, &data)
+            .await?;
+        //devices that change later, or not at all, aren't in the response
+        if changed.is_empty() {
+            changed.push(self.client.state(&entity.entity_id).await?);
+        }
+        Ok(ToolOutput::Result(ServiceOutcome::Done(
+            changed
+                .iter()
+                .map(|state| entity_state(state, &[]))
+                .collect(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HomeAssistantClient, get_tools};
+    use crate::config::HomeAssistant;
+    use crate::confirmations::Confirmations;
+    use crate::tools::{Tool, ToolContext};
+    use poem::listener::{Acceptor, Listener, TcpListener};
+    use poem::web::{Data, Json, Path};
+    use poem::{EndpointExt, Request, Route, Server, handler, http::StatusCode};
+    use serde_json::{Value, json};
+    use std::sync::{Arc, Mutex};
+    use tokio_util::sync::CancellationToken;
+    use uuid::Uuid;
+
+    const TOKEN: &str = "long-lived-token";
+
+    type Calls = Arc<Mutex<Vec<(String, Value)>>>;
+
+    fn authorized(request: &Request) -> poem::Result<()> {
+        match request.header("Authorization") {
+            Some(header) if header == format!("Bearer {}", TOKEN) => Ok(()),
+            _ => Err(poem::Error::from_status(StatusCode::UNAUTHORIZED)),
+        }
+    }
+
+    fn states() -> Value {
+        json!([
+            {"entity_id": "light.kitchen", "state": "off",
+                "attributes": {"friendly_name": "Kitchen Lights", "supported_features": 44}},
+            {"entity_id": "lock.front_door", "state": "locked",
+                "attributes": {"friendly_name": "Front Door"}},
+            {"entity_id": "cover.garage_door", "state": "open",
+                "attributes": {"friendly_name": "Garage Door", "device_class": "garage"},
+                "last_changed": "2026-10-19T07:30:00+00:00"},
+            {"entity_id": "sensor.garage_door_battery", "state": "87",
+                "attributes": {"friendly_name": "Garage Door Battery", "unit_of_measurement": "%"}},
+            {"entity_id": "sensor.upstairs_temperature", "state": "20.5",
+                "attributes": {"friendly_name": "Upstairs Temperature"}}
+        ])
+    }
+
+    #[handler]
+    fn get_states(request: &Request) -> poem::Result<Json<Value>> {
+        authorized(request)?;
+        Ok(Json(states()))
+    }
+
+    #[handler]
+    fn get_state(request: &Request, Path(entity_id): Path<String>) -> poem::Result<Json<Value>> {
+        authorized(request)?;
+        states()
+            .as_array()
+            .and_then(|states| {
+                states
+                    .iter()
+                    .find(|state| state["entity_id"] == entity_id)
+                    .cloned()
+            })
+            .map(Json)
+            .ok_or_else(|| poem::Error::from_status(StatusCode::NOT_FOUND))
+    }
+
+    #[handler]
+    fn get_services(request: &Request) -> poem::Result<Json<Value>> {
+        authorized(request)?;
+        Ok(Json(json!([
+            {"domain": "light", "services": {
+                "turn_on": {"name": "Turn on", "description": "Turns on lights.", "fields": {
+                    "transition": {"description": "Seconds to fade.", "selector": {"number": {}}},
+                    "advanced_fields": {"collapsed": true, "fields": {
+                        "brightness": {"description": "Brightness 0 to 255.", "example": 120,
+                            "selector": {"number": {"min": 0, "max": 255}}}
+                    }}
+                }},
+                "turn_off": {"description": "Turns off lights."}
+            }},
+            {"domain": "lock", "services": {
+                "lock": {"description": "Locks a lock."},
+                "unlock": {"description": "Unlocks a lock."}
+            }},
+            {"domain": "sensor", "services": {}}
+        ])))
+    }
+
+    #[handler]
+    fn render_template(request: &Request) -> poem::Result<String> {
+        authorized(request)?;
+        Ok(json!([
+            {"id": "garage", "name": "Garage",
+                "entities": ["cover.garage_door", "sensor.garage_door_battery"]},
+            {"id": "kitchen", "name": "Kitchen", "entities": ["light.kitchen"]}
+        ])
+        .to_string())
+    }
+
+    #[handler]
+    fn call_service(
+        request: &Request,
+        Path((domain, service)): Path<(String, String)>,
+        Json(data): Json<Value>,
+        Data(calls): Data<&Calls>,
+    ) -> poem::Result<Json<Value>> {
+        authorized(request)?;
+        calls
+            .lock()
+            .unwrap()
+            .push((format!("{}.{}", domain, service), data));
+        Ok(Json(json!([])))
+    }
+
+    //a mock of the parts of the Home Assistant REST API that are used
+    async fn mock_home_assistant() -> (String, Calls) {
+        let calls: Calls = Arc::new(Mutex::new(vec![]));
+        let app = Route::new()
+            .at("/api/states", get_states)
+            .at("/api/states/:entity_id", get_state)
+            .at("/api/services", get_services)
+            .at("/api/services/:domain/:service", call_service)
+            .at("/api/template", render_template)
+            .data(calls.clone());
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let address = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+        (format!("http://{}", address), calls)
+    }
+
+    fn config(url: &str) -> HomeAssistant {
+        HomeAssistant {
+            url: url.to_string(),
+            token: TOKEN.to_string(),
+            domains: vec![
+                "light".to_string(),
+                "lock".to_string(),
+                "switch".to_string(),
+            ],
+            confirm: vec!["lock.unlock".to_string()],
+        }
+    }
+
+    fn context() -> ToolContext {
+        ToolContext {
+            user_id: Uuid::new_v4(),
+            roles: vec![],
+            session_id: Uuid::new_v4(),
+            bot_name: "helper".to_string(),
+            span_id: Uuid::new_v4().to_string(),
+            cancellation_token: CancellationToken::new(),
+            timezone: chrono_tz::UTC,
+        }
+    }
+
+    async fn tool(url: &str, name: &str) -> Arc<dyn Tool + Send + Sync> {
+        get_tools(&config(url), &Arc::new(Confirmations::new()))
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|tool| tool.name() == name)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_makes_tools_for_allowed_domains() {
+        let (url, _) = mock_home_assistant().await;
+        let tools = get_tools(&config(&url), &Arc::new(Confirmations::new()))
+            .await
+            .unwrap();
+        let names: Vec<&String> = tools.iter().map(|tool| tool.name()).collect();
+        assert_eq!(names, vec!["ha_states", "ha_areas", "ha_light", "ha_lock"]);
+        let light = &tools[2];
+        assert!(light.description().contains("Kitchen Lights"));
+        let parameters = light.parameters();
+        assert_eq!(
+            parameters["properties"]["service"]["enum"],
+            json!(["turn_off", "turn_on"])
+        );
+        assert_eq!(
+            parameters["properties"]["data"]["properties"]["brightness"],
+            json!({"type": "number", "description": "Brightness 0 to 255. Eg 120"})
+        );
+    }
+
+    #[tokio::test]
+    async fn it_needs_the_token() {
+        let (url, _) = mock_home_assistant().await;
+        assert!(
+            HomeAssistantClient::new(&url, "wrong")
+                .states()
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_reads_states() {
+        let (url, _) = mock_home_assistant().await;
+        let result = tool(&url, "ha_states")
+            .await
+            .invoke(r#"{"search": "the garage door"}"#.to_string(), &context())
+            .await
+            .unwrap();
+        assert_eq!(result["result"][0]["entity_id"], "cover.garage_door");
+        assert_eq!(result["result"][0]["state"], "open");
+        assert_eq!(result["result"][0]["area"], "Garage");
+        assert_eq!(
+            result["result"][1]["entity_id"],
+            "sensor.garage_door_battery"
+        );
+        let result = tool(&url, "ha_states")
+            .await
+            .invoke(r#"{"area": "kitchen"}"#.to_string(), &context())
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            json!({"result": [{"entity_id": "light.kitchen", "name": "Kitchen Lights",
+                "state": "off", "area": "Kitchen", "attributes": {}, "last_changed": null}]})
+        );
+    }
+
+    #[tokio::test]
+    async fn it_calls_services_by_device_name() {
+        let (url, calls) = mock_home_assistant().await;
+        let result = tool(&url, "ha_light")
+            .await
+            .invoke(
+                r#"{"service": "turn_on", "entity": "kitchen lights",
+                    "data": {"brightness": 120, "area_id": "kitchen", "device_id": "all"}}"#
+                    .to_string(),
+                &context(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result["result"]["done"][0]["entity_id"], "light.kitchen");
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![(
+                "light.turn_on".to_string(),
+                json!({"entity_id": "light.kitchen", "brightness": 120})
+            )]
+        );
+        let result = tool(&url, "ha_light")
+            .await
+            .invoke(
+                r#"{"service": "explode", "entity": "kitchen lights"}"#.to_string(),
+                &context(),
+            )
+            .await
+            .unwrap();
+        assert!(result["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn it_asks_before_unlocking() {
+        let (url, calls) = mock_home_assistant().await;
+        let lock = tool(&url, "ha_lock").await;
+        let asked = context();
+        let result = lock
+            .invoke(
+                r#"{"service": "unlock", "entity": "front door"}"#.to_string(),
+                &asked,
+            )
+            .await
+            .unwrap();
+        let confirmation = result["result"]["needs_confirmation"]["confirmation"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let args = json!({"service": "unlock", "entity": "front door",
+            "confirmation": confirmation})
+        .to_string();
+        //the model can't confirm on the user's behalf in the same turn
+        let result = lock.invoke(args.clone(), &asked).await.unwrap();
+        assert!(result["error"].is_string());
+        assert!(calls.lock().unwrap().is_empty());
+        let replied = ToolContext {
+            span_id: Uuid::new_v4().to_string(),
+            ..asked.clone()
+        };
+        let result = lock.invoke(args, &replied).await.unwrap();
+        assert_eq!(result["result"]["done"][0]["entity_id"], "lock.front_door");
+        assert_eq!(calls.lock().unwrap()[0].0, "lock.unlock");
+        //locking doesn't need confirming
+        lock.invoke(
+            r#"{"service": "lock", "entity": "front door"}"#.to_string(),
+            &asked,
+        )
+        .await
+        .unwrap();
+        assert_eq!(calls.lock().unwrap()[1].0, "lock.lock");
+    }
+}
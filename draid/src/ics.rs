@@ -0,0 +1,670 @@
+//reading and writing iCalendar (RFC 5545), the parts of VEVENT the calendar keeps
+use crate::dates::localize;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+
+//longest line before it is folded, in bytes
+const MAX_LINE: usize = 75;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IcsEvent {
+    pub uid: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub all_day: bool,
+    pub rrule: Option<String>,
+    pub exdates: Vec<DateTime<Utc>>,
+    //recurrences are expanded and all day events start at midnight in this timezone
+    pub timezone: Tz,
+    //CN, or the address before the @
+    pub attendees: Vec<String>,
+}
+
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+//long lines are continued on the next line after a space or tab
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+//splits on the separator where it isn't inside double quotes
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == separator && !in_quotes {
+            parts.push(&text[start..index]);
+            start = index + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+//NAME;PARAM=value;PARAM="quoted: value":VALUE
+fn parse_line(line: &str) -> Option<Property> {
+    let mut in_quotes = false;
+    let (colon, _) = line.char_indices().find(|(_, c)| {
+        if *c == '"' {
+            in_quotes = !in_quotes;
+        }
+        *c == ':' && !in_quotes
+    })?;
+    let parts = split_unquoted(&line[..colon], ';');
+    let params = parts[1..]
+        .iter()
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.to_uppercase(), value.trim_matches('"').to_string()))
+        .collect();
+    Some(Property {
+        name: parts[0].trim().to_uppercase(),
+        params,
+        value: line[colon + 1..].to_string(),
+    })
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn parse_timezone(tzid: &str) -> Option<Tz> {
+    tzid.trim_start_matches('/').parse().ok()
+}
+
+//a time skipped by daylight saving moves forward past the gap
+fn to_utc(naive: NaiveDateTime, timezone: Tz) -> Option<DateTime<Utc>> {
+    localize(&timezone, naive)
+        .or_else(|_| localize(&timezone, naive + Duration::hours(1)))
+        .ok()
+        .map(|datetime| datetime.with_timezone(&Utc))
+}
+
+//a date, a UTC time ending in Z or a local time in the TZID timezone, floating
+//times being in the default timezone. returns the time, whether it is a whole
+//day and the timezone it was given in
+fn parse_time(value: &str, property: &Property, default: Tz) -> Option<(DateTime<Utc>, bool, Tz)> {
+    let value = value.trim();
+    let timezone = property
+        .param("TZID")
+        .and_then(parse_timezone)
+        .unwrap_or(default);
+    if property.param("VALUE") == Some("DATE") || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((
+            to_utc(date.and_time(Default::default()), timezone)?,
+            true,
+            timezone,
+        ));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((naive.and_utc(), false, Tz::UTC));
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some((to_utc(naive, timezone)?, false, timezone))
+}
+
+//eg P1W, P1DT12H or PT1H30M. events can't end before they start, so negative
+//durations aren't read, nor ones too long to count
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim().trim_start_matches('+').strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            _ => {
+                let amount: i64 = number.parse().ok()?;
+                number.clear();
+                let part = match c {
+                    'W' => Duration::try_weeks(amount),
+                    'D' => Duration::try_days(amount),
+                    'H' => Duration::try_hours(amount),
+                    'M' => Duration::try_minutes(amount),
+                    'S' => Duration::try_seconds(amount),
+                    _ => return None,
+                }?;
+                total = total.checked_add(&part)?;
+            }
+        }
+    }
+    Some(total)
+}
+
+fn attendee_name(property: &Property) -> Option<String> {
+    if let Some(name) = property.param("CN")
+        && !name.trim().is_empty()
+    {
+        return Some(name.trim().to_string());
+    }
+    let address = property.value.trim();
+    let address = address
+        .strip_prefix("mailto:")
+        .or_else(|| address.strip_prefix("MAILTO:"))?;
+    address.split('@').next().map(String::from)
+}
+
+fn event_from(properties: &[Property], default: Tz) -> Option<IcsEvent> {
+    let find = |name: &str| properties.iter().find(|property| property.name == name);
+    //changes to a single occurrence of a recurring event aren't kept
+    if find("RECURRENCE-ID").is_some()
+        || find("STATUS").is_some_and(|status| status.value.eq_ignore_ascii_case("CANCELLED"))
+    {
+        return None;
+    }
+    let start = find("DTSTART")?;
+    let (starts_at, all_day, timezone) = parse_time(&start.value, start, default)?;
+    let ends_at = match (find("DTEND"), find("DURATION")) {
+        (Some(end), _) => parse_time(&end.value, end, default)?.0,
+        (None, Some(duration)) => starts_at.checked_add_signed(parse_duration(&duration.value)?)?,
+        (None, None) if all_day => starts_at + Duration::days(1),
+        (None, None) => starts_at,
+    };
+    let title = find("SUMMARY")
+        .map(|summary| unescape(&summary.value))
+        .unwrap_or_else(|| "Untitled".to_string());
+    //a UID is required, but events without one still import the same way twice
+    let uid = match find("UID") {
+        Some(uid) => uid.value.trim().to_string(),
+        None => sha256::digest(format!("{}{}", title, start.value)),
+    };
+    let exdates = properties
+        .iter()
+        .filter(|property| property.name == "EXDATE")
+        .flat_map(|property| {
+            property
+                .value
+                .split(',')
+                .filter_map(|value| parse_time(value, property, timezone))
+                .map(|(exdate, _, _)| exdate)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    Some(IcsEvent {
+        uid,
+        title,
+        description: find("DESCRIPTION").map(|description| unescape(&description.value)),
+        location: find("LOCATION").map(|location| unescape(&location.value)),
+        starts_at,
+        ends_at: ends_at.max(starts_at),
+        all_day,
+        rrule: find("RRULE").map(|rrule| rrule.value.trim().to_string()),
+        exdates,
+        timezone,
+        attendees: properties
+            .iter()
+            .filter(|property| property.name == "ATTENDEE")
+            .filter_map(attendee_name)
+            .collect(),
+    })
+}
+
+//the events of a calendar file. events that can't be read are skipped
+pub fn parse_calendar(text: &str, default: Tz) -> Vec<IcsEvent> {
+    let mut events = vec![];
+    let mut event: Option<Vec<Property>> = None;
+    //components inside an event, eg VALARM
+    let mut nested = 0;
+    for line in unfold(text) {
+        let Some(property) = parse_line(&line) else {
+            continue;
+        };
+        let component = property.value.trim().to_uppercase();
+        match (property.name.as_str(), component.as_str(), &mut event) {
+            ("BEGIN", "VEVENT", _) => {
+                event = Some(vec![]);
+                nested = 0;
+            }
+            ("END", "VEVENT", Some(properties)) => {
+                events.extend(event_from(properties, default));
+                event = None;
+            }
+            ("BEGIN", _, Some(_)) => nested += 1,
+            ("END", _, Some(_)) => nested -= 1,
+            (_, _, Some(properties)) if nested == 0 => properties.push(property),
+            _ => {}
+        }
+    }
+    events
+}
+
+//folds at character boundaries so multi-byte characters aren't split
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+fn time_property(name: &str, time: &DateTime<Utc>, all_day: bool, timezone: Tz) -> String {
+    let local = time.with_timezone(&timezone);
+    if all_day {
+        format!("{};VALUE=DATE:{}", name, local.format("%Y%m%d"))
+    } else if timezone == Tz::UTC {
+        format!("{}:{}", name, time.format("%Y%m%dT%H%M%SZ"))
+    } else {
+        format!(
+            "{};TZID={}:{}",
+            name,
+            timezone.name(),
+            local.format("%Y%m%dT%H%M%S")
+        )
+    }
+}
+
+//household members have no addresses, invalid:nomail is what calendar apps use then
+fn attendee_line(attendee: &str) -> String {
+    format!(
+        "ATTENDEE;CN=\"{}\":invalid:nomail",
+        attendee.replace('"', "")
+    )
+}
+
+//the properties of an event, without its attendees
+fn event_lines(event: &IcsEvent, stamp: DateTime<Utc>) -> Vec<String> {
+    let mut lines = vec![
+        format!("UID:{}", event.uid),
+        format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")),
+        time_property("DTSTART", &event.starts_at, event.all_day, event.timezone),
+        time_property("DTEND", &event.ends_at, event.all_day, event.timezone),
+        format!("SUMMARY:{}", escape(&event.title)),
+    ];
+    if let Some(description) = &event.description {
+        lines.push(format!("DESCRIPTION:{}", escape(description)));
+    }
+    if let Some(location) = &event.location {
+        lines.push(format!("LOCATION:{}", escape(location)));
+    }
+    if let Some(rrule) = &event.rrule {
+        lines.push(format!("RRULE:{}", rrule.trim_start_matches("RRULE:")));
+    }
+    for exdate in &event.exdates {
+        lines.push(time_property(
+            "EXDATE",
+            exdate,
+            event.all_day,
+            event.timezone,
+        ));
+    }
+    lines
+}
+
+fn to_text(lines: &[String]) -> String {
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+pub fn write_calendar(name: &str, events: &[IcsEvent], stamp: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//draid//calendar//EN".to_string(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.extend(event_lines(event, stamp));
+        lines.extend(
+            event
+                .attendees
+                .iter()
+                .map(|attendee| attendee_line(attendee)),
+        );
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    to_text(&lines)
+}
+
+//the properties of the main event that an edit to the event changes
+fn changed_properties(current: &IcsEvent, event: &IcsEvent) -> Vec<&'static str> {
+    let times_changed = (current.all_day, current.timezone) != (event.all_day, event.timezone);
+    let mut changed = vec!["DTSTAMP"];
+    if times_changed || current.starts_at != event.starts_at {
+        changed.push("DTSTART");
+    }
+    if times_changed || current.ends_at != event.ends_at {
+        changed.extend(["DTEND", "DURATION"]);
+    }
+    if current.title != event.title {
+        changed.push("SUMMARY");
+    }
+    if current.description != event.description {
+        changed.push("DESCRIPTION");
+    }
+    if current.location != event.location {
+        changed.push("LOCATION");
+    }
+    if current.rrule != event.rrule {
+        changed.push("RRULE");
+    }
+    if times_changed || current.exdates != event.exdates {
+        changed.push("EXDATE");
+    }
+    changed
+}
+
+//the calendar object from the server with the event's changes written into its
+//main event, so that what the calendar doesn't keep is kept on the server, eg
+//alarms, changes to single occurrences and the organizer. attendees from outside
+//the household stay, member gives the username of one who is in it. none when
+//there is no such event to change
+pub fn patch_calendar(
+    original: &str,
+    event: &IcsEvent,
+    stamp: DateTime<Utc>,
+    member: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let lines = unfold(original);
+    //the line ending the main event and its own properties by line
+    let mut main: Option<(usize, Vec<(usize, Property)>)> = None;
+    let mut properties: Option<Vec<(usize, Property)>> = None;
+    let mut nested = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let Some(property) = parse_line(line) else {
+            continue;
+        };
+        let component = property.value.trim().to_uppercase();
+        match (property.name.as_str(), component.as_str(), &mut properties) {
+            ("BEGIN", "VEVENT", _) => {
+                properties = Some(vec![]);
+                nested = 0;
+            }
+            ("END", "VEVENT", Some(_)) => {
+                let event_properties = properties.take().unwrap_or_default();
+                let is_main = event_properties.iter().all(|(_, property)| {
+                    property.name != "RECURRENCE-ID"
+                        && (property.name != "UID" || property.value.trim() == event.uid)
+                });
+                if main.is_none() && is_main {
+                    main = Some((index, event_properties));
+                }
+            }
+            ("BEGIN", _, Some(_)) => nested += 1,
+            ("END", _, Some(_)) => nested -= 1,
+            (_, _, Some(event_properties)) if nested == 0 => {
+                event_properties.push((index, property))
+            }
+            _ => {}
+        }
+    }
+    let (end, properties) = main?;
+    let (indexes, properties): (Vec<usize>, Vec<Property>) = properties.into_iter().unzip();
+    let current = event_from(&properties, event.timezone)?;
+    let changed = changed_properties(&current, event);
+    let mut attending = vec![];
+    let mut patched = vec![];
+    for (index, line) in lines.into_iter().enumerate() {
+        if index == end {
+            patched.extend(event_lines(event, stamp).into_iter().filter(|line| {
+                parse_line(line).is_some_and(|property| changed.contains(&property.name.as_str()))
+            }));
+            patched.extend(
+                event
+                    .attendees
+                    .iter()
+                    .filter(|attendee| !attending.contains(*attendee))
+                    .map(|attendee| attendee_line(attendee)),
+            );
+        }
+        if let Some(position) = indexes.iter().position(|own| *own == index) {
+            let property = &properties[position];
+            if changed.contains(&property.name.as_str()) {
+                continue;
+            }
+            if property.name == "ATTENDEE"
+                && let Some(username) = attendee_name(property).and_then(|name| member(&name))
+            {
+                if !event.attendees.contains(&username) {
+                    continue;
+                }
+                attending.push(username);
+            }
+        }
+        patched.push(line);
+    }
+    Some(to_text(&patched))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IcsEvent, fold, parse_calendar, parse_duration, patch_calendar, write_calendar};
+    use chrono::{Duration, TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:soccer@example.com\r
+DTSTART;TZID=America/New_York:20261024T090000\r
+DURATION:PT1H30M\r
+SUMMARY:Soccer practice\\, field 3\r
+DESCRIPTION:Bring water\\nand shin guards\r
+RRULE:FREQ=WEEKLY;BYDAY=SA\r
+EXDATE;TZID=America/New_York:20261031T090000\r
+ATTENDEE;CN=\"Sam\";ROLE=REQ-PARTICIPANT:mailto:sam@example.com\r
+ATTENDEE:mailto:alex@example.com\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+DESCRIPTION:Reminder\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:birthday@example.com\r
+DTSTART;VALUE=DATE:20261101\r
+SUMMARY:Grandma's birthday with a title long enough that it needs to be folded over\r
+  two lines\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:cancelled@example.com\r
+DTSTART:20261025T150000Z\r
+STATUS:CANCELLED\r
+SUMMARY:Cancelled\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn it_reads_events() {
+        let events = parse_calendar(CALENDAR, Tz::Europe__London);
+        assert_eq!(events.len(), 2);
+        let soccer = &events[0];
+        assert_eq!(soccer.title, "Soccer practice, field 3");
+        assert_eq!(
+            soccer.description.as_deref(),
+            Some("Bring water\nand shin guards")
+        );
+        assert_eq!(
+            soccer.starts_at,
+            Utc.with_ymd_and_hms(2026, 10, 24, 13, 0, 0).unwrap()
+        );
+        assert_eq!(soccer.ends_at - soccer.starts_at, Duration::minutes(90));
+        assert_eq!(soccer.timezone, Tz::America__New_York);
+        assert_eq!(soccer.rrule.as_deref(), Some("FREQ=WEEKLY;BYDAY=SA"));
+        assert_eq!(
+            soccer.exdates,
+            vec![Utc.with_ymd_and_hms(2026, 10, 31, 13, 0, 0).unwrap()]
+        );
+        assert_eq!(soccer.attendees, vec!["Sam", "alex"]);
+        let birthday = &events[1];
+        assert!(birthday.all_day);
+        assert_eq!(
+            birthday.title,
+            "Grandma's birthday with a title long enough that it needs to be folded over two lines"
+        );
+        assert_eq!(
+            birthday.starts_at,
+            Utc.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(birthday.ends_at - birthday.starts_at, Duration::days(1));
+    }
+
+    #[test]
+    fn it_writes_what_it_reads() {
+        let events = parse_calendar(CALENDAR, Tz::Europe__London);
+        let written = write_calendar("Sam", &events, Utc::now());
+        assert!(written.contains("DTSTART;TZID=America/New_York:20261024T090000\r\n"));
+        assert!(written.contains("DTSTART;VALUE=DATE:20261101\r\n"));
+        assert!(written.lines().all(|line| line.len() <= 75));
+        assert_eq!(parse_calendar(&written, Tz::Europe__London), events);
+    }
+
+    #[test]
+    fn it_skips_durations_it_cant_use() {
+        assert_eq!(parse_duration("P1DT12H"), Some(Duration::hours(36)));
+        assert_eq!(parse_duration("-PT1H"), None);
+        assert_eq!(parse_duration("P99999999999999999W"), None);
+        let calendar = CALENDAR.replace("DURATION:PT1H30M", "DURATION:P100000000W");
+        let events = parse_calendar(&calendar, Tz::Europe__London);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].all_day);
+    }
+
+    #[test]
+    fn it_patches_only_what_changed() {
+        let original = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:dinner@phone\r
+DTSTAMP:20261001T000000Z\r
+DTSTART:20261020T170000Z\r
+DURATION:PT2H\r
+RRULE:FREQ=WEEKLY\r
+SUMMARY:Family dinner\r
+ORGANIZER;CN=Sam:mailto:sam@example.com\r
+ATTENDEE;CN=Sam:mailto:sam@example.com\r
+ATTENDEE;CN=Grandma:mailto:grandma@example.com\r
+X-APPLE-TRAVEL-ADVISORY-BEHAVIOR:AUTOMATIC\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+TRIGGER:-PT30M\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:dinner@phone\r
+RECURRENCE-ID:20261027T170000Z\r
+DTSTART:20261027T180000Z\r
+DURATION:PT2H\r
+SUMMARY:Family dinner, late\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let mut event = parse_calendar(original, Tz::UTC).remove(0);
+        event.title = "Family dinner at Grandma's".to_string();
+        event.attendees = vec!["alex".to_string()];
+        let member = |name: &str| match name {
+            "Sam" => Some("sam".to_string()),
+            "alex" => Some("alex".to_string()),
+            _ => None,
+        };
+        let patched = patch_calendar(original, &event, Utc::now(), member).unwrap();
+        assert!(patched.contains("SUMMARY:Family dinner at Grandma's\r\n"));
+        assert!(!patched.contains("SUMMARY:Family dinner\r\n"));
+        assert!(patched.contains("DURATION:PT2H\r\n"));
+        assert!(!patched.contains("DTEND"));
+        assert!(patched.contains("ORGANIZER;CN=Sam:mailto:sam@example.com\r\n"));
+        assert!(!patched.contains("ATTENDEE;CN=Sam"));
+        assert!(patched.contains("ATTENDEE;CN=Grandma:mailto:grandma@example.com\r\n"));
+        assert!(patched.contains("ATTENDEE;CN=\"alex\":invalid:nomail\r\n"));
+        assert!(patched.contains("X-APPLE-TRAVEL-ADVISORY-BEHAVIOR:AUTOMATIC\r\n"));
+        assert!(patched.contains("TRIGGER:-PT30M\r\n"));
+        assert!(patched.contains("SUMMARY:Family dinner, late\r\n"));
+        assert!(!patched.contains("DTSTAMP:20261001T000000Z"));
+        let events = parse_calendar(&patched, Tz::UTC);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].title, event.title);
+        assert_eq!(events[0].ends_at, event.ends_at);
+        //nothing to patch
+        assert!(
+            patch_calendar(
+                "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n",
+                &event,
+                Utc::now(),
+                member
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn it_folds_without_splitting_characters() {
+        let line = format!("SUMMARY:{}", "é".repeat(50));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn it_writes_utc_times() {
+        let event = IcsEvent {
+            uid: "call".to_string(),
+            title: "Call".to_string(),
+            description: None,
+            location: None,
+            starts_at: Utc.with_ymd_and_hms(2026, 10, 20, 17, 0, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2026, 10, 20, 17, 30, 0).unwrap(),
+            all_day: false,
+            rrule: None,
+            exdates: vec![],
+            timezone: Tz::UTC,
+            attendees: vec![],
+        };
+        let written = write_calendar("Household", &[event], Utc::now());
+        assert!(written.contains("DTSTART:20261020T170000Z\r\n"));
+        assert!(written.contains("DTEND:20261020T173000Z\r\n"));
+    }
+}
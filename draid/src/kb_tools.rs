@@ -1,7 +1,9 @@
 use crate::config::KB;
-use crate::tools::Tool;
+use crate::tools::{Tool, ToolContext, ToolResult, TypedTool, typed_tool};
 use reqwest::Client as HttpClient;
-use serde_json::{Value, json};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
 use std::sync::Arc;
 #[derive(Clone)]
 pub struct KBTool {
@@ -13,10 +15,12 @@ pub struct KBTool {
 
 impl KBTool {
     pub fn new(url: String, kb_config: KB) -> Self {
-        let description = format!(
-            "Knowledge base containing information on {}",
-            kb_config.name
-        );
+        let description = kb_config.description.unwrap_or_else(|| {
+            format!(
+                "Knowledge base containing information on {}",
+                kb_config.name
+            )
+        });
         Self {
             name: kb_config.name,
             description,
@@ -26,47 +30,38 @@ impl KBTool {
     }
 }
 
-// Generated impl block, replacing placeholders with parsed values
+#[derive(Deserialize, JsonSchema)]
+pub struct KBArgs {
+    /// Search term to send to knowledge base
+    content: String,
+}
+
 #[async_trait::async_trait]
-impl Tool for KBTool {
+impl TypedTool for KBTool {
+    type Args = KBArgs;
+    type Output = ToolResult<Vec<String>>;
     fn name(&self) -> &String {
-        // Use the generated static string literal
         &self.name
     }
     fn description(&self) -> &String {
         &self.description
     }
-    fn parameters(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {
-                "content": {
-                    "type": "string",
-                    "description": "Search term to send to knowledge base",
-                },
-            },
-            "required": ["content"],
-        })
-    }
-    async fn invoke(&self, args: String) -> anyhow::Result<Value> {
+    async fn call(&self, args: KBArgs, _context: &ToolContext) -> anyhow::Result<Self::Output> {
         let client = HttpClient::new();
 
         let kb_url = format!("{}/knowledge_base/{}/similar", self.url, self.name);
 
-        let args: Value = serde_json::from_str(&args)?;
-        let content = args["content"].as_str().unwrap();
-
-        // The LitInt for num_results is directly interpolated
-        let body = json!({"text": content, "num_results": self.num_results});
+        let body = json!({"text": args.content, "num_results": self.num_results});
 
         let response = client.post(kb_url).json(&body).send().await?;
         let result = response.json::<Vec<String>>().await?;
 
-        Ok(json!({"result": result}))
+        Ok(ToolResult { result })
     }
 }
+typed_tool!(KBTool);
 
-//url cloning is unfortunate, but only happens at initial startup
+//url cloning is unfortunate, but only happens when the tools are refreshed
 pub fn get_tools(kb_configs: Vec<KB>, url: &str) -> Vec<Arc<dyn Tool + Send + Sync>> {
     kb_configs
         .into_iter()
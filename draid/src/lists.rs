@@ -0,0 +1,463 @@
+use crate::fuzzy::{best_match, normalize};
+use crate::notifications::{Notification, Notifications};
+use crate::psql_lists::{
+    List, ListItem, ListItemRequest, ListItemUpdate, ListWithItems, create_list, create_list_item,
+    delete_list_item, get_list_items, get_lists, update_list_item,
+};
+use crate::tools::{ToolContext, ToolOutput, TypedTool, typed_tool};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+//"grocery list", "the groceries" and "Grocery" all mean the same list
+fn list_key(name: &str) -> String {
+    let name = normalize(name);
+    name.strip_suffix(" list").unwrap_or(&name).to_string()
+}
+
+pub async fn find_list(name: &str, pool: &PgPool) -> sqlx::Result<Option<List>> {
+    let lists = get_lists(pool).await?;
+    let keys: Vec<String> = lists.iter().map(|list| list_key(&list.name)).collect();
+    let index = best_match(&list_key(name), keys.iter().map(|key| key.as_str()));
+    Ok(index.map(|index| lists[index].clone()))
+}
+
+pub async fn find_or_create_list(name: &str, pool: &PgPool) -> sqlx::Result<List> {
+    match find_list(name, pool).await? {
+        Some(list) => Ok(list),
+        None => create_list(name.trim(), pool).await,
+    }
+}
+
+pub async fn list_with_items(list: List, pool: &PgPool) -> sqlx::Result<ListWithItems> {
+    let items = get_list_items(&list.id, pool).await?;
+    Ok(ListWithItems {
+        id: list.id,
+        name: list.name,
+        items,
+    })
+}
+
+fn find_item<'a>(name: &str, items: &'a [ListItem]) -> Option<&'a ListItem> {
+    best_match(name, items.iter().map(|item| item.name.as_str())).map(|index| &items[index])
+}
+
+//what adding an item did to the list
+#[derive(Debug, PartialEq)]
+pub enum ItemChange {
+    Added,
+    Updated,
+    Unchanged,
+}
+
+//a checked off item counts as added again, one still on the list only changes
+//if it's given a different quantity
+fn item_change(existing: Option<&ListItem>, item: &ListItemRequest) -> ItemChange {
+    match existing {
+        None => ItemChange::Added,
+        Some(existing) if existing.checked => ItemChange::Added,
+        Some(existing) if item.quantity.is_some() && item.quantity != existing.quantity => {
+            ItemChange::Updated
+        }
+        Some(_) => ItemChange::Unchanged,
+    }
+}
+
+//adding something already on the list doesn't add it twice. a checked off item
+//is put back on the list instead
+pub async fn add_item(
+    list_id: &Uuid,
+    item: &ListItemRequest,
+    added_by: &Uuid,
+    pool: &PgPool,
+) -> sqlx::Result<(ListItem, ItemChange)> {
+    let items = get_list_items(list_id, pool).await?;
+    let existing = find_item(&item.name, &items);
+    let change = item_change(existing, item);
+    let item = match (existing, &change) {
+        (None, _) => create_list_item(list_id, item, added_by, pool).await?,
+        (Some(existing), ItemChange::Unchanged) => existing.clone(),
+        (Some(existing), _) => {
+            let update = ListItemUpdate {
+                name: None,
+                quantity: item.quantity.clone(),
+                checked: Some(false),
+            };
+            update_list_item(&existing.id, &update, pool).await?
+        }
+    };
+    Ok((item, change))
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ItemArg {
+    /// Item name, eg "oat milk"
+    name: String,
+    /// Optional amount, eg "2 cartons"
+    #[serde(default)]
+    quantity: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct AddListItemsTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+    notifications: Arc<Notifications>,
+}
+
+impl AddListItemsTool {
+    pub fn new(pool: PgPool, notifications: Arc<Notifications>) -> Self {
+        Self {
+            name: "add_to_list".to_string(),
+            description: "Add items to a shared household list, eg the grocery or to-do list. \
+                The list is created if it doesn't exist. Items already on the list aren't added twice"
+                .to_string(),
+            pool,
+            notifications,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct AddListItemsArgs {
+    /// Name of the list, eg "groceries" or "to-do"
+    list: String,
+    /// Items to add
+    items: Vec<ItemArg>,
+}
+
+#[derive(Serialize)]
+pub struct AddedItems {
+    list: String,
+    added: Vec<String>,
+    updated: Vec<String>,
+    already_on_list: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for AddListItemsTool {
+    type Args = AddListItemsArgs;
+    type Output = ToolOutput<AddedItems>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: AddListItemsArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let list = find_or_create_list(&args.list, &self.pool).await?;
+        let mut result = AddedItems {
+            list: list.name.clone(),
+            added: vec![],
+            updated: vec![],
+            already_on_list: vec![],
+        };
+        for item in args.items {
+            let item = ListItemRequest {
+                name: item.name,
+                quantity: item.quantity,
+            };
+            let (item, change) = add_item(&list.id, &item, &context.user_id, &self.pool).await?;
+            match change {
+                ItemChange::Added => result.added.push(item.name),
+                ItemChange::Updated => result.updated.push(item.name),
+                ItemChange::Unchanged => result.already_on_list.push(item.name),
+            }
+        }
+        if !result.added.is_empty() || !result.updated.is_empty() {
+            self.notifications.broadcast(Notification::ListChanged {
+                list_id: list.id,
+                changed_by: context.user_id,
+            });
+        }
+        Ok(ToolOutput::Result(result))
+    }
+}
+typed_tool!(AddListItemsTool);
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ListItemsArgs {
+    /// Name of the list, eg "groceries" or "to-do"
+    list: String,
+    /// Names of the items
+    items: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ChangedItems {
+    list: String,
+    changed: Vec<String>,
+    not_found: Vec<String>,
+}
+
+//applies a change to each named item that can be found on the list
+async fn change_items<F, Fut>(
+    args: ListItemsArgs,
+    context: &ToolContext,
+    pool: &PgPool,
+    notifications: &Notifications,
+    change: F,
+) -> anyhow::Result<ToolOutput<ChangedItems>>
+where
+    F: Fn(ListItem) -> Fut,
+    Fut: Future<Output = sqlx::Result<()>>,
+{
+    let Some(list) = find_list(&args.list, pool).await? else {
+        return Ok(ToolOutput::Error(format!("No list called {}", args.list)));
+    };
+    let items = get_list_items(&list.id, pool).await?;
+    let mut result = ChangedItems {
+        list: list.name.clone(),
+        changed: vec![],
+        not_found: vec![],
+    };
+    for name in args.items {
+        match find_item(&name, &items) {
+            Some(item) => {
+                result.changed.push(item.name.clone());
+                change(item.clone()).await?;
+            }
+            None => result.not_found.push(name),
+        }
+    }
+    if !result.changed.is_empty() {
+        notifications.broadcast(Notification::ListChanged {
+            list_id: list.id,
+            changed_by: context.user_id,
+        });
+    }
+    Ok(ToolOutput::Result(result))
+}
+
+#[derive(Clone)]
+pub struct RemoveListItemsTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+    notifications: Arc<Notifications>,
+}
+
+impl RemoveListItemsTool {
+    pub fn new(pool: PgPool, notifications: Arc<Notifications>) -> Self {
+        Self {
+            name: "remove_from_list".to_string(),
+            description: "Remove items from a shared household list".to_string(),
+            pool,
+            notifications,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TypedTool for RemoveListItemsTool {
+    type Args = ListItemsArgs;
+    type Output = ToolOutput<ChangedItems>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: ListItemsArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let pool = &self.pool;
+        change_items(
+            args,
+            context,
+            pool,
+            &self.notifications,
+            |item| async move {
+                delete_list_item(&item.id, pool).await?;
+                Ok(())
+            },
+        )
+        .await
+    }
+}
+typed_tool!(RemoveListItemsTool);
+
+#[derive(Clone)]
+pub struct CheckListItemsTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+    notifications: Arc<Notifications>,
+}
+
+impl CheckListItemsTool {
+    pub fn new(pool: PgPool, notifications: Arc<Notifications>) -> Self {
+        Self {
+            name: "check_off_list_items".to_string(),
+            description: "Check items off a shared household list, eg once they're bought or done"
+                .to_string(),
+            pool,
+            notifications,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TypedTool for CheckListItemsTool {
+    type Args = ListItemsArgs;
+    type Output = ToolOutput<ChangedItems>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: ListItemsArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let pool = &self.pool;
+        change_items(
+            args,
+            context,
+            pool,
+            &self.notifications,
+            |item| async move {
+                let update = ListItemUpdate {
+                    name: None,
+                    quantity: None,
+                    checked: Some(true),
+                };
+                update_list_item(&item.id, &update, pool).await?;
+                Ok(())
+            },
+        )
+        .await
+    }
+}
+typed_tool!(CheckListItemsTool);
+
+#[derive(Clone)]
+pub struct ReadListTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl ReadListTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "read_list".to_string(),
+            description: "Read the items on a shared household list. \
+                Without a list name, returns the names of all lists"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ReadListArgs {
+    /// Name of the list, leave out to get all list names
+    #[serde(default)]
+    list: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum ReadListResult {
+    Lists(Vec<String>),
+    List(ListWithItems),
+}
+
+#[async_trait::async_trait]
+impl TypedTool for ReadListTool {
+    type Args = ReadListArgs;
+    type Output = ToolOutput<ReadListResult>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: ReadListArgs,
+        _context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let Some(name) = args.list else {
+            let lists = get_lists(&self.pool).await?;
+            return Ok(ToolOutput::Result(ReadListResult::Lists(
+                lists.into_iter().map(|list| list.name).collect(),
+            )));
+        };
+        Ok(match find_list(&name, &self.pool).await? {
+            Some(list) => ToolOutput::Result(ReadListResult::List(
+                list_with_items(list, &self.pool).await?,
+            )),
+            None => ToolOutput::Error(format!("No list called {}", name)),
+        })
+    }
+}
+typed_tool!(ReadListTool);
+
+#[cfg(test)]
+mod tests {
+    use super::{ItemChange, item_change, list_key};
+    use crate::psql_lists::{ListItem, ListItemRequest};
+    use uuid::Uuid;
+
+    fn list_item(quantity: Option<&str>, checked: bool) -> ListItem {
+        ListItem {
+            id: Uuid::new_v4(),
+            list_id: Uuid::new_v4(),
+            name: "milk".to_string(),
+            quantity: quantity.map(String::from),
+            checked,
+            added_by: None,
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    fn request(quantity: Option<&str>) -> ListItemRequest {
+        ListItemRequest {
+            name: "milk".to_string(),
+            quantity: quantity.map(String::from),
+        }
+    }
+
+    #[test]
+    fn it_reports_what_adding_an_item_changed() {
+        let on_list = list_item(Some("1 carton"), false);
+        let checked = list_item(None, true);
+        assert_eq!(item_change(None, &request(None)), ItemChange::Added);
+        assert_eq!(
+            item_change(Some(&checked), &request(None)),
+            ItemChange::Added
+        );
+        assert_eq!(
+            item_change(Some(&on_list), &request(Some("2 cartons"))),
+            ItemChange::Updated
+        );
+        assert_eq!(
+            item_change(Some(&on_list), &request(Some("1 carton"))),
+            ItemChange::Unchanged
+        );
+        assert_eq!(
+            item_change(Some(&on_list), &request(None)),
+            ItemChange::Unchanged
+        );
+    }
+
+    #[test]
+    fn it_treats_list_names_loosely() {
+        assert_eq!(list_key("Grocery list"), "grocery");
+        assert_eq!(list_key("the groceries"), "grocery");
+        assert_eq!(list_key("To-Do"), "to do");
+    }
+}
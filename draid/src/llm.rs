@@ -1,5 +1,5 @@
 use crate::psql_memory::{MessageResult, MessageType};
-use crate::tools::{Tool, ToolError, ToolRegistry};
+use crate::tools::{Tool, ToolContext, ToolError, ToolPermissions, ToolRegistry, ToolSet};
 use async_openai::types::CreateChatCompletionStreamResponse;
 use async_openai::{
     Client,
@@ -17,8 +17,9 @@ use async_openai::{
 use futures::future::join_all;
 use futures::{SinkExt, StreamExt};
 use poem::web::websocket::{Message, WebSocketStream};
+use poem_openapi::{Enum, Object};
 use serde::Serialize;
-use serde_json::Value;
+use serde_json::{Value, json};
 use std::sync::Arc;
 use tokio::task;
 use tokio::task::JoinHandle;
@@ -28,12 +29,20 @@ fn get_llm(api_endpoint: &str) -> Client<OpenAIConfig> {
     Client::with_config(OpenAIConfig::default().with_api_base(format!("{}/v1", api_endpoint)))
 }
 
+#[derive(Clone, Copy)]
+pub struct ModelParameters {
+    pub temperature: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub top_p: Option<f32>,
+}
+
 #[derive(Clone)]
 pub struct Bot {
+    name: String,
     model_name: String,
     system_prompt: &'static str,
     llm: Client<OpenAIConfig>,
-    tools: Option<Vec<Arc<dyn Tool + Send + Sync>>>,
+    tools: Option<ToolSet>,
     temperature: Option<f32>,
     presence_penalty: Option<f32>,
     top_p: Option<f32>,
@@ -41,24 +50,27 @@ pub struct Bot {
 
 impl Bot {
     pub fn new(
+        name: &str,
         model_name: String,
         system_prompt: &'static str,
         api_endpoint: &str,
-        temperature: Option<f32>,
-        presence_penalty: Option<f32>,
-        top_p: Option<f32>,
-        tools: Option<Vec<Arc<dyn Tool + Send + Sync>>>,
+        parameters: ModelParameters,
+        tools: Option<ToolSet>,
     ) -> Self {
         Self {
+            name: name.to_string(),
             model_name,
             system_prompt,
             llm: get_llm(api_endpoint),
-            temperature,
-            presence_penalty,
-            top_p,
+            temperature: parameters.temperature,
+            presence_penalty: parameters.presence_penalty,
+            top_p: parameters.top_p,
             tools,
         }
     }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 }
 
 fn get_req(
@@ -158,11 +170,13 @@ fn contains_stop_word(token: &str) -> bool {
     token.ends_with(STOP_WORD)
 }
 
+//keyed by the choice and the call's index in it, so the calls stay in the order
+//the model asked for them
 fn construct_tool_call(
     stream_chunk: CreateChatCompletionStreamResponse,
-) -> std::collections::HashMap<(u32, u32), ChatCompletionMessageToolCall> {
-    let mut tool_results: std::collections::HashMap<(u32, u32), ChatCompletionMessageToolCall> =
-        std::collections::HashMap::new();
+) -> std::collections::BTreeMap<(u32, u32), ChatCompletionMessageToolCall> {
+    let mut tool_results: std::collections::BTreeMap<(u32, u32), ChatCompletionMessageToolCall> =
+        std::collections::BTreeMap::new();
     stream_chunk
         .choices
         .into_iter()
@@ -204,7 +218,7 @@ fn construct_tool_call(
     tool_results
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Enum)]
 enum TokenCategory {
     Message,
     ChainOfThought,
@@ -213,22 +227,83 @@ enum TokenCategory {
 pub struct FullMessage {
     pub message: String,
     pub reasoning: String,
+    //tools called on the way to the message, in the order they were requested
+    pub tool_calls: Vec<ToolCallTrace>,
+}
+
+#[derive(Clone, Serialize, Object)]
+pub struct ToolCallTrace {
+    pub name: String,
+    pub arguments: Value,
+    pub result: Value,
 }
 
 pub enum ChatStreamResult {
     Message(FullMessage),
-    ToolCalls(std::collections::HashMap<(u32, u32), ChatCompletionMessageToolCall>),
+    ToolCalls(std::collections::BTreeMap<(u32, u32), ChatCompletionMessageToolCall>),
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Object)]
 #[serde(rename_all = "camelCase")]
+#[oai(rename_all = "camelCase")]
 pub struct WebSocketToken {
     token_type: TokenCategory,
     tokens: String,
 }
 
-async fn process_chat_stream(
-    tx: &mut WebSocketStream,
+impl WebSocketToken {
+    //the tokens if they are part of the answer rather than the reasoning
+    pub fn message(&self) -> Option<&str> {
+        match self.token_type {
+            TokenCategory::Message => Some(&self.tokens),
+            TokenCategory::ChainOfThought => None,
+        }
+    }
+
+    //the tokens if they are part of the reasoning
+    pub fn reasoning(&self) -> Option<&str> {
+        match self.token_type {
+            TokenCategory::ChainOfThought => Some(&self.tokens),
+            TokenCategory::Message => None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn answer(tokens: &str) -> Self {
+        Self {
+            token_type: TokenCategory::Message,
+            tokens: tokens.to_string(),
+        }
+    }
+}
+
+//where the tokens go as they are generated, eg a websocket
+#[async_trait::async_trait]
+pub trait TokenSink: Send {
+    async fn send_token(&mut self, token: WebSocketToken) -> anyhow::Result<()>;
+}
+
+#[async_trait::async_trait]
+impl TokenSink for WebSocketStream {
+    async fn send_token(&mut self, token: WebSocketToken) -> anyhow::Result<()> {
+        self.send(Message::Text(serde_json::to_string(&token)?))
+            .await?;
+        Ok(())
+    }
+}
+
+//for callers that only want the full message, eg voice satellites
+pub struct DiscardTokens;
+
+#[async_trait::async_trait]
+impl TokenSink for DiscardTokens {
+    async fn send_token(&mut self, _token: WebSocketToken) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+async fn process_chat_stream<S: TokenSink + ?Sized>(
+    tx: &mut S,
     mut stream: ChatCompletionResponseStream,
 ) -> anyhow::Result<ChatStreamResult> {
     let mut chain_of_thought = String::new();
@@ -239,8 +314,7 @@ async fn process_chat_stream(
             token_type: TokenCategory::ChainOfThought,
             tokens,
         };
-        tx.send(Message::Text(serde_json::to_string(&ws_token)?))
-            .await?;
+        tx.send_token(ws_token).await?;
         Ok(())
     };
     // chain of thought
@@ -284,8 +358,7 @@ async fn process_chat_stream(
                     token_type: TokenCategory::Message,
                     tokens,
                 };
-                tx.send(Message::Text(serde_json::to_string(&ws_token)?))
-                    .await?;
+                tx.send_token(ws_token).await?;
             }
             Some(FinishReason::ToolCalls) => {
                 return Ok(match tool_call_result {
@@ -299,6 +372,7 @@ async fn process_chat_stream(
                 return Ok(ChatStreamResult::Message(FullMessage {
                     message: full_message_no_tools,
                     reasoning: chain_of_thought,
+                    tool_calls: vec![],
                 }));
             }
         }
@@ -307,16 +381,18 @@ async fn process_chat_stream(
     Ok(ChatStreamResult::Message(FullMessage {
         message: full_message_no_tools,
         reasoning: chain_of_thought,
+        tool_calls: vec![],
     }))
 }
 
-pub async fn chat_with_tools(
+pub async fn chat_with_tools<S: TokenSink + ?Sized>(
     bot: &Bot,
-    tx: &mut WebSocketStream,
+    tx: &mut S,
     previous_messages: &[MessageResult],
     new_message: &str,
-    span_id: &String,
+    context: &ToolContext,
 ) -> anyhow::Result<FullMessage> {
+    let span_id = &context.span_id;
     info!(
         tool_use = false,
         endpoint = "query",
@@ -325,9 +401,17 @@ pub async fn chat_with_tools(
     );
     //create storage for tool calls
     let mut registry = ToolRegistry::new();
-    let req = construct_messages(get_req(&bot, &bot.tools)?, previous_messages, new_message)?;
-
-    match &bot.tools {
+    //tools can change at runtime, so take a consistent view for this request.
+    //only tools the user is permitted to use are offered to the model
+    let tools = bot.tools.as_ref().map(|tools| tools.snapshot_for(context));
+    let permissions = bot
+        .tools
+        .as_ref()
+        .map(|tools| tools.permissions.clone())
+        .unwrap_or_default();
+    let req = construct_messages(get_req(&bot, &tools)?, previous_messages, new_message)?;
+
+    match &tools {
         Some(tools) => {
             for tool in tools {
                 //clone arc, cheap
@@ -349,8 +433,15 @@ pub async fn chat_with_tools(
             //no tools since we don't want to call the tools a second time
             let req_no_tools =
                 construct_messages(get_req(&bot, &None)?, previous_messages, new_message)?;
-            let stream =
-                tool_response(&bot.llm, registry, req_no_tools, tool_calls, &span_id).await?;
+            let (stream, tool_calls) = tool_response(
+                &bot.llm,
+                registry,
+                &permissions,
+                req_no_tools,
+                tool_calls,
+                context,
+            )
+            .await?;
 
             let stream_result = process_chat_stream(tx, stream).await?;
             Ok(match stream_result {
@@ -361,7 +452,10 @@ pub async fn chat_with_tools(
                         span_id,
                         "Completed response"
                     );
-                    Ok(full_message_with_tools)
+                    Ok(FullMessage {
+                        tool_calls,
+                        ..full_message_with_tools
+                    })
                 }
                 _ => Err(OpenAIError::StreamError(
                     "Message required from tool call result".to_string(),
@@ -380,41 +474,125 @@ pub async fn chat_with_tools(
     }
 }
 
+//single non-streaming completion with its own instructions instead of the bot's
+//system prompt, eg for extracting structured data. reasoning is dropped
+pub async fn complete(bot: &Bot, instructions: &str, message: &str) -> anyhow::Result<String> {
+    let req = CreateChatCompletionRequest {
+        model: bot.model_name.clone(),
+        temperature: bot.temperature,
+        top_p: bot.top_p,
+        messages: vec![
+            ChatCompletionRequestSystemMessageArgs::default()
+                .content(instructions)
+                .build()?
+                .into(),
+            ChatCompletionRequestUserMessageArgs::default()
+                .content(message)
+                .build()?
+                .into(),
+        ],
+        ..Default::default()
+    };
+    let response = bot.llm.chat().create(req).await?;
+    let content = response
+        .choices
+        .into_iter()
+        .find_map(|choice| choice.message.content)
+        .unwrap_or_default();
+    Ok(match content.split_once(STOP_WORD) {
+        Some((_reasoning, content)) => content.trim().to_string(),
+        None => content.trim().to_string(),
+    })
+}
+
 fn get_truncation_index(content: &String) -> usize {
     std::cmp::min(50, content.len())
 }
 async fn tool_response<T: Clone>(
     client: &Client<OpenAIConfig>,
     mut registry: ToolRegistry, //consumes registry
+    permissions: &ToolPermissions,
     mut req: CreateChatCompletionRequest,
-    tools: std::collections::HashMap<T, ChatCompletionMessageToolCall>,
-    span_id: &str,
-) -> anyhow::Result<ChatCompletionResponseStream> {
+    tools: std::collections::BTreeMap<T, ChatCompletionMessageToolCall>,
+    context: &ToolContext,
+) -> anyhow::Result<(ChatCompletionResponseStream, Vec<ToolCallTrace>)> {
+    let span_id = &context.span_id;
     let handles: Vec<JoinHandle<(String, Result<Value, anyhow::Error>)>> = tools
         .iter()
         .map(|(_id, tool_call)| {
             let tool_call_func_name = tool_call.function.name.clone();
             let tool_call_func_args = tool_call.function.arguments.clone();
             let id = tool_call.id.clone();
+            //permissions may have changed since the tools were offered, so check again
+            if !permissions.is_allowed(&tool_call_func_name, context) {
+                info!(
+                    tool_use = true,
+                    endpoint = "query",
+                    span_id,
+                    message = format!(
+                        "permission denied for tool {} for user {} ({:?}) via bot {}",
+                        tool_call_func_name, context.user_id, context.roles, context.bot_name
+                    )
+                );
+                let denied: task::JoinHandle<(String, Result<Value, anyhow::Error>)> =
+                    tokio::spawn(async move {
+                        let message = format!("Permission denied for tool {}", tool_call_func_name);
+                        (id, Ok(json!({ "error": message })))
+                    });
+                return Ok(denied);
+            }
             let func = registry
                 .map
                 .remove(tool_call_func_name.as_str())
                 .ok_or_else(|| ToolError {
                     name: tool_call_func_name,
                 })?;
+            info!(
+                tool_use = true,
+                endpoint = "query",
+                span_id,
+                message = format!(
+                    "invoking tool {} for user {} ({:?}) in session {} via bot {}",
+                    func.name(),
+                    context.user_id,
+                    context.roles,
+                    context.session_id,
+                    context.bot_name
+                )
+            );
+            let context = context.clone();
             let result: task::JoinHandle<(String, Result<Value, anyhow::Error>)> =
-                tokio::spawn(async move { (id, func.invoke(tool_call_func_args).await) });
+                tokio::spawn(async move {
+                    let result = tokio::select! {
+                        result = func.invoke(tool_call_func_args, &context) => result,
+                        _ = context.cancellation_token.cancelled() => {
+                            Err(anyhow::anyhow!("Tool call cancelled"))
+                        }
+                    };
+                    (id, result)
+                });
             Ok(result)
         })
         .collect::<Result<Vec<_>, ToolError>>()?;
     let results = join_all(handles).await;
 
+    let mut traces = vec![];
     let tool_messages: Vec<ChatCompletionRequestMessage> = results
         .into_iter()
         .map(|v| {
             let v = v?;
             let id = v.0;
-            let content = v.1?.to_string();
+            let result = v.1?;
+            let content = result.to_string();
+            if let Some(tool_call) = tools.values().find(|tool_call| tool_call.id == id) {
+                traces.push(ToolCallTrace {
+                    name: tool_call.function.name.clone(),
+                    //the model can produce invalid json, keep it as it was
+                    arguments: serde_json::from_str(&tool_call.function.arguments)
+                        .unwrap_or_else(|_| Value::String(tool_call.function.arguments.clone())),
+                    result,
+                });
+            }
 
             let truncate_content_for_log: usize = get_truncation_index(&content);
             info!(
@@ -444,7 +622,7 @@ async fn tool_response<T: Clone>(
             .into();
     req.messages.push(assistant_message);
     req.messages.extend(tool_messages);
-    Ok(client.chat().create_stream(req).await?)
+    Ok((client.chat().create_stream(req).await?, traces))
 }
 
 #[cfg(test)]
@@ -534,12 +712,15 @@ mod tests {
     #[test]
     fn it_gets_req_correctly() {
         let bot = Bot::new(
+            "helper",
             "model".to_string(),
             "system prompt",
             "http://localhost:11434",
-            Some(0.5),
-            Some(0.6),
-            Some(0.7),
+            ModelParameters {
+                temperature: Some(0.5),
+                presence_penalty: Some(0.6),
+                top_p: Some(0.7),
+            },
             None,
         );
 
@@ -560,4 +741,25 @@ mod tests {
             _ => panic!("Expected System message"),
         }
     }
+
+    #[test]
+    fn it_keeps_tool_calls_in_the_order_requested() {
+        let chunk: CreateChatCompletionStreamResponse = serde_json::from_value(json!({
+            "id": "chatcmpl-test",
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": "model",
+            "choices": [{"index": 0, "finish_reason": null, "delta": {"tool_calls": [
+                {"index": 2, "id": "c", "type": "function", "function": {"name": "date", "arguments": "{}"}},
+                {"index": 0, "id": "a", "type": "function", "function": {"name": "calculator", "arguments": "{}"}},
+                {"index": 1, "id": "b", "type": "function", "function": {"name": "recipes", "arguments": "{}"}}
+            ]}}]
+        }))
+        .unwrap();
+        let names: Vec<String> = construct_tool_call(chunk)
+            .into_values()
+            .map(|tool_call| tool_call.function.name)
+            .collect();
+        assert_eq!(names, vec!["calculator", "recipes", "date"]);
+    }
 }
@@ -1,33 +1,76 @@
 mod api;
+mod audio;
 mod auth;
+mod calculator;
+mod caldav;
+mod calendar;
+mod chat;
+mod chat_protocol;
+mod chores;
 mod config;
+mod confirmations;
+mod dates;
 mod dbtracing;
 mod embedding;
+mod fuzzy;
+mod home_assistant;
+mod ics;
 mod kb_tools;
+mod lists;
 mod llm;
 mod mcp_tools;
+mod meal_plans;
 mod models;
+mod mqtt;
+mod notifications;
+mod openai;
+mod pantry;
 mod prompts;
+mod psql_api_keys;
+mod psql_caldav;
+mod psql_calendar;
+mod psql_chores;
+mod psql_lists;
+mod psql_mcp;
+mod psql_meal_plans;
 mod psql_memory;
+mod psql_pantry;
+mod psql_recipes;
+mod psql_reminders;
+mod psql_tools;
 mod psql_users;
 mod psql_vectors;
+mod recipes;
+mod reminders;
+mod secrets;
+mod tool_manager;
 mod tools;
-
-use api::{Api, helper_ws_handler, tutor_ws_handler};
-use auth::{JwtMiddleware, WSMiddleware};
+mod units;
+mod wyoming;
+
+use api::{Api, helper_ws_handler, notifications_ws_handler, tutor_ws_handler, voice_ws_handler};
+use audio::AudioClient;
+use auth::{ApiKeyMiddleware, JwtMiddleware, WSMiddleware};
+use caldav::run_caldav_sync;
+use chat_protocol::{AnswerStreams, run_eviction};
 use config::Config;
 use dbtracing::create_logging;
 use embedding::EmbeddingClient;
+use llm::ModelParameters;
 use models::get_bots;
+use notifications::Notifications;
+use openai::{chat_completions, list_models};
 use poem::middleware::Tracing;
-use poem::{EndpointExt, Route, listener::TcpListener};
+use poem::{EndpointExt, Route, get, listener::TcpListener, post};
 use poem_openapi::OpenApiService;
 use psql_users::create_init_admin_user;
-use psql_vectors::write_knowledge_base;
+use reminders::run_scheduler;
+use secrets::Secrets;
 use sqlx::PgPool;
 use std::env;
 use std::sync::Arc;
-use tools::{AddTool, TimeTool, Tool};
+use tool_manager::ToolManager;
+use wyoming::run_wyoming_server;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
@@ -38,12 +81,28 @@ async fn main() -> Result<(), anyhow::Error> {
     let open_ai_compatable_endpoint_embedding = env::var("OPEN_AI_COMPATABLE_ENDPOINT_EMBEDDING")
         .unwrap_or_else(|_e| "http://localhost:11434".to_string());
     let jwt_secret = env::var("JWT_SECRET").unwrap().into_bytes();
+    //encrypts the passwords of other services, eg CalDAV servers
+    let secrets = Arc::new(Secrets::new(
+        &env::var("CREDENTIALS_SECRET")
+            .map(String::into_bytes)
+            .unwrap_or_else(|_e| jwt_secret.clone()),
+    ));
     let psql_url = env::var("PSQL_DATABASE_URL").unwrap();
     let init_admin_password = env::var("INIT_ADMIN_PASSWORD").unwrap();
     let port = env::var("PORT").unwrap_or_else(|_e| "3000".to_string());
     let address = env::var("ADDRESS").unwrap_or_else(|_e| "0.0.0.0".to_string());
     let actual_endpoint_for_swagger =
         env::var("HOSTNAME").unwrap_or_else(|_e| "http://localhost:3000".to_string());
+    //whisper.cpp's server needs --inference-path /v1/audio/transcriptions
+    let open_ai_compatable_endpoint_transcription =
+        env::var("OPEN_AI_COMPATABLE_ENDPOINT_TRANSCRIPTION")
+            .unwrap_or_else(|_e| "http://localhost:8080".to_string());
+    let open_ai_compatable_endpoint_speech = env::var("OPEN_AI_COMPATABLE_ENDPOINT_SPEECH")
+        .unwrap_or_else(|_e| "http://localhost:8880".to_string());
+    let transcription_model =
+        env::var("TRANSCRIPTION_MODEL").unwrap_or_else(|_e| "whisper-1".to_string());
+    let speech_model = env::var("SPEECH_MODEL").unwrap_or_else(|_e| "kokoro".to_string());
+    let speech_voice = env::var("SPEECH_VOICE").unwrap_or_else(|_e| "af_heart".to_string());
     let kb_endpoint = env::var("KNOWLEDGE_BASE_ENDPOINT")
         .unwrap_or_else(|_e| "http://127.0.0.1:3000".to_string());
 
@@ -98,55 +157,94 @@ async fn main() -> Result<(), anyhow::Error> {
         &open_ai_compatable_endpoint_embedding,
     ));
 
+    let audio_client = Arc::new(AudioClient::new(
+        &open_ai_compatable_endpoint_transcription,
+        transcription_model,
+        &open_ai_compatable_endpoint_speech,
+        speech_model,
+        speech_voice,
+    ));
+
+    //events pushed to connected clients, eg reminders and shared list changes
+    let notifications = Arc::new(Notifications::new());
+    let answers = Arc::new(AnswerStreams::new());
+
     //tools
+    //TOOL_CONFIG seeds the database, tools are managed through the api afterwards
     let tool_config: Config = serde_json::from_str(&tool_config_raw)?;
-    let kb_arcs = kb_tools::get_tools(tool_config.kb, &kb_endpoint);
-    for kb_arc in kb_arcs.iter() {
-        match write_knowledge_base(&kb_arc.name(), &pool).await {
-            Ok(result) => println!(
-                "Created knowledge base {} with index {}",
-                kb_arc.name(),
-                result
-            ),
-            Err(e) => println!("Failed to create knowledge base: {}", e),
-        }
-    }
-
-    let (mcp_arcs, _servers) = mcp_tools::get_tools_and_servers(tool_config.mcp).await?;
-
-    let mut helper_tools: Vec<Arc<dyn Tool + Send + Sync>> =
-        vec![Arc::new(AddTool::new()), Arc::new(TimeTool::new())];
-    helper_tools.extend(kb_arcs);
-    helper_tools.extend(mcp_arcs);
+    let wyoming_config = tool_config.wyoming.clone();
+    let tool_manager = Arc::new(ToolManager::new(
+        pool.clone(),
+        kb_endpoint,
+        notifications.clone(),
+        embedding_client.clone(),
+        tool_config.home_assistant.clone(),
+        tool_config.mqtt.clone(),
+    ));
+    tool_manager.seed(tool_config).await?;
+    tool_manager.refresh().await?;
 
     //bots
     let bots = Arc::new(get_bots(
         model_name.to_string(),
         open_ai_compatable_endpoint_chat,
-        helper_tools,
-        temperature,
-        presence_penalty,
-        top_p,
+        tool_manager.helper_tools.clone(),
+        ModelParameters {
+            temperature,
+            presence_penalty,
+            top_p,
+        },
     ));
 
     //logging setup
     //this is a future, can be awaited but then blocks everything
     let _logging_handle = create_logging(&pool);
 
+    //reminders
+    let _scheduler_handle = run_scheduler(&pool, &notifications);
+
+    //calendars on CalDAV servers
+    let _caldav_handle = run_caldav_sync(&pool, &secrets);
+
+    //answers kept for clients to resume
+    let _eviction_handle = run_eviction(&answers);
+
+    //voice satellites
+    let _wyoming_handle = wyoming_config
+        .map(|config| run_wyoming_server(config, &pool, &bots, &tool_manager.mcp_servers));
+
     //API setup
     let api_service = OpenApiService::new(Api, "Draid", "1.0").server(actual_endpoint_for_swagger);
     let ui = api_service.swagger_ui();
 
     let app = Route::new()
         .nest("/", api_service.with(JwtMiddleware)) //what about login?
+        .nest(
+            "/v1",
+            Route::new()
+                .at("/models", get(list_models))
+                .at("/chat/completions", post(chat_completions))
+                .with(ApiKeyMiddleware),
+        )
         .at("/ws/tutor", tutor_ws_handler.with(WSMiddleware))
         .at("/ws/helper", helper_ws_handler.with(WSMiddleware))
+        .at("/ws/voice", voice_ws_handler.with(WSMiddleware))
+        .at(
+            "/ws/notifications",
+            notifications_ws_handler.with(WSMiddleware),
+        )
         .nest("/docs", ui)
         .with(Tracing)
         .data(jwt_secret)
+        .data(secrets)
         .data(pool)
         .data(bots)
-        .data(embedding_client);
+        .data(tool_manager.mcp_servers.clone())
+        .data(tool_manager)
+        .data(notifications)
+        .data(answers)
+        .data(embedding_client)
+        .data(audio_client);
     poem::Server::new(TcpListener::bind(format!("{}:{}", address, port)))
         .run(app)
         .await?;
@@ -1,13 +1,310 @@
 use crate::config::{MCP, MCPType};
-use crate::tools::Tool;
+use crate::models::{McpPrompt, McpPromptArgument, McpResource};
+use crate::psql_mcp::BotResource;
+use crate::psql_memory::{MessageResult, MessageType};
+use crate::tools::{Tool, ToolContext, glob_match};
 use futures::future;
 use rmcp::{
     RoleClient, ServiceExt,
-    model::{CallToolRequestParam, Tool as McpTool},
+    model::{
+        CallToolRequestParam, GetPromptRequestParam, PromptMessageContent,
+        ReadResourceRequestParam, ResourceContents, Tool as McpTool,
+    },
     service::{RunningService, ServerSink},
 };
 use serde_json::Value;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use tracing::info;
+
+#[derive(Debug)]
+pub struct McpServerError {
+    pub name: String,
+}
+
+impl std::error::Error for McpServerError {}
+
+impl fmt::Display for McpServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MCP server {} not found", self.name)
+    }
+}
+
+pub struct McpServer {
+    pub config: MCP,
+    service: RunningService<RoleClient, ()>,
+    tools: Vec<Arc<dyn Tool + Send + Sync>>,
+}
+
+impl McpServer {
+    pub async fn connect(config: MCP) -> anyhow::Result<Self> {
+        let (tools, service) =
+            get_server_and_tools_for_single_mcp(config.mcp_type.clone(), config.url.clone())
+                .await?;
+        let tools = server_tools(tools, service.peer(), &config);
+        Ok(Self {
+            config,
+            service,
+            tools,
+        })
+    }
+    fn supports_resources(&self) -> bool {
+        self.service
+            .peer_info()
+            .is_some_and(|info| info.capabilities.resources.is_some())
+    }
+    fn supports_prompts(&self) -> bool {
+        self.service
+            .peer_info()
+            .is_some_and(|info| info.capabilities.prompts.is_some())
+    }
+}
+
+//holds the running services so they don't get cleaned up, and exposes
+//the non-tool parts of MCP (resources and prompts)
+pub struct McpServers {
+    servers: RwLock<Vec<Arc<McpServer>>>,
+}
+
+impl McpServers {
+    pub fn new() -> Self {
+        Self {
+            servers: RwLock::new(vec![]),
+        }
+    }
+
+    //cheap clone of the arcs so the lock is never held across an await
+    fn snapshot(&self) -> Vec<Arc<McpServer>> {
+        self.servers
+            .read()
+            .map(|servers| servers.clone())
+            .unwrap_or_default()
+    }
+
+    fn get(&self, server_name: &str) -> Result<Arc<McpServer>, McpServerError> {
+        self.snapshot()
+            .into_iter()
+            .find(|server| server.config.name == server_name)
+            .ok_or_else(|| McpServerError {
+                name: server_name.to_string(),
+            })
+    }
+
+    pub fn tools(&self) -> Vec<Arc<dyn Tool + Send + Sync>> {
+        self.snapshot()
+            .iter()
+            .flat_map(|server| server.tools.iter().cloned())
+            .collect()
+    }
+
+    //adds an already connected server, replacing any with the same name
+    pub fn insert(&self, server: McpServer) {
+        if let Ok(mut servers) = self.servers.write() {
+            servers.retain(|s| s.config.name != server.config.name);
+            servers.push(Arc::new(server));
+        }
+    }
+
+    //connects servers that are new or changed, and drops servers that no longer exist.
+    //a server that fails to connect is skipped so it can't take down the rest
+    pub async fn sync(&self, configs: Vec<MCP>) {
+        let current = self.snapshot();
+        let futures = configs.into_iter().map(|config| {
+            let existing = current
+                .iter()
+                .find(|server| server.config == config)
+                .cloned();
+            async move {
+                match existing {
+                    Some(server) => Some(server),
+                    None => match McpServer::connect(config.clone()).await {
+                        Ok(server) => Some(Arc::new(server)),
+                        Err(e) => {
+                            info!("Failed to connect to MCP server {}: {}", config.name, e);
+                            None
+                        }
+                    },
+                }
+            }
+        });
+        let servers = future::join_all(futures)
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        if let Ok(mut current) = self.servers.write() {
+            *current = servers;
+        }
+    }
+
+    pub async fn list_resources(&self) -> anyhow::Result<Vec<McpResource>> {
+        let servers = self.snapshot();
+        let futures = servers
+            .iter()
+            .filter(|server| server.supports_resources())
+            .map(|server| async move {
+                let resources = server.service.peer().list_all_resources().await?;
+                Ok::<_, anyhow::Error>(
+                    resources
+                        .into_iter()
+                        .map(|resource| McpResource {
+                            server: server.config.name.clone(),
+                            uri: resource.raw.uri,
+                            name: resource.raw.name,
+                            description: resource.raw.description,
+                            mime_type: resource.raw.mime_type,
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            });
+        let results = future::try_join_all(futures).await?;
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    pub async fn list_prompts(&self) -> anyhow::Result<Vec<McpPrompt>> {
+        let servers = self.snapshot();
+        let futures = servers
+            .iter()
+            .filter(|server| server.supports_prompts())
+            .map(|server| async move {
+                let prompts = server.service.peer().list_all_prompts().await?;
+                Ok::<_, anyhow::Error>(
+                    prompts
+                        .into_iter()
+                        .map(|prompt| McpPrompt {
+                            server: server.config.name.clone(),
+                            name: prompt.name,
+                            description: prompt.description,
+                            arguments: prompt
+                                .arguments
+                                .unwrap_or_default()
+                                .into_iter()
+                                .map(|argument| McpPromptArgument {
+                                    name: argument.name,
+                                    description: argument.description,
+                                    required: argument.required.unwrap_or(false),
+                                })
+                                .collect(),
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            });
+        let results = future::try_join_all(futures).await?;
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    //only text contents are returned; blobs can't be passed to the model as context
+    pub async fn read_resource(&self, server_name: &str, uri: &str) -> anyhow::Result<String> {
+        let server = self.get(server_name)?;
+        let result = server
+            .service
+            .peer()
+            .read_resource(ReadResourceRequestParam {
+                uri: uri.to_string(),
+            })
+            .await?;
+        Ok(result
+            .contents
+            .into_iter()
+            .filter_map(|content| match content {
+                ResourceContents::TextResourceContents { text, .. } => Some(text),
+                ResourceContents::BlobResourceContents { .. } => None,
+            })
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+
+    //expands a prompt template into the text of a single chat turn
+    pub async fn expand_prompt(
+        &self,
+        server_name: &str,
+        prompt_name: &str,
+        arguments: HashMap<String, String>,
+    ) -> anyhow::Result<String> {
+        let server = self.get(server_name)?;
+        let arguments = arguments
+            .into_iter()
+            .map(|(key, value)| (key, Value::String(value)))
+            .collect();
+        let result = server
+            .service
+            .peer()
+            .get_prompt(GetPromptRequestParam {
+                name: prompt_name.to_string(),
+                arguments: Some(arguments),
+            })
+            .await?;
+        Ok(prompt_text(result.messages.into_iter().map(|m| m.content)))
+    }
+
+    //attached resources are added to the conversation as system messages
+    pub async fn resource_context(&self, resources: &[BotResource]) -> Vec<MessageResult> {
+        let futures = resources
+            .iter()
+            .map(|resource| self.read_resource(&resource.server, &resource.uri));
+        future::join_all(futures)
+            .await
+            .into_iter()
+            .zip(resources)
+            .filter_map(|(result, resource)| match result {
+                Ok(text) => Some(MessageResult {
+                    content: format!("Context from {}:\n{}", resource.uri, text),
+                    reasoning: "".to_string(),
+                    message_type: MessageType::SystemMessage,
+                    timestamp: chrono::Utc::now(),
+                }),
+                Err(e) => {
+                    info!("Failed to read resource {}: {}", resource.uri, e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+fn prompt_text(contents: impl Iterator<Item = PromptMessageContent>) -> String {
+    contents
+        .filter_map(|content| match content {
+            PromptMessageContent::Text { text } => Some(text),
+            PromptMessageContent::Resource { resource } => match resource.raw.resource {
+                ResourceContents::TextResourceContents { text, .. } => Some(text),
+                ResourceContents::BlobResourceContents { .. } => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+//each tool keeps its own name, so the model can tell a server's tools apart
+fn server_tools(
+    tools: Vec<McpTool>,
+    server: &ServerSink,
+    config: &MCP,
+) -> Vec<Arc<dyn Tool + Send + Sync>> {
+    tools
+        .into_iter()
+        .filter(|tool| is_tool_allowed(config, &tool.name))
+        .map(|tool| {
+            Arc::new(MCPTool::new(tool, server.clone(), config.clone()))
+                as Arc<dyn Tool + Send + Sync>
+        })
+        .collect()
+}
+
+fn is_tool_allowed(config: &MCP, tool_name: &str) -> bool {
+    let included = config.include_tools.is_empty()
+        || config
+            .include_tools
+            .iter()
+            .any(|pattern| glob_match(pattern, tool_name));
+    let excluded = config
+        .exclude_tools
+        .iter()
+        .any(|pattern| glob_match(pattern, tool_name));
+    included && !excluded
+}
 
 async fn get_server_and_tools_for_single_mcp(
     mcp_type: MCPType,
@@ -27,45 +324,6 @@ async fn get_server_and_tools_for_single_mcp(
     Ok((tools, server))
 }
 
-pub async fn get_tools_and_servers(
-    mcp_configs: Vec<MCP>,
-) -> anyhow::Result<(
-    Vec<Arc<dyn Tool + Send + Sync>>,
-    Vec<RunningService<RoleClient, ()>>,
-)> {
-    let futures: Vec<_> = mcp_configs
-        .into_iter()
-        .map(|mcp_config| async move {
-            let (tools, server) = get_server_and_tools_for_single_mcp(
-                mcp_config.mcp_type.clone(),
-                mcp_config.url.clone(),
-            )
-            .await?;
-            let tools: Vec<Arc<dyn Tool + Send + Sync>> = tools
-                .into_iter()
-                .map(|tool| {
-                    Arc::new(MCPTool::new(
-                        tool,
-                        server.peer().clone(),
-                        mcp_config.clone(),
-                    )) as Arc<dyn Tool + Send + Sync>
-                })
-                .collect();
-            Ok::<_, anyhow::Error>((tools, server))
-        })
-        .collect();
-    let results = future::try_join_all(futures).await?;
-
-    let (tools, servers): (Vec<_>, Vec<_>) = results.into_iter().unzip();
-    let tools: Vec<_> = tools
-        .into_iter()
-        .map(|tool_v| tool_v.into_iter())
-        .flatten()
-        .collect();
-    //servers is only returned so that it doesn't get cleaned up when this function completes
-    Ok((tools, servers))
-}
-
 #[derive(Clone)]
 pub struct MCPTool {
     tool: McpTool,
@@ -76,11 +334,19 @@ pub struct MCPTool {
 
 impl MCPTool {
     pub fn new(tool: McpTool, server: ServerSink, mcp_config: MCP) -> Self {
+        let name = tool.name.to_string();
+        let description = match mcp_config.tool_descriptions.get(&name) {
+            Some(description) => description.clone(),
+            None => match &tool.description {
+                Some(description) => format!("{} {}", mcp_config.description, description),
+                None => mcp_config.description,
+            },
+        };
         Self {
             tool,
             server,
-            name: mcp_config.name,
-            description: mcp_config.description,
+            name,
+            description,
         }
     }
 }
@@ -98,7 +364,7 @@ impl Tool for MCPTool {
     fn parameters(&self) -> Value {
         serde_json::to_value(&self.tool.input_schema).unwrap_or(serde_json::json!({}))
     }
-    async fn invoke(&self, args: String) -> anyhow::Result<Value> {
+    async fn invoke(&self, args: String, _context: &ToolContext) -> anyhow::Result<Value> {
         let args: Value = serde_json::from_str(&args)?;
         let arguments = match args {
             Value::Object(map) => Some(map),
@@ -115,3 +381,89 @@ impl Tool for MCPTool {
         Ok(json_value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{is_tool_allowed, prompt_text, server_tools};
+    use crate::config::{MCP, MCPType};
+    use crate::tools::{ToolPermissions, ToolRegistry, ToolSet};
+    use rmcp::model::{PromptMessageContent, Tool as McpTool};
+    use rmcp::{ServerHandler, ServiceExt};
+    use std::sync::Arc;
+
+    //a server that only answers the handshake, the tools are made up by the test
+    struct Handshake;
+
+    impl ServerHandler for Handshake {}
+
+    fn mcp_config(include_tools: Vec<&str>, exclude_tools: Vec<&str>) -> MCP {
+        MCP {
+            name: "devin".to_string(),
+            description: "Devin".to_string(),
+            url: "http://localhost".to_string(),
+            mcp_type: MCPType::STREAM,
+            include_tools: include_tools.into_iter().map(String::from).collect(),
+            exclude_tools: exclude_tools.into_iter().map(String::from).collect(),
+            tool_descriptions: Default::default(),
+        }
+    }
+
+    #[test]
+    fn it_includes_all_tools_if_no_filters() {
+        let config = mcp_config(vec![], vec![]);
+        assert!(is_tool_allowed(&config, "ask_question"));
+    }
+
+    #[test]
+    fn it_applies_exclude_after_include() {
+        let config = mcp_config(vec!["read_*"], vec!["*structure"]);
+        assert!(is_tool_allowed(&config, "read_wiki_contents"));
+        assert!(!is_tool_allowed(&config, "read_wiki_structure"));
+        assert!(!is_tool_allowed(&config, "ask_question"));
+    }
+
+    #[test]
+    fn it_joins_text_prompt_content() {
+        let contents = vec![
+            PromptMessageContent::text("Plan dinner"),
+            PromptMessageContent::text("for four people"),
+        ];
+        let result = prompt_text(contents.into_iter());
+        assert_eq!(result, "Plan dinner\n\nfor four people");
+    }
+
+    #[tokio::test]
+    async fn it_keeps_every_tool_of_a_server() {
+        let (client_side, server_side) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            if let Ok(server) = Handshake.serve(server_side).await {
+                let _ = server.waiting().await;
+            }
+        });
+        let client = ().serve(client_side).await.unwrap();
+        let schema = Arc::new(serde_json::Map::new());
+        let tools = vec![
+            McpTool::new("ask_question", "Asks about a repository", schema.clone()),
+            McpTool::new("read_wiki_contents", "Reads the wiki", schema.clone()),
+            McpTool::new("read_wiki_structure", "Lists the wiki's pages", schema),
+        ];
+        let mut config = mcp_config(vec![], vec!["*structure"]);
+        config
+            .tool_descriptions
+            .insert("ask_question".to_string(), "Ask Devin".to_string());
+        let tools = server_tools(tools, client.peer(), &config);
+        let tool_set = ToolSet::new(tools, ToolPermissions::default());
+        let mut registry = ToolRegistry::new();
+        for tool in tool_set.snapshot() {
+            registry.register(tool);
+        }
+        let mut names: Vec<&String> = registry.map.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["ask_question", "read_wiki_contents"]);
+        assert_eq!(registry.map["ask_question"].description(), "Ask Devin");
+        assert_eq!(
+            registry.map["read_wiki_contents"].description(),
+            "Devin Reads the wiki"
+        );
+    }
+}
@@ -0,0 +1,775 @@
+use crate::dates::parse_datetime;
+use crate::fuzzy::{best_match, normalize};
+use crate::lists::{add_item, find_or_create_list};
+use crate::notifications::{Notification, Notifications};
+use crate::pantry::{EXPIRING_SOON_DAYS, mentions};
+use crate::psql_lists::ListItemRequest;
+use crate::psql_meal_plans::{
+    MealPlan, MealPlanEntryRequest, MealPlanRequest, PlannedMeal, get_meal_plan,
+    get_meal_plan_for_week, replace_meal_plan, replace_meal_plan_day,
+};
+use crate::psql_pantry::{PantryItem, get_expiring_pantry_items, get_pantry_items};
+use crate::psql_recipes::{
+    Ingredient, RecipeSummary, get_recipe, get_recipe_ingredient_names, get_recipes,
+};
+use crate::recipes::{
+    METRIC_MASS, METRIC_VOLUME, find_recipe, readable_unit, round, scale_ingredients,
+};
+use crate::tools::{ToolContext, ToolOutput, TypedTool, typed_tool};
+use crate::units::{Dimension, convert, find_density, find_unit};
+use chrono::{Datelike, Duration, NaiveDate};
+use poem_openapi::Object;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub const MAX_DINNERS: i32 = 7;
+
+//weeks start on monday
+pub fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Duration::days(day.weekday().num_days_from_monday() as i64)
+}
+
+fn fits(recipe: &RecipeSummary, request: &MealPlanRequest) -> bool {
+    let tags: Vec<String> = recipe.tags.iter().map(|tag| normalize(tag)).collect();
+    let restrictions_met = request
+        .restrictions
+        .iter()
+        .all(|restriction| tags.contains(&normalize(restriction)));
+    //most extracted recipes don't say how long they take, so they aren't ruled out
+    let in_time = match (request.max_minutes, recipe.total_minutes) {
+        (Some(max_minutes), Some(minutes)) => minutes <= max_minutes,
+        _ => true,
+    };
+    restrictions_met && in_time
+}
+
+fn shuffle_key(id: &Uuid, week_start: &NaiveDate) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (id, week_start).hash(&mut hasher);
+    hasher.finish()
+}
+
+//recipes that fit the constraints, best first: those using up food that expires soon,
+//then those that weren't planned the week before. ties are shuffled differently
+//each week so that plans don't repeat
+fn rank_recipes(
+    recipes: Vec<RecipeSummary>,
+    request: &MealPlanRequest,
+    uses_expiring: &HashMap<Uuid, usize>,
+    last_week: &HashSet<Uuid>,
+    week_start: &NaiveDate,
+) -> Vec<RecipeSummary> {
+    let mut recipes: Vec<RecipeSummary> = recipes
+        .into_iter()
+        .filter(|recipe| fits(recipe, request))
+        .collect();
+    recipes.sort_by_key(|recipe| {
+        (
+            std::cmp::Reverse(uses_expiring.get(&recipe.id).copied().unwrap_or(0)),
+            last_week.contains(&recipe.id),
+            shuffle_key(&recipe.id, week_start),
+        )
+    });
+    recipes
+}
+
+//proposes dinners from the recipe box and saves them, replacing any plan for that
+//week. the error is for constraints that can't be met
+pub async fn plan_meals(
+    request: &MealPlanRequest,
+    today: NaiveDate,
+    pool: &PgPool,
+) -> anyhow::Result<Result<MealPlan, String>> {
+    if !(1..=MAX_DINNERS).contains(&request.dinners) {
+        return Ok(Err(format!(
+            "A week has between 1 and {} dinners",
+            MAX_DINNERS
+        )));
+    }
+    let week_start = week_start(request.week.unwrap_or(today));
+    let expiring =
+        get_expiring_pantry_items(&(today + Duration::days(EXPIRING_SOON_DAYS)), pool).await?;
+    let mut uses_expiring = HashMap::new();
+    for ingredient in get_recipe_ingredient_names(pool).await? {
+        if expiring
+            .iter()
+            .any(|item| mentions(&ingredient.name, &item.name))
+        {
+            *uses_expiring.entry(ingredient.recipe_id).or_insert(0) += 1;
+        }
+    }
+    let last_week: HashSet<Uuid> =
+        match get_meal_plan_for_week(&(week_start - Duration::weeks(1)), pool).await? {
+            Some(plan) => plan
+                .entries
+                .iter()
+                .filter_map(|entry| entry.recipe_id)
+                .collect(),
+            None => HashSet::new(),
+        };
+    let recipes = rank_recipes(
+        get_recipes(pool).await?,
+        request,
+        &uses_expiring,
+        &last_week,
+        &week_start,
+    );
+    if recipes.is_empty() {
+        return Ok(Err(
+            "No recipes in the recipe box fit those constraints".to_string()
+        ));
+    }
+    if recipes.len() < request.dinners as usize {
+        return Ok(Err(format!(
+            "Only {} recipes in the recipe box fit those constraints, plan fewer dinners",
+            recipes.len()
+        )));
+    }
+    let meals: Vec<PlannedMeal> = recipes
+        .into_iter()
+        .take(request.dinners as usize)
+        .enumerate()
+        .map(|(day, recipe)| PlannedMeal {
+            day: week_start + Duration::days(day as i64),
+            recipe_id: Some(recipe.id),
+            title: recipe.title,
+            servings: request.servings.or(recipe.servings),
+        })
+        .collect();
+    let id = replace_meal_plan(&week_start, request, &meals, pool).await?;
+    Ok(Ok(get_meal_plan(&id, pool).await?))
+}
+
+//a meal is named after its recipe unless it is given a title of its own
+pub async fn planned_meal(
+    entry: &MealPlanEntryRequest,
+    pool: &PgPool,
+) -> anyhow::Result<Result<PlannedMeal, String>> {
+    let recipe = match &entry.recipe_id {
+        Some(recipe_id) => match get_recipe(recipe_id, pool).await {
+            Ok(recipe) => Some(recipe),
+            Err(sqlx::Error::RowNotFound) => {
+                return Ok(Err(format!("No recipe with id {}", recipe_id)));
+            }
+            Err(e) => return Err(e.into()),
+        },
+        None => None,
+    };
+    let title = match (&entry.title, recipe) {
+        (Some(title), _) => title.clone(),
+        (None, Some(recipe)) => recipe.title,
+        (None, None) => return Ok(Err("A meal needs a recipe or a title".to_string())),
+    };
+    Ok(Ok(PlannedMeal {
+        day: entry.day,
+        recipe_id: entry.recipe_id,
+        title,
+        servings: entry.servings,
+    }))
+}
+
+#[derive(Serialize, Object, Clone, Debug, PartialEq)]
+pub struct ShoppingItem {
+    pub name: String,
+    pub quantity: Option<f64>,
+    pub unit: Option<String>,
+}
+
+impl ShoppingItem {
+    //as written on a shopping list, eg "1.5 kg"
+    pub fn amount(&self) -> Option<String> {
+        match (self.quantity, &self.unit) {
+            (Some(quantity), Some(unit)) => Some(format!("{} {}", quantity, unit)),
+            (Some(quantity), None) => Some(quantity.to_string()),
+            (None, _) => None,
+        }
+    }
+}
+
+//everything needed of one food, by kind of measurement
+#[derive(Default)]
+struct Amounts {
+    grams: f64,
+    millilitres: f64,
+    count: f64,
+    //units the registry doesn't know, eg cans or cloves
+    other: Vec<(String, f64)>,
+    //eg "salt to taste"
+    unmeasured: bool,
+}
+
+fn in_unit(quantity: f64, from: &str, to: &str) -> f64 {
+    convert(quantity, from, to, None).map_or(0.0, |(quantity, _)| quantity)
+}
+
+impl Amounts {
+    fn add(&mut self, quantity: Option<f64>, unit: Option<&str>) {
+        let Some(quantity) = quantity else {
+            self.unmeasured = true;
+            return;
+        };
+        let Some(unit) = unit else {
+            self.count += quantity;
+            return;
+        };
+        match find_unit(unit).map(|found| found.dimension) {
+            Ok(Dimension::Mass) => self.grams += in_unit(quantity, unit, "g"),
+            Ok(Dimension::Volume) => self.millilitres += in_unit(quantity, unit, "ml"),
+            _ => {
+                let unit = normalize(unit);
+                match self.other.iter_mut().find(|(other, _)| *other == unit) {
+                    Some((_, total)) => *total += quantity,
+                    None => self.other.push((unit, quantity)),
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.grams == 0.0
+            && self.millilitres == 0.0
+            && self.count == 0.0
+            && self.other.is_empty()
+            && !self.unmeasured
+    }
+}
+
+//what is still to buy of a food once what's on hand is taken away
+fn remaining(name: &str, mut need: Amounts, mut have: Amounts) -> Vec<ShoppingItem> {
+    //weigh volumes when the food is measured by weight too, eg flour in cups and a bag in kg
+    if let Ok(density) = find_density(name)
+        && (need.grams > 0.0 || have.grams > 0.0)
+        && (need.millilitres > 0.0 || have.millilitres > 0.0)
+    {
+        need.grams += need.millilitres * density;
+        need.millilitres = 0.0;
+        have.grams += have.millilitres * density;
+        have.millilitres = 0.0;
+    }
+    let mut items = vec![];
+    let mut push = |quantity: f64, unit: Option<String>| {
+        //floating point leftovers from unit conversion count as covered
+        if quantity > 1e-6 {
+            items.push(ShoppingItem {
+                name: name.to_string(),
+                quantity: Some(round(quantity)),
+                unit,
+            });
+        }
+    };
+    for (quantity, base, units) in [
+        (need.grams - have.grams, "g", METRIC_MASS),
+        (need.millilitres - have.millilitres, "ml", METRIC_VOLUME),
+    ] {
+        let unit = readable_unit(quantity, units);
+        push(in_unit(quantity, base, unit), Some(unit.to_string()));
+    }
+    push(need.count - have.count, None);
+    for (unit, quantity) in &need.other {
+        let on_hand = have
+            .other
+            .iter()
+            .find(|(other, _)| other == unit)
+            .map_or(0.0, |(_, quantity)| *quantity);
+        push(quantity - on_hand, Some(unit.clone()));
+    }
+    //"salt to taste" is only bought when there is none at all
+    if items.is_empty() && need.unmeasured && have.is_empty() {
+        items.push(ShoppingItem {
+            name: name.to_string(),
+            quantity: None,
+            unit: None,
+        });
+    }
+    items
+}
+
+//adds up the ingredients, so 2 cups and 250 ml of milk are one line, and takes
+//away what the pantry already has
+pub fn still_needed(ingredients: &[Ingredient], pantry: &[PantryItem]) -> Vec<ShoppingItem> {
+    let mut needed: Vec<(String, Amounts)> = vec![];
+    for ingredient in ingredients {
+        let index = match best_match(
+            &ingredient.name,
+            needed.iter().map(|(name, _)| name.as_str()),
+        ) {
+            Some(index) => index,
+            None => {
+                needed.push((ingredient.name.clone(), Amounts::default()));
+                needed.len() - 1
+            }
+        };
+        needed[index]
+            .1
+            .add(ingredient.quantity, ingredient.unit.as_deref());
+    }
+    needed
+        .into_iter()
+        .flat_map(|(name, need)| {
+            let mut have = Amounts::default();
+            if let Some(index) = best_match(&name, pantry.iter().map(|item| item.name.as_str())) {
+                have.add(Some(pantry[index].quantity), pantry[index].unit.as_deref());
+            }
+            remaining(&name, need, have)
+        })
+        .collect()
+}
+
+//ingredients of every planned recipe, scaled to the servings planned
+pub async fn shopping_list(plan: &MealPlan, pool: &PgPool) -> anyhow::Result<Vec<ShoppingItem>> {
+    let mut ingredients = vec![];
+    for entry in &plan.entries {
+        let Some(recipe_id) = entry.recipe_id else {
+            continue;
+        };
+        let recipe = get_recipe(&recipe_id, pool).await?;
+        let factor = match (entry.servings, recipe.servings) {
+            (Some(wanted), Some(makes)) if makes > 0 => wanted as f64 / makes as f64,
+            _ => 1.0,
+        };
+        ingredients.extend(scale_ingredients(&recipe.ingredients, factor));
+    }
+    Ok(still_needed(&ingredients, &get_pantry_items(pool).await?))
+}
+
+fn today(context: &ToolContext) -> NaiveDate {
+    chrono::Utc::now()
+        .with_timezone(&context.timezone)
+        .date_naive()
+}
+
+//the day named, eg "tuesday" or "next week", in the user's timezone
+fn parse_day(text: &str, context: &ToolContext) -> Result<NaiveDate, String> {
+    let now = chrono::Utc::now().with_timezone(&context.timezone);
+    parse_datetime(text, now)
+        .map(|day| day.date_naive())
+        .map_err(|e| e.to_string())
+}
+
+fn week_of(week: Option<&str>, context: &ToolContext) -> Result<NaiveDate, String> {
+    match week {
+        Some(week) => parse_day(week, context).map(week_start),
+        None => Ok(week_start(today(context))),
+    }
+}
+
+#[derive(Clone)]
+pub struct PlanMealsTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl PlanMealsTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "plan_meals".to_string(),
+            description: "Plan the week's dinners from the recipe box and save the plan, \
+                replacing any plan already made for that week. \
+                Recipes using food that expires soon are picked first"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PlanMealsArgs {
+    /// Number of dinners to plan, from 1 to 7
+    dinners: i32,
+    /// Tags every recipe must have, eg ["vegetarian", "gluten-free"]
+    #[serde(default)]
+    restrictions: Vec<String>,
+    /// Longest a recipe may take to make, in minutes
+    #[serde(default)]
+    max_minutes: Option<i32>,
+    /// Number of people to cook for
+    #[serde(default)]
+    servings: Option<i32>,
+    /// Any day in the week to plan, eg "next week". Defaults to this week
+    #[serde(default)]
+    week: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for PlanMealsTool {
+    type Args = PlanMealsArgs;
+    type Output = ToolOutput<MealPlan>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: PlanMealsArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let week = match week_of(args.week.as_deref(), context) {
+            Ok(week) => week,
+            Err(e) => return Ok(ToolOutput::Error(e)),
+        };
+        let request = MealPlanRequest {
+            week: Some(week),
+            dinners: args.dinners,
+            restrictions: args.restrictions,
+            max_minutes: args.max_minutes,
+            servings: args.servings,
+        };
+        Ok(plan_meals(&request, today(context), &self.pool)
+            .await?
+            .into())
+    }
+}
+typed_tool!(PlanMealsTool);
+
+#[derive(Clone)]
+pub struct ReadMealPlanTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl ReadMealPlanTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "read_meal_plan".to_string(),
+            description: "Read the dinners planned for a week".to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct MealPlanWeekArgs {
+    /// Any day in the week, eg "next week". Defaults to this week
+    #[serde(default)]
+    week: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for ReadMealPlanTool {
+    type Args = MealPlanWeekArgs;
+    type Output = ToolOutput<MealPlan>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: MealPlanWeekArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let week = match week_of(args.week.as_deref(), context) {
+            Ok(week) => week,
+            Err(e) => return Ok(ToolOutput::Error(e)),
+        };
+        Ok(match get_meal_plan_for_week(&week, &self.pool).await? {
+            Some(plan) => ToolOutput::Result(plan),
+            None => ToolOutput::Error(format!("There is no meal plan for the week of {}", week)),
+        })
+    }
+}
+typed_tool!(ReadMealPlanTool);
+
+#[derive(Clone)]
+pub struct ChangeMealPlanTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl ChangeMealPlanTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "change_meal_plan".to_string(),
+            description: "Change the dinner planned for a day to a recipe from the recipe box \
+                or another meal, eg \"leftovers\". Leave out both to clear the day"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ChangeMealPlanArgs {
+    /// The day to change, eg "thursday" or "2026-11-05"
+    day: String,
+    /// Title of a recipe from the recipe box
+    #[serde(default)]
+    recipe: Option<String>,
+    /// A meal that isn't in the recipe box, eg "takeout"
+    #[serde(default)]
+    meal: Option<String>,
+    /// Number of people to cook for
+    #[serde(default)]
+    servings: Option<i32>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for ChangeMealPlanTool {
+    type Args = ChangeMealPlanArgs;
+    type Output = ToolOutput<MealPlan>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: ChangeMealPlanArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let day = match parse_day(&args.day, context) {
+            Ok(day) => day,
+            Err(e) => return Ok(ToolOutput::Error(e)),
+        };
+        let Some(plan) = get_meal_plan_for_week(&week_start(day), &self.pool).await? else {
+            return Ok(ToolOutput::Error(format!(
+                "There is no meal plan for the week of {}, make one with plan_meals first",
+                week_start(day)
+            )));
+        };
+        let meal = match (args.recipe, args.meal) {
+            (Some(title), _) => match find_recipe(&title, &self.pool).await? {
+                Some(recipe) => Some(PlannedMeal {
+                    day,
+                    recipe_id: Some(recipe.id),
+                    title: recipe.title,
+                    servings: args.servings.or(plan.servings).or(recipe.servings),
+                }),
+                None => return Ok(ToolOutput::Error(format!("No recipe called {}", title))),
+            },
+            (None, Some(title)) => Some(PlannedMeal {
+                day,
+                recipe_id: None,
+                title,
+                servings: args.servings,
+            }),
+            (None, None) => None,
+        };
+        replace_meal_plan_day(&plan.id, &day, meal.as_ref(), &self.pool).await?;
+        Ok(ToolOutput::Result(
+            get_meal_plan(&plan.id, &self.pool).await?,
+        ))
+    }
+}
+typed_tool!(ChangeMealPlanTool);
+
+#[derive(Clone)]
+pub struct MealPlanShoppingListTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+    notifications: Arc<Notifications>,
+}
+
+impl MealPlanShoppingListTool {
+    pub fn new(pool: PgPool, notifications: Arc<Notifications>) -> Self {
+        Self {
+            name: "meal_plan_shopping_list".to_string(),
+            description: "Work out what to buy for a week's meal plan: the ingredients added up, \
+                less what is already in the pantry. Optionally adds them to a shared list"
+                .to_string(),
+            pool,
+            notifications,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct MealPlanShoppingListArgs {
+    /// Any day in the week, eg "next week". Defaults to this week
+    #[serde(default)]
+    week: Option<String>,
+    /// Name of a list to add the items to, eg "groceries"
+    #[serde(default)]
+    add_to_list: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct MealPlanShoppingList {
+    items: Vec<ShoppingItem>,
+    added_to: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for MealPlanShoppingListTool {
+    type Args = MealPlanShoppingListArgs;
+    type Output = ToolOutput<MealPlanShoppingList>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: MealPlanShoppingListArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let week = match week_of(args.week.as_deref(), context) {
+            Ok(week) => week,
+            Err(e) => return Ok(ToolOutput::Error(e)),
+        };
+        let Some(plan) = get_meal_plan_for_week(&week, &self.pool).await? else {
+            return Ok(ToolOutput::Error(format!(
+                "There is no meal plan for the week of {}",
+                week
+            )));
+        };
+        let items = shopping_list(&plan, &self.pool).await?;
+        let added_to = match args.add_to_list {
+            Some(name) => {
+                let list = find_or_create_list(&name, &self.pool).await?;
+                for item in &items {
+                    let item = ListItemRequest {
+                        name: item.name.clone(),
+                        quantity: item.amount(),
+                    };
+                    add_item(&list.id, &item, &context.user_id, &self.pool).await?;
+                }
+                self.notifications.broadcast(Notification::ListChanged {
+                    list_id: list.id,
+                    changed_by: context.user_id,
+                });
+                Some(list.name)
+            }
+            None => None,
+        };
+        Ok(ToolOutput::Result(MealPlanShoppingList { items, added_to }))
+    }
+}
+typed_tool!(MealPlanShoppingListTool);
+
+#[cfg(test)]
+mod tests {
+    use super::{ShoppingItem, rank_recipes, still_needed, week_start};
+    use crate::psql_meal_plans::MealPlanRequest;
+    use crate::psql_pantry::PantryItem;
+    use crate::psql_recipes::{Ingredient, RecipeSummary};
+    use chrono::NaiveDate;
+    use std::collections::{HashMap, HashSet};
+    use uuid::Uuid;
+
+    fn ingredient(name: &str, quantity: Option<f64>, unit: Option<&str>) -> Ingredient {
+        Ingredient {
+            name: name.to_string(),
+            quantity,
+            unit: unit.map(String::from),
+            note: None,
+        }
+    }
+
+    fn pantry_item(name: &str, quantity: f64, unit: Option<&str>) -> PantryItem {
+        PantryItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            quantity,
+            unit: unit.map(String::from),
+            location: None,
+            expires_on: None,
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    fn item(name: &str, quantity: Option<f64>, unit: Option<&str>) -> ShoppingItem {
+        ShoppingItem {
+            name: name.to_string(),
+            quantity,
+            unit: unit.map(String::from),
+        }
+    }
+
+    fn recipe(title: &str, tags: &[&str], total_minutes: Option<i32>) -> RecipeSummary {
+        RecipeSummary {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            servings: Some(4),
+            total_minutes,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn it_starts_weeks_on_monday() {
+        let monday = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        assert_eq!(week_start(monday), monday);
+        assert_eq!(
+            week_start(NaiveDate::from_ymd_opt(2026, 10, 25).unwrap()),
+            monday
+        );
+    }
+
+    #[test]
+    fn it_adds_up_ingredients_and_takes_away_the_pantry() {
+        let ingredients = [
+            ingredient("milk", Some(2.0), Some("cup")),
+            ingredient("Milk", Some(250.0), Some("ml")),
+            ingredient("eggs", Some(3.0), None),
+            ingredient("egg", Some(2.0), None),
+            ingredient("flour", Some(4.0), Some("cup")),
+            ingredient("tomatoes", Some(2.0), Some("cans")),
+            ingredient("salt", None, None),
+            ingredient("pepper", None, None),
+        ];
+        let pantry = [
+            pantry_item("eggs", 6.0, None),
+            pantry_item("rye flour", 0.5, Some("kg")),
+            pantry_item("pepper", 1.0, Some("jar")),
+        ];
+        assert_eq!(
+            still_needed(&ingredients, &pantry),
+            vec![
+                item("milk", Some(723.18), Some("ml")),
+                item("flour", Some(946.35), Some("ml")),
+                item("tomatoes", Some(2.0), Some("can")),
+                item("salt", None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_weighs_food_measured_both_ways() {
+        let ingredients = [ingredient("flour", Some(4.0), Some("cup"))];
+        let pantry = [pantry_item("flour", 250.0, Some("g"))];
+        assert_eq!(
+            still_needed(&ingredients, &pantry),
+            vec![item("flour", Some(250.62), Some("g"))]
+        );
+    }
+
+    #[test]
+    fn it_ranks_recipes_that_fit() {
+        let quick = recipe("Quick curry", &["Vegetarian"], Some(30));
+        let slow = recipe("Slow stew", &["vegetarian"], Some(180));
+        let untimed = recipe("Dal", &["vegetarian"], None);
+        let meat = recipe("Burgers", &[], Some(20));
+        let request = MealPlanRequest {
+            week: None,
+            dinners: 3,
+            restrictions: vec!["vegetarian".to_string()],
+            max_minutes: Some(45),
+            servings: None,
+        };
+        let uses_expiring = HashMap::from([(untimed.id, 1)]);
+        let ranked = rank_recipes(
+            vec![quick.clone(), slow, untimed.clone(), meat],
+            &request,
+            &uses_expiring,
+            &HashSet::new(),
+            &NaiveDate::from_ymd_opt(2026, 10, 19).unwrap(),
+        );
+        let titles: Vec<&str> = ranked.iter().map(|recipe| recipe.title.as_str()).collect();
+        assert_eq!(titles, vec!["Dal", "Quick curry"]);
+    }
+}
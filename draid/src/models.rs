@@ -1,11 +1,12 @@
-use crate::llm::Bot;
+use crate::llm::{Bot, ModelParameters, ToolCallTrace, WebSocketToken};
 use crate::prompts::{HELPER_PROMPT, TUTOR_PROMPT};
 use crate::psql_memory::MessageResult;
-use crate::psql_users::{SessionDB, UserResponse};
-use crate::tools::Tool;
-use poem_openapi::payload::Json;
-use poem_openapi::{ApiResponse, Enum, Object};
+use crate::psql_users::{Role, SessionDB, UserResponse};
+use crate::tools::ToolSet;
+use poem_openapi::payload::{Binary, Json, PlainText};
+use poem_openapi::{ApiResponse, Enum, Object, Union};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -26,32 +27,53 @@ pub struct Bots {
     pub tutor_bot: Arc<Bot>,
 }
 
+impl Bots {
+    pub fn get(&self, name: &str) -> Option<&Arc<Bot>> {
+        match name {
+            HELPER_BOT => Some(&self.helper_bot),
+            TUTOR_BOT => Some(&self.tutor_bot),
+            _ => None,
+        }
+    }
+
+    //the bots the user has the role for, the same check the websockets make
+    pub fn permitted(&self, roles: &[Role]) -> Vec<&Arc<Bot>> {
+        BOT_ROLES
+            .iter()
+            .filter(|(_, role)| roles.contains(role))
+            .filter_map(|(name, _)| self.get(name))
+            .collect()
+    }
+}
+
+const BOT_ROLES: [(&str, Role); 2] = [(HELPER_BOT, Role::Helper), (TUTOR_BOT, Role::Tutor)];
+
+pub const HELPER_BOT: &str = "helper";
+pub const TUTOR_BOT: &str = "tutor";
+
 pub fn get_bots(
     model_name: String,
     open_ai_compatable_endpoint: String,
-    helper_tools: Vec<Arc<dyn Tool + Send + Sync>>,
-    temperature: Option<f32>,
-    presence_penalty: Option<f32>,
-    top_p: Option<f32>,
+    helper_tools: ToolSet,
+    parameters: ModelParameters,
 ) -> Bots {
     let bots = Bots {
         helper_bot: Arc::new(Bot::new(
+            HELPER_BOT,
             model_name.clone(),
             HELPER_PROMPT,
             &open_ai_compatable_endpoint,
-            temperature,
-            presence_penalty,
-            top_p,
-            Some(helper_tools),
+            parameters,
+            Some(helper_tools.clone()),
         )),
         tutor_bot: Arc::new(Bot::new(
+            TUTOR_BOT,
             model_name,
             TUTOR_PROMPT,
             &open_ai_compatable_endpoint,
-            temperature,
-            presence_penalty,
-            top_p,
-            None, //no tools
+            parameters,
+            //no tools until an admin grants some to the tutor's users
+            Some(helper_tools.granted_only()),
         )),
     };
     bots
@@ -97,6 +119,44 @@ pub enum SuccessResponse {
     Success(Json<StatusResponse>),
 }
 
+#[derive(ApiResponse)]
+pub enum CalendarResponse {
+    #[oai(status = 200, content_type = "text/calendar")]
+    Calendar(PlainText<String>),
+}
+
+#[derive(ApiResponse)]
+pub enum AudioResponse {
+    #[oai(status = 200, content_type = "audio/mpeg")]
+    Audio(Binary<Vec<u8>>),
+}
+
+#[derive(Debug, Serialize, Object)]
+pub struct TranscriptionResponse {
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize, Object)]
+pub struct SpeakRequest {
+    pub text: String,
+    //a voice the speech server knows, eg af_heart for Kokoro. defaults to SPEECH_VOICE
+    pub voice: Option<String>,
+}
+
+//the key is only shown once, only its hash is stored
+#[derive(Debug, Serialize, Object)]
+pub struct NewApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub key: String,
+}
+
+//the feed url is secret, anyone with it can read the calendar
+#[derive(Debug, Serialize, Object)]
+pub struct CalendarFeed {
+    pub url: String,
+}
+
 #[derive(ApiResponse)]
 pub enum UsersResponse {
     // Status 200: Success
@@ -124,6 +184,66 @@ pub enum MessageResponse {
     SuccessMultiple(Json<Vec<MessageResult>>),
 }
 
+#[derive(Deserialize, Object)]
+pub struct ChatRequest {
+    pub message: String,
+    pub session_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Enum)]
+pub enum SourceKind {
+    //a resource attached to the bot, given to it as context
+    Resource,
+    //a knowledge base the bot searched with a tool
+    KnowledgeBase,
+}
+
+#[derive(Debug, Serialize, Object)]
+pub struct Source {
+    pub kind: SourceKind,
+    pub name: String,
+    //what the bot was given from the source, empty for resources
+    pub passages: Vec<String>,
+}
+
+#[derive(Serialize, Object)]
+pub struct ChatResponse {
+    pub message: String,
+    pub reasoning: String,
+    pub tool_calls: Vec<ToolCallTrace>,
+    pub sources: Vec<Source>,
+}
+
+#[derive(Serialize, Object)]
+pub struct ChatError {
+    pub msg: String,
+}
+
+//tokens as they are generated, then the whole response or the error that ended it
+#[derive(Union)]
+#[oai(discriminator_name = "type")]
+pub enum ChatEvent {
+    #[oai(mapping = "token")]
+    Token(WebSocketToken),
+    #[oai(mapping = "done")]
+    Done(ChatResponse),
+    #[oai(mapping = "error")]
+    Error(ChatError),
+}
+
+fn envelope_protocol() -> u32 {
+    crate::chat_protocol::PROTOCOL_VERSION
+}
+
+#[derive(Deserialize)]
+pub struct ChatQuery {
+    pub session_id: Uuid,
+    //the protocol's version. clients get the envelopes unless they ask for the
+    //original protocol, a bare prompt per connection, with v=1
+    #[serde(default = "envelope_protocol")]
+    pub v: u32,
+}
+
 #[derive(Deserialize)] // No deny_unknown_fields; let token slide, it's inert here
 pub struct SessionQuery {
     pub session_id: Uuid,
@@ -140,3 +260,48 @@ pub struct PromptKb {
     pub text: String,
     pub num_results: i16,
 }
+
+#[derive(Serialize, Object)]
+pub struct McpResource {
+    pub server: String,
+    pub uri: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+#[derive(Serialize, Object)]
+pub struct McpPromptArgument {
+    pub name: String,
+    pub description: Option<String>,
+    pub required: bool,
+}
+
+#[derive(Serialize, Object)]
+pub struct McpPrompt {
+    pub server: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub arguments: Vec<McpPromptArgument>,
+}
+
+//a quick action is answered by the bot as a turn of the session
+#[derive(Deserialize, Object)]
+pub struct QuickActionRequest {
+    pub bot: String,
+    pub session_id: Uuid,
+    pub arguments: HashMap<String, String>,
+}
+
+#[derive(Serialize, Object)]
+pub struct QuickActionResponse {
+    //the expanded prompt, saved to the session as the user's message
+    pub prompt: String,
+    pub response: ChatResponse,
+}
+
+#[derive(Serialize, Object)]
+pub struct ToolDescription {
+    pub name: String,
+    pub description: String,
+}
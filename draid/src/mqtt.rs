@@ -0,0 +1,550 @@
+//devices that speak MQTT directly, eg Zigbee2MQTT and Tasmota. the configured
+//topics become tools and the read topics are kept subscribed, so the last state
+//is at hand without asking the device
+use crate::config::{Mqtt, MqttAction, MqttTool};
+use crate::tools::{Tool, ToolContext, ToolOutput};
+use chrono::{DateTime, Utc};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS, SubscribeFilter};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::info;
+
+//wait before reconnecting to an unreachable broker
+const RECONNECT_SECONDS: u64 = 5;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Message {
+    pub topic: String,
+    //json if the payload parses, otherwise the text
+    pub payload: Value,
+    pub received_at: DateTime<Utc>,
+}
+
+//whether a topic matches a filter with + (one level) and # (the rest) wildcards
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut levels = topic.split('/');
+    for part in filter.split('/') {
+        match (part, levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (part, Some(level)) if part == level => {}
+            _ => return false,
+        }
+    }
+    levels.next().is_none()
+}
+
+fn argument_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+//the {{name}} placeholders in the text, by where they start and end and the name
+fn placeholders(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut found = vec![];
+    let mut from = 0;
+    while let Some(start) = text[from..].find("{{").map(|start| from + start) {
+        let Some(end) = text[start..].find("}}").map(|end| start + end + 2) else {
+            break;
+        };
+        found.push((start, end, text[start + 2..end - 2].trim()));
+        from = end;
+    }
+    found
+}
+
+//replaces each {{name}} in the text with the argument
+fn render_text(text: &str, args: &Map<String, Value>) -> Result<String, String> {
+    let mut rendered = String::new();
+    let mut rest = 0;
+    for (start, end, name) in placeholders(text) {
+        let value = args
+            .get(name)
+            .ok_or_else(|| format!("Missing argument {}", name))?;
+        rendered.push_str(&text[rest..start]);
+        rendered.push_str(&argument_text(value));
+        rest = end;
+    }
+    rendered.push_str(&text[rest..]);
+    Ok(rendered)
+}
+
+//an argument fills a single level of a topic, so it can't add levels or wildcards
+fn render_topic(topic: &str, args: &Map<String, Value>) -> Result<String, String> {
+    for (_, _, name) in placeholders(topic) {
+        if let Some(value) = args.get(name)
+            && argument_text(value).contains(['/', '+', '#'])
+        {
+            return Err(format!("{} can't contain /, + or #", name));
+        }
+    }
+    render_text(topic, args)
+}
+
+//a string that is only a placeholder, its name
+fn whole_placeholder(text: &str) -> Option<&str> {
+    match placeholders(text.trim()).as_slice() {
+        [(0, end, name)] if *end == text.trim().len() => Some(name),
+        _ => None,
+    }
+}
+
+//fills in a payload template, a string that is only a placeholder becomes the
+//argument itself so numbers and objects keep their type. fields of an object
+//that are only a placeholder are left out when the argument is, required
+//arguments having been checked already
+pub fn render(template: &Value, args: &Map<String, Value>) -> Result<Value, String> {
+    match template {
+        Value::String(text) => {
+            if let Some(name) = whole_placeholder(text) {
+                return args
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| format!("Missing argument {}", name));
+            }
+            Ok(Value::String(render_text(text, args)?))
+        }
+        Value::Array(items) => Ok(Value::Array(
+            items
+                .iter()
+                .map(|item| render(item, args))
+                .collect::<Result<_, _>>()?,
+        )),
+        Value::Object(fields) => Ok(Value::Object(
+            fields
+                .iter()
+                .filter(|(_, value)| {
+                    value
+                        .as_str()
+                        .and_then(whole_placeholder)
+                        .is_none_or(|name| args.contains_key(name))
+                })
+                .map(|(key, value)| Ok((key.clone(), render(value, args)?)))
+                .collect::<Result<_, String>>()?,
+        )),
+        value => Ok(value.clone()),
+    }
+}
+
+//the subscription for a read topic, placeholders match any level
+fn subscription(topic: &str) -> String {
+    topic
+        .split('/')
+        .map(|level| if level.contains("{{") { "+" } else { level })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn payload_bytes(payload: &Value) -> Vec<u8> {
+    match payload {
+        Value::Null => vec![],
+        Value::String(text) => text.clone().into_bytes(),
+        payload => payload.to_string().into_bytes(),
+    }
+}
+
+fn missing_required(schema: &Value, args: &Map<String, Value>) -> Option<String> {
+    schema["required"]
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .find(|name| !args.contains_key(*name))
+        .map(String::from)
+}
+
+pub struct MqttDevices {
+    client: AsyncClient,
+    cache: Mutex<HashMap<String, Message>>,
+    tools: Vec<MqttTool>,
+}
+
+impl MqttDevices {
+    //the connection is kept up in the background, reconnecting and subscribing again
+    pub fn connect(config: &Mqtt) -> Arc<Self> {
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+        let (client, mut eventloop) = AsyncClient::new(options, 64);
+        let devices = Arc::new(Self {
+            client,
+            cache: Mutex::new(HashMap::new()),
+            tools: config.tools.clone(),
+        });
+        let subscriptions: Vec<SubscribeFilter> = config
+            .tools
+            .iter()
+            .filter(|tool| tool.action == MqttAction::Read)
+            .map(|tool| SubscribeFilter::new(subscription(&tool.topic), QoS::AtLeastOnce))
+            .collect();
+        //the event loop only holds a weak reference so it ends with the devices
+        let weak = Arc::downgrade(&devices);
+        tokio::spawn(async move {
+            loop {
+                let event = eventloop.poll().await;
+                let Some(devices) = weak.upgrade() else {
+                    break;
+                };
+                match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!(
+                            tool_use = false,
+                            endpoint = "mqtt",
+                            message = "connected to the mqtt broker"
+                        );
+                        if !subscriptions.is_empty()
+                            && let Err(e) = devices.client.try_subscribe_many(subscriptions.clone())
+                        {
+                            info!(
+                                tool_use = false,
+                                endpoint = "mqtt",
+                                message = format!("Failed to subscribe to mqtt topics: {}", e)
+                            );
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        devices.remember(&publish.topic, &publish.payload);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        info!(
+                            tool_use = false,
+                            endpoint = "mqtt",
+                            message = format!("MQTT connection error: {}", e)
+                        );
+                        tokio::time::sleep(Duration::from_secs(RECONNECT_SECONDS)).await;
+                    }
+                }
+            }
+        });
+        devices
+    }
+
+    pub fn remember(&self, topic: &str, payload: &[u8]) {
+        let mut cache = self
+            .cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        //an empty retained message clears the state
+        if payload.is_empty() {
+            cache.remove(topic);
+            return;
+        }
+        let payload = serde_json::from_slice(payload)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(payload).to_string()));
+        cache.insert(
+            topic.to_string(),
+            Message {
+                topic: topic.to_string(),
+                payload,
+                received_at: Utc::now(),
+            },
+        );
+    }
+
+    //the cached messages on topics matching the filter
+    pub fn read(&self, filter: &str) -> Vec<Message> {
+        let cache = self
+            .cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut messages: Vec<Message> = cache
+            .values()
+            .filter(|message| topic_matches(filter, &message.topic))
+            .cloned()
+            .collect();
+        messages.sort_by(|a, b| a.topic.cmp(&b.topic));
+        messages
+    }
+
+    pub fn tools(self: &Arc<Self>) -> Vec<Arc<dyn Tool + Send + Sync>> {
+        self.tools
+            .iter()
+            .map(|config| {
+                Arc::new(MqttDeviceTool {
+                    config: config.clone(),
+                    devices: self.clone(),
+                }) as Arc<dyn Tool + Send + Sync>
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Published {
+    pub topic: String,
+    pub payload: Value,
+}
+
+pub struct MqttDeviceTool {
+    config: MqttTool,
+    devices: Arc<MqttDevices>,
+}
+
+impl MqttDeviceTool {
+    async fn publish(&self, args: &Map<String, Value>) -> anyhow::Result<ToolOutput<Published>> {
+        let topic = match render_topic(&self.config.topic, args) {
+            Ok(topic) => topic,
+            Err(e) => return Ok(ToolOutput::Error(e)),
+        };
+        //an argument mustn't turn a command into a wildcard
+        if topic.contains(['+', '#']) {
+            return Ok(ToolOutput::Error(format!("Invalid topic {}", topic)));
+        }
+        let payload = match render(&self.config.payload, args) {
+            Ok(payload) => payload,
+            Err(e) => return Ok(ToolOutput::Error(e)),
+        };
+        self.devices
+            .client
+            .publish(
+                &topic,
+                QoS::AtLeastOnce,
+                self.config.retain,
+                payload_bytes(&payload),
+            )
+            .await?;
+        Ok(ToolOutput::Result(Published { topic, payload }))
+    }
+
+    fn read(&self, args: &Map<String, Value>) -> ToolOutput<Vec<Message>> {
+        let filter = match render_topic(&self.config.topic, args) {
+            Ok(filter) => filter,
+            Err(e) => return ToolOutput::Error(e),
+        };
+        let messages = self.devices.read(&filter);
+        if messages.is_empty() {
+            return ToolOutput::Error(format!("Nothing has been received on {} yet", filter));
+        }
+        ToolOutput::Result(messages)
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for MqttDeviceTool {
+    fn name(&self) -> &String {
+        &self.config.name
+    }
+    fn description(&self) -> &String {
+        &self.config.description
+    }
+    fn parameters(&self) -> Value {
+        self.config.schema.clone()
+    }
+    async fn invoke(&self, args: String, _context: &ToolContext) -> anyhow::Result<Value> {
+        let args = if args.trim().is_empty() { "{}" } else { &args };
+        let args: Map<String, Value> = serde_json::from_str(args).map_err(|e| {
+            anyhow::anyhow!("Invalid arguments for tool {}: {}", self.config.name, e)
+        })?;
+        if let Some(name) = missing_required(&self.config.schema, &args) {
+            return Ok(serde_json::to_value(ToolOutput::<()>::Error(format!(
+                "Missing argument {}",
+                name
+            )))?);
+        }
+        Ok(match self.config.action {
+            MqttAction::Publish => serde_json::to_value(self.publish(&args).await?)?,
+            MqttAction::Read => serde_json::to_value(self.read(&args))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MqttDevices, render, subscription, topic_matches};
+    use crate::config::Mqtt;
+    use crate::tools::{Tool, ToolContext};
+    use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+    use serde_json::{Map, Value, json};
+    use std::time::Duration;
+    use tokio_util::sync::CancellationToken;
+    use uuid::Uuid;
+
+    fn context() -> ToolContext {
+        ToolContext {
+            user_id: Uuid::new_v4(),
+            roles: vec![],
+            session_id: Uuid::new_v4(),
+            bot_name: "helper".to_string(),
+            span_id: Uuid::new_v4().to_string(),
+            cancellation_token: CancellationToken::new(),
+            timezone: chrono_tz::UTC,
+        }
+    }
+
+    fn args(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    fn config(host: &str, port: u16) -> Mqtt {
+        serde_json::from_value(json!({
+            "host": host,
+            "port": port,
+            "client_id": format!("draid-test-{}", Uuid::new_v4()),
+            "tools": [
+                {"name": "set_light", "description": "Turn a light on or off",
+                    "action": "publish", "topic": "draid-test/{{light}}/set",
+                    "schema": {"type": "object", "properties": {
+                        "light": {"type": "string"},
+                        "state": {"type": "string", "enum": ["ON", "OFF"]},
+                        "brightness": {"type": "integer"}
+                    }, "required": ["light", "state"]},
+                    "payload": {"state": "{{state}}", "brightness": "{{brightness}}"}},
+                {"name": "read_garage_door", "description": "Whether the garage door is open",
+                    "action": "read", "topic": "draid-test/garage_door"},
+                {"name": "read_sensors", "description": "The latest sensor readings",
+                    "action": "read", "topic": "draid-test/sensors/#"}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn it_matches_topics() {
+        assert!(topic_matches(
+            "zigbee2mqtt/garage_door",
+            "zigbee2mqtt/garage_door"
+        ));
+        assert!(!topic_matches(
+            "zigbee2mqtt/garage_door",
+            "zigbee2mqtt/garage_door/set"
+        ));
+        assert!(topic_matches(
+            "zigbee2mqtt/+/state",
+            "zigbee2mqtt/lamp/state"
+        ));
+        assert!(!topic_matches("zigbee2mqtt/+/state", "zigbee2mqtt/lamp"));
+        assert!(topic_matches("tele/#", "tele/plug/SENSOR"));
+        assert!(topic_matches("tele/#", "tele"));
+        assert!(!topic_matches("tele/#", "stat/plug"));
+        assert_eq!(subscription("stat/{{device}}/POWER"), "stat/+/POWER");
+    }
+
+    #[test]
+    fn it_renders_payloads() {
+        let template = json!({"state": "{{state}}", "brightness": "{{ brightness }}",
+            "effect": "fade {{state}}", "transition": 2});
+        assert_eq!(
+            render(&template, &args(json!({"state": "ON", "brightness": 120}))).unwrap(),
+            json!({"state": "ON", "brightness": 120, "effect": "fade ON", "transition": 2})
+        );
+        //an optional argument that isn't given leaves its field out
+        assert_eq!(
+            render(&template, &args(json!({"state": "ON"}))).unwrap(),
+            json!({"state": "ON", "effect": "fade ON", "transition": 2})
+        );
+        assert!(render(&template, &args(json!({"brightness": 120}))).is_err());
+        assert_eq!(
+            render(&json!("{{power}}"), &args(json!({"power": "OFF"}))).unwrap(),
+            json!("OFF")
+        );
+    }
+
+    #[tokio::test]
+    async fn it_reads_the_cache() {
+        //nothing listens on the port, so only the cache is used
+        let devices = MqttDevices::connect(&config("127.0.0.1", 1));
+        let tools = devices.tools();
+        let names: Vec<&String> = tools.iter().map(|tool| tool.name()).collect();
+        assert_eq!(names, vec!["set_light", "read_garage_door", "read_sensors"]);
+        let result = tools[1].invoke("".to_string(), &context()).await.unwrap();
+        assert!(result["error"].is_string());
+        devices.remember("draid-test/garage_door", br#"{"contact": false}"#);
+        devices.remember("draid-test/sensors/hall", b"21.5");
+        devices.remember("draid-test/sensors/attic", b"warm");
+        let result = tools[1].invoke("{}".to_string(), &context()).await.unwrap();
+        assert_eq!(result["result"][0]["payload"], json!({"contact": false}));
+        let result = tools[2].invoke("{}".to_string(), &context()).await.unwrap();
+        assert_eq!(result["result"][0]["payload"], json!("warm"));
+        assert_eq!(result["result"][1]["payload"], json!(21.5));
+        let result = tools[0]
+            .invoke(r#"{"light": "kitchen"}"#.to_string(), &context())
+            .await
+            .unwrap();
+        assert_eq!(result["error"], "Missing argument state");
+        let result = tools[0]
+            .invoke(
+                r#"{"light": "kitchen/../#", "state": "ON"}"#.to_string(),
+                &context(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result["error"], "light can't contain /, + or #");
+    }
+
+    //needs a broker, eg mosquitto -p 1883, and MQTT_TEST_HOST=localhost
+    #[tokio::test]
+    #[ignore]
+    async fn it_talks_to_a_broker() {
+        let host = std::env::var("MQTT_TEST_HOST").unwrap();
+        let port: u16 = std::env::var("MQTT_TEST_PORT")
+            .map(|port| port.parse().unwrap())
+            .unwrap_or(1883);
+        let (device, mut eventloop) = AsyncClient::new(
+            MqttOptions::new(format!("draid-device-{}", Uuid::new_v4()), &host, port),
+            10,
+        );
+        device
+            .subscribe("draid-test/+/set", QoS::AtLeastOnce)
+            .await
+            .unwrap();
+        device
+            .publish(
+                "draid-test/garage_door",
+                QoS::AtLeastOnce,
+                true,
+                r#"{"contact": false}"#,
+            )
+            .await
+            .unwrap();
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            loop {
+                if let Ok(Event::Incoming(Packet::Publish(publish))) = eventloop.poll().await {
+                    let _ = sender.send(publish).await;
+                }
+            }
+        });
+
+        let devices = MqttDevices::connect(&config(&host, port));
+        let tools = devices.tools();
+        let mut result = Value::Null;
+        for _ in 0..50 {
+            result = tools[1].invoke("{}".to_string(), &context()).await.unwrap();
+            if result.get("result").is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(result["result"][0]["payload"], json!({"contact": false}));
+
+        let result = tools[0]
+            .invoke(
+                r#"{"light": "kitchen", "state": "ON", "brightness": 120}"#.to_string(),
+                &context(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result["result"]["topic"], "draid-test/kitchen/set");
+        let publish = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(publish.topic, "draid-test/kitchen/set");
+        assert_eq!(
+            serde_json::from_slice::<Value>(&publish.payload).unwrap(),
+            json!({"state": "ON", "brightness": 120})
+        );
+        //clears the retained message
+        device
+            .publish("draid-test/garage_door", QoS::AtLeastOnce, true, vec![])
+            .await
+            .unwrap();
+    }
+}
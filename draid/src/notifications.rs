@@ -0,0 +1,117 @@
+use serde::Serialize;
+use sqlx::types::chrono;
+use std::{collections::HashMap, sync::RwLock};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notification {
+    Reminder {
+        id: Uuid,
+        message: String,
+        due_at: chrono::DateTime<chrono::Utc>,
+    },
+    //a shared list was edited, clients should reload it
+    ListChanged {
+        list_id: Uuid,
+        changed_by: Uuid,
+    },
+}
+
+//per user channels for pushing events to every connected client of that user
+pub struct Notifications {
+    channels: RwLock<HashMap<Uuid, broadcast::Sender<Notification>>>,
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        Self {
+            channels: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn subscribe(&self, user_id: Uuid) -> broadcast::Receiver<Notification> {
+        let mut channels = self
+            .channels
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        channels
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(32).0)
+            .subscribe()
+    }
+
+    //returns whether any client was listening
+    pub fn send(&self, user_id: Uuid, notification: Notification) -> bool {
+        let mut channels = self
+            .channels
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let delivered = channels
+            .get(&user_id)
+            .is_some_and(|sender| sender.send(notification).is_ok());
+        if !delivered {
+            //every receiver has been dropped
+            channels.remove(&user_id);
+        }
+        delivered
+    }
+
+    //sends to every connected user, for changes to things the household shares
+    pub fn broadcast(&self, notification: Notification) {
+        let mut channels = self
+            .channels
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        channels.retain(|_, sender| sender.send(notification.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Notification, Notifications};
+    use sqlx::types::chrono;
+    use uuid::Uuid;
+
+    fn reminder() -> Notification {
+        Notification::Reminder {
+            id: Uuid::new_v4(),
+            message: "move the laundry".to_string(),
+            due_at: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_delivers_only_to_the_user() {
+        let notifications = Notifications::new();
+        let user = Uuid::new_v4();
+        let mut receiver = notifications.subscribe(user);
+        let mut other = notifications.subscribe(Uuid::new_v4());
+        assert!(notifications.send(user, reminder()));
+        assert!(matches!(
+            receiver.recv().await,
+            Ok(Notification::Reminder { .. })
+        ));
+        assert!(other.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn it_broadcasts_to_everyone() {
+        let notifications = Notifications::new();
+        let mut first = notifications.subscribe(Uuid::new_v4());
+        let mut second = notifications.subscribe(Uuid::new_v4());
+        notifications.broadcast(reminder());
+        assert!(first.recv().await.is_ok());
+        assert!(second.recv().await.is_ok());
+    }
+
+    #[test]
+    fn it_reports_when_nobody_is_listening() {
+        let notifications = Notifications::new();
+        let user = Uuid::new_v4();
+        assert!(!notifications.send(user, reminder()));
+        drop(notifications.subscribe(user));
+        assert!(!notifications.send(user, reminder()));
+    }
+}
@@ -0,0 +1,542 @@
+use crate::auth::UserIdentification;
+use crate::chat::{chat_once, chat_turn};
+use crate::llm::{Bot, DiscardTokens, FullMessage, TokenSink, WebSocketToken};
+use crate::mcp_tools::McpServers;
+use crate::models::Bots;
+use crate::psql_memory::{MessageResult, MessageType};
+use futures::StreamExt;
+use futures::channel::mpsc;
+use poem::http::StatusCode;
+use poem::web::sse::{Event, SSE};
+use poem::web::{Data, Json};
+use poem::{Error, IntoResponse, Response, Result, handler};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+use uuid::Uuid;
+
+//an OpenAI compatible api so chat clients can use the bots as a model. the
+//api key stands in for the user, so the bots' tools act for them
+
+pub fn new_api_key() -> String {
+    format!(
+        "draid-{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+//keys are only stored hashed, they're random enough not to need a salt
+pub fn api_key_hash(key: &str) -> String {
+    sha256::digest(key)
+}
+
+//errors in the shape OpenAI clients expect
+pub fn openai_error(status: StatusCode, message: &str) -> Error {
+    let error_type = match status {
+        StatusCode::UNAUTHORIZED => "authentication_error",
+        StatusCode::NOT_FOUND => "not_found_error",
+        StatusCode::INTERNAL_SERVER_ERROR => "server_error",
+        _ => "invalid_request_error",
+    };
+    let body = json!({"error": {"message": message, "type": error_type, "code": null}});
+    Error::from_response(
+        Response::builder()
+            .status(status)
+            .content_type("application/json")
+            .body(body.to_string()),
+    )
+}
+
+#[handler]
+pub async fn list_models(
+    Data(bots): Data<&Arc<Bots>>,
+    Data(user): Data<&UserIdentification>, //attached from api key middleware
+) -> Json<Value> {
+    let data: Vec<Value> = bots
+        .permitted(&user.roles)
+        .into_iter()
+        .map(|bot| json!({"id": bot.name(), "object": "model", "created": 0, "owned_by": "draid"}))
+        .collect();
+    Json(json!({"object": "list", "data": data}))
+}
+
+#[derive(Deserialize)]
+pub struct ChatMessage {
+    role: String,
+    #[serde(default)]
+    content: Value,
+}
+
+#[derive(Deserialize)]
+pub struct ChatCompletionRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    #[serde(default)]
+    stream: bool,
+    //not part of OpenAI's api. continues a draid session and saves the exchange
+    //to it, the session's history is used instead of the earlier messages
+    session_id: Option<Uuid>,
+}
+
+//content is either a string or a list of parts, only text parts are kept
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter(|part| part["type"] == "text")
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => "".to_string(),
+    }
+}
+
+//the earlier messages become the history and the last one the prompt. tool
+//messages belong to the client's own tools, so they're left out
+fn split_messages(messages: &[ChatMessage]) -> Result<(Vec<MessageResult>, String), String> {
+    let (last, earlier) = messages
+        .split_last()
+        .ok_or_else(|| "messages must not be empty".to_string())?;
+    if last.role != "user" {
+        return Err("the last message must be from the user".to_string());
+    }
+    let history = earlier
+        .iter()
+        .filter_map(|message| {
+            let message_type = match message.role.as_str() {
+                "system" | "developer" => MessageType::SystemMessage,
+                "user" => MessageType::HumanMessage,
+                "assistant" => MessageType::AIMessage,
+                _ => return None,
+            };
+            Some(MessageResult {
+                content: content_text(&message.content),
+                reasoning: "".to_string(),
+                message_type,
+                timestamp: chrono::Utc::now(),
+            })
+        })
+        .collect();
+    Ok((history, content_text(&last.content)))
+}
+
+struct Completion {
+    id: String,
+    created: i64,
+    model: String,
+}
+
+impl Completion {
+    fn new(model: &str) -> Self {
+        Self {
+            id: format!("chatcmpl-{}", Uuid::new_v4().simple()),
+            created: chrono::Utc::now().timestamp(),
+            model: model.to_string(),
+        }
+    }
+
+    fn chunk(&self, delta: Value, finish_reason: Option<&str>) -> String {
+        json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+        })
+        .to_string()
+    }
+}
+
+//sends each token as a chunk to the SSE stream
+struct ChunkSink {
+    completion: Arc<Completion>,
+    sender: mpsc::UnboundedSender<String>,
+}
+
+#[async_trait::async_trait]
+impl TokenSink for ChunkSink {
+    async fn send_token(&mut self, token: WebSocketToken) -> anyhow::Result<()> {
+        let delta = match (token.message(), token.reasoning()) {
+            (Some(content), _) => json!({"role": "assistant", "content": content}),
+            (_, Some(reasoning)) => json!({"role": "assistant", "reasoning_content": reasoning}),
+            _ => return Ok(()),
+        };
+        //the client may have gone, the answer is still finished and saved
+        let _ = self
+            .sender
+            .unbounded_send(self.completion.chunk(delta, None));
+        Ok(())
+    }
+}
+
+struct Exchange {
+    bot: Arc<Bot>,
+    history: Vec<MessageResult>,
+    prompt: String,
+    session_id: Option<Uuid>,
+    user: UserIdentification,
+    pool: PgPool,
+    servers: Arc<McpServers>,
+}
+
+impl Exchange {
+    async fn run<S: TokenSink + ?Sized>(self, tx: &mut S) -> anyhow::Result<FullMessage> {
+        match self.session_id {
+            Some(session_id) => {
+                chat_turn(
+                    &self.bot,
+                    tx,
+                    &self.prompt,
+                    session_id,
+                    &self.user,
+                    &self.pool,
+                    &self.servers,
+                )
+                .await
+            }
+            None => {
+                chat_once(
+                    &self.bot,
+                    tx,
+                    self.history,
+                    &self.prompt,
+                    &self.user,
+                    &self.pool,
+                    &self.servers,
+                )
+                .await
+            }
+        }
+    }
+}
+
+fn stream_completion(exchange: Exchange, completion: Completion) -> Response {
+    let (sender, receiver) = mpsc::unbounded();
+    let completion = Arc::new(completion);
+    let mut sink = ChunkSink {
+        completion: completion.clone(),
+        sender: sender.clone(),
+    };
+    tokio::spawn(async move {
+        let end = match exchange.run(&mut sink).await {
+            Ok(_) => completion.chunk(json!({}), Some("stop")),
+            Err(e) => {
+                info!(
+                    tool_use = false,
+                    endpoint = "chat_completions",
+                    message = format!("Error in chat completion: {}", e)
+                );
+                json!({"error": {"message": e.to_string(), "type": "server_error", "code": null}})
+                    .to_string()
+            }
+        };
+        //the client may have gone already
+        let _ = sender.unbounded_send(end);
+        let _ = sender.unbounded_send("[DONE]".to_string());
+    });
+    SSE::new(receiver.map(Event::message))
+        .keep_alive(Duration::from_secs(15))
+        .into_response()
+}
+
+#[handler]
+pub async fn chat_completions(
+    Json(request): Json<ChatCompletionRequest>,
+    Data(bots): Data<&Arc<Bots>>,
+    Data(pool): Data<&PgPool>,
+    Data(servers): Data<&Arc<McpServers>>,
+    Data(user): Data<&UserIdentification>, //attached from api key middleware
+) -> Result<Response> {
+    let bot = bots
+        .permitted(&user.roles)
+        .into_iter()
+        .find(|bot| bot.name() == request.model)
+        .ok_or_else(|| {
+            openai_error(
+                StatusCode::NOT_FOUND,
+                &format!("The model '{}' does not exist", request.model),
+            )
+        })?;
+    let (history, prompt) = split_messages(&request.messages)
+        .map_err(|msg| openai_error(StatusCode::BAD_REQUEST, &msg))?;
+    let exchange = Exchange {
+        bot: bot.clone(),
+        history,
+        prompt,
+        session_id: request.session_id,
+        user: user.clone(),
+        pool: pool.clone(),
+        servers: servers.clone(),
+    };
+    let completion = Completion::new(&request.model);
+    if request.stream {
+        return Ok(stream_completion(exchange, completion));
+    }
+    let full_message = exchange.run(&mut DiscardTokens).await.map_err(|e| {
+        info!(
+            tool_use = false,
+            endpoint = "chat_completions",
+            message = format!("Error in chat completion: {}", e)
+        );
+        openai_error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
+    })?;
+    Ok(Json(json!({
+        "id": completion.id,
+        "object": "chat.completion",
+        "created": completion.created,
+        "model": completion.model,
+        "choices": [{
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "content": full_message.message,
+                "reasoning_content": full_message.reasoning
+            },
+            "finish_reason": "stop"
+        }],
+        "usage": {"prompt_tokens": 0, "completion_tokens": 0, "total_tokens": 0}
+    }))
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::confirmations::Confirmations;
+    use crate::llm::ModelParameters;
+    use crate::models::{HELPER_BOT, get_bots};
+    use crate::psql_users::Role;
+    use crate::tools::{Tool, ToolContext, ToolPermissions, ToolSet};
+    use poem::listener::{Acceptor, Listener, TcpListener};
+    use poem::{EndpointExt, Route, Server, post};
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn message(role: &str, content: Value) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content,
+        }
+    }
+
+    #[test]
+    fn it_splits_messages() {
+        let messages = vec![
+            message("system", json!("be brief")),
+            message("user", json!("hi")),
+            message("assistant", json!("hello")),
+            message("tool", json!("{}")),
+            message(
+                "user",
+                json!([{"type": "text", "text": "what's for dinner?"}, {"type": "image_url"}]),
+            ),
+        ];
+        let (history, prompt) = split_messages(&messages).unwrap();
+        assert_eq!(prompt, "what's for dinner?");
+        let types: Vec<MessageType> = history.into_iter().map(|m| m.message_type).collect();
+        assert!(matches!(
+            types.as_slice(),
+            [
+                MessageType::SystemMessage,
+                MessageType::HumanMessage,
+                MessageType::AIMessage
+            ]
+        ));
+        assert!(split_messages(&[]).is_err());
+        assert!(split_messages(&[message("assistant", json!("hi"))]).is_err());
+    }
+
+    #[test]
+    fn it_only_lists_permitted_bots() {
+        let bots = get_bots(
+            "model".to_string(),
+            "http://localhost:1".to_string(),
+            ToolSet::new(vec![], ToolPermissions::default()),
+            ModelParameters {
+                temperature: None,
+                presence_penalty: None,
+                top_p: None,
+            },
+        );
+        let names: Vec<&str> = bots
+            .permitted(&[Role::Helper])
+            .into_iter()
+            .map(|bot| bot.name())
+            .collect();
+        assert_eq!(names, vec![HELPER_BOT]);
+    }
+
+    #[test]
+    fn it_hashes_keys() {
+        let key = new_api_key();
+        assert!(key.starts_with("draid-"));
+        assert_ne!(key, new_api_key());
+        assert_eq!(api_key_hash(&key), api_key_hash(&key));
+        assert_ne!(api_key_hash(&key), key);
+    }
+
+    //a door that only unlocks once the user confirmed
+    struct FrontDoor {
+        name: String,
+        description: String,
+        confirmations: Confirmations,
+        unlocked: Arc<AtomicBool>,
+    }
+
+    #[async_trait::async_trait]
+    impl Tool for FrontDoor {
+        fn name(&self) -> &String {
+            &self.name
+        }
+        fn description(&self) -> &String {
+            &self.description
+        }
+        fn parameters(&self) -> Value {
+            json!({"type": "object", "properties": {"confirmation": {"type": "string"}}})
+        }
+        async fn invoke(&self, args: String, context: &ToolContext) -> anyhow::Result<Value> {
+            let args: Value = serde_json::from_str(&args)?;
+            let Some(code) = args["confirmation"].as_str() else {
+                let code = self.confirmations.request("unlock", context);
+                return Ok(json!({"confirmation": code}));
+            };
+            Ok(match self.confirmations.confirm(code, "unlock", context) {
+                Ok(()) => {
+                    self.unlocked.store(true, Ordering::SeqCst);
+                    json!({"unlocked": true})
+                }
+                Err(e) => json!({"error": e}),
+            })
+        }
+    }
+
+    fn model_chunk(delta: Value, finish_reason: Option<&str>) -> String {
+        json!({
+            "id": "chatcmpl-test",
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": "model",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+        })
+        .to_string()
+    }
+
+    //a model that asks to unlock the door, confirms with the code from its last
+    //answer when the user says yes, and answers with the door's result
+    #[handler]
+    fn chat_model(Json(request): Json<Value>) -> SSE {
+        let messages = request["messages"].as_array().cloned().unwrap_or_default();
+        let last = messages.last().cloned().unwrap_or_default();
+        let mut chunks = vec![model_chunk(json!({"content": "</think>"}), None)];
+        if last["role"] == "tool" {
+            chunks.push(model_chunk(json!({"content": last["content"]}), None));
+            chunks.push(model_chunk(json!({}), Some("stop")));
+        } else {
+            let arguments = match last["content"].as_str() {
+                Some("yes") => {
+                    let asked = messages
+                        .iter()
+                        .rev()
+                        .find(|message| message["role"] == "assistant")
+                        .and_then(|message| message["content"].as_str())
+                        .unwrap_or_default();
+                    let asked: Value = serde_json::from_str(asked).unwrap_or_default();
+                    json!({"confirmation": asked["confirmation"]})
+                }
+                _ => json!({}),
+            };
+            chunks.push(model_chunk(
+                json!({"tool_calls": [{"index": 0, "id": "call_1", "type": "function",
+                    "function": {"name": "front_door", "arguments": arguments.to_string()}}]}),
+                None,
+            ));
+            chunks.push(model_chunk(json!({}), Some("tool_calls")));
+        }
+        chunks.push("[DONE]".to_string());
+        SSE::new(futures::stream::iter(chunks).map(Event::message))
+    }
+
+    async fn serve(app: impl poem::Endpoint + 'static) -> String {
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let address = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+        format!("http://{}", address)
+    }
+
+    //needs the database, eg DATABASE_URL=postgres://localhost/draid cargo test -- --ignored
+    #[tokio::test]
+    #[ignore]
+    async fn it_confirms_in_the_next_request() {
+        let pool = PgPoolOptions::new()
+            .connect(&std::env::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+        let model = serve(Route::new().at("/v1/chat/completions", post(chat_model))).await;
+        let unlocked = Arc::new(AtomicBool::new(false));
+        let door: Arc<dyn Tool + Send + Sync> = Arc::new(FrontDoor {
+            name: "front_door".to_string(),
+            description: "Unlocks the front door".to_string(),
+            confirmations: Confirmations::new(),
+            unlocked: unlocked.clone(),
+        });
+        let bots = Arc::new(get_bots(
+            "model".to_string(),
+            model,
+            ToolSet::new(vec![door], ToolPermissions::default()),
+            ModelParameters {
+                temperature: None,
+                presence_penalty: None,
+                top_p: None,
+            },
+        ));
+        let user = UserIdentification {
+            username: "sam".to_string(),
+            id: Uuid::new_v4(),
+            roles: vec![Role::Helper],
+            timezone: chrono_tz::UTC,
+        };
+        let url = serve(
+            Route::new()
+                .at("/v1/chat/completions", post(chat_completions))
+                .data(bots)
+                .data(pool)
+                .data(Arc::new(McpServers::new()))
+                .data(user),
+        )
+        .await;
+        let client = reqwest::Client::new();
+        let complete = async |messages: Value| -> String {
+            let response: Value = client
+                .post(format!("{}/v1/chat/completions", url))
+                .json(&json!({"model": HELPER_BOT, "messages": messages}))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            response["choices"][0]["message"]["content"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        let asked = complete(json!([{"role": "user", "content": "unlock the front door"}])).await;
+        assert!(!unlocked.load(Ordering::SeqCst));
+        let answer = complete(json!([
+            {"role": "user", "content": "unlock the front door"},
+            {"role": "assistant", "content": asked},
+            {"role": "user", "content": "yes"}
+        ]))
+        .await;
+        assert_eq!(answer, r#"{"unlocked":true}"#);
+        assert!(unlocked.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,556 @@
+use crate::dates::parse_datetime;
+use crate::embedding::{EmbeddingClient, get_embeddings};
+use crate::fuzzy::{best_match, normalize};
+use crate::psql_pantry::{
+    PantryItem, PantryItemRequest, create_pantry_item, delete_pantry_item,
+    get_expiring_pantry_items, get_pantry_items, update_pantry_quantity,
+};
+use crate::psql_vectors::{get_docs_with_similar_content, get_knowledge_base};
+use crate::tools::{ToolContext, ToolOutput, TypedTool, typed_tool};
+use crate::units::{convert, find_unit};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+//knowledge base searched for recipe ideas
+pub const RECIPES_KB: &str = "recipes";
+//how far ahead "expiring soon" looks by default
+pub const EXPIRING_SOON_DAYS: i64 = 3;
+
+//converts an amount into the unit the pantry already tracks the item in,
+//eg 200 g of flour into cups when the bag was stocked in cups
+pub fn to_stock_unit(
+    name: &str,
+    quantity: f64,
+    unit: Option<&str>,
+    stock_unit: Option<&str>,
+) -> Result<f64, String> {
+    match (unit, stock_unit) {
+        (None, None) => Ok(quantity),
+        (Some(unit), Some(stock_unit)) => {
+            //same unit under different spellings, eg "cups" and "cup", or units the
+            //registry doesn't know, eg "cans"
+            let same = match (find_unit(unit), find_unit(stock_unit)) {
+                (Ok(a), Ok(b)) => a.symbol == b.symbol,
+                _ => normalize(unit) == normalize(stock_unit),
+            };
+            if same {
+                return Ok(quantity);
+            }
+            convert(quantity, unit, stock_unit, Some(name))
+                .map(|(quantity, _)| quantity)
+                .map_err(|e| e.to_string())
+        }
+        (Some(unit), None) => Err(format!(
+            "{} is counted without a unit, not in {}",
+            name, unit
+        )),
+        (None, Some(stock_unit)) => Err(format!("{} is tracked in {}", name, stock_unit)),
+    }
+}
+
+//whole word match so that "egg" isn't found in "eggplant"
+pub fn mentions(text: &str, name: &str) -> bool {
+    let name = normalize(name);
+    !name.is_empty() && format!(" {} ", normalize(text)).contains(&format!(" {} ", name))
+}
+
+fn find_item(name: &str, items: &[PantryItem]) -> Option<usize> {
+    best_match(name, items.iter().map(|item| item.name.as_str()))
+}
+
+//whether an item is kept where the new one goes, any place will do when none
+//is given
+fn kept_in(item: &PantryItem, location: Option<&str>) -> bool {
+    location.is_none_or(|location| {
+        item.location
+            .as_deref()
+            .is_some_and(|kept| normalize(kept) == normalize(location))
+    })
+}
+
+//adds to an existing item in the same place when there is one. the earliest
+//expiry is kept since there is no telling which package gets used first
+pub async fn stock_item(
+    item: &PantryItemRequest,
+    pool: &PgPool,
+) -> anyhow::Result<Result<PantryItem, String>> {
+    if item.quantity < 0.0 {
+        return Ok(Err("the quantity can't be negative".to_string()));
+    }
+    let items: Vec<PantryItem> = get_pantry_items(pool)
+        .await?
+        .into_iter()
+        .filter(|existing| kept_in(existing, item.location.as_deref()))
+        .collect();
+    let Some(existing) = find_item(&item.name, &items).map(|index| &items[index]) else {
+        return Ok(Ok(create_pantry_item(item, pool).await?));
+    };
+    let quantity = match to_stock_unit(
+        &existing.name,
+        item.quantity,
+        item.unit.as_deref(),
+        existing.unit.as_deref(),
+    ) {
+        Ok(quantity) => quantity,
+        Err(e) => return Ok(Err(e)),
+    };
+    let expires_on = match (existing.expires_on, item.expires_on) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    Ok(Ok(update_pantry_quantity(
+        &existing.id,
+        existing.quantity + quantity,
+        expires_on,
+        pool,
+    )
+    .await?))
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct StockArg {
+    /// Item name, eg "eggs" or "whole milk"
+    name: String,
+    /// Amount, eg 12
+    quantity: f64,
+    /// Unit of the amount, leave out for things that are counted, eg eggs
+    #[serde(default)]
+    unit: Option<String>,
+    /// Where it is kept, eg fridge, freezer or pantry
+    #[serde(default)]
+    location: Option<String>,
+    /// When it expires, eg "2026-11-02", "next friday" or "in 5 days"
+    #[serde(default)]
+    expires: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct StockResult {
+    stocked: Vec<PantryItem>,
+    errors: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct AddPantryItemsTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl AddPantryItemsTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "add_to_pantry".to_string(),
+            description: "Record food added to the fridge, freezer or pantry, eg after shopping. \
+                Amounts are added to what is already there"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct AddPantryItemsArgs {
+    /// Items that were added
+    items: Vec<StockArg>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for AddPantryItemsTool {
+    type Args = AddPantryItemsArgs;
+    type Output = ToolOutput<StockResult>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: AddPantryItemsArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let now = chrono::Utc::now().with_timezone(&context.timezone);
+        let mut result = StockResult {
+            stocked: vec![],
+            errors: vec![],
+        };
+        for item in args.items {
+            let expires_on = match item
+                .expires
+                .as_deref()
+                .map(|expires| parse_datetime(expires, now))
+            {
+                Some(Ok(expires)) => Some(expires.date_naive()),
+                Some(Err(e)) => {
+                    result.errors.push(format!("{}: {}", item.name, e));
+                    continue;
+                }
+                None => None,
+            };
+            let request = PantryItemRequest {
+                name: item.name,
+                quantity: item.quantity,
+                unit: item.unit,
+                location: item.location,
+                expires_on,
+            };
+            match stock_item(&request, &self.pool).await? {
+                Ok(item) => result.stocked.push(item),
+                Err(e) => result.errors.push(format!("{}: {}", request.name, e)),
+            }
+        }
+        Ok(ToolOutput::Result(result))
+    }
+}
+typed_tool!(AddPantryItemsTool);
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UseArg {
+    /// Item name, eg "eggs"
+    name: String,
+    /// Amount used, eg 2
+    quantity: f64,
+    /// Unit of the amount, leave out for things that are counted
+    #[serde(default)]
+    unit: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct UseResult {
+    remaining: Vec<PantryItem>,
+    used_up: Vec<String>,
+    errors: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct UsePantryItemsTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl UsePantryItemsTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "use_from_pantry".to_string(),
+            description: "Record food that was used or thrown out, eg \"we used 2 eggs\". \
+                Items are removed once none is left"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UsePantryItemsArgs {
+    /// Items that were used
+    items: Vec<UseArg>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for UsePantryItemsTool {
+    type Args = UsePantryItemsArgs;
+    type Output = ToolOutput<UseResult>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: UsePantryItemsArgs,
+        _context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        //kept up to date as items are used so that the same item named twice
+        //isn't taken from its original amount both times
+        let mut items = get_pantry_items(&self.pool).await?;
+        let mut result = UseResult {
+            remaining: vec![],
+            used_up: vec![],
+            errors: vec![],
+        };
+        for used in args.items {
+            if used.quantity < 0.0 {
+                result
+                    .errors
+                    .push(format!("{}: the quantity can't be negative", used.name));
+                continue;
+            }
+            let Some(index) = find_item(&used.name, &items) else {
+                result
+                    .errors
+                    .push(format!("{} isn't in the pantry", used.name));
+                continue;
+            };
+            let item = &items[index];
+            let quantity = match to_stock_unit(
+                &item.name,
+                used.quantity,
+                used.unit.as_deref(),
+                item.unit.as_deref(),
+            ) {
+                Ok(quantity) => quantity,
+                Err(e) => {
+                    result.errors.push(format!("{}: {}", item.name, e));
+                    continue;
+                }
+            };
+            let remaining = item.quantity - quantity;
+            let id = item.id;
+            result.remaining.retain(|item| item.id != id);
+            //floating point leftovers from unit conversion count as used up
+            if remaining <= 1e-6 {
+                delete_pantry_item(&id, &self.pool).await?;
+                result.used_up.push(items.remove(index).name);
+            } else {
+                let item =
+                    update_pantry_quantity(&id, remaining, item.expires_on, &self.pool).await?;
+                items[index] = item.clone();
+                result.remaining.push(item);
+            }
+        }
+        Ok(ToolOutput::Result(result))
+    }
+}
+typed_tool!(UsePantryItemsTool);
+
+#[derive(Clone)]
+pub struct ReadPantryTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl ReadPantryTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "read_pantry".to_string(),
+            description: "List the food in the fridge, freezer and pantry, \
+                optionally only what expires soon"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ReadPantryArgs {
+    /// Only items expiring within this many days, including anything already expired
+    #[serde(default)]
+    expiring_within_days: Option<i64>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for ReadPantryTool {
+    type Args = ReadPantryArgs;
+    type Output = ToolOutput<Vec<PantryItem>>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: ReadPantryArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let items = match args.expiring_within_days {
+            Some(days) => {
+                let before = expiry_cutoff(context, days);
+                get_expiring_pantry_items(&before, &self.pool).await?
+            }
+            None => get_pantry_items(&self.pool).await?,
+        };
+        Ok(ToolOutput::Result(items))
+    }
+}
+typed_tool!(ReadPantryTool);
+
+fn expiry_cutoff(context: &ToolContext, days: i64) -> chrono::NaiveDate {
+    let today = chrono::Utc::now()
+        .with_timezone(&context.timezone)
+        .date_naive();
+    today
+        .checked_add_signed(chrono::Duration::days(days))
+        .unwrap_or(today)
+}
+
+#[derive(Clone)]
+pub struct WhatCanICookTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+    client: Arc<EmbeddingClient>,
+}
+
+impl WhatCanICookTool {
+    pub fn new(pool: PgPool, client: Arc<EmbeddingClient>) -> Self {
+        Self {
+            name: "what_can_i_cook".to_string(),
+            description: "Suggest recipes from the recipe collection that use up food \
+                from the pantry, prioritising what expires soon"
+                .to_string(),
+            pool,
+            client,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct WhatCanICookArgs {
+    /// Prioritise items expiring within this many days, defaults to 3
+    #[serde(default)]
+    expiring_within_days: Option<i64>,
+    /// Number of recipes to look at, defaults to 3
+    #[serde(default)]
+    num_results: Option<i16>,
+}
+
+#[derive(Serialize)]
+pub struct RecipeSuggestion {
+    recipe: String,
+    uses_expiring: Vec<String>,
+    uses: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct CookingSuggestions {
+    expiring: Vec<PantryItem>,
+    recipes: Vec<RecipeSuggestion>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for WhatCanICookTool {
+    type Args = WhatCanICookArgs;
+    type Output = ToolOutput<CookingSuggestions>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: WhatCanICookArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let Ok(kb) = get_knowledge_base(RECIPES_KB, &self.pool).await else {
+            return Ok(ToolOutput::Error(format!(
+                "There is no {} knowledge base to search",
+                RECIPES_KB
+            )));
+        };
+        let items = get_pantry_items(&self.pool).await?;
+        if items.is_empty() {
+            return Ok(ToolOutput::Error("The pantry is empty".to_string()));
+        }
+        let before = expiry_cutoff(
+            context,
+            args.expiring_within_days.unwrap_or(EXPIRING_SOON_DAYS),
+        );
+        let expiring: Vec<PantryItem> = items
+            .iter()
+            .filter(|item| {
+                item.expires_on
+                    .is_some_and(|expires_on| expires_on <= before)
+            })
+            .cloned()
+            .collect();
+        //search with what needs using first, falling back to everything on hand
+        let search_items = if expiring.is_empty() {
+            &items
+        } else {
+            &expiring
+        };
+        let query = format!(
+            "recipe with {}",
+            search_items
+                .iter()
+                .map(|item| item.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let embeddings = get_embeddings(&self.client, &query).await?;
+        let recipes = get_docs_with_similar_content(
+            kb.id,
+            embeddings,
+            args.num_results.unwrap_or(3),
+            &self.pool,
+        )
+        .await?;
+        let mut recipes: Vec<RecipeSuggestion> = recipes
+            .into_iter()
+            .map(|recipe| RecipeSuggestion {
+                uses_expiring: expiring
+                    .iter()
+                    .filter(|item| mentions(&recipe, &item.name))
+                    .map(|item| item.name.clone())
+                    .collect(),
+                uses: items
+                    .iter()
+                    .filter(|item| mentions(&recipe, &item.name))
+                    .map(|item| item.name.clone())
+                    .collect(),
+                recipe,
+            })
+            .collect();
+        recipes.sort_by_key(|recipe| {
+            std::cmp::Reverse((recipe.uses_expiring.len(), recipe.uses.len()))
+        });
+        Ok(ToolOutput::Result(CookingSuggestions { expiring, recipes }))
+    }
+}
+typed_tool!(WhatCanICookTool);
+
+#[cfg(test)]
+mod tests {
+    use super::{PantryItem, kept_in, mentions, to_stock_unit};
+    use uuid::Uuid;
+
+    #[test]
+    fn it_converts_to_the_stock_unit() {
+        assert_eq!(to_stock_unit("eggs", 2.0, None, None), Ok(2.0));
+        assert_eq!(
+            to_stock_unit("milk", 1.0, Some("cups"), Some("cup")),
+            Ok(1.0)
+        );
+        assert_eq!(
+            to_stock_unit("beans", 2.0, Some("cans"), Some("can")),
+            Ok(2.0)
+        );
+        let grams = to_stock_unit("flour", 1.0, Some("cup"), Some("g")).unwrap();
+        assert!((grams - 125.16).abs() < 0.01);
+        assert!(to_stock_unit("eggs", 2.0, Some("g"), None).is_err());
+        assert!(to_stock_unit("rice", 2.0, Some("cm"), Some("g")).is_err());
+    }
+
+    #[test]
+    fn it_finds_whole_word_mentions() {
+        let recipe = "Shakshuka: simmer the tomatoes, then crack in 4 eggs.";
+        assert!(mentions(recipe, "Egg"));
+        assert!(mentions(recipe, "tomato"));
+        assert!(!mentions("Roasted eggplant", "eggs"));
+    }
+
+    #[test]
+    fn it_only_merges_items_kept_in_the_same_place() {
+        let item = |location: Option<&str>| PantryItem {
+            id: Uuid::new_v4(),
+            name: "peas".to_string(),
+            quantity: 1.0,
+            unit: None,
+            location: location.map(str::to_string),
+            expires_on: None,
+            updated_at: chrono::Utc::now(),
+        };
+        assert!(kept_in(&item(Some("Freezer")), Some("freezer")));
+        assert!(kept_in(&item(Some("freezer")), None));
+        assert!(!kept_in(&item(Some("pantry")), Some("freezer")));
+        assert!(!kept_in(&item(None), Some("freezer")));
+    }
+}
@@ -29,3 +29,19 @@ You are a friendly, patient, and encouraging homework tutor specifically for gra
 
 Always maintain a supportive and accessible tone. Your responses should be easy for a child to understand. End your responses with a question that prompts the student to take the next step and think for themselves.
 "#;
+
+pub const RECIPE_EXTRACTION_PROMPT: &str = r#"
+Extract the recipe from the text the user sends. Reply with only a JSON object, no other text, in this shape:
+{"title": "Blueberry Muffins", "servings": 12, "total_minutes": 40, "ingredients": [{"name": "all-purpose flour", "quantity": 2, "unit": "cup", "note": "sifted"}], "steps": ["Whisk together the flour and salt."], "tags": ["baking", "breakfast"]}
+
+* quantity is a number, so write 1.5 rather than "1 1/2". Use null when there is no amount, eg "salt to taste".
+* unit is null for things that are counted, eg 2 eggs.
+* note holds preparation details, eg "softened" or "finely chopped", otherwise null.
+* servings is the number of servings or pieces the recipe makes, or null if the text doesn't say.
+* total_minutes is the preparation and cooking time together, or null if the text doesn't say.
+* steps are the instructions in order, one per entry, without the numbering.
+* tags are a few short lowercase words for the course, cuisine or diet, eg "dessert", "mexican", "gluten-free".
+* Leave out stories, tips and variations that aren't part of the recipe itself.
+
+If the text doesn't contain a recipe, reply with null.
+"#;
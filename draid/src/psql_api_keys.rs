@@ -0,0 +1,82 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::chrono};
+use uuid::Uuid;
+
+//the key itself is only in the response when it's created
+#[derive(Serialize, Object)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Deserialize, Object)]
+pub struct ApiKeyRequest {
+    pub name: String,
+}
+
+pub async fn create_api_key(
+    username_id: &Uuid,
+    name: &str,
+    key_hash: &str,
+    pool: &PgPool,
+) -> sqlx::Result<ApiKey> {
+    sqlx::query_as!(
+        ApiKey,
+        r#"
+        INSERT INTO api_keys (id, username_id, name, key_hash)
+        VALUES (gen_random_uuid(), $1, $2, $3)
+        RETURNING id, name, created_at, last_used_at
+        "#,
+        username_id,
+        name,
+        key_hash
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn get_api_keys(username_id: &Uuid, pool: &PgPool) -> sqlx::Result<Vec<ApiKey>> {
+    sqlx::query_as!(
+        ApiKey,
+        r#"
+        SELECT id, name, created_at, last_used_at
+        FROM api_keys WHERE username_id=$1
+        ORDER BY created_at
+        "#,
+        username_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+//returns false if the user has no such key
+pub async fn delete_api_key(id: &Uuid, username_id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM api_keys WHERE id=$1 AND username_id=$2
+        "#,
+        id,
+        username_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+//the username the key belongs to, marking the key as used
+pub async fn use_api_key(key_hash: &str, pool: &PgPool) -> sqlx::Result<Option<String>> {
+    let user = sqlx::query!(
+        r#"
+        UPDATE api_keys SET last_used_at=NOW()
+        FROM users WHERE users.id=api_keys.username_id AND api_keys.key_hash=$1
+        RETURNING users.username
+        "#,
+        key_hash
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(user.map(|user| user.username))
+}
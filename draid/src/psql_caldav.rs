@@ -0,0 +1,248 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::chrono};
+use uuid::Uuid;
+
+//the password is never sent back
+#[derive(Serialize, Object, Clone)]
+pub struct CalDavAccount {
+    pub id: Uuid,
+    pub url: String,
+    pub username: String,
+    pub shared: bool,
+    pub last_synced_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Deserialize, Object)]
+pub struct CalDavAccountRequest {
+    //the calendar collection, eg http://localhost:5232/sam/calendar/
+    pub url: String,
+    pub username: String,
+    pub password: String,
+    //events found on a shared calendar are for the whole household
+    #[oai(default)]
+    pub shared: bool,
+}
+
+pub struct CalDavCredentials {
+    pub id: Uuid,
+    pub username_id: Uuid,
+    pub url: String,
+    pub username: String,
+    //sealed, see secrets.rs
+    pub password: String,
+    pub shared: bool,
+}
+
+pub struct CalDavLink {
+    pub href: String,
+    //none once the event is deleted here
+    pub event_id: Option<Uuid>,
+    pub etag: Option<String>,
+    //whether the event was edited here since it was last synced
+    pub local_changed: bool,
+}
+
+pub async fn get_caldav_account(
+    username_id: &Uuid,
+    pool: &PgPool,
+) -> sqlx::Result<Option<CalDavAccount>> {
+    sqlx::query_as!(
+        CalDavAccount,
+        r#"
+        SELECT id, url, username, shared, last_synced_at, last_error
+        FROM caldav_accounts WHERE username_id=$1
+        "#,
+        username_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+//a different calendar starts over, events are matched to the new one by uid. the
+//password is stored as given, so it is sealed first
+pub async fn set_caldav_account(
+    username_id: &Uuid,
+    account: &CalDavAccountRequest,
+    password: &str,
+    pool: &PgPool,
+) -> sqlx::Result<CalDavAccount> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM caldav_links USING caldav_accounts
+        WHERE caldav_links.account_id=caldav_accounts.id
+            AND caldav_accounts.username_id=$1 AND caldav_accounts.url<>$2
+        "#,
+        username_id,
+        &account.url
+    )
+    .execute(&mut *tx)
+    .await?;
+    let account = sqlx::query_as!(
+        CalDavAccount,
+        r#"
+        INSERT INTO caldav_accounts (id, username_id, url, username, password, shared)
+        VALUES (gen_random_uuid(), $1, $2, $3, $4, $5)
+        ON CONFLICT (username_id) DO UPDATE SET url=$2, username=$3, password=$4, shared=$5,
+            last_error=NULL
+        RETURNING id, url, username, shared, last_synced_at, last_error
+        "#,
+        username_id,
+        &account.url,
+        &account.username,
+        password,
+        account.shared
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(account)
+}
+
+//returns false if the user had no account. events stay on the calendar
+pub async fn delete_caldav_account(username_id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM caldav_accounts WHERE username_id=$1
+        "#,
+        username_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_caldav_credentials(pool: &PgPool) -> sqlx::Result<Vec<CalDavCredentials>> {
+    sqlx::query_as!(
+        CalDavCredentials,
+        r#"
+        SELECT id, username_id, url, username, password, shared FROM caldav_accounts
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_user_caldav_credentials(
+    username_id: &Uuid,
+    pool: &PgPool,
+) -> sqlx::Result<Option<CalDavCredentials>> {
+    sqlx::query_as!(
+        CalDavCredentials,
+        r#"
+        SELECT id, username_id, url, username, password, shared FROM caldav_accounts
+        WHERE username_id=$1
+        "#,
+        username_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn set_caldav_password(
+    account_id: &Uuid,
+    password: &str,
+    pool: &PgPool,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE caldav_accounts SET password=$2 WHERE id=$1
+        "#,
+        account_id,
+        password
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_caldav_status(
+    account_id: &Uuid,
+    error: Option<&str>,
+    pool: &PgPool,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE caldav_accounts SET last_synced_at=NOW(), last_error=$2 WHERE id=$1
+        "#,
+        account_id,
+        error
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_caldav_links(account_id: &Uuid, pool: &PgPool) -> sqlx::Result<Vec<CalDavLink>> {
+    sqlx::query_as!(
+        CalDavLink,
+        r#"
+        SELECT caldav_links.href, caldav_links.event_id, caldav_links.etag,
+            COALESCE(events.updated_at > caldav_links.synced_at, FALSE) as "local_changed!"
+        FROM caldav_links LEFT JOIN events ON events.id=caldav_links.event_id
+        WHERE caldav_links.account_id=$1
+        "#,
+        account_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn set_caldav_link(
+    account_id: &Uuid,
+    href: &str,
+    event_id: &Uuid,
+    etag: Option<&str>,
+    pool: &PgPool,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO caldav_links (account_id, href, event_id, etag) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (account_id, href) DO UPDATE SET event_id=$3, etag=$4, synced_at=NOW()
+        "#,
+        account_id,
+        href,
+        event_id,
+        etag
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_caldav_link(account_id: &Uuid, href: &str, pool: &PgPool) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM caldav_links WHERE account_id=$1 AND href=$2
+        "#,
+        account_id,
+        href
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+//events the user attends and the household's that aren't on the server yet
+pub async fn get_unlinked_events(
+    account_id: &Uuid,
+    username_id: &Uuid,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<Uuid>> {
+    let events = sqlx::query!(
+        r#"
+        SELECT id FROM events
+        WHERE (EXISTS(SELECT 1 FROM event_attendees WHERE event_id=events.id AND username_id=$2)
+            OR NOT EXISTS(SELECT 1 FROM event_attendees WHERE event_id=events.id))
+            AND NOT EXISTS(SELECT 1 FROM caldav_links WHERE account_id=$1 AND event_id=events.id)
+        ORDER BY starts_at
+        "#,
+        account_id,
+        username_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(events.into_iter().map(|event| event.id).collect())
+}
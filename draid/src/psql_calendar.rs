@@ -0,0 +1,354 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::chrono};
+use uuid::Uuid;
+
+#[derive(Serialize, sqlx::FromRow, Object, Clone, Debug, PartialEq)]
+pub struct EventAttendee {
+    pub username_id: Uuid,
+    pub username: String,
+}
+
+#[derive(Serialize, Object, Clone)]
+pub struct Event {
+    pub id: Uuid,
+    pub uid: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub starts_at: chrono::DateTime<chrono::Utc>,
+    pub ends_at: chrono::DateTime<chrono::Utc>,
+    pub all_day: bool,
+    pub rrule: Option<String>,
+    pub exdates: Vec<chrono::DateTime<chrono::Utc>>,
+    pub timezone: String,
+    //nobody means the whole household
+    pub attendees: Vec<EventAttendee>,
+}
+
+#[derive(Deserialize, Object)]
+pub struct EventRequest {
+    pub title: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub starts_at: chrono::DateTime<chrono::Utc>,
+    pub ends_at: chrono::DateTime<chrono::Utc>,
+    #[oai(default)]
+    pub all_day: bool,
+    //RFC 5545 recurrence rule, eg FREQ=WEEKLY;BYDAY=SA
+    pub rrule: Option<String>,
+    //IANA name recurrences are expanded in, defaults to the user's timezone
+    pub timezone: Option<String>,
+    #[oai(default)]
+    pub attendees: Vec<Uuid>,
+}
+
+//fields that aren't given are left unchanged
+#[derive(Deserialize, Object)]
+pub struct EventUpdate {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub starts_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub ends_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub all_day: Option<bool>,
+    pub rrule: Option<String>,
+    pub timezone: Option<String>,
+    pub attendees: Option<Vec<Uuid>>,
+}
+
+//an event checked and ready to be saved
+pub struct EventData {
+    pub uid: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub starts_at: chrono::DateTime<chrono::Utc>,
+    pub ends_at: chrono::DateTime<chrono::Utc>,
+    pub all_day: bool,
+    pub rrule: Option<String>,
+    pub exdates: Vec<chrono::DateTime<chrono::Utc>>,
+    pub timezone: String,
+    pub attendees: Vec<Uuid>,
+}
+
+struct EventRow {
+    id: Uuid,
+    uid: String,
+    title: String,
+    description: Option<String>,
+    location: Option<String>,
+    starts_at: chrono::DateTime<chrono::Utc>,
+    ends_at: chrono::DateTime<chrono::Utc>,
+    all_day: bool,
+    rrule: Option<String>,
+    exdates: Vec<chrono::DateTime<chrono::Utc>>,
+    timezone: String,
+}
+
+struct EventAttendeeRow {
+    event_id: Uuid,
+    username_id: Uuid,
+    username: String,
+}
+
+async fn with_attendees(events: Vec<EventRow>, pool: &PgPool) -> sqlx::Result<Vec<Event>> {
+    let ids: Vec<Uuid> = events.iter().map(|event| event.id).collect();
+    let attendees = sqlx::query_as!(
+        EventAttendeeRow,
+        r#"
+        SELECT event_attendees.event_id, event_attendees.username_id, users.username
+        FROM event_attendees JOIN users ON users.id=event_attendees.username_id
+        WHERE event_attendees.event_id = ANY($1)
+        ORDER BY users.username
+        "#,
+        &ids
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(events
+        .into_iter()
+        .map(|event| Event {
+            attendees: attendees
+                .iter()
+                .filter(|attendee| attendee.event_id == event.id)
+                .map(|attendee| EventAttendee {
+                    username_id: attendee.username_id,
+                    username: attendee.username.clone(),
+                })
+                .collect(),
+            id: event.id,
+            uid: event.uid,
+            title: event.title,
+            description: event.description,
+            location: event.location,
+            starts_at: event.starts_at,
+            ends_at: event.ends_at,
+            all_day: event.all_day,
+            rrule: event.rrule,
+            exdates: event.exdates,
+            timezone: event.timezone,
+        })
+        .collect())
+}
+
+pub async fn get_event(id: &Uuid, pool: &PgPool) -> sqlx::Result<Event> {
+    let event = sqlx::query_as!(
+        EventRow,
+        r#"
+        SELECT id, uid, title, description, location, starts_at, ends_at, all_day, rrule,
+            exdates, timezone
+        FROM events WHERE id=$1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+    let mut events = with_attendees(vec![event], pool).await?;
+    Ok(events.remove(0))
+}
+
+pub async fn get_event_by_uid(uid: &str, pool: &PgPool) -> sqlx::Result<Option<Event>> {
+    let event = sqlx::query_as!(
+        EventRow,
+        r#"
+        SELECT id, uid, title, description, location, starts_at, ends_at, all_day, rrule,
+            exdates, timezone
+        FROM events WHERE uid=$1
+        "#,
+        uid
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(with_attendees(event.into_iter().collect(), pool)
+        .await?
+        .pop())
+}
+
+//events that may have an occurrence between the two times: recurring events
+//that started before the end and single events overlapping the range
+pub async fn get_events_between(
+    from: &chrono::DateTime<chrono::Utc>,
+    to: &chrono::DateTime<chrono::Utc>,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<Event>> {
+    let events = sqlx::query_as!(
+        EventRow,
+        r#"
+        SELECT id, uid, title, description, location, starts_at, ends_at, all_day, rrule,
+            exdates, timezone
+        FROM events WHERE starts_at < $2 AND (ends_at > $1 OR rrule IS NOT NULL)
+        ORDER BY starts_at
+        "#,
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await?;
+    with_attendees(events, pool).await
+}
+
+//events the user attends and those for the whole household
+pub async fn get_user_events(username_id: &Uuid, pool: &PgPool) -> sqlx::Result<Vec<Event>> {
+    let events = sqlx::query_as!(
+        EventRow,
+        r#"
+        SELECT id, uid, title, description, location, starts_at, ends_at, all_day, rrule,
+            exdates, timezone
+        FROM events
+        WHERE EXISTS(SELECT 1 FROM event_attendees WHERE event_id=events.id AND username_id=$1)
+            OR NOT EXISTS(SELECT 1 FROM event_attendees WHERE event_id=events.id)
+        ORDER BY starts_at
+        "#,
+        username_id
+    )
+    .fetch_all(pool)
+    .await?;
+    with_attendees(events, pool).await
+}
+
+async fn set_attendees(
+    event_id: &Uuid,
+    attendees: &[Uuid],
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM event_attendees WHERE event_id=$1
+        "#,
+        event_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO event_attendees (event_id, username_id)
+        SELECT $1, username_id FROM UNNEST($2::UUID[]) AS username_id
+        ON CONFLICT DO NOTHING
+        "#,
+        event_id,
+        attendees
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+//an event with the same uid is replaced, so importing a calendar twice updates it
+pub async fn upsert_event(
+    event: &EventData,
+    created_by: Option<&Uuid>,
+    pool: &PgPool,
+) -> sqlx::Result<Uuid> {
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO events (id, uid, title, description, location, starts_at, ends_at, all_day,
+            rrule, exdates, timezone, created_by)
+        VALUES (gen_random_uuid(), $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        ON CONFLICT (uid) DO UPDATE SET title=$2, description=$3, location=$4, starts_at=$5,
+            ends_at=$6, all_day=$7, rrule=$8, exdates=$9, timezone=$10, updated_at=NOW()
+        RETURNING id
+        "#,
+        &event.uid,
+        &event.title,
+        event.description.as_ref(),
+        event.location.as_ref(),
+        event.starts_at,
+        event.ends_at,
+        event.all_day,
+        event.rrule.as_ref(),
+        &event.exdates,
+        &event.timezone,
+        created_by
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    set_attendees(&result.id, &event.attendees, &mut tx).await?;
+    tx.commit().await?;
+    Ok(result.id)
+}
+
+//returns false if there was no such event
+pub async fn update_event(id: &Uuid, update: &EventUpdate, pool: &PgPool) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query!(
+        r#"
+        UPDATE events
+        SET title=COALESCE($2, title), description=COALESCE($3, description),
+            location=COALESCE($4, location), starts_at=COALESCE($5, starts_at),
+            ends_at=COALESCE($6, ends_at), all_day=COALESCE($7, all_day),
+            rrule=COALESCE($8, rrule), timezone=COALESCE($9, timezone), updated_at=NOW()
+        WHERE id=$1
+        "#,
+        id,
+        update.title.as_ref(),
+        update.description.as_ref(),
+        update.location.as_ref(),
+        update.starts_at,
+        update.ends_at,
+        update.all_day,
+        update.rrule.as_ref(),
+        update.timezone.as_ref()
+    )
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    if let Some(attendees) = &update.attendees {
+        set_attendees(id, attendees, &mut tx).await?;
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+//returns false if there was no such event
+pub async fn delete_event(id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM event_attendees WHERE event_id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM events WHERE id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_feed_user(token: &str, pool: &PgPool) -> sqlx::Result<Option<Uuid>> {
+    let feed = sqlx::query!(
+        r#"
+        SELECT username_id FROM calendar_feeds WHERE token=$1
+        "#,
+        token
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(feed.map(|feed| feed.username_id))
+}
+
+//a new token replaces the old one, so a leaked feed url stops working
+pub async fn set_feed_token(username_id: &Uuid, token: &str, pool: &PgPool) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO calendar_feeds (username_id, token) VALUES ($1, $2)
+        ON CONFLICT (username_id) DO UPDATE SET token=$2, created_at=NOW()
+        "#,
+        username_id,
+        token
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,310 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::chrono};
+use uuid::Uuid;
+
+#[derive(Serialize, sqlx::FromRow, Object, Clone, Debug, PartialEq)]
+pub struct ChoreMember {
+    pub username_id: Uuid,
+    pub username: String,
+}
+
+#[derive(Serialize, Object, Clone)]
+pub struct Chore {
+    pub id: Uuid,
+    pub name: String,
+    pub frequency_days: i32,
+    pub points: i32,
+    //in rotation order
+    pub members: Vec<ChoreMember>,
+}
+
+#[derive(Deserialize, Object)]
+pub struct ChoreRequest {
+    pub name: String,
+    //eg 1 for daily and 7 for weekly
+    pub frequency_days: i32,
+    pub points: i32,
+    //users taking turns, in rotation order
+    #[oai(default)]
+    pub members: Vec<Uuid>,
+}
+
+//fields that aren't given are left unchanged
+#[derive(Deserialize, Object)]
+pub struct ChoreUpdate {
+    pub name: Option<String>,
+    pub frequency_days: Option<i32>,
+    pub points: Option<i32>,
+    pub members: Option<Vec<Uuid>>,
+}
+
+#[derive(Serialize, sqlx::FromRow, Object, Clone)]
+pub struct ChoreCompletion {
+    pub id: Uuid,
+    pub chore_id: Uuid,
+    pub username_id: Uuid,
+    pub points: i32,
+    pub completed_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize, Object)]
+pub struct ChoreCompletionRequest {
+    pub username_id: Uuid,
+}
+
+//when each user last did each chore
+pub struct LastDone {
+    pub chore_id: Uuid,
+    pub username_id: Uuid,
+    pub username: String,
+    pub completed_at: chrono::DateTime<chrono::Utc>,
+}
+
+struct ChoreRow {
+    id: Uuid,
+    name: String,
+    frequency_days: i32,
+    points: i32,
+}
+
+struct ChoreMemberRow {
+    chore_id: Uuid,
+    username_id: Uuid,
+    username: String,
+}
+
+pub async fn get_chores(pool: &PgPool) -> sqlx::Result<Vec<Chore>> {
+    let chores = sqlx::query_as!(
+        ChoreRow,
+        r#"
+        SELECT id, name, frequency_days, points FROM chores ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+    let members = sqlx::query_as!(
+        ChoreMemberRow,
+        r#"
+        SELECT chore_members.chore_id, chore_members.username_id, users.username
+        FROM chore_members JOIN users ON users.id=chore_members.username_id
+        ORDER BY chore_members.position
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(chores
+        .into_iter()
+        .map(|chore| Chore {
+            members: members
+                .iter()
+                .filter(|member| member.chore_id == chore.id)
+                .map(|member| ChoreMember {
+                    username_id: member.username_id,
+                    username: member.username.clone(),
+                })
+                .collect(),
+            id: chore.id,
+            name: chore.name,
+            frequency_days: chore.frequency_days,
+            points: chore.points,
+        })
+        .collect())
+}
+
+pub async fn get_chore(id: &Uuid, pool: &PgPool) -> sqlx::Result<Chore> {
+    let chore = sqlx::query_as!(
+        ChoreRow,
+        r#"
+        SELECT id, name, frequency_days, points FROM chores WHERE id=$1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+    let members = sqlx::query_as!(
+        ChoreMember,
+        r#"
+        SELECT chore_members.username_id, users.username
+        FROM chore_members JOIN users ON users.id=chore_members.username_id
+        WHERE chore_members.chore_id=$1
+        ORDER BY chore_members.position
+        "#,
+        id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(Chore {
+        id: chore.id,
+        name: chore.name,
+        frequency_days: chore.frequency_days,
+        points: chore.points,
+        members,
+    })
+}
+
+async fn set_members(
+    chore_id: &Uuid,
+    members: &[Uuid],
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM chore_members WHERE chore_id=$1
+        "#,
+        chore_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    for (position, username_id) in members.iter().enumerate() {
+        sqlx::query!(
+            r#"
+            INSERT INTO chore_members (chore_id, username_id, position)
+            VALUES ($1, $2, $3)
+            "#,
+            chore_id,
+            username_id,
+            position as i32
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn create_chore(chore: &ChoreRequest, pool: &PgPool) -> sqlx::Result<Uuid> {
+    let mut tx = pool.begin().await?;
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO chores (id, name, frequency_days, points)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        id,
+        &chore.name,
+        chore.frequency_days,
+        chore.points
+    )
+    .execute(&mut *tx)
+    .await?;
+    set_members(&id, &chore.members, &mut tx).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+//returns false if there was no such chore
+pub async fn update_chore(id: &Uuid, update: &ChoreUpdate, pool: &PgPool) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query!(
+        r#"
+        UPDATE chores
+        SET name=COALESCE($2, name), frequency_days=COALESCE($3, frequency_days),
+            points=COALESCE($4, points)
+        WHERE id=$1
+        "#,
+        id,
+        update.name.as_ref(),
+        update.frequency_days,
+        update.points
+    )
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    if let Some(members) = &update.members {
+        set_members(id, members, &mut tx).await?;
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+//returns false if there was no such chore
+pub async fn delete_chore(id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM chore_completions WHERE chore_id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM chore_members WHERE chore_id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM chores WHERE id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_last_done(pool: &PgPool) -> sqlx::Result<Vec<LastDone>> {
+    sqlx::query_as!(
+        LastDone,
+        r#"
+        SELECT chore_completions.chore_id, chore_completions.username_id, users.username,
+            MAX(chore_completions.completed_at) AS "completed_at!"
+        FROM chore_completions JOIN users ON users.id=chore_completions.username_id
+        GROUP BY chore_completions.chore_id, chore_completions.username_id, users.username
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_chore_completions(pool: &PgPool) -> sqlx::Result<Vec<ChoreCompletion>> {
+    sqlx::query_as!(
+        ChoreCompletion,
+        r#"
+        SELECT id, chore_id, username_id, points, completed_at
+        FROM chore_completions ORDER BY completed_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+//the chore's points are earned as they are now
+pub async fn create_chore_completion(
+    chore_id: &Uuid,
+    username_id: &Uuid,
+    pool: &PgPool,
+) -> sqlx::Result<ChoreCompletion> {
+    sqlx::query_as!(
+        ChoreCompletion,
+        r#"
+        INSERT INTO chore_completions (id, chore_id, username_id, points)
+        SELECT gen_random_uuid(), id, $2, points FROM chores WHERE id=$1
+        RETURNING id, chore_id, username_id, points, completed_at
+        "#,
+        chore_id,
+        username_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+//returns false if there was no such completion
+pub async fn delete_chore_completion(id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM chore_completions WHERE id=$1
+        "#,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
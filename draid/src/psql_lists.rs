@@ -0,0 +1,194 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::chrono};
+use uuid::Uuid;
+
+#[derive(Serialize, sqlx::FromRow, Object, Clone)]
+pub struct List {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, sqlx::FromRow, Object, Clone)]
+pub struct ListItem {
+    pub id: Uuid,
+    pub list_id: Uuid,
+    pub name: String,
+    pub quantity: Option<String>,
+    pub checked: bool,
+    pub added_by: Option<Uuid>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Object)]
+pub struct ListWithItems {
+    pub id: Uuid,
+    pub name: String,
+    pub items: Vec<ListItem>,
+}
+
+#[derive(Deserialize, Object)]
+pub struct ListRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize, Object)]
+pub struct ListItemRequest {
+    pub name: String,
+    pub quantity: Option<String>,
+}
+
+//fields that aren't given are left unchanged
+#[derive(Deserialize, Object)]
+pub struct ListItemUpdate {
+    pub name: Option<String>,
+    pub quantity: Option<String>,
+    pub checked: Option<bool>,
+}
+
+pub async fn get_lists(pool: &PgPool) -> sqlx::Result<Vec<List>> {
+    sqlx::query_as!(
+        List,
+        r#"
+        SELECT id, name, created_at FROM lists ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_list(id: &Uuid, pool: &PgPool) -> sqlx::Result<List> {
+    sqlx::query_as!(
+        List,
+        r#"
+        SELECT id, name, created_at FROM lists WHERE id=$1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn create_list(name: &str, pool: &PgPool) -> sqlx::Result<List> {
+    sqlx::query_as!(
+        List,
+        r#"
+        INSERT INTO lists (id, name)
+        VALUES (gen_random_uuid(), $1)
+        RETURNING id, name, created_at
+        "#,
+        name
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn rename_list(id: &Uuid, name: &str, pool: &PgPool) -> sqlx::Result<List> {
+    sqlx::query_as!(
+        List,
+        r#"
+        UPDATE lists SET name=$2 WHERE id=$1
+        RETURNING id, name, created_at
+        "#,
+        id,
+        name
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn delete_list(id: &Uuid, pool: &PgPool) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM list_items WHERE list_id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM lists WHERE id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn get_list_items(list_id: &Uuid, pool: &PgPool) -> sqlx::Result<Vec<ListItem>> {
+    sqlx::query_as!(
+        ListItem,
+        r#"
+        SELECT id, list_id, name, quantity, checked, added_by, updated_at
+        FROM list_items WHERE list_id=$1
+        ORDER BY checked, created_at
+        "#,
+        list_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn create_list_item(
+    list_id: &Uuid,
+    item: &ListItemRequest,
+    added_by: &Uuid,
+    pool: &PgPool,
+) -> sqlx::Result<ListItem> {
+    sqlx::query_as!(
+        ListItem,
+        r#"
+        INSERT INTO list_items (id, list_id, name, quantity, added_by)
+        VALUES (gen_random_uuid(), $1, $2, $3, $4)
+        RETURNING id, list_id, name, quantity, checked, added_by, updated_at
+        "#,
+        list_id,
+        &item.name,
+        item.quantity.as_ref(),
+        added_by
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn update_list_item(
+    id: &Uuid,
+    update: &ListItemUpdate,
+    pool: &PgPool,
+) -> sqlx::Result<ListItem> {
+    sqlx::query_as!(
+        ListItem,
+        r#"
+        UPDATE list_items
+        SET name=COALESCE($2, name), quantity=COALESCE($3, quantity),
+            checked=COALESCE($4, checked), updated_at=NOW()
+        WHERE id=$1
+        RETURNING id, list_id, name, quantity, checked, added_by, updated_at
+        "#,
+        id,
+        update.name.as_ref(),
+        update.quantity.as_ref(),
+        update.checked
+    )
+    .fetch_one(pool)
+    .await
+}
+
+//returns the list the item was on, or None if there was no such item
+pub async fn delete_list_item(id: &Uuid, pool: &PgPool) -> sqlx::Result<Option<Uuid>> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM list_items WHERE id=$1
+        RETURNING list_id
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(deleted.map(|deleted| deleted.list_id))
+}
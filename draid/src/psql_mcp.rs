@@ -0,0 +1,185 @@
+use crate::config::{MCP, MCPType};
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::Json};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Serialize, sqlx::FromRow, Object)]
+pub struct BotResource {
+    pub id: Uuid,
+    pub bot: String,
+    pub server: String,
+    pub uri: String,
+}
+
+#[derive(Deserialize, Object)]
+pub struct BotResourceRequest {
+    pub server: String,
+    pub uri: String,
+}
+
+pub async fn get_bot_resources(bot: &str, pool: &PgPool) -> sqlx::Result<Vec<BotResource>> {
+    sqlx::query_as!(
+        BotResource,
+        r#"
+        SELECT id, bot, server, uri FROM bot_resources WHERE bot=$1
+        "#,
+        bot
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn attach_bot_resource(
+    bot: &str,
+    resource: &BotResourceRequest,
+    pool: &PgPool,
+) -> sqlx::Result<BotResource> {
+    sqlx::query_as!(
+        BotResource,
+        r#"
+        INSERT INTO bot_resources (id, bot, server, uri)
+        VALUES (gen_random_uuid(), $1, $2, $3)
+        RETURNING id, bot, server, uri
+        "#,
+        bot,
+        &resource.server,
+        &resource.uri
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn detach_bot_resource(bot: &str, id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM bot_resources WHERE bot=$1 AND id=$2
+        "#,
+        bot,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[derive(Serialize, sqlx::FromRow, Object)]
+pub struct McpServerDB {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub url: String,
+    pub mcp_type: MCPType,
+    pub include_tools: Vec<String>,
+    pub exclude_tools: Vec<String>,
+    pub tool_descriptions: Json<HashMap<String, String>>,
+}
+
+impl From<McpServerDB> for MCP {
+    fn from(server: McpServerDB) -> Self {
+        MCP {
+            name: server.name,
+            description: server.description,
+            url: server.url,
+            mcp_type: server.mcp_type,
+            include_tools: server.include_tools,
+            exclude_tools: server.exclude_tools,
+            tool_descriptions: server.tool_descriptions.0,
+        }
+    }
+}
+
+pub async fn get_mcp_servers(pool: &PgPool) -> sqlx::Result<Vec<McpServerDB>> {
+    sqlx::query_as!(
+        McpServerDB,
+        r#"
+        SELECT id, name, description, url, mcp_type as "mcp_type: MCPType",
+        include_tools, exclude_tools,
+        tool_descriptions as "tool_descriptions: Json<HashMap<String, String>>"
+        FROM mcp_servers
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn create_mcp_server(server: &MCP, pool: &PgPool) -> sqlx::Result<McpServerDB> {
+    sqlx::query_as!(
+        McpServerDB,
+        r#"
+        INSERT INTO mcp_servers
+        (id, name, description, url, mcp_type, include_tools, exclude_tools, tool_descriptions)
+        VALUES (gen_random_uuid(), $1, $2, $3, $4, $5, $6, $7)
+        RETURNING id, name, description, url, mcp_type as "mcp_type: MCPType",
+        include_tools, exclude_tools,
+        tool_descriptions as "tool_descriptions: Json<HashMap<String, String>>"
+        "#,
+        &server.name,
+        &server.description,
+        &server.url,
+        server.mcp_type.clone() as MCPType,
+        &server.include_tools,
+        &server.exclude_tools,
+        Json(&server.tool_descriptions) as _
+    )
+    .fetch_one(pool)
+    .await
+}
+
+//used when seeding from TOOL_CONFIG, so servers edited through the api are left alone
+pub async fn create_mcp_server_if_not_exists(server: &MCP, pool: &PgPool) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO mcp_servers
+        (id, name, description, url, mcp_type, include_tools, exclude_tools, tool_descriptions)
+        VALUES (gen_random_uuid(), $1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        &server.name,
+        &server.description,
+        &server.url,
+        server.mcp_type.clone() as MCPType,
+        &server.include_tools,
+        &server.exclude_tools,
+        Json(&server.tool_descriptions) as _
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn update_mcp_server(id: &Uuid, server: &MCP, pool: &PgPool) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE mcp_servers
+        SET name=$1, description=$2, url=$3, mcp_type=$4,
+        include_tools=$5, exclude_tools=$6, tool_descriptions=$7
+        WHERE id=$8
+        "#,
+        &server.name,
+        &server.description,
+        &server.url,
+        server.mcp_type.clone() as MCPType,
+        &server.include_tools,
+        &server.exclude_tools,
+        Json(&server.tool_descriptions) as _,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete_mcp_server(id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM mcp_servers WHERE id=$1
+        "#,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,309 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::chrono};
+use uuid::Uuid;
+
+#[derive(Serialize, sqlx::FromRow, Object, Clone)]
+pub struct MealPlanEntry {
+    pub id: Uuid,
+    pub plan_id: Uuid,
+    pub day: chrono::NaiveDate,
+    pub recipe_id: Option<Uuid>,
+    pub title: String,
+    pub servings: Option<i32>,
+}
+
+#[derive(Serialize, Object, Clone)]
+pub struct MealPlan {
+    pub id: Uuid,
+    pub week_start: chrono::NaiveDate,
+    pub dinners: i32,
+    pub restrictions: Vec<String>,
+    pub max_minutes: Option<i32>,
+    pub servings: Option<i32>,
+    pub entries: Vec<MealPlanEntry>,
+}
+
+//constraints a plan is proposed from
+#[derive(Deserialize, Object)]
+pub struct MealPlanRequest {
+    //any day of the week to plan, defaults to this week
+    pub week: Option<chrono::NaiveDate>,
+    pub dinners: i32,
+    //tags every recipe needs, eg vegetarian
+    #[oai(default)]
+    pub restrictions: Vec<String>,
+    pub max_minutes: Option<i32>,
+    //people to cook for, otherwise each recipe's own servings
+    pub servings: Option<i32>,
+}
+
+//a meal is either from the recipe box or just a title, eg "leftovers"
+#[derive(Deserialize, Object)]
+pub struct MealPlanEntryRequest {
+    pub day: chrono::NaiveDate,
+    pub recipe_id: Option<Uuid>,
+    pub title: Option<String>,
+    pub servings: Option<i32>,
+}
+
+//fields that aren't given are left unchanged. a title without a recipe makes
+//the meal one that isn't from the recipe box
+#[derive(Deserialize, Object)]
+pub struct MealPlanEntryUpdate {
+    pub day: Option<chrono::NaiveDate>,
+    pub recipe_id: Option<Uuid>,
+    pub title: Option<String>,
+    pub servings: Option<i32>,
+}
+
+pub struct PlannedMeal {
+    pub day: chrono::NaiveDate,
+    pub recipe_id: Option<Uuid>,
+    pub title: String,
+    pub servings: Option<i32>,
+}
+
+struct MealPlanRow {
+    id: Uuid,
+    week_start: chrono::NaiveDate,
+    dinners: i32,
+    restrictions: Vec<String>,
+    max_minutes: Option<i32>,
+    servings: Option<i32>,
+}
+
+async fn with_entries(plan: MealPlanRow, pool: &PgPool) -> sqlx::Result<MealPlan> {
+    let entries = sqlx::query_as!(
+        MealPlanEntry,
+        r#"
+        SELECT id, plan_id, day, recipe_id, title, servings
+        FROM meal_plan_entries WHERE plan_id=$1
+        ORDER BY day, title
+        "#,
+        plan.id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(MealPlan {
+        id: plan.id,
+        week_start: plan.week_start,
+        dinners: plan.dinners,
+        restrictions: plan.restrictions,
+        max_minutes: plan.max_minutes,
+        servings: plan.servings,
+        entries,
+    })
+}
+
+pub async fn get_meal_plan(id: &Uuid, pool: &PgPool) -> sqlx::Result<MealPlan> {
+    let plan = sqlx::query_as!(
+        MealPlanRow,
+        r#"
+        SELECT id, week_start, dinners, restrictions, max_minutes, servings
+        FROM meal_plans WHERE id=$1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+    with_entries(plan, pool).await
+}
+
+pub async fn get_meal_plan_for_week(
+    week_start: &chrono::NaiveDate,
+    pool: &PgPool,
+) -> sqlx::Result<Option<MealPlan>> {
+    let plan = sqlx::query_as!(
+        MealPlanRow,
+        r#"
+        SELECT id, week_start, dinners, restrictions, max_minutes, servings
+        FROM meal_plans WHERE week_start=$1
+        "#,
+        week_start
+    )
+    .fetch_optional(pool)
+    .await?;
+    match plan {
+        Some(plan) => Ok(Some(with_entries(plan, pool).await?)),
+        None => Ok(None),
+    }
+}
+
+//a new plan for a week replaces the one already there
+pub async fn replace_meal_plan(
+    week_start: &chrono::NaiveDate,
+    request: &MealPlanRequest,
+    meals: &[PlannedMeal],
+    pool: &PgPool,
+) -> sqlx::Result<Uuid> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM meal_plan_entries
+        WHERE plan_id IN (SELECT id FROM meal_plans WHERE week_start=$1)
+        "#,
+        week_start
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM meal_plans WHERE week_start=$1
+        "#,
+        week_start
+    )
+    .execute(&mut *tx)
+    .await?;
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO meal_plans (id, week_start, dinners, restrictions, max_minutes, servings)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        id,
+        week_start,
+        request.dinners,
+        &request.restrictions,
+        request.max_minutes,
+        request.servings
+    )
+    .execute(&mut *tx)
+    .await?;
+    for meal in meals {
+        sqlx::query!(
+            r#"
+            INSERT INTO meal_plan_entries (id, plan_id, day, recipe_id, title, servings)
+            VALUES (gen_random_uuid(), $1, $2, $3, $4, $5)
+            "#,
+            id,
+            meal.day,
+            meal.recipe_id,
+            &meal.title,
+            meal.servings
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(id)
+}
+
+//returns false if there was no such plan
+pub async fn delete_meal_plan(id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM meal_plan_entries WHERE plan_id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM meal_plans WHERE id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn create_meal_plan_entry(
+    plan_id: &Uuid,
+    meal: &PlannedMeal,
+    pool: &PgPool,
+) -> sqlx::Result<MealPlanEntry> {
+    sqlx::query_as!(
+        MealPlanEntry,
+        r#"
+        INSERT INTO meal_plan_entries (id, plan_id, day, recipe_id, title, servings)
+        VALUES (gen_random_uuid(), $1, $2, $3, $4, $5)
+        RETURNING id, plan_id, day, recipe_id, title, servings
+        "#,
+        plan_id,
+        meal.day,
+        meal.recipe_id,
+        &meal.title,
+        meal.servings
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn update_meal_plan_entry(
+    id: &Uuid,
+    update: &MealPlanEntryUpdate,
+    pool: &PgPool,
+) -> sqlx::Result<MealPlanEntry> {
+    sqlx::query_as!(
+        MealPlanEntry,
+        r#"
+        UPDATE meal_plan_entries
+        SET day=COALESCE($2, day),
+            recipe_id=CASE WHEN $4::TEXT IS NULL THEN COALESCE($3, recipe_id) ELSE $3 END,
+            title=COALESCE($4, title), servings=COALESCE($5, servings)
+        WHERE id=$1
+        RETURNING id, plan_id, day, recipe_id, title, servings
+        "#,
+        id,
+        update.day,
+        update.recipe_id,
+        update.title.as_ref(),
+        update.servings
+    )
+    .fetch_one(pool)
+    .await
+}
+
+//what is planned for a day of a plan, none clears the day
+pub async fn replace_meal_plan_day(
+    plan_id: &Uuid,
+    day: &chrono::NaiveDate,
+    meal: Option<&PlannedMeal>,
+    pool: &PgPool,
+) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM meal_plan_entries WHERE plan_id=$1 AND day=$2
+        "#,
+        plan_id,
+        day
+    )
+    .execute(&mut *tx)
+    .await?;
+    if let Some(meal) = meal {
+        sqlx::query!(
+            r#"
+            INSERT INTO meal_plan_entries (id, plan_id, day, recipe_id, title, servings)
+            VALUES (gen_random_uuid(), $1, $2, $3, $4, $5)
+            "#,
+            plan_id,
+            meal.day,
+            meal.recipe_id,
+            &meal.title,
+            meal.servings
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+//returns false if there was no such entry
+pub async fn delete_meal_plan_entry(id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM meal_plan_entries WHERE id=$1
+        "#,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
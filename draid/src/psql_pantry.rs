@@ -0,0 +1,110 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::chrono};
+use uuid::Uuid;
+
+#[derive(Serialize, sqlx::FromRow, Object, Clone)]
+pub struct PantryItem {
+    pub id: Uuid,
+    pub name: String,
+    pub quantity: f64,
+    pub unit: Option<String>,
+    //fridge, freezer, pantry...
+    pub location: Option<String>,
+    pub expires_on: Option<chrono::NaiveDate>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize, Object)]
+pub struct PantryItemRequest {
+    pub name: String,
+    pub quantity: f64,
+    pub unit: Option<String>,
+    pub location: Option<String>,
+    pub expires_on: Option<chrono::NaiveDate>,
+}
+
+pub async fn get_pantry_items(pool: &PgPool) -> sqlx::Result<Vec<PantryItem>> {
+    sqlx::query_as!(
+        PantryItem,
+        r#"
+        SELECT id, name, quantity, unit, location, expires_on, updated_at
+        FROM pantry_items ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+//items expiring on or before the date, soonest first. includes already expired items
+pub async fn get_expiring_pantry_items(
+    before: &chrono::NaiveDate,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<PantryItem>> {
+    sqlx::query_as!(
+        PantryItem,
+        r#"
+        SELECT id, name, quantity, unit, location, expires_on, updated_at
+        FROM pantry_items WHERE expires_on <= $1
+        ORDER BY expires_on
+        "#,
+        before
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn create_pantry_item(
+    item: &PantryItemRequest,
+    pool: &PgPool,
+) -> sqlx::Result<PantryItem> {
+    sqlx::query_as!(
+        PantryItem,
+        r#"
+        INSERT INTO pantry_items (id, name, quantity, unit, location, expires_on)
+        VALUES (gen_random_uuid(), $1, $2, $3, $4, $5)
+        RETURNING id, name, quantity, unit, location, expires_on, updated_at
+        "#,
+        &item.name,
+        item.quantity,
+        item.unit.as_ref(),
+        item.location.as_ref(),
+        item.expires_on
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn update_pantry_quantity(
+    id: &Uuid,
+    quantity: f64,
+    expires_on: Option<chrono::NaiveDate>,
+    pool: &PgPool,
+) -> sqlx::Result<PantryItem> {
+    sqlx::query_as!(
+        PantryItem,
+        r#"
+        UPDATE pantry_items
+        SET quantity=$2, expires_on=$3, updated_at=NOW()
+        WHERE id=$1
+        RETURNING id, name, quantity, unit, location, expires_on, updated_at
+        "#,
+        id,
+        quantity,
+        expires_on
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn delete_pantry_item(id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM pantry_items WHERE id=$1
+        "#,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,227 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::chrono};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Object, Clone, Debug, PartialEq)]
+pub struct Ingredient {
+    pub name: String,
+    pub quantity: Option<f64>,
+    pub unit: Option<String>,
+    //eg "finely chopped"
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow, Object, Clone)]
+pub struct RecipeSummary {
+    pub id: Uuid,
+    pub title: String,
+    pub servings: Option<i32>,
+    pub total_minutes: Option<i32>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Object, Clone)]
+pub struct Recipe {
+    pub id: Uuid,
+    pub title: String,
+    pub servings: Option<i32>,
+    //preparation and cooking time together
+    pub total_minutes: Option<i32>,
+    pub ingredients: Vec<Ingredient>,
+    pub steps: Vec<String>,
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+//also the shape recipes are extracted into by the llm
+#[derive(Serialize, Deserialize, Object, Debug, PartialEq)]
+pub struct RecipeRequest {
+    pub title: String,
+    #[serde(default)]
+    pub servings: Option<i32>,
+    #[serde(default)]
+    pub total_minutes: Option<i32>,
+    #[serde(default)]
+    #[oai(default)]
+    pub ingredients: Vec<Ingredient>,
+    #[serde(default)]
+    #[oai(default)]
+    pub steps: Vec<String>,
+    #[serde(default)]
+    #[oai(default)]
+    pub tags: Vec<String>,
+}
+
+pub struct RecipeIngredientName {
+    pub recipe_id: Uuid,
+    pub name: String,
+}
+
+struct RecipeRow {
+    id: Uuid,
+    title: String,
+    servings: Option<i32>,
+    total_minutes: Option<i32>,
+    steps: Vec<String>,
+    tags: Vec<String>,
+    source: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub async fn get_recipes(pool: &PgPool) -> sqlx::Result<Vec<RecipeSummary>> {
+    sqlx::query_as!(
+        RecipeSummary,
+        r#"
+        SELECT id, title, servings, total_minutes, tags FROM recipes ORDER BY title
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_recipe(id: &Uuid, pool: &PgPool) -> sqlx::Result<Recipe> {
+    let recipe = sqlx::query_as!(
+        RecipeRow,
+        r#"
+        SELECT id, title, servings, total_minutes, steps, tags, source, created_at
+        FROM recipes WHERE id=$1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+    let ingredients = sqlx::query_as!(
+        Ingredient,
+        r#"
+        SELECT name, quantity, unit, note FROM recipe_ingredients
+        WHERE recipe_id=$1 ORDER BY position
+        "#,
+        id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(Recipe {
+        id: recipe.id,
+        title: recipe.title,
+        servings: recipe.servings,
+        total_minutes: recipe.total_minutes,
+        ingredients,
+        steps: recipe.steps,
+        tags: recipe.tags,
+        source: recipe.source,
+        created_at: recipe.created_at,
+    })
+}
+
+//ingredient names of every recipe, for searching by ingredient
+pub async fn get_recipe_ingredient_names(pool: &PgPool) -> sqlx::Result<Vec<RecipeIngredientName>> {
+    sqlx::query_as!(
+        RecipeIngredientName,
+        r#"
+        SELECT recipe_id, name FROM recipe_ingredients ORDER BY recipe_id, position
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn recipe_source_exists(source: &str, pool: &PgPool) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM recipes WHERE source=$1) AS "exists!"
+        "#,
+        source
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(result.exists)
+}
+
+//the recipe imported from a page, if there is one
+pub async fn find_recipe_by_source(source: &str, pool: &PgPool) -> sqlx::Result<Option<Uuid>> {
+    let result = sqlx::query!(
+        r#"
+        SELECT id FROM recipes WHERE source=$1 ORDER BY created_at LIMIT 1
+        "#,
+        source
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(result.map(|row| row.id))
+}
+
+pub async fn create_recipe(
+    recipe: &RecipeRequest,
+    source: Option<&str>,
+    pool: &PgPool,
+) -> sqlx::Result<Uuid> {
+    let mut tx = pool.begin().await?;
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO recipes (id, title, servings, total_minutes, steps, tags, source)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        id,
+        &recipe.title,
+        recipe.servings,
+        recipe.total_minutes,
+        &recipe.steps,
+        &recipe.tags,
+        source
+    )
+    .execute(&mut *tx)
+    .await?;
+    for (position, ingredient) in recipe.ingredients.iter().enumerate() {
+        sqlx::query!(
+            r#"
+            INSERT INTO recipe_ingredients (id, recipe_id, position, name, quantity, unit, note)
+            VALUES (gen_random_uuid(), $1, $2, $3, $4, $5, $6)
+            "#,
+            id,
+            position as i32,
+            &ingredient.name,
+            ingredient.quantity,
+            ingredient.unit.as_ref(),
+            ingredient.note.as_ref()
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(id)
+}
+
+//returns false if there was no such recipe. planned meals keep their title
+//when the recipe is deleted
+pub async fn delete_recipe(id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        UPDATE meal_plan_entries SET recipe_id=NULL WHERE recipe_id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM recipe_ingredients WHERE recipe_id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM recipes WHERE id=$1
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(result.rows_affected() > 0)
+}
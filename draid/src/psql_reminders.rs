@@ -0,0 +1,111 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::chrono};
+use uuid::Uuid;
+
+#[derive(Serialize, sqlx::FromRow, Object, Clone)]
+pub struct Reminder {
+    pub id: Uuid,
+    pub username_id: Uuid,
+    pub message: String,
+    pub starts_at: chrono::DateTime<chrono::Utc>,
+    pub due_at: chrono::DateTime<chrono::Utc>,
+    pub rrule: Option<String>,
+    pub timezone: String,
+    pub fired_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Deserialize, Object)]
+pub struct ReminderRequest {
+    pub message: String,
+    pub due_at: chrono::DateTime<chrono::Utc>,
+    //RFC 5545 recurrence rule, eg FREQ=WEEKLY;BYDAY=MO
+    pub rrule: Option<String>,
+}
+
+//reminders that are still waiting to fire
+pub async fn get_reminders(username_id: &Uuid, pool: &PgPool) -> sqlx::Result<Vec<Reminder>> {
+    sqlx::query_as!(
+        Reminder,
+        r#"
+        SELECT id, username_id, message, starts_at, due_at, rrule, timezone, fired_at
+        FROM reminders WHERE username_id=$1 AND NOT done
+        ORDER BY due_at
+        "#,
+        username_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn create_reminder(
+    username_id: &Uuid,
+    message: &str,
+    starts_at: &chrono::DateTime<chrono::Utc>,
+    rrule: Option<&str>,
+    timezone: &str,
+    pool: &PgPool,
+) -> sqlx::Result<Reminder> {
+    sqlx::query_as!(
+        Reminder,
+        r#"
+        INSERT INTO reminders (id, username_id, message, starts_at, due_at, rrule, timezone)
+        VALUES (gen_random_uuid(), $1, $2, $3, $3, $4, $5)
+        RETURNING id, username_id, message, starts_at, due_at, rrule, timezone, fired_at
+        "#,
+        username_id,
+        message,
+        starts_at,
+        rrule,
+        timezone
+    )
+    .fetch_one(pool)
+    .await
+}
+
+//returns false if the reminder doesn't exist or belongs to someone else
+pub async fn delete_reminder(id: &Uuid, username_id: &Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM reminders WHERE id=$1 AND username_id=$2
+        "#,
+        id,
+        username_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_due_reminders(pool: &PgPool) -> sqlx::Result<Vec<Reminder>> {
+    sqlx::query_as!(
+        Reminder,
+        r#"
+        SELECT id, username_id, message, starts_at, due_at, rrule, timezone, fired_at
+        FROM reminders WHERE NOT done AND due_at <= NOW()
+        ORDER BY due_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+//next_due is None once a reminder has no occurrences left
+pub async fn mark_reminder_fired(
+    id: &Uuid,
+    next_due: Option<chrono::DateTime<chrono::Utc>>,
+    pool: &PgPool,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE reminders
+        SET fired_at=NOW(), due_at=COALESCE($2, due_at), done=($2::timestamptz IS NULL)
+        WHERE id=$1
+        "#,
+        id,
+        next_due
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use crate::psql_users::Role;
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+//grants access to every tool matching the glob to a role or a user
+#[derive(Serialize, sqlx::FromRow, Object, Clone)]
+pub struct ToolPermission {
+    pub id: Uuid,
+    pub tool: String,
+    pub role: Option<Role>,
+    pub username_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, Object)]
+pub struct ToolPermissionRequest {
+    pub tool: String,
+    pub role: Option<Role>,
+    pub username_id: Option<Uuid>,
+}
+
+pub async fn get_tool_permissions(pool: &PgPool) -> sqlx::Result<Vec<ToolPermission>> {
+    sqlx::query_as!(
+        ToolPermission,
+        r#"
+        SELECT id, tool, role as "role: Role", username_id FROM tool_permissions
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn create_tool_permission(
+    permission: &ToolPermissionRequest,
+    pool: &PgPool,
+) -> sqlx::Result<ToolPermission> {
+    sqlx::query_as!(
+        ToolPermission,
+        r#"
+        INSERT INTO tool_permissions (id, tool, role, username_id)
+        VALUES (gen_random_uuid(), $1, $2, $3)
+        RETURNING id, tool, role as "role: Role", username_id
+        "#,
+        &permission.tool,
+        permission.role as Option<Role>,
+        permission.username_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn delete_tool_permission(id: &Uuid, pool: &PgPool) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM tool_permissions WHERE id=$1
+        "#,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
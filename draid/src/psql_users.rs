@@ -24,7 +24,7 @@ fn check_password(password: &str, hashed_password: &str) -> Result<(), Error> {
     Argon2::default().verify_password(password.as_bytes(), &parsed_hash)
 }
 
-#[derive(Serialize, Deserialize, Type, PartialEq, Eq, Hash, Enum)]
+#[derive(Serialize, Deserialize, Type, PartialEq, Eq, Hash, Enum, Clone, Copy, Debug)]
 #[serde(rename_all = "lowercase")]
 #[sqlx(type_name = "role_type", rename_all = "lowercase")]
 pub enum Role {
@@ -48,6 +48,7 @@ impl fmt::Display for Role {
 struct UserDB {
     id: Uuid,
     username: String,
+    timezone: String,
 }
 #[derive(sqlx::FromRow)]
 struct RoleDB {
@@ -56,7 +57,7 @@ struct RoleDB {
 }
 #[derive(Serialize, sqlx::FromRow, Object)]
 pub struct SessionDB {
-    id: Uuid,
+    pub id: Uuid,
     username_id: Uuid,
     session_start: chrono::DateTime<chrono::Utc>,
 }
@@ -66,12 +67,15 @@ pub struct UserResponse {
     pub id: Uuid,
     pub username: String,
     pub roles: Vec<Role>,
+    pub timezone: String,
 }
 #[derive(Deserialize, Object)]
 pub struct UserRequest {
     pub username: String,
     pub password: Option<String>,
     pub roles: Vec<Role>,
+    //IANA name, eg America/New_York. left unchanged when not given
+    pub timezone: Option<String>,
 }
 
 pub struct HashedPassword {
@@ -98,6 +102,7 @@ pub async fn create_init_admin_user(init_password: String, pool: &PgPool) -> sql
         username: "admin".to_string(),
         password: Some(init_password), //intentionally don't start up if not set
         roles: vec![Role::Admin],
+        timezone: None,
     };
     if get_user(&admin_user.username, pool).await.is_err() {
         create_user(&admin_user, pool).await?;
@@ -108,7 +113,7 @@ pub async fn get_user(username: &str, pool: &PgPool) -> sqlx::Result<UserRespons
     let user_db = sqlx::query_as!(
         UserDB,
         r#"
-        SELECT id, username FROM users where username=$1
+        SELECT id, username, timezone FROM users where username=$1
         "#,
         &username
     )
@@ -127,17 +132,21 @@ pub async fn get_user(username: &str, pool: &PgPool) -> sqlx::Result<UserRespons
         id: user_db.id,
         username: user_db.username,
         roles: roles.into_iter().map(|v| v.role).collect(),
+        timezone: user_db.timezone,
     })
 }
 
+//everything else of the user goes with them, shared list items and household
+//events stay without them, see the migrations
 pub async fn delete_user(username_id: &Uuid, pool: &PgPool) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
     sqlx::query!(
         r#"
         DELETE FROM roles where username_id=$1
         "#,
         &username_id
     )
-    .execute(pool)
+    .execute(&mut *tx)
     .await?;
     sqlx::query!(
         r#"
@@ -145,16 +154,16 @@ pub async fn delete_user(username_id: &Uuid, pool: &PgPool) -> sqlx::Result<()>
         "#,
         &username_id
     )
-    .execute(pool)
+    .execute(&mut *tx)
     .await?;
-    Ok(())
+    tx.commit().await
 }
 
 pub async fn get_all_users(pool: &PgPool) -> sqlx::Result<Vec<UserResponse>> {
     let users_db = sqlx::query_as!(
         UserDB,
         r#"
-        SELECT id, username FROM users
+        SELECT id, username, timezone FROM users
         "#,
     )
     .fetch_all(pool)
@@ -180,6 +189,7 @@ pub async fn get_all_users(pool: &PgPool) -> sqlx::Result<Vec<UserResponse>> {
             id: user.id,
             username: user.username,
             roles: roles_by_user.remove(&user.id).unwrap_or_default(),
+            timezone: user.timezone,
         })
         .collect())
 }
@@ -215,12 +225,13 @@ pub async fn create_user(user: &UserRequest, pool: &PgPool) -> sqlx::Result<()>
     let user_db = sqlx::query_as!(
         UserDB,
         r#"
-        INSERT INTO users (id, username, hashed_password)
-        VALUES (gen_random_uuid(), $1, $2)
-        RETURNING id, username
+        INSERT INTO users (id, username, hashed_password, timezone)
+        VALUES (gen_random_uuid(), $1, $2, COALESCE($3, 'UTC'))
+        RETURNING id, username, timezone
         "#,
         &user.username,
-        &hashed_password
+        &hashed_password,
+        user.timezone.as_ref()
     )
     .fetch_one(pool)
     .await?;
@@ -237,11 +248,12 @@ pub async fn patch_user(id: &Uuid, user: &UserRequest, pool: &PgPool) -> sqlx::R
                 UserDB,
                 r#"
                 UPDATE users
-                set username=$1, hashed_password=$2
-                where id=$3
+                set username=$1, hashed_password=$2, timezone=COALESCE($3, timezone)
+                where id=$4
                 "#,
                 &user.username,
                 &hashed_password,
+                user.timezone.as_ref(),
                 &id
             )
             .execute(pool)
@@ -250,10 +262,11 @@ pub async fn patch_user(id: &Uuid, user: &UserRequest, pool: &PgPool) -> sqlx::R
             UserDB,
             r#"
                 UPDATE users
-                set username=$1
-                where id=$2
+                set username=$1, timezone=COALESCE($2, timezone)
+                where id=$3
                 "#,
             &user.username,
+            user.timezone.as_ref(),
             &id
         )
         .execute(pool),
@@ -1,6 +1,7 @@
+use crate::config::KB;
 use pgvector::Vector;
 use poem_openapi::Object;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{Error, PgPool, Row, postgres::PgRow};
 
 //if top num_matches are all in same document, will only return one document
@@ -85,32 +86,107 @@ pub async fn write_document(
     Ok(result.id)
 }
 
-pub async fn write_knowledge_base(name: &str, pool: &PgPool) -> sqlx::Result<i64> {
+pub async fn write_knowledge_base(kb: &KB, pool: &PgPool) -> sqlx::Result<i64> {
     let result = sqlx::query_as!(
         IdOnly,
-        r#"INSERT INTO knowledge_bases (name) VALUES ($1) RETURNING id"#,
-        name,
+        r#"INSERT INTO knowledge_bases (name, description, num_results) VALUES ($1, $2, $3) RETURNING id"#,
+        &kb.name,
+        kb.description,
+        kb.num_results
     )
     .fetch_one(pool)
     .await?;
     Ok(result.id)
 }
+
+#[derive(Deserialize, Object)]
+pub struct KnowledgeBaseUpdate {
+    pub description: Option<String>,
+    pub num_results: i32,
+}
+
+pub async fn update_knowledge_base(
+    name: &str,
+    kb: &KnowledgeBaseUpdate,
+    pool: &PgPool,
+) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"UPDATE knowledge_bases SET description=$2, num_results=$3 WHERE name=$1"#,
+        name,
+        kb.description,
+        kb.num_results
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+//documents are shared across knowledge bases, so only remove the ones left without vectors
+pub async fn delete_knowledge_base(id: i64, pool: &PgPool) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    let document_ids: Vec<i64> = sqlx::query_scalar!(
+        r#"DELETE FROM vectors WHERE kb_id=$1 RETURNING document_id"#,
+        id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM content WHERE document_id = ANY($1)
+        AND document_id NOT IN (SELECT document_id FROM vectors)
+        "#,
+        &document_ids
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM documents WHERE id = ANY($1)
+        AND id NOT IN (SELECT document_id FROM vectors)
+        "#,
+        &document_ids
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(r#"DELETE FROM knowledge_bases WHERE id=$1"#, id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Object)]
 pub struct KnowledgeBase {
     pub id: i64,
-    name: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub num_results: i32,
 }
+
+impl From<KnowledgeBase> for KB {
+    fn from(kb: KnowledgeBase) -> Self {
+        KB {
+            name: kb.name,
+            description: kb.description,
+            num_results: kb.num_results,
+        }
+    }
+}
+
 pub async fn get_knowledge_bases(pool: &PgPool) -> sqlx::Result<Vec<KnowledgeBase>> {
-    let result = sqlx::query_as!(KnowledgeBase, r#"SELECT id, name from knowledge_bases"#)
-        .fetch_all(pool)
-        .await?;
+    let result = sqlx::query_as!(
+        KnowledgeBase,
+        r#"SELECT id, name, description, num_results from knowledge_bases"#
+    )
+    .fetch_all(pool)
+    .await?;
     Ok(result)
 }
 
 pub async fn get_knowledge_base(name: &str, pool: &PgPool) -> sqlx::Result<KnowledgeBase> {
     let result = sqlx::query_as!(
         KnowledgeBase,
-        r#"SELECT id, name from knowledge_bases where name=$1"#,
+        r#"SELECT id, name, description, num_results from knowledge_bases where name=$1"#,
         name
     )
     .fetch_one(pool)
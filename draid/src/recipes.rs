@@ -0,0 +1,844 @@
+use crate::fuzzy::{MATCH_THRESHOLD, best_match, normalize, similarity};
+use crate::llm::{Bot, complete};
+use crate::pantry::mentions;
+use crate::prompts::RECIPE_EXTRACTION_PROMPT;
+use crate::psql_recipes::{
+    Ingredient, Recipe, RecipeRequest, create_recipe, get_recipe, get_recipe_ingredient_names,
+    get_recipes, recipe_source_exists,
+};
+use crate::tools::{ToolContext, ToolOutput, TypedTool, typed_tool};
+use crate::units::{Dimension, Unit, convert, find_density, find_unit};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+fn fraction(c: char) -> Option<f64> {
+    match c {
+        '½' => Some(1.0 / 2.0),
+        '⅓' => Some(1.0 / 3.0),
+        '⅔' => Some(2.0 / 3.0),
+        '¼' => Some(1.0 / 4.0),
+        '¾' => Some(3.0 / 4.0),
+        '⅕' => Some(1.0 / 5.0),
+        '⅛' => Some(1.0 / 8.0),
+        '⅜' => Some(3.0 / 8.0),
+        '⅝' => Some(5.0 / 8.0),
+        '⅞' => Some(7.0 / 8.0),
+        _ => None,
+    }
+}
+
+//a single number at the start of the text, eg "2", "0.5", "1/2", "½" or "1½"
+fn parse_number(text: &str) -> Option<(f64, &str)> {
+    let mut chars = text.chars();
+    if let Some(value) = chars.next().and_then(fraction) {
+        return Some((value, chars.as_str()));
+    }
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let mut value: f64 = text[..end].parse().ok()?;
+    let mut rest = &text[end..];
+    if let Some(denominator) = rest.strip_prefix('/') {
+        let end = denominator
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(denominator.len());
+        let denominator: f64 = denominator[..end].parse().ok()?;
+        if denominator == 0.0 {
+            return None;
+        }
+        value /= denominator;
+        rest = &rest[1 + end..];
+    }
+    let mut chars = rest.chars();
+    if let Some(part) = chars.next().and_then(fraction) {
+        value += part;
+        rest = chars.as_str();
+    }
+    Some((value, rest))
+}
+
+//the amount an ingredient line starts with, eg "1 1/2 cups" is 1.5. ranges like
+//"2-3 cloves" use the lower amount. returns the amount and the rest of the line
+fn parse_quantity(text: &str) -> Option<(f64, &str)> {
+    let (mut quantity, rest) = parse_number(text)?;
+    let mut rest = rest.trim_start();
+    if quantity.fract() == 0.0
+        && let Some((part, after)) = parse_number(rest)
+        && part < 1.0
+    {
+        quantity += part;
+        rest = after.trim_start();
+    }
+    if let Some(upper) = rest
+        .strip_prefix('-')
+        .or_else(|| rest.strip_prefix('–'))
+        .or_else(|| rest.strip_prefix("to "))
+        && let Some((_, after)) = parse_number(upper.trim_start())
+    {
+        rest = after.trim_start();
+    }
+    Some((quantity, rest))
+}
+
+//the unit an amount is followed by, which can be two words, eg "fl oz". only
+//cooking units count so that "bake at 180 C" isn't read as an amount
+fn take_unit(text: &str) -> (Option<&'static Unit>, &str) {
+    for count in [2, 1] {
+        let mut parts = text.splitn(count + 1, ' ');
+        let words: Vec<&str> = parts.by_ref().take(count).collect();
+        if words.len() < count {
+            continue;
+        }
+        if let Ok(unit) = find_unit(&words.join(" "))
+            && matches!(unit.dimension, Dimension::Volume | Dimension::Mass)
+        {
+            return (Some(unit), parts.next().unwrap_or(""));
+        }
+    }
+    (None, text)
+}
+
+//splits a line like "1 ½ cups flour, sifted" into its parts
+pub fn parse_ingredient(line: &str) -> Ingredient {
+    let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+    let (quantity, unit, rest) = match parse_quantity(&line) {
+        Some((quantity, rest)) => {
+            let (unit, rest) = take_unit(rest);
+            (Some(quantity), unit, rest)
+        }
+        None => (None, None, line.as_str()),
+    };
+    let (name, note) = match rest.split_once(',') {
+        Some((name, note)) => (name, Some(note.trim().to_string())),
+        None => (rest, None),
+    };
+    let name = name.trim();
+    Ingredient {
+        name: name.strip_prefix("of ").unwrap_or(name).to_string(),
+        quantity,
+        unit: unit.map(|unit| unit.symbol.to_string()),
+        note: note.filter(|note| !note.is_empty()),
+    }
+}
+
+//models like to wrap json in a markdown code block
+fn parse_extracted(reply: &str) -> serde_json::Result<Option<RecipeRequest>> {
+    let reply = reply
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```");
+    let recipe: Option<RecipeRequest> = serde_json::from_str(reply)?;
+    Ok(recipe.filter(|recipe| !recipe.title.trim().is_empty()))
+}
+
+pub async fn extract_recipe(bot: &Bot, text: &str) -> anyhow::Result<Option<RecipeRequest>> {
+    let reply = complete(bot, RECIPE_EXTRACTION_PROMPT, text).await?;
+    Ok(parse_extracted(&reply)?)
+}
+
+//structures a document ingested into the recipes knowledge base. documents that
+//were already extracted, or that have no recipe in them, return None
+pub async fn ingest_recipe(
+    bot: &Bot,
+    text: &str,
+    source: &str,
+    pool: &PgPool,
+) -> anyhow::Result<Option<Uuid>> {
+    if recipe_source_exists(source, pool).await? {
+        return Ok(None);
+    }
+    match extract_recipe(bot, text).await? {
+        Some(recipe) => Ok(Some(create_recipe(&recipe, Some(source), pool).await?)),
+        None => Ok(None),
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#039;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+//contents of the <script type="application/ld+json"> blocks of a page
+fn json_ld_blocks(html: &str) -> Vec<Value> {
+    //ascii lowercasing keeps the byte offsets the same as the original
+    let lower = html.to_ascii_lowercase();
+    let mut blocks = vec![];
+    let mut from = 0;
+    while let Some(start) = lower[from..].find("<script").map(|start| from + start) {
+        let Some(tag_end) = lower[start..].find('>').map(|end| start + end) else {
+            break;
+        };
+        let Some(end) = lower[tag_end..].find("</script").map(|end| tag_end + end) else {
+            break;
+        };
+        if lower[start..tag_end].contains("application/ld+json")
+            && let Ok(block) = serde_json::from_str(html[tag_end + 1..end].trim())
+        {
+            blocks.push(block);
+        }
+        from = end;
+    }
+    blocks
+}
+
+fn is_recipe(value: &Value) -> bool {
+    match &value["@type"] {
+        Value::String(kind) => kind == "Recipe",
+        Value::Array(kinds) => kinds.iter().any(|kind| kind == "Recipe"),
+        _ => false,
+    }
+}
+
+//recipes can be top level, in an array, or nested in a @graph
+fn find_json_ld_recipes<'a>(value: &'a Value, recipes: &mut Vec<&'a Value>) {
+    match value {
+        Value::Array(values) => values
+            .iter()
+            .for_each(|value| find_json_ld_recipes(value, recipes)),
+        Value::Object(object) => {
+            if is_recipe(value) {
+                recipes.push(value);
+            } else if let Some(graph) = object.get("@graph") {
+                find_json_ld_recipes(graph, recipes);
+            }
+        }
+        _ => (),
+    }
+}
+
+//a value that is either a string or a list of strings, eg keywords
+fn strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(text) => text.split(',').map(decode_entities).collect(),
+        Value::Array(values) => values.iter().flat_map(strings).collect(),
+        _ => vec![],
+    }
+}
+
+//instructions are a string, a list of strings or HowToSteps, or HowToSections of steps
+fn steps(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(text) => text
+            .lines()
+            .map(decode_entities)
+            .filter(|step| !step.is_empty())
+            .collect(),
+        Value::Array(values) => values.iter().flat_map(steps).collect(),
+        Value::Object(object) => match object.get("itemListElement") {
+            Some(items) => steps(items),
+            None => object.get("text").map(steps).unwrap_or_default(),
+        },
+        _ => vec![],
+    }
+}
+
+//recipeYield is eg 12, "12", "12 muffins" or ["12", "12 muffins"]
+fn servings(value: &Value) -> Option<i32> {
+    match value {
+        Value::Number(number) => number.as_f64().map(|servings| servings.round() as i32),
+        Value::String(text) => text
+            .split(|c: char| !c.is_ascii_digit())
+            .find(|number| !number.is_empty())
+            .and_then(|number| number.parse().ok()),
+        Value::Array(values) => values.iter().find_map(servings),
+        _ => None,
+    }
+}
+
+//ISO 8601 durations like "PT1H30M" or "P0DT0H45M"
+fn minutes(value: &Value) -> Option<i32> {
+    let duration = value.as_str()?.trim().strip_prefix('P')?;
+    let mut minutes = 0.0;
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in duration.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' | '.' => number.push(c),
+            designator => {
+                let amount: f64 = number.parse().ok()?;
+                number.clear();
+                minutes += match (designator, in_time) {
+                    ('D', false) => amount * 24.0 * 60.0,
+                    ('H', true) => amount * 60.0,
+                    ('M', true) => amount,
+                    ('S', true) => amount / 60.0,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(minutes.round() as i32)
+}
+
+fn recipe_from_json_ld(value: &Value) -> Option<RecipeRequest> {
+    let title = decode_entities(value["name"].as_str()?);
+    if title.is_empty() {
+        return None;
+    }
+    let mut tags: Vec<String> = vec![];
+    for tag in ["recipeCategory", "recipeCuisine", "keywords"]
+        .iter()
+        .flat_map(|key| strings(&value[key]))
+    {
+        let tag = tag.to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    Some(RecipeRequest {
+        title,
+        servings: servings(&value["recipeYield"]),
+        total_minutes: minutes(&value["totalTime"]).or_else(|| {
+            Some(minutes(&value["prepTime"]).unwrap_or(0) + minutes(&value["cookTime"])?)
+        }),
+        ingredients: strings(&value["recipeIngredient"])
+            .iter()
+            .map(|line| parse_ingredient(line))
+            .filter(|ingredient| !ingredient.name.is_empty())
+            .collect(),
+        steps: steps(&value["recipeInstructions"]),
+        tags,
+    })
+}
+
+//schema.org Recipe JSON-LD embedded in a saved recipe page
+pub fn recipes_from_html(html: &str) -> Vec<(RecipeRequest, Option<String>)> {
+    let blocks = json_ld_blocks(html);
+    let mut values = vec![];
+    blocks
+        .iter()
+        .for_each(|block| find_json_ld_recipes(block, &mut values));
+    values
+        .into_iter()
+        .filter_map(|value| {
+            let url = value["url"].as_str().map(String::from);
+            recipe_from_json_ld(value).map(|recipe| (recipe, url))
+        })
+        .collect()
+}
+
+pub fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+pub fn scale_ingredients(ingredients: &[Ingredient], factor: f64) -> Vec<Ingredient> {
+    ingredients
+        .iter()
+        .map(|ingredient| Ingredient {
+            quantity: ingredient.quantity.map(|quantity| round(quantity * factor)),
+            ..ingredient.clone()
+        })
+        .collect()
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    Metric,
+    Us,
+}
+
+//units amounts are written in, each from the amount in g or ml where it takes over
+pub const METRIC_MASS: &[(&str, f64)] = &[("g", 0.0), ("kg", 1000.0)];
+pub const METRIC_VOLUME: &[(&str, f64)] = &[("ml", 0.0), ("l", 1000.0)];
+const US_MASS: &[(&str, f64)] = &[("oz", 0.0), ("lb", 453.59237)];
+const US_VOLUME: &[(&str, f64)] = &[("tsp", 0.0), ("tbsp", 14.78676478125), ("cup", 59.1470591)];
+
+//the largest of the units that an amount in g or ml reaches
+pub fn readable_unit(base: f64, units: &[(&'static str, f64)]) -> &'static str {
+    units
+        .iter()
+        .rev()
+        .find(|(_, from)| base >= *from)
+        .map_or(units[0].0, |(symbol, _)| *symbol)
+}
+
+//converts into the unit of the system that reads best, eg 0.25 cup of sugar into 50 g.
+//metric weighs anything with a known density. other amounts are left alone
+pub fn convert_ingredient(ingredient: &Ingredient, system: UnitSystem) -> Ingredient {
+    let (Some(quantity), Some(unit)) = (ingredient.quantity, ingredient.unit.as_deref()) else {
+        return ingredient.clone();
+    };
+    let Ok(from) = find_unit(unit) else {
+        return ingredient.clone();
+    };
+    let (base_unit, units) = match (system, from.dimension) {
+        (UnitSystem::Metric, Dimension::Volume) if find_density(&ingredient.name).is_ok() => {
+            ("g", METRIC_MASS)
+        }
+        (UnitSystem::Metric, Dimension::Volume) => ("ml", METRIC_VOLUME),
+        (UnitSystem::Metric, Dimension::Mass) => ("g", METRIC_MASS),
+        (UnitSystem::Us, Dimension::Volume) => ("ml", US_VOLUME),
+        (UnitSystem::Us, Dimension::Mass) => ("g", US_MASS),
+        _ => return ingredient.clone(),
+    };
+    let Ok((base, _)) = convert(quantity, unit, base_unit, Some(&ingredient.name)) else {
+        return ingredient.clone();
+    };
+    match convert(base, base_unit, readable_unit(base, units), None) {
+        Ok((quantity, unit)) => Ingredient {
+            quantity: Some(round(quantity)),
+            unit: Some(unit.symbol.to_string()),
+            ..ingredient.clone()
+        },
+        Err(_) => ingredient.clone(),
+    }
+}
+
+//whether a recipe ingredient is what was asked for, eg "flour" is in "gluten-free flour"
+fn uses_ingredient(ingredient: &str, query: &str) -> bool {
+    mentions(ingredient, query) || similarity(ingredient, query) >= MATCH_THRESHOLD
+}
+
+//recipes are found by title, loosely, or by words in the title
+pub async fn find_recipe(title: &str, pool: &PgPool) -> sqlx::Result<Option<Recipe>> {
+    let recipes = get_recipes(pool).await?;
+    let index =
+        best_match(title, recipes.iter().map(|recipe| recipe.title.as_str())).or_else(|| {
+            recipes
+                .iter()
+                .position(|recipe| mentions(&recipe.title, title))
+        });
+    match index {
+        Some(index) => Ok(Some(get_recipe(&recipes[index].id, pool).await?)),
+        None => Ok(None),
+    }
+}
+
+#[derive(Serialize)]
+pub struct RecipeIngredients {
+    title: String,
+    servings: Option<f64>,
+    ingredients: Vec<Ingredient>,
+}
+
+#[derive(Clone)]
+pub struct ReadRecipeTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl ReadRecipeTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "read_recipe".to_string(),
+            description: "Read a recipe from the recipe box: ingredients, steps and servings"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ReadRecipeArgs {
+    /// Title of the recipe, eg "blueberry muffins"
+    recipe: String,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for ReadRecipeTool {
+    type Args = ReadRecipeArgs;
+    type Output = ToolOutput<Recipe>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: ReadRecipeArgs,
+        _context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        Ok(match find_recipe(&args.recipe, &self.pool).await? {
+            Some(recipe) => ToolOutput::Result(recipe),
+            None => ToolOutput::Error(format!("No recipe called {}", args.recipe)),
+        })
+    }
+}
+typed_tool!(ReadRecipeTool);
+
+#[derive(Clone)]
+pub struct ScaleRecipeTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl ScaleRecipeTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "scale_recipe".to_string(),
+            description: "Scale the ingredients of a recipe from the recipe box \
+                to a number of servings, or by a factor, eg 2 to double it"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ScaleRecipeArgs {
+    /// Title of the recipe, eg "blueberry muffins"
+    recipe: String,
+    /// Number of servings wanted
+    #[serde(default)]
+    servings: Option<f64>,
+    /// Multiply the recipe by this instead, eg 0.5 to halve it
+    #[serde(default)]
+    factor: Option<f64>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for ScaleRecipeTool {
+    type Args = ScaleRecipeArgs;
+    type Output = ToolOutput<RecipeIngredients>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: ScaleRecipeArgs,
+        _context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let Some(recipe) = find_recipe(&args.recipe, &self.pool).await? else {
+            return Ok(ToolOutput::Error(format!(
+                "No recipe called {}",
+                args.recipe
+            )));
+        };
+        let original = recipe.servings.map(f64::from);
+        let factor = match (args.factor, args.servings, original) {
+            (Some(factor), _, _) => factor,
+            (None, Some(servings), Some(original)) if original > 0.0 => servings / original,
+            (None, Some(_), _) => {
+                return Ok(ToolOutput::Error(format!(
+                    "{} doesn't say how many servings it makes, scale it by a factor instead",
+                    recipe.title
+                )));
+            }
+            (None, None, _) => {
+                return Ok(ToolOutput::Error(
+                    "Give either the servings or a factor".to_string(),
+                ));
+            }
+        };
+        if factor <= 0.0 || !factor.is_finite() {
+            return Ok(ToolOutput::Error(
+                "Recipes can only be scaled by a positive amount".to_string(),
+            ));
+        }
+        Ok(ToolOutput::Result(RecipeIngredients {
+            title: recipe.title,
+            servings: original.map(|servings| round(servings * factor)),
+            ingredients: scale_ingredients(&recipe.ingredients, factor),
+        }))
+    }
+}
+typed_tool!(ScaleRecipeTool);
+
+#[derive(Clone)]
+pub struct ConvertRecipeUnitsTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl ConvertRecipeUnitsTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "convert_recipe_units".to_string(),
+            description: "Convert the ingredient amounts of a recipe from the recipe box \
+                to metric (grams and millilitres) or US customary units (cups and ounces)"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ConvertRecipeUnitsArgs {
+    /// Title of the recipe, eg "blueberry muffins"
+    recipe: String,
+    /// Units to convert to
+    system: UnitSystem,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for ConvertRecipeUnitsTool {
+    type Args = ConvertRecipeUnitsArgs;
+    type Output = ToolOutput<RecipeIngredients>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: ConvertRecipeUnitsArgs,
+        _context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let Some(recipe) = find_recipe(&args.recipe, &self.pool).await? else {
+            return Ok(ToolOutput::Error(format!(
+                "No recipe called {}",
+                args.recipe
+            )));
+        };
+        Ok(ToolOutput::Result(RecipeIngredients {
+            title: recipe.title,
+            servings: recipe.servings.map(f64::from),
+            ingredients: recipe
+                .ingredients
+                .iter()
+                .map(|ingredient| convert_ingredient(ingredient, args.system))
+                .collect(),
+        }))
+    }
+}
+typed_tool!(ConvertRecipeUnitsTool);
+
+#[derive(Clone)]
+pub struct FindRecipesTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl FindRecipesTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "find_recipes".to_string(),
+            description: "Search the recipe box for recipes using some ingredients, \
+                optionally with a tag like \"dessert\" or \"vegetarian\". \
+                Recipes using the most of the ingredients come first"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct FindRecipesArgs {
+    /// Ingredients the recipes should use, eg ["chicken", "rice"]
+    #[serde(default)]
+    ingredients: Vec<String>,
+    /// Only recipes with this tag
+    #[serde(default)]
+    tag: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RecipeMatch {
+    id: Uuid,
+    title: String,
+    tags: Vec<String>,
+    uses: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for FindRecipesTool {
+    type Args = FindRecipesArgs;
+    type Output = ToolOutput<Vec<RecipeMatch>>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: FindRecipesArgs,
+        _context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let recipes = get_recipes(&self.pool).await?;
+        let ingredients = get_recipe_ingredient_names(&self.pool).await?;
+        let mut matches: Vec<RecipeMatch> = recipes
+            .into_iter()
+            .filter(|recipe| match &args.tag {
+                Some(tag) => recipe
+                    .tags
+                    .iter()
+                    .any(|recipe_tag| normalize(recipe_tag) == normalize(tag)),
+                None => true,
+            })
+            .map(|recipe| RecipeMatch {
+                uses: args
+                    .ingredients
+                    .iter()
+                    .filter(|query| {
+                        ingredients.iter().any(|ingredient| {
+                            ingredient.recipe_id == recipe.id
+                                && uses_ingredient(&ingredient.name, query)
+                        })
+                    })
+                    .cloned()
+                    .collect(),
+                id: recipe.id,
+                title: recipe.title,
+                tags: recipe.tags,
+            })
+            .filter(|recipe| args.ingredients.is_empty() || !recipe.uses.is_empty())
+            .collect();
+        matches.sort_by_key(|recipe| std::cmp::Reverse(recipe.uses.len()));
+        Ok(ToolOutput::Result(matches))
+    }
+}
+typed_tool!(FindRecipesTool);
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        UnitSystem, convert_ingredient, parse_extracted, parse_ingredient, recipes_from_html,
+        scale_ingredients, uses_ingredient,
+    };
+    use crate::psql_recipes::Ingredient;
+
+    fn ingredient(name: &str, quantity: Option<f64>, unit: Option<&str>) -> Ingredient {
+        Ingredient {
+            name: name.to_string(),
+            quantity,
+            unit: unit.map(String::from),
+            note: None,
+        }
+    }
+
+    #[test]
+    fn it_parses_ingredient_lines() {
+        assert_eq!(
+            parse_ingredient("1 ½ cups gluten-free flour"),
+            ingredient("gluten-free flour", Some(1.5), Some("cup"))
+        );
+        assert_eq!(
+            parse_ingredient("1 1/2 tsp baking powder"),
+            ingredient("baking powder", Some(1.5), Some("tsp"))
+        );
+        assert_eq!(
+            parse_ingredient("2 eggs"),
+            ingredient("eggs", Some(2.0), None)
+        );
+        assert_eq!(
+            parse_ingredient("2-3 cloves garlic"),
+            ingredient("cloves garlic", Some(2.0), None)
+        );
+        assert_eq!(
+            parse_ingredient("4 fl oz whole milk"),
+            ingredient("whole milk", Some(4.0), Some("fl oz"))
+        );
+        assert_eq!(parse_ingredient("salt"), ingredient("salt", None, None));
+        let butter = parse_ingredient("½ cup of butter, softened");
+        assert_eq!(butter.name, "butter");
+        assert_eq!(butter.quantity, Some(0.5));
+        assert_eq!(butter.note.as_deref(), Some("softened"));
+    }
+
+    #[test]
+    fn it_imports_json_ld_recipes() {
+        let html = r#"<html><head>
+            <script type="application/ld+json">{"@context": "https://schema.org", "@graph": [
+                {"@type": "WebPage", "name": "Muffins"},
+                {"@type": ["Recipe"], "name": "Blueberry Muffins &amp; More",
+                 "url": "https://example.com/muffins",
+                 "recipeYield": ["12", "12 muffins"],
+                 "prepTime": "PT15M", "cookTime": "PT1H5M",
+                 "recipeIngredient": ["2 cups flour", "2 eggs"],
+                 "recipeInstructions": [{"@type": "HowToSection", "itemListElement": [
+                    {"@type": "HowToStep", "text": "Mix."},
+                    {"@type": "HowToStep", "text": "Bake."}]}],
+                 "recipeCategory": "Breakfast", "keywords": "muffins, Breakfast"}
+            ]}</script>
+            <script>var notJsonLd = 1;</script>
+            </head></html>"#;
+        let recipes = recipes_from_html(html);
+        assert_eq!(recipes.len(), 1);
+        let (recipe, url) = &recipes[0];
+        assert_eq!(recipe.title, "Blueberry Muffins & More");
+        assert_eq!(url.as_deref(), Some("https://example.com/muffins"));
+        assert_eq!(recipe.servings, Some(12));
+        assert_eq!(recipe.total_minutes, Some(80));
+        assert_eq!(
+            recipe.ingredients[0],
+            ingredient("flour", Some(2.0), Some("cup"))
+        );
+        assert_eq!(recipe.steps, vec!["Mix.", "Bake."]);
+        assert_eq!(recipe.tags, vec!["breakfast", "muffins"]);
+    }
+
+    #[test]
+    fn it_parses_extracted_recipes() {
+        let reply = "```json\n{\"title\": \"Toast\", \"servings\": 1, \
+            \"ingredients\": [{\"name\": \"bread\", \"quantity\": 1, \"unit\": null, \"note\": null}], \
+            \"steps\": [\"Toast the bread.\"]}\n```";
+        let recipe = parse_extracted(reply).unwrap().unwrap();
+        assert_eq!(recipe.title, "Toast");
+        assert_eq!(
+            recipe.ingredients,
+            vec![ingredient("bread", Some(1.0), None)]
+        );
+        assert!(recipe.tags.is_empty());
+        assert_eq!(parse_extracted("null").unwrap(), None);
+        assert!(parse_extracted("I couldn't find a recipe").is_err());
+    }
+
+    #[test]
+    fn it_scales_ingredients() {
+        let scaled = scale_ingredients(
+            &[
+                ingredient("flour", Some(1.5), Some("cup")),
+                ingredient("salt", None, None),
+            ],
+            2.0 / 3.0,
+        );
+        assert_eq!(scaled[0].quantity, Some(1.0));
+        assert_eq!(scaled[1].quantity, None);
+    }
+
+    #[test]
+    fn it_converts_ingredients_between_systems() {
+        let sugar = convert_ingredient(
+            &ingredient("sugar", Some(0.25), Some("cup")),
+            UnitSystem::Metric,
+        );
+        assert_eq!(sugar.unit.as_deref(), Some("g"));
+        assert_eq!(sugar.quantity, Some(49.98));
+        let stock = convert_ingredient(
+            &ingredient("chicken stock", Some(6.0), Some("cup")),
+            UnitSystem::Metric,
+        );
+        assert_eq!(stock.unit.as_deref(), Some("l"));
+        assert_eq!(stock.quantity, Some(1.42));
+        let vanilla = convert_ingredient(
+            &ingredient("vanilla extract", Some(5.0), Some("ml")),
+            UnitSystem::Us,
+        );
+        assert_eq!(vanilla.unit.as_deref(), Some("tsp"));
+        assert_eq!(vanilla.quantity, Some(1.01));
+        let eggs = ingredient("eggs", Some(2.0), None);
+        assert_eq!(convert_ingredient(&eggs, UnitSystem::Us), eggs);
+    }
+
+    #[test]
+    fn it_matches_ingredients_loosely() {
+        assert!(uses_ingredient("gluten-free flour", "flour"));
+        assert!(uses_ingredient("blueberries", "blueberry"));
+        assert!(!uses_ingredient("eggplant", "egg"));
+    }
+}
@@ -0,0 +1,336 @@
+use crate::dates::parse_datetime;
+use crate::notifications::{Notification, Notifications};
+use crate::psql_reminders::{
+    Reminder, create_reminder, delete_reminder, get_due_reminders, get_reminders,
+    mark_reminder_fired,
+};
+use crate::tools::{ToolContext, ToolOutput, TypedTool, typed_tool};
+use chrono_tz::Tz;
+use rrule::{RRule, RRuleError, Unvalidated};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::info;
+use uuid::Uuid;
+
+//how often the scheduler looks for due reminders
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(10);
+
+pub fn parse_rrule(rrule: &str) -> Result<RRule<Unvalidated>, RRuleError> {
+    rrule.trim().trim_start_matches("RRULE:").parse()
+}
+
+//first occurrence of the rule at or after `after`, expanded in the reminder's timezone
+//so that "every day at 7am" stays at 7am across daylight saving changes
+pub fn next_occurrence(
+    starts_at: chrono::DateTime<chrono::Utc>,
+    rrule: &str,
+    timezone: Tz,
+    after: chrono::DateTime<chrono::Utc>,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, RRuleError> {
+    let timezone = rrule::Tz::from(timezone);
+    let set = parse_rrule(rrule)?.build(starts_at.with_timezone(&timezone))?;
+    Ok(set
+        .after(after.with_timezone(&timezone))
+        .all(1)
+        .dates
+        .first()
+        .map(|date| date.with_timezone(&chrono::Utc)))
+}
+
+//checked in the timezone the rule will be expanded in
+pub fn validate_rrule(
+    rrule: &str,
+    starts_at: chrono::DateTime<chrono::Utc>,
+    timezone: Tz,
+) -> Result<(), RRuleError> {
+    next_occurrence(starts_at, rrule, timezone, starts_at).map(|_| ())
+}
+
+async fn fire_reminder(
+    reminder: Reminder,
+    pool: &PgPool,
+    notifications: &Notifications,
+) -> anyhow::Result<()> {
+    let delivered = notifications.send(
+        reminder.username_id,
+        Notification::Reminder {
+            id: reminder.id,
+            message: reminder.message.clone(),
+            due_at: reminder.due_at,
+        },
+    );
+    //nobody is connected, so it stays due until one of the user's clients is
+    if !delivered {
+        return Ok(());
+    }
+    info!(
+        tool_use = false,
+        endpoint = "reminders",
+        span_id = reminder.id.to_string(),
+        message = format!(
+            "fired reminder {} for user {}",
+            reminder.id, reminder.username_id
+        )
+    );
+    //skip occurrences missed while the server was down rather than firing them all at once
+    let next_due = match &reminder.rrule {
+        Some(rrule) => {
+            let after = reminder.due_at.max(chrono::Utc::now()) + chrono::Duration::seconds(1);
+            let timezone = reminder.timezone.parse().unwrap_or(Tz::UTC);
+            next_occurrence(reminder.starts_at, rrule, timezone, after)
+        }
+        None => Ok(None),
+    };
+    //a rule that can't be expanded ends the reminder, otherwise it would fire
+    //again on every tick
+    mark_reminder_fired(
+        &reminder.id,
+        next_due.as_ref().ok().copied().flatten(),
+        pool,
+    )
+    .await?;
+    next_due?;
+    Ok(())
+}
+
+//fires due reminders in the background for as long as the server runs
+pub fn run_scheduler(pool: &PgPool, notifications: &Arc<Notifications>) -> JoinHandle<()> {
+    let pool = pool.clone();
+    let notifications = notifications.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+        loop {
+            interval.tick().await;
+            let reminders = match get_due_reminders(&pool).await {
+                Ok(reminders) => reminders,
+                Err(e) => {
+                    info!(
+                        tool_use = false,
+                        endpoint = "reminders",
+                        message = format!("Failed to get due reminders: {}", e)
+                    );
+                    continue;
+                }
+            };
+            for reminder in reminders {
+                let id = reminder.id;
+                if let Err(e) = fire_reminder(reminder, &pool, &notifications).await {
+                    info!(
+                        tool_use = false,
+                        endpoint = "reminders",
+                        message = format!("Failed to fire reminder {}: {}", id, e)
+                    );
+                }
+            }
+        }
+    })
+}
+
+#[derive(Clone)]
+pub struct CreateReminderTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl CreateReminderTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "create_reminder".to_string(),
+            description: "Set a reminder or timer for the user. Use an rrule for recurring \
+                reminders, eg FREQ=WEEKLY;BYDAY=TU for every Tuesday"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateReminderArgs {
+    /// What to remind the user about
+    message: String,
+    /// When the reminder first fires, eg "in 40 minutes", "tomorrow at 8am", "2026-03-03 17:00"
+    when: String,
+    /// RFC 5545 recurrence rule for recurring reminders, eg FREQ=DAILY or FREQ=MONTHLY;BYMONTHDAY=1
+    #[serde(default)]
+    rrule: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for CreateReminderTool {
+    type Args = CreateReminderArgs;
+    type Output = ToolOutput<Reminder>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: CreateReminderArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        let now = chrono::Utc::now().with_timezone(&context.timezone);
+        let starts_at = match parse_datetime(&args.when, now) {
+            Ok(starts_at) => starts_at.with_timezone(&chrono::Utc),
+            Err(e) => return Ok(ToolOutput::Error(e.to_string())),
+        };
+        if let Some(rrule) = &args.rrule
+            && let Err(e) = validate_rrule(rrule, starts_at, context.timezone)
+        {
+            return Ok(ToolOutput::Error(format!("Invalid rrule: {}", e)));
+        }
+        let reminder = create_reminder(
+            &context.user_id,
+            &args.message,
+            &starts_at,
+            args.rrule.as_deref(),
+            context.timezone.name(),
+            &self.pool,
+        )
+        .await?;
+        Ok(ToolOutput::Result(reminder))
+    }
+}
+typed_tool!(CreateReminderTool);
+
+#[derive(Clone)]
+pub struct ListRemindersTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl ListRemindersTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "list_reminders".to_string(),
+            description: "List the user's upcoming reminders and timers".to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ListRemindersArgs {}
+
+#[async_trait::async_trait]
+impl TypedTool for ListRemindersTool {
+    type Args = ListRemindersArgs;
+    type Output = ToolOutput<Vec<Reminder>>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        _args: ListRemindersArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        Ok(ToolOutput::Result(
+            get_reminders(&context.user_id, &self.pool).await?,
+        ))
+    }
+}
+typed_tool!(ListRemindersTool);
+
+#[derive(Clone)]
+pub struct CancelReminderTool {
+    name: String,
+    description: String,
+    pool: PgPool,
+}
+
+impl CancelReminderTool {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            name: "cancel_reminder".to_string(),
+            description: "Cancel one of the user's reminders or timers by id. \
+                Use list_reminders to find the id"
+                .to_string(),
+            pool,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CancelReminderArgs {
+    /// Id of the reminder to cancel
+    id: Uuid,
+}
+
+#[async_trait::async_trait]
+impl TypedTool for CancelReminderTool {
+    type Args = CancelReminderArgs;
+    type Output = ToolOutput<Uuid>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(
+        &self,
+        args: CancelReminderArgs,
+        context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        Ok(
+            if delete_reminder(&args.id, &context.user_id, &self.pool).await? {
+                ToolOutput::Result(args.id)
+            } else {
+                ToolOutput::Error(format!("No reminder with id {}", args.id))
+            },
+        )
+    }
+}
+typed_tool!(CancelReminderTool);
+
+#[cfg(test)]
+mod tests {
+    use super::{next_occurrence, validate_rrule};
+    use chrono::TimeZone;
+
+    #[test]
+    fn it_expands_recurrences_in_the_local_timezone() {
+        //7am in New York, before daylight saving ends on Nov 1st 2026
+        let starts_at = chrono::Utc
+            .with_ymd_and_hms(2026, 10, 30, 11, 0, 0)
+            .unwrap();
+        let after = chrono::Utc.with_ymd_and_hms(2026, 11, 1, 13, 0, 0).unwrap();
+        let next = next_occurrence(
+            starts_at,
+            "RRULE:FREQ=DAILY",
+            chrono_tz::America::New_York,
+            after,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            next,
+            chrono::Utc.with_ymd_and_hms(2026, 11, 2, 12, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_ends_recurrences_after_count() {
+        let starts_at = chrono::Utc.with_ymd_and_hms(2026, 10, 19, 9, 0, 0).unwrap();
+        let after = chrono::Utc.with_ymd_and_hms(2026, 10, 22, 0, 0, 0).unwrap();
+        let next = next_occurrence(starts_at, "FREQ=DAILY;COUNT=3", chrono_tz::UTC, after).unwrap();
+        assert!(next.is_none());
+    }
+
+    #[test]
+    fn it_rejects_invalid_rrules() {
+        let starts_at = chrono::Utc::now();
+        let timezone = chrono_tz::Europe::London;
+        assert!(validate_rrule("FREQ=WEEKLY;BYDAY=MO", starts_at, timezone).is_ok());
+        assert!(validate_rrule("FREQ=SOMETIMES", starts_at, timezone).is_err());
+    }
+}
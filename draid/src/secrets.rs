@@ -0,0 +1,88 @@
+//passwords draid sends on to other services, eg CalDAV servers, can't be hashed
+//so they are encrypted before they are stored
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+//marks an encrypted value, anything else was stored before encryption
+const SEALED_PREFIX: &str = "sealed:";
+const NONCE_LENGTH: usize = 12;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+pub struct Secrets {
+    cipher: ChaCha20Poly1305,
+}
+
+impl Secrets {
+    //the key is derived from a secret of any length
+    pub fn new(secret: &[u8]) -> Self {
+        let key = from_hex(&sha256::digest(secret)).unwrap_or_default();
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+        }
+    }
+
+    pub fn seal(&self, plaintext: &str) -> anyhow::Result<String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|e| anyhow::anyhow!("Failed to encrypt: {}", e))?;
+        Ok(format!(
+            "{}{}{}",
+            SEALED_PREFIX,
+            to_hex(&nonce),
+            to_hex(&ciphertext)
+        ))
+    }
+
+    //values stored before encryption are returned as they are
+    pub fn open(&self, stored: &str) -> anyhow::Result<String> {
+        let Some(sealed) = stored.strip_prefix(SEALED_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let bytes = from_hex(sealed)
+            .filter(|bytes| bytes.len() > NONCE_LENGTH)
+            .ok_or_else(|| anyhow::anyhow!("The stored secret is corrupted"))?;
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("The stored secret was sealed with a different key"))?;
+        Ok(String::from_utf8(plaintext)?)
+    }
+}
+
+pub fn is_sealed(stored: &str) -> bool {
+    stored.starts_with(SEALED_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Secrets, is_sealed};
+
+    #[test]
+    fn it_seals_and_opens_secrets() {
+        let secrets = Secrets::new(b"jwt secret");
+        let sealed = secrets.seal("app password").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("app password"));
+        assert_ne!(sealed, secrets.seal("app password").unwrap());
+        assert_eq!(secrets.open(&sealed).unwrap(), "app password");
+        assert_eq!(secrets.open("stored before").unwrap(), "stored before");
+        assert!(Secrets::new(b"another secret").open(&sealed).is_err());
+        assert!(secrets.open("sealed:abc").is_err());
+    }
+}
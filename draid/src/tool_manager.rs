@@ -0,0 +1,201 @@
+use crate::calendar::{CreateEventTool, FreeBusyTool, UpcomingEventsTool};
+use crate::chores::{ChoreScoresTool, CompleteChoreTool, ReadChoresTool};
+use crate::config::{Config, HomeAssistant, KB, MCP, Mqtt};
+use crate::confirmations::Confirmations;
+use crate::embedding::EmbeddingClient;
+use crate::home_assistant;
+use crate::kb_tools;
+use crate::lists::{AddListItemsTool, CheckListItemsTool, ReadListTool, RemoveListItemsTool};
+use crate::mcp_tools::McpServers;
+use crate::meal_plans::{
+    ChangeMealPlanTool, MealPlanShoppingListTool, PlanMealsTool, ReadMealPlanTool,
+};
+use crate::mqtt::MqttDevices;
+use crate::notifications::Notifications;
+use crate::pantry::{AddPantryItemsTool, ReadPantryTool, UsePantryItemsTool, WhatCanICookTool};
+use crate::psql_mcp::{create_mcp_server_if_not_exists, get_mcp_servers};
+use crate::psql_tools::get_tool_permissions;
+use crate::psql_vectors::{get_knowledge_bases, write_knowledge_base};
+use crate::recipes::{ConvertRecipeUnitsTool, FindRecipesTool, ReadRecipeTool, ScaleRecipeTool};
+use crate::reminders::{CancelReminderTool, CreateReminderTool, ListRemindersTool};
+use crate::tools::{CalculatorTool, ConvertUnitsTool, DateTool, Tool, ToolPermissions, ToolSet};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::info;
+
+//rebuilds the helper tools from the knowledge base and mcp server definitions in the database
+pub struct ToolManager {
+    pool: PgPool,
+    kb_endpoint: String,
+    notifications: Arc<Notifications>,
+    embedding_client: Arc<EmbeddingClient>,
+    home_assistant: Option<HomeAssistant>,
+    confirmations: Arc<Confirmations>,
+    mqtt: Option<Arc<MqttDevices>>,
+    pub mcp_servers: Arc<McpServers>,
+    pub helper_tools: ToolSet,
+}
+
+impl ToolManager {
+    pub fn new(
+        pool: PgPool,
+        kb_endpoint: String,
+        notifications: Arc<Notifications>,
+        embedding_client: Arc<EmbeddingClient>,
+        home_assistant: Option<HomeAssistant>,
+        mqtt: Option<Mqtt>,
+    ) -> Self {
+        Self {
+            pool,
+            kb_endpoint,
+            notifications,
+            embedding_client,
+            home_assistant,
+            confirmations: Arc::new(Confirmations::new()),
+            mqtt: mqtt.as_ref().map(MqttDevices::connect),
+            mcp_servers: Arc::new(McpServers::new()),
+            helper_tools: ToolSet::new(vec![], ToolPermissions::default()),
+        }
+    }
+
+    //TOOL_CONFIG only adds definitions that aren't already in the database
+    pub async fn seed(&self, config: Config) -> anyhow::Result<()> {
+        for kb in config.kb.iter() {
+            match write_knowledge_base(kb, &self.pool).await {
+                Ok(result) => info!(
+                    tool_use = false,
+                    endpoint = "knowledge_base",
+                    message = format!("Created knowledge base {} with index {}", kb.name, result)
+                ),
+                Err(e) => info!(
+                    tool_use = false,
+                    endpoint = "knowledge_base",
+                    message = format!("Failed to create knowledge base: {}", e)
+                ),
+            }
+        }
+        for mcp in config.mcp.iter() {
+            create_mcp_server_if_not_exists(mcp, &self.pool).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn refresh_permissions(&self) -> anyhow::Result<()> {
+        let permissions = get_tool_permissions(&self.pool).await?;
+        self.helper_tools.permissions.replace(permissions);
+        Ok(())
+    }
+
+    //the tools that don't depend on the database definitions
+    fn builtin_tools(&self) -> Vec<Arc<dyn Tool + Send + Sync>> {
+        vec![
+            Arc::new(CalculatorTool::new()),
+            Arc::new(ConvertUnitsTool::new()),
+            Arc::new(DateTool::new()),
+            Arc::new(CreateReminderTool::new(self.pool.clone())),
+            Arc::new(ListRemindersTool::new(self.pool.clone())),
+            Arc::new(CancelReminderTool::new(self.pool.clone())),
+            Arc::new(AddListItemsTool::new(
+                self.pool.clone(),
+                self.notifications.clone(),
+            )),
+            Arc::new(RemoveListItemsTool::new(
+                self.pool.clone(),
+                self.notifications.clone(),
+            )),
+            Arc::new(CheckListItemsTool::new(
+                self.pool.clone(),
+                self.notifications.clone(),
+            )),
+            Arc::new(ReadListTool::new(self.pool.clone())),
+            Arc::new(AddPantryItemsTool::new(self.pool.clone())),
+            Arc::new(UsePantryItemsTool::new(self.pool.clone())),
+            Arc::new(ReadPantryTool::new(self.pool.clone())),
+            Arc::new(WhatCanICookTool::new(
+                self.pool.clone(),
+                self.embedding_client.clone(),
+            )),
+            Arc::new(FindRecipesTool::new(self.pool.clone())),
+            Arc::new(ReadRecipeTool::new(self.pool.clone())),
+            Arc::new(ScaleRecipeTool::new(self.pool.clone())),
+            Arc::new(ConvertRecipeUnitsTool::new(self.pool.clone())),
+            Arc::new(PlanMealsTool::new(self.pool.clone())),
+            Arc::new(ReadMealPlanTool::new(self.pool.clone())),
+            Arc::new(ChangeMealPlanTool::new(self.pool.clone())),
+            Arc::new(MealPlanShoppingListTool::new(
+                self.pool.clone(),
+                self.notifications.clone(),
+            )),
+            Arc::new(ReadChoresTool::new(self.pool.clone())),
+            Arc::new(CompleteChoreTool::new(self.pool.clone())),
+            Arc::new(ChoreScoresTool::new(self.pool.clone())),
+            Arc::new(UpcomingEventsTool::new(self.pool.clone())),
+            Arc::new(FreeBusyTool::new(self.pool.clone())),
+            Arc::new(CreateEventTool::new(self.pool.clone())),
+        ]
+    }
+
+    pub async fn refresh(&self) -> anyhow::Result<()> {
+        self.refresh_permissions().await?;
+        let kbs: Vec<KB> = get_knowledge_bases(&self.pool)
+            .await?
+            .into_iter()
+            .map(KB::from)
+            .collect();
+        let mcps: Vec<MCP> = get_mcp_servers(&self.pool)
+            .await?
+            .into_iter()
+            .map(MCP::from)
+            .collect();
+        self.mcp_servers.sync(mcps).await;
+
+        let mut tools = self.builtin_tools();
+        tools.extend(kb_tools::get_tools(kbs, &self.kb_endpoint));
+        tools.extend(self.mcp_servers.tools());
+        //an unreachable home assistant shouldn't take the other tools down with it
+        if let Some(config) = &self.home_assistant {
+            match home_assistant::get_tools(config, &self.confirmations).await {
+                Ok(ha_tools) => tools.extend(ha_tools),
+                Err(e) => info!(
+                    tool_use = false,
+                    endpoint = "home_assistant",
+                    message = format!("Failed to discover Home Assistant tools: {}", e)
+                ),
+            }
+        }
+        if let Some(mqtt) = &self.mqtt {
+            tools.extend(mqtt.tools());
+        }
+        self.helper_tools.replace(tools);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::postgres::PgPoolOptions;
+
+    #[tokio::test]
+    async fn it_registers_each_helper_tool_once() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/draid")
+            .unwrap();
+        let manager = ToolManager::new(
+            pool,
+            "http://localhost:1".to_string(),
+            Arc::new(Notifications::new()),
+            Arc::new(EmbeddingClient::new(
+                "model".to_string(),
+                "http://localhost:1",
+            )),
+            None,
+            None,
+        );
+        let tools = manager.builtin_tools();
+        let mut names: Vec<&String> = tools.iter().map(|tool| tool.name()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), tools.len());
+    }
+}
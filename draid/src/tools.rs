@@ -1,14 +1,41 @@
+use crate::calculator::evaluate;
+use crate::dates::{DurationUnit, add_duration, parse_datetime};
+use crate::psql_tools::ToolPermission;
+use crate::psql_users::Role;
+use crate::units::convert;
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::{Value, json};
 
+use chrono_tz::Tz;
 use sqlx::types::chrono;
-use std::{ops::Deref, sync::Arc};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, RwLock},
+};
+
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+//who and what a tool call is made on behalf of
+#[derive(Clone)]
+pub struct ToolContext {
+    pub user_id: Uuid,
+    pub roles: Vec<Role>,
+    pub session_id: Uuid,
+    pub bot_name: String,
+    pub span_id: String,
+    pub cancellation_token: CancellationToken,
+    pub timezone: Tz,
+}
 
 #[async_trait::async_trait]
 pub trait Tool: Send + Sync {
     fn name(&self) -> &String;
     fn description(&self) -> &String;
     fn parameters(&self) -> Value;
-    async fn invoke(&self, args: String) -> anyhow::Result<Value>;
+    async fn invoke(&self, args: String, context: &ToolContext) -> anyhow::Result<Value>;
 }
 
 #[async_trait::async_trait]
@@ -27,8 +54,8 @@ where
     fn parameters(&self) -> Value {
         self.deref().parameters()
     }
-    async fn invoke(&self, args: String) -> anyhow::Result<Value> {
-        self.deref().invoke(args).await
+    async fn invoke(&self, args: String, context: &ToolContext) -> anyhow::Result<Value> {
+        self.deref().invoke(args, context).await
     }
 }
 
@@ -60,86 +87,683 @@ impl ToolRegistry {
     }
 }
 
+//supports * (any run of characters) and ? (any single character)
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    //position of the last * and the name index it was matched against
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            p = star_p + 1;
+            n = star_n + 1;
+            backtrack = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+//a tool without any permissions is available to everyone using the bot, unless
+//the bot only gets granted tools. once a tool has permissions, only the listed
+//roles and users can use it
+#[derive(Clone, Default)]
+pub struct ToolPermissions {
+    permissions: Arc<RwLock<Vec<ToolPermission>>>,
+    granted_only: bool,
+}
+
+impl ToolPermissions {
+    //the same permissions, but tools nobody was granted aren't available
+    pub fn granted_only(&self) -> Self {
+        Self {
+            permissions: self.permissions.clone(),
+            granted_only: true,
+        }
+    }
+    pub fn replace(&self, permissions: Vec<ToolPermission>) {
+        if let Ok(mut current) = self.permissions.write() {
+            *current = permissions;
+        }
+    }
+    pub fn is_allowed(&self, tool_name: &str, context: &ToolContext) -> bool {
+        let permissions = match self.permissions.read() {
+            Ok(permissions) => permissions,
+            Err(_) => return false,
+        };
+        is_allowed(&permissions, tool_name, context, self.granted_only)
+    }
+}
+
+fn is_allowed(
+    permissions: &[ToolPermission],
+    tool_name: &str,
+    context: &ToolContext,
+    granted_only: bool,
+) -> bool {
+    let mut matching = permissions
+        .iter()
+        .filter(|permission| glob_match(&permission.tool, tool_name))
+        .peekable();
+    if matching.peek().is_none() {
+        return !granted_only;
+    }
+    matching.any(|permission| {
+        permission
+            .role
+            .is_some_and(|role| context.roles.contains(&role))
+            || permission.username_id == Some(context.user_id)
+    })
+}
+
+//tools available to a bot, swapped out whenever tool definitions change at runtime
+#[derive(Clone)]
+pub struct ToolSet {
+    tools: Arc<RwLock<Vec<Arc<dyn Tool + Send + Sync>>>>,
+    pub permissions: ToolPermissions,
+}
+
+impl ToolSet {
+    pub fn new(tools: Vec<Arc<dyn Tool + Send + Sync>>, permissions: ToolPermissions) -> Self {
+        Self {
+            tools: Arc::new(RwLock::new(tools)),
+            permissions,
+        }
+    }
+    //the same tools, for a bot that only gets the ones granted to its users
+    pub fn granted_only(&self) -> Self {
+        Self {
+            tools: self.tools.clone(),
+            permissions: self.permissions.granted_only(),
+        }
+    }
+    pub fn snapshot(&self) -> Vec<Arc<dyn Tool + Send + Sync>> {
+        self.tools
+            .read()
+            .map(|tools| tools.clone())
+            .unwrap_or_default()
+    }
+    //only the tools the calling user is permitted to use
+    pub fn snapshot_for(&self, context: &ToolContext) -> Vec<Arc<dyn Tool + Send + Sync>> {
+        self.snapshot()
+            .into_iter()
+            .filter(|tool| self.permissions.is_allowed(tool.name(), context))
+            .collect()
+    }
+    pub fn replace(&self, tools: Vec<Arc<dyn Tool + Send + Sync>>) {
+        if let Ok(mut current) = self.tools.write() {
+            *current = tools;
+        }
+    }
+}
+
+//typed alternative to implementing Tool by hand: the json schema sent to the model is
+//derived from Args, so the schema and the argument parsing can't drift apart.
+//use typed_tool!(MyTool) to generate the Tool impl
+#[async_trait::async_trait]
+pub trait TypedTool: Send + Sync {
+    type Args: DeserializeOwned + JsonSchema + Send;
+    type Output: Serialize;
+    fn name(&self) -> &String;
+    fn description(&self) -> &String;
+    async fn call(&self, args: Self::Args, context: &ToolContext) -> anyhow::Result<Self::Output>;
+}
+
+pub fn typed_parameters<T: TypedTool>() -> Value {
+    let mut schema = serde_json::to_value(schema_for!(T::Args)).unwrap_or_else(|_| json!({}));
+    if let Some(schema) = schema.as_object_mut() {
+        //metadata only, the model doesn't need it
+        schema.remove("$schema");
+        schema.remove("title");
+    }
+    schema
+}
+
+pub async fn typed_invoke<T: TypedTool>(
+    tool: &T,
+    args: String,
+    context: &ToolContext,
+) -> anyhow::Result<Value> {
+    //models sometimes send nothing at all for tools without arguments
+    let args = if args.trim().is_empty() { "{}" } else { &args };
+    let args: T::Args = serde_json::from_str(args)
+        .map_err(|e| anyhow::anyhow!("Invalid arguments for tool {}: {}", tool.name(), e))?;
+    let output = tool.call(args, context).await?;
+    Ok(serde_json::to_value(output)?)
+}
+
+macro_rules! typed_tool {
+    ($tool:ty) => {
+        #[async_trait::async_trait]
+        impl $crate::tools::Tool for $tool {
+            fn name(&self) -> &String {
+                $crate::tools::TypedTool::name(self)
+            }
+            fn description(&self) -> &String {
+                $crate::tools::TypedTool::description(self)
+            }
+            fn parameters(&self) -> serde_json::Value {
+                $crate::tools::typed_parameters::<$tool>()
+            }
+            async fn invoke(
+                &self,
+                args: String,
+                context: &$crate::tools::ToolContext,
+            ) -> anyhow::Result<serde_json::Value> {
+                $crate::tools::typed_invoke(self, args, context).await
+            }
+        }
+    };
+}
+pub(crate) use typed_tool;
+
+#[derive(Serialize)]
+pub struct ToolResult<T: Serialize> {
+    pub result: T,
+}
+
+//errors the model can act on, like a malformed expression, are returned as output so
+//the model can correct itself instead of the whole request failing
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolOutput<T: Serialize> {
+    Result(T),
+    Error(String),
+}
+
+impl<T: Serialize, E: std::fmt::Display> From<Result<T, E>> for ToolOutput<T> {
+    fn from(result: Result<T, E>) -> Self {
+        match result {
+            Ok(value) => ToolOutput::Result(value),
+            Err(e) => ToolOutput::Error(e.to_string()),
+        }
+    }
+}
+
 #[derive(Clone)]
-pub struct AddTool {
+pub struct CalculatorTool {
     name: String,
     description: String,
 }
 
-impl AddTool {
+impl CalculatorTool {
     pub fn new() -> Self {
         Self {
             name: "calculator".to_string(),
-            description: "Add some numbers".to_string(),
+            description: "Evaluate a math expression. Supports + - * / ^, parentheses, \
+                percentages (100 + 15% is 115, 20% * 50 is 10), \
+                sqrt, pow, round(x, digits), min, max, abs, floor, ceil, ln, log, exp, \
+                pi, e and named variables"
+                .to_string(),
         }
     }
 }
 
+#[derive(Deserialize, JsonSchema)]
+pub struct CalculatorArgs {
+    /// Expression to evaluate, eg "round(87.50 * (1 + 18%) / people, 2)"
+    expression: String,
+    /// Values for any variables used in the expression
+    #[serde(default)]
+    variables: HashMap<String, f64>,
+}
+
 #[async_trait::async_trait]
-impl Tool for AddTool {
+impl TypedTool for CalculatorTool {
+    type Args = CalculatorArgs;
+    type Output = ToolOutput<f64>;
     fn name(&self) -> &String {
         &self.name
     }
     fn description(&self) -> &String {
         &self.description
     }
-    fn parameters(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {
-                "a": {
-                    "type": "number",
-                    "description": "First number",
-                },
-                "b": {
-                    "type": "number",
-                    "description": "Second number",
-                },
-            },
-            "required": ["a", "b"],
-        })
-    }
-    async fn invoke(&self, args: String) -> anyhow::Result<Value> {
-        let args: Value = serde_json::from_str(&args)?;
-        let result = args["a"].as_number().unwrap().as_f64().unwrap()
-            + args["b"].as_number().unwrap().as_f64().unwrap();
-        Ok(json!({"result":result}))
+    async fn call(
+        &self,
+        args: CalculatorArgs,
+        _context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        Ok(evaluate(&args.expression, &args.variables).into())
     }
 }
+typed_tool!(CalculatorTool);
 
 #[derive(Clone)]
-pub struct TimeTool {
+pub struct ConvertUnitsTool {
     name: String,
     description: String,
 }
 
-impl TimeTool {
+impl ConvertUnitsTool {
     pub fn new() -> Self {
         Self {
-            name: "time".to_string(),
-            description: "Get current time".to_string(),
+            name: "convert_units".to_string(),
+            description: "Convert a quantity between units of length, area, volume, mass or \
+                temperature, eg tbsp to ml, °F to °C, sq ft to m2. Volumes are US customary. \
+                Converting between volume and mass, eg cups of flour to grams, needs the ingredient"
+                .to_string(),
         }
     }
 }
 
+#[derive(Deserialize, JsonSchema)]
+pub struct ConvertUnitsArgs {
+    /// Quantity to convert
+    value: f64,
+    /// Unit to convert from, eg "cup", "tbsp", "°F", "sq ft"
+    from: String,
+    /// Unit to convert to, eg "g", "ml", "°C", "m2"
+    to: String,
+    /// Ingredient being measured, only needed between volume and mass, eg "flour"
+    #[serde(default)]
+    ingredient: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Conversion {
+    value: f64,
+    unit: &'static str,
+}
+
 #[async_trait::async_trait]
-impl Tool for TimeTool {
+impl TypedTool for ConvertUnitsTool {
+    type Args = ConvertUnitsArgs;
+    type Output = ToolOutput<Conversion>;
     fn name(&self) -> &String {
         &self.name
     }
     fn description(&self) -> &String {
         &self.description
     }
-    fn parameters(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {
-            },
-            "required": [],
-        })
-    }
-    async fn invoke(&self, _args: String) -> anyhow::Result<Value> {
-        let result = chrono::Local::now();
-        Ok(json!({"result":result}))
+    async fn call(
+        &self,
+        args: ConvertUnitsArgs,
+        _context: &ToolContext,
+    ) -> anyhow::Result<Self::Output> {
+        Ok(
+            convert(args.value, &args.from, &args.to, args.ingredient.as_deref())
+                .map(|(value, unit)| Conversion {
+                    //enough precision for a kitchen scale without float noise
+                    value: (value * 1000.0).round() / 1000.0,
+                    unit: unit.symbol,
+                })
+                .into(),
+        )
+    }
+}
+typed_tool!(ConvertUnitsTool);
+
+#[derive(Clone)]
+pub struct DateTool {
+    name: String,
+    description: String,
+}
+
+impl DateTool {
+    pub fn new() -> Self {
+        Self {
+            name: "date".to_string(),
+            description: "Work with dates and times in the user's timezone: get the current \
+                time, add or subtract a duration, count the days between two dates or find the \
+                weekday of a date. Dates can be natural language, eg 'next friday at 5pm', \
+                'March 3rd', 'in 40 minutes', 'thanksgiving', or ISO 8601"
+                .to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DateOperation {
+    #[default]
+    Now,
+    Add,
+    Subtract,
+    Difference,
+    Weekday,
+    Parse,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DateUnit {
+    Minutes,
+    Hours,
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+impl From<DateUnit> for DurationUnit {
+    fn from(unit: DateUnit) -> Self {
+        match unit {
+            DateUnit::Minutes => DurationUnit::Minutes,
+            DateUnit::Hours => DurationUnit::Hours,
+            DateUnit::Days => DurationUnit::Days,
+            DateUnit::Weeks => DurationUnit::Weeks,
+            DateUnit::Months => DurationUnit::Months,
+            DateUnit::Years => DurationUnit::Years,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DateArgs {
+    /// What to do, defaults to now
+    #[serde(default)]
+    operation: DateOperation,
+    /// Date to start from, defaults to now
+    #[serde(default)]
+    date: Option<String>,
+    /// Second date for difference, defaults to now
+    #[serde(default)]
+    end_date: Option<String>,
+    /// Amount to add or subtract
+    #[serde(default)]
+    amount: Option<i64>,
+    /// Unit of the amount to add or subtract
+    #[serde(default)]
+    unit: Option<DateUnit>,
+}
+
+#[derive(Serialize)]
+pub struct DateInfo {
+    datetime: String,
+    date: String,
+    time: String,
+    weekday: String,
+    timezone: String,
+}
+
+impl From<chrono::DateTime<Tz>> for DateInfo {
+    fn from(datetime: chrono::DateTime<Tz>) -> Self {
+        Self {
+            datetime: datetime.to_rfc3339(),
+            date: datetime.format("%Y-%m-%d").to_string(),
+            time: datetime.format("%H:%M").to_string(),
+            weekday: datetime.format("%A").to_string(),
+            timezone: datetime.timezone().name().to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DateDifference {
+    //calendar days, so "until friday" counts midnights rather than 24 hour blocks
+    days: i64,
+    hours: i64,
+    minutes: i64,
+    from: DateInfo,
+    to: DateInfo,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum DateValue {
+    Date(DateInfo),
+    Difference(Box<DateDifference>),
+}
+
+fn date_operation(args: DateArgs, now: chrono::DateTime<Tz>) -> anyhow::Result<DateValue> {
+    let date = match &args.date {
+        Some(date) => parse_datetime(date, now)?,
+        None => now,
+    };
+    Ok(match args.operation {
+        DateOperation::Now | DateOperation::Weekday | DateOperation::Parse => {
+            DateValue::Date(date.into())
+        }
+        DateOperation::Add | DateOperation::Subtract => {
+            let (amount, unit) = args
+                .amount
+                .zip(args.unit)
+                .ok_or_else(|| anyhow::anyhow!("amount and unit are required"))?;
+            let amount = match args.operation {
+                DateOperation::Subtract => -amount,
+                _ => amount,
+            };
+            DateValue::Date(add_duration(date, amount, unit.into())?.into())
+        }
+        DateOperation::Difference => {
+            let end = match &args.end_date {
+                Some(end) => parse_datetime(end, now)?,
+                None => now,
+            };
+            let elapsed = end - date;
+            DateValue::Difference(Box::new(DateDifference {
+                days: (end.date_naive() - date.date_naive()).num_days(),
+                hours: elapsed.num_hours(),
+                minutes: elapsed.num_minutes(),
+                from: date.into(),
+                to: end.into(),
+            }))
+        }
+    })
+}
+
+#[async_trait::async_trait]
+impl TypedTool for DateTool {
+    type Args = DateArgs;
+    type Output = ToolOutput<DateValue>;
+    fn name(&self) -> &String {
+        &self.name
+    }
+    fn description(&self) -> &String {
+        &self.description
+    }
+    async fn call(&self, args: DateArgs, context: &ToolContext) -> anyhow::Result<Self::Output> {
+        let now = chrono::Utc::now().with_timezone(&context.timezone);
+        Ok(date_operation(args, now).into())
+    }
+}
+typed_tool!(DateTool);
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CalculatorTool, ConvertUnitsTool, DateArgs, DateTool, DateValue, Tool, ToolContext,
+        date_operation, glob_match, is_allowed,
+    };
+    use crate::psql_tools::ToolPermission;
+    use crate::psql_users::Role;
+    use chrono::TimeZone;
+    use tokio_util::sync::CancellationToken;
+    use uuid::Uuid;
+
+    fn context(user_id: Uuid, roles: Vec<Role>) -> ToolContext {
+        ToolContext {
+            user_id,
+            roles,
+            session_id: Uuid::new_v4(),
+            bot_name: "helper".to_string(),
+            span_id: Uuid::new_v4().to_string(),
+            cancellation_token: CancellationToken::new(),
+            timezone: chrono_tz::UTC,
+        }
+    }
+
+    fn permission(tool: &str, role: Option<Role>, username_id: Option<Uuid>) -> ToolPermission {
+        ToolPermission {
+            id: Uuid::new_v4(),
+            tool: tool.to_string(),
+            role,
+            username_id,
+        }
+    }
+
+    #[test]
+    fn it_matches_globs() {
+        assert!(glob_match("read_*", "read_wiki_contents"));
+        assert!(glob_match("*wiki*", "read_wiki_contents"));
+        assert!(glob_match("ask_?uestion", "ask_question"));
+        assert!(glob_match("*", "anything"));
+        assert!(!glob_match("read_*", "ask_question"));
+        assert!(!glob_match("read", "read_wiki_contents"));
+    }
+
+    #[test]
+    fn it_allows_tools_without_permissions() {
+        let permissions = vec![permission("ha_*", Some(Role::Admin), None)];
+        let context = context(Uuid::new_v4(), vec![Role::Helper]);
+        assert!(is_allowed(&permissions, "calculator", &context, false));
+    }
+
+    #[test]
+    fn it_allows_tools_by_role() {
+        let permissions = vec![permission("ha_*", Some(Role::Admin), None)];
+        let admin = context(Uuid::new_v4(), vec![Role::Admin, Role::Helper]);
+        let helper = context(Uuid::new_v4(), vec![Role::Helper]);
+        assert!(is_allowed(&permissions, "ha_unlock", &admin, false));
+        assert!(!is_allowed(&permissions, "ha_unlock", &helper, false));
+    }
+
+    #[test]
+    fn it_allows_tools_by_user() {
+        let user_id = Uuid::new_v4();
+        let permissions = vec![permission("ask_question", None, Some(user_id))];
+        assert!(is_allowed(
+            &permissions,
+            "ask_question",
+            &context(user_id, vec![Role::Helper]),
+            false
+        ));
+        assert!(!is_allowed(
+            &permissions,
+            "ask_question",
+            &context(Uuid::new_v4(), vec![Role::Helper]),
+            false
+        ));
+    }
+
+    #[test]
+    fn it_only_allows_granted_tools_if_asked() {
+        let permissions = vec![permission("calculator", Some(Role::Tutor), None)];
+        let tutor = context(Uuid::new_v4(), vec![Role::Tutor]);
+        let helper = context(Uuid::new_v4(), vec![Role::Helper]);
+        assert!(is_allowed(&permissions, "calculator", &tutor, true));
+        assert!(!is_allowed(&permissions, "calculator", &helper, true));
+        assert!(!is_allowed(&permissions, "date", &tutor, true));
+        assert!(is_allowed(&permissions, "date", &tutor, false));
+    }
+
+    #[test]
+    fn it_derives_parameters_from_args() {
+        let parameters = CalculatorTool::new().parameters();
+        assert_eq!(parameters["type"], "object");
+        assert_eq!(parameters["properties"]["expression"]["type"], "string");
+        assert_eq!(parameters["required"], serde_json::json!(["expression"]));
+        assert!(parameters.get("$schema").is_none());
+    }
+
+    #[tokio::test]
+    async fn it_invokes_typed_tool() {
+        let result = CalculatorTool::new()
+            .invoke(
+                r#"{"expression": "a + 2", "variables": {"a": 1.5}}"#.to_string(),
+                &context(Uuid::new_v4(), vec![]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, serde_json::json!({"result": 3.5}));
+    }
+
+    #[tokio::test]
+    async fn it_returns_err_on_invalid_args() {
+        let result = CalculatorTool::new()
+            .invoke(
+                r#"{"expression": 1}"#.to_string(),
+                &context(Uuid::new_v4(), vec![]),
+            )
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn it_returns_evaluation_errors_to_the_model() {
+        let result = CalculatorTool::new()
+            .invoke(
+                r#"{"expression": "1 / 0"}"#.to_string(),
+                &context(Uuid::new_v4(), vec![]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, serde_json::json!({"error": "Division by zero"}));
+    }
+
+    #[tokio::test]
+    async fn it_converts_units() {
+        let tool = ConvertUnitsTool::new();
+        let context = context(Uuid::new_v4(), vec![]);
+        let result = tool
+            .invoke(
+                r#"{"value": 2, "from": "cups", "to": "g", "ingredient": "sugar"}"#.to_string(),
+                &context,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!({"result": {"value": 399.834, "unit": "g"}})
+        );
+        let result = tool
+            .invoke(
+                r#"{"value": 2, "from": "cups", "to": "inches"}"#.to_string(),
+                &context,
+            )
+            .await
+            .unwrap();
+        assert!(result["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn it_accepts_empty_args_for_tools_without_arguments() {
+        let result = DateTool::new()
+            .invoke("".to_string(), &context(Uuid::new_v4(), vec![]))
+            .await
+            .unwrap();
+        assert_eq!(result["result"]["timezone"], "UTC");
+    }
+
+    #[test]
+    fn it_counts_days_between_dates() {
+        let now = chrono_tz::America::Chicago
+            .with_ymd_and_hms(2026, 10, 19, 22, 0, 0)
+            .unwrap();
+        let args: DateArgs = serde_json::from_value(serde_json::json!({
+            "operation": "difference",
+            "date": "today",
+            "end_date": "thanksgiving"
+        }))
+        .unwrap();
+        match date_operation(args, now).unwrap() {
+            DateValue::Difference(difference) => assert_eq!(difference.days, 38),
+            DateValue::Date(_) => panic!("expected a difference"),
+        }
+    }
+
+    #[test]
+    fn it_subtracts_durations() {
+        let now = chrono_tz::Europe::Berlin
+            .with_ymd_and_hms(2026, 3, 31, 9, 0, 0)
+            .unwrap();
+        let args: DateArgs = serde_json::from_value(serde_json::json!({
+            "operation": "subtract",
+            "amount": 1,
+            "unit": "months"
+        }))
+        .unwrap();
+        match date_operation(args, now).unwrap() {
+            DateValue::Date(date) => assert_eq!(date.date, "2026-02-28"),
+            DateValue::Difference(_) => panic!("expected a date"),
+        }
     }
 }
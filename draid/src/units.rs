@@ -0,0 +1,433 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dimension {
+    Length,
+    Area,
+    Volume,
+    Mass,
+    Temperature,
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dimension::Length => write!(f, "length"),
+            Dimension::Area => write!(f, "area"),
+            Dimension::Volume => write!(f, "volume"),
+            Dimension::Mass => write!(f, "mass"),
+            Dimension::Temperature => write!(f, "temperature"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UnitError {
+    UnknownUnit(String),
+    UnknownIngredient(String),
+    MissingIngredient {
+        from: Dimension,
+        to: Dimension,
+    },
+    IncompatibleDimensions {
+        from: String,
+        from_dimension: Dimension,
+        to: String,
+        to_dimension: Dimension,
+    },
+}
+
+impl std::error::Error for UnitError {}
+
+impl fmt::Display for UnitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnitError::UnknownUnit(unit) => write!(f, "Unknown unit '{}'", unit),
+            UnitError::UnknownIngredient(ingredient) => write!(
+                f,
+                "No density known for '{}', convert using the same dimension instead",
+                ingredient
+            ),
+            UnitError::MissingIngredient { from, to } => write!(
+                f,
+                "Converting {} to {} needs an ingredient to look up its density",
+                from, to
+            ),
+            UnitError::IncompatibleDimensions {
+                from,
+                from_dimension,
+                to,
+                to_dimension,
+            } => write!(
+                f,
+                "Can't convert {} ({}) to {} ({})",
+                from, from_dimension, to, to_dimension
+            ),
+        }
+    }
+}
+
+pub struct Unit {
+    pub symbol: &'static str,
+    aliases: &'static [&'static str],
+    pub dimension: Dimension,
+    //multiplier to the base unit of the dimension: m, m², ml, g. kelvin for temperature
+    factor: f64,
+    //added after scaling, only used for temperature
+    offset: f64,
+}
+
+const fn unit(
+    symbol: &'static str,
+    aliases: &'static [&'static str],
+    dimension: Dimension,
+    factor: f64,
+) -> Unit {
+    Unit {
+        symbol,
+        aliases,
+        dimension,
+        factor,
+        offset: 0.0,
+    }
+}
+
+//volumes are US customary, which is what recipes online overwhelmingly use
+static UNITS: &[Unit] = &[
+    unit(
+        "mm",
+        &["millimeter", "millimeters", "millimetre", "millimetres"],
+        Dimension::Length,
+        0.001,
+    ),
+    unit(
+        "cm",
+        &["centimeter", "centimeters", "centimetre", "centimetres"],
+        Dimension::Length,
+        0.01,
+    ),
+    unit(
+        "m",
+        &["meter", "meters", "metre", "metres"],
+        Dimension::Length,
+        1.0,
+    ),
+    unit(
+        "km",
+        &["kilometer", "kilometers", "kilometre", "kilometres"],
+        Dimension::Length,
+        1000.0,
+    ),
+    unit("in", &["inch", "inches", "\""], Dimension::Length, 0.0254),
+    unit("ft", &["foot", "feet", "'"], Dimension::Length, 0.3048),
+    unit("yd", &["yard", "yards"], Dimension::Length, 0.9144),
+    unit("mi", &["mile", "miles"], Dimension::Length, 1609.344),
+    unit(
+        "cm2",
+        &["cm²", "sq cm", "square centimeter", "square centimeters"],
+        Dimension::Area,
+        0.0001,
+    ),
+    unit(
+        "m2",
+        &[
+            "m²",
+            "sq m",
+            "square meter",
+            "square meters",
+            "square metre",
+            "square metres",
+        ],
+        Dimension::Area,
+        1.0,
+    ),
+    unit(
+        "in2",
+        &["in²", "sq in", "square inch", "square inches"],
+        Dimension::Area,
+        0.00064516,
+    ),
+    unit(
+        "ft2",
+        &["ft²", "sq ft", "sqft", "square foot", "square feet"],
+        Dimension::Area,
+        0.09290304,
+    ),
+    unit(
+        "yd2",
+        &["yd²", "sq yd", "square yard", "square yards"],
+        Dimension::Area,
+        0.83612736,
+    ),
+    unit("acre", &["acres"], Dimension::Area, 4046.8564224),
+    unit("ha", &["hectare", "hectares"], Dimension::Area, 10000.0),
+    unit(
+        "ml",
+        &[
+            "milliliter",
+            "milliliters",
+            "millilitre",
+            "millilitres",
+            "cc",
+        ],
+        Dimension::Volume,
+        1.0,
+    ),
+    unit(
+        "cl",
+        &["centiliter", "centiliters", "centilitre", "centilitres"],
+        Dimension::Volume,
+        10.0,
+    ),
+    unit(
+        "dl",
+        &["deciliter", "deciliters", "decilitre", "decilitres"],
+        Dimension::Volume,
+        100.0,
+    ),
+    unit(
+        "l",
+        &["liter", "liters", "litre", "litres"],
+        Dimension::Volume,
+        1000.0,
+    ),
+    unit(
+        "tsp",
+        &["teaspoon", "teaspoons", "t"],
+        Dimension::Volume,
+        4.92892159375,
+    ),
+    unit(
+        "tbsp",
+        &["tablespoon", "tablespoons", "tbs", "T"],
+        Dimension::Volume,
+        14.78676478125,
+    ),
+    unit(
+        "fl oz",
+        &["floz", "fluid ounce", "fluid ounces"],
+        Dimension::Volume,
+        29.5735295625,
+    ),
+    //recipes write "2 c" for cups, so only a capital C is Celsius
+    unit("cup", &["cups", "c"], Dimension::Volume, 236.5882365),
+    unit("pt", &["pint", "pints"], Dimension::Volume, 473.176473),
+    unit("qt", &["quart", "quarts"], Dimension::Volume, 946.352946),
+    unit(
+        "gal",
+        &["gallon", "gallons"],
+        Dimension::Volume,
+        3785.411784,
+    ),
+    unit("mg", &["milligram", "milligrams"], Dimension::Mass, 0.001),
+    unit("g", &["gram", "grams", "gr"], Dimension::Mass, 1.0),
+    unit(
+        "kg",
+        &["kilogram", "kilograms", "kilo", "kilos"],
+        Dimension::Mass,
+        1000.0,
+    ),
+    unit("oz", &["ounce", "ounces"], Dimension::Mass, 28.349523125),
+    unit(
+        "lb",
+        &["lbs", "pound", "pounds"],
+        Dimension::Mass,
+        453.59237,
+    ),
+    unit("st", &["stone", "stones"], Dimension::Mass, 6350.29318),
+    Unit {
+        symbol: "°C",
+        aliases: &["C", "celsius", "degc", "degrees celsius"],
+        dimension: Dimension::Temperature,
+        factor: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        symbol: "°F",
+        aliases: &["f", "fahrenheit", "degf", "degrees fahrenheit"],
+        dimension: Dimension::Temperature,
+        factor: 5.0 / 9.0,
+        offset: 273.15 - 32.0 * 5.0 / 9.0,
+    },
+    Unit {
+        symbol: "K",
+        aliases: &["kelvin"],
+        dimension: Dimension::Temperature,
+        factor: 1.0,
+        offset: 0.0,
+    },
+];
+
+//grams per millilitre, used to convert between volume and mass
+static DENSITIES: &[(&str, &[&str], f64)] = &[
+    ("water", &[], 1.0),
+    ("milk", &[], 1.03),
+    ("cream", &["heavy cream", "whipping cream"], 1.01),
+    ("butter", &[], 0.911),
+    ("oil", &["vegetable oil", "olive oil", "canola oil"], 0.92),
+    ("honey", &[], 1.42),
+    ("maple syrup", &["syrup"], 1.32),
+    (
+        "flour",
+        &["all-purpose flour", "all purpose flour", "plain flour"],
+        0.529,
+    ),
+    ("bread flour", &[], 0.55),
+    ("whole wheat flour", &["wholemeal flour"], 0.507),
+    (
+        "sugar",
+        &["granulated sugar", "white sugar", "caster sugar"],
+        0.845,
+    ),
+    ("brown sugar", &["packed brown sugar"], 0.93),
+    (
+        "powdered sugar",
+        &["icing sugar", "confectioners sugar"],
+        0.507,
+    ),
+    ("salt", &["table salt"], 1.217),
+    ("kosher salt", &[], 0.609),
+    ("baking soda", &["bicarbonate of soda"], 0.92),
+    ("baking powder", &[], 0.811),
+    ("cocoa powder", &["cocoa"], 0.355),
+    ("rice", &["white rice"], 0.845),
+    ("rolled oats", &["oats"], 0.355),
+    ("yogurt", &["yoghurt"], 1.03),
+    ("peanut butter", &[], 1.09),
+    ("chocolate chips", &[], 0.718),
+    ("grated parmesan", &["parmesan"], 0.423),
+];
+
+fn normalize(name: &str) -> String {
+    name.trim()
+        .trim_end_matches('.')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn find_unit(name: &str) -> Result<&'static Unit, UnitError> {
+    let normalized = normalize(name);
+    //exact matches first so that "T" (tablespoon) and "t" (teaspoon) stay distinct
+    UNITS
+        .iter()
+        .find(|unit| unit.symbol == normalized || unit.aliases.contains(&normalized.as_str()))
+        .or_else(|| {
+            let lower = normalized.to_lowercase();
+            UNITS.iter().find(|unit| {
+                unit.symbol.to_lowercase() == lower
+                    || unit
+                        .aliases
+                        .iter()
+                        .any(|alias| alias.to_lowercase() == lower)
+            })
+        })
+        .ok_or(UnitError::UnknownUnit(name.to_string()))
+}
+
+pub fn find_density(ingredient: &str) -> Result<f64, UnitError> {
+    let normalized = normalize(ingredient).to_lowercase();
+    DENSITIES
+        .iter()
+        .find(|(name, aliases, _)| *name == normalized || aliases.contains(&normalized.as_str()))
+        .map(|(_, _, density)| *density)
+        .ok_or(UnitError::UnknownIngredient(ingredient.to_string()))
+}
+
+//converts between units of the same dimension, or between volume and mass
+//when an ingredient with a known density is given
+pub fn convert(
+    value: f64,
+    from: &str,
+    to: &str,
+    ingredient: Option<&str>,
+) -> Result<(f64, &'static Unit), UnitError> {
+    let from_unit = find_unit(from)?;
+    let to_unit = find_unit(to)?;
+    let base = value * from_unit.factor + from_unit.offset;
+    let base = match (from_unit.dimension, to_unit.dimension) {
+        (a, b) if a == b => base,
+        (Dimension::Volume, Dimension::Mass) | (Dimension::Mass, Dimension::Volume) => {
+            let ingredient = ingredient.ok_or(UnitError::MissingIngredient {
+                from: from_unit.dimension,
+                to: to_unit.dimension,
+            })?;
+            let density = find_density(ingredient)?;
+            if from_unit.dimension == Dimension::Volume {
+                base * density
+            } else {
+                base / density
+            }
+        }
+        (from_dimension, to_dimension) => {
+            return Err(UnitError::IncompatibleDimensions {
+                from: from.to_string(),
+                from_dimension,
+                to: to.to_string(),
+                to_dimension,
+            });
+        }
+    };
+    Ok(((base - to_unit.offset) / to_unit.factor, to_unit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UnitError, convert};
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!(
+            (value - expected).abs() < 0.01,
+            "expected {} but got {}",
+            expected,
+            value
+        );
+    }
+
+    #[test]
+    fn it_converts_within_a_dimension() {
+        assert_close(convert(2.0, "tbsp", "ml", None).unwrap().0, 29.57);
+        assert_close(convert(12.0, "inches", "cm", None).unwrap().0, 30.48);
+        assert_close(convert(400.0, "sq ft", "m2", None).unwrap().0, 37.16);
+        assert_close(convert(3.0, "tsp", "T", None).unwrap().0, 1.0);
+    }
+
+    #[test]
+    fn it_converts_temperatures() {
+        assert_close(convert(350.0, "F", "C", None).unwrap().0, 176.67);
+        assert_close(
+            convert(100.0, "celsius", "fahrenheit", None).unwrap().0,
+            212.0,
+        );
+        assert_close(convert(0.0, "°C", "K", None).unwrap().0, 273.15);
+        assert_close(convert(2.0, "c", "ml", None).unwrap().0, 473.18);
+    }
+
+    #[test]
+    fn it_converts_volume_to_mass_by_density() {
+        let (grams, unit) = convert(1.0, "cup", "g", Some("Flour")).unwrap();
+        assert_close(grams, 125.16);
+        assert_eq!(unit.symbol, "g");
+        assert_close(convert(100.0, "g", "ml", Some("water")).unwrap().0, 100.0);
+    }
+
+    #[test]
+    fn it_rejects_incompatible_dimensions() {
+        assert!(matches!(
+            convert(1.0, "cup", "cm", None),
+            Err(UnitError::IncompatibleDimensions { .. })
+        ));
+        assert!(matches!(
+            convert(1.0, "cup", "g", None),
+            Err(UnitError::MissingIngredient { .. })
+        ));
+        assert!(matches!(
+            convert(1.0, "cup", "g", Some("gravel")),
+            Err(UnitError::UnknownIngredient(_))
+        ));
+        assert!(matches!(
+            convert(1.0, "smidgen", "g", None),
+            Err(UnitError::UnknownUnit(_))
+        ));
+    }
+}
@@ -0,0 +1,557 @@
+//a Wyoming protocol server, so Home Assistant voice satellites can use the helper
+//bot. speech to text happens before draid, the transcript arrives as a handle
+//(transcript) or intent (recognize) request and the answer goes back as text
+use crate::auth::UserIdentification;
+use crate::chat::chat_turn;
+use crate::config::{Wyoming, WyomingSatellite};
+use crate::llm::DiscardTokens;
+use crate::mcp_tools::McpServers;
+use crate::models::Bots;
+use crate::psql_users::{Role, create_session, get_user};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::info;
+use uuid::Uuid;
+
+//version of the protocol the events follow
+const WYOMING_VERSION: &str = "1.5.2";
+
+//a satellite's conversation starts over after this long without a request
+const SESSION_MINUTES: i64 = 10;
+
+//longest header line, data or payload of an event, in bytes
+const MAX_EVENT_BYTES: usize = 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WyomingEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    #[serde(default)]
+    pub data: Map<String, Value>,
+}
+
+impl WyomingEvent {
+    pub fn new(event_type: &str, data: Value) -> Self {
+        Self {
+            event_type: event_type.to_string(),
+            data: match data {
+                Value::Object(data) => data,
+                _ => Map::new(),
+            },
+        }
+    }
+
+    fn text(&self) -> &str {
+        self.data
+            .get("text")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    }
+}
+
+//the json line each event starts with, newer clients send the data after it
+#[derive(Deserialize)]
+struct Header {
+    #[serde(rename = "type")]
+    event_type: String,
+    #[serde(default)]
+    data: Option<Map<String, Value>>,
+    #[serde(default)]
+    data_length: Option<usize>,
+    #[serde(default)]
+    payload_length: Option<usize>,
+}
+
+fn checked_length(length: Option<usize>, part: &str) -> anyhow::Result<Option<usize>> {
+    match length.filter(|length| *length > 0) {
+        Some(length) if length > MAX_EVENT_BYTES => Err(anyhow::anyhow!(
+            "The event's {} of {} bytes is over the limit of {}",
+            part,
+            length,
+            MAX_EVENT_BYTES
+        )),
+        length => Ok(length),
+    }
+}
+
+//None once the client has closed the connection
+pub async fn read_event<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> anyhow::Result<Option<WyomingEvent>> {
+    let mut line = String::new();
+    let read = (&mut *reader)
+        .take(MAX_EVENT_BYTES as u64 + 1)
+        .read_line(&mut line)
+        .await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if read > MAX_EVENT_BYTES {
+        return Err(anyhow::anyhow!(
+            "The event's header is over the limit of {} bytes",
+            MAX_EVENT_BYTES
+        ));
+    }
+    let header: Header = serde_json::from_str(&line)?;
+    let mut data = header.data.unwrap_or_default();
+    if let Some(length) = checked_length(header.data_length, "data")? {
+        let mut bytes = vec![0; length];
+        reader.read_exact(&mut bytes).await?;
+        let extra: Map<String, Value> = serde_json::from_slice(&bytes)?;
+        data.extend(extra);
+    }
+    //audio isn't used, the satellite's speech has already been transcribed
+    if let Some(length) = checked_length(header.payload_length, "payload")? {
+        let mut bytes = vec![0; length];
+        reader.read_exact(&mut bytes).await?;
+    }
+    Ok(Some(WyomingEvent {
+        event_type: header.event_type,
+        data,
+    }))
+}
+
+pub async fn write_event<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    event: &WyomingEvent,
+) -> anyhow::Result<()> {
+    let mut line = serde_json::to_string(&json!({
+        "type": event.event_type,
+        "data": event.data,
+        "version": WYOMING_VERSION,
+    }))?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+//the satellite named in the event's context, else the one connecting from the
+//address. a satellite with an address is only itself when it connects from
+//there, so another device can't name it. unlisted satellites use the default
+//user, named by their address
+fn find_satellite(
+    event: &WyomingEvent,
+    peer: IpAddr,
+    config: &Wyoming,
+) -> Option<WyomingSatellite> {
+    let context = event.data.get("context").and_then(Value::as_object);
+    let id = context.and_then(|context| {
+        ["satellite_id", "device_id"]
+            .iter()
+            .find_map(|key| context.get(*key).and_then(Value::as_str))
+    });
+    let peer = peer.to_string();
+    id.and_then(|id| {
+        config.satellites.iter().find(|satellite| {
+            satellite.name == id
+                && satellite
+                    .address
+                    .as_ref()
+                    .is_none_or(|address| *address == peer)
+        })
+    })
+    .or_else(|| {
+        config
+            .satellites
+            .iter()
+            .find(|satellite| satellite.address.as_ref() == Some(&peer))
+    })
+    .cloned()
+    .or_else(|| {
+        config
+            .default_username
+            .as_ref()
+            .map(|username| WyomingSatellite {
+                name: id.map_or(peer.clone(), String::from),
+                address: Some(peer.clone()),
+                username: username.clone(),
+            })
+    })
+}
+
+fn info(config: &Wyoming) -> WyomingEvent {
+    let attribution = json!({ "name": "draid", "url": "" });
+    let program = |description: &str| {
+        json!({
+            "name": "draid",
+            "attribution": attribution,
+            "installed": true,
+            "description": description,
+            "version": env!("CARGO_PKG_VERSION"),
+            "models": [{
+                "name": "helper",
+                "attribution": attribution,
+                "installed": true,
+                "description": "The household helper",
+                "version": env!("CARGO_PKG_VERSION"),
+                "languages": config.languages,
+            }],
+        })
+    };
+    let mut handle = program("Answers requests with the household helper");
+    handle["supports_handled_streaming"] = json!(false);
+    WyomingEvent::new(
+        "info",
+        json!({
+            "handle": [handle],
+            "intent": [program("Carries out requests with the household helper")],
+        }),
+    )
+}
+
+pub struct WyomingServer {
+    config: Wyoming,
+    pool: PgPool,
+    bots: Arc<Bots>,
+    servers: Arc<McpServers>,
+    //satellite name to its session and when it was last used
+    sessions: Mutex<HashMap<String, (Uuid, DateTime<Utc>)>>,
+}
+
+impl WyomingServer {
+    pub fn new(
+        config: Wyoming,
+        pool: &PgPool,
+        bots: &Arc<Bots>,
+        servers: &Arc<McpServers>,
+    ) -> Self {
+        Self {
+            config,
+            pool: pool.clone(),
+            bots: bots.clone(),
+            servers: servers.clone(),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    //a recent session of the satellite carries on, so follow up questions work
+    async fn session(&self, satellite: &str, user: &UserIdentification) -> anyhow::Result<Uuid> {
+        let now = Utc::now();
+        let recent = self
+            .sessions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(satellite)
+            .filter(|(_, used_at)| now - *used_at < Duration::minutes(SESSION_MINUTES))
+            .map(|(session_id, _)| *session_id);
+        let session_id = match recent {
+            Some(session_id) => session_id,
+            None => create_session(&user.id, &self.pool).await?.id,
+        };
+        self.sessions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(satellite.to_string(), (session_id, now));
+        Ok(session_id)
+    }
+
+    //the helper's answer, or why the request can't be handled
+    async fn answer(&self, event: &WyomingEvent, peer: IpAddr) -> Result<String, String> {
+        let Some(satellite) = find_satellite(event, peer, &self.config) else {
+            return Err("This satellite isn't set up to use the helper".to_string());
+        };
+        let user = get_user(&satellite.username, &self.pool)
+            .await
+            .map(|user| UserIdentification::from(&user))
+            .map_err(|e| {
+                info!(
+                    tool_use = false,
+                    endpoint = "wyoming",
+                    message = format!("Failed to get user {}: {}", satellite.username, e)
+                );
+                "This satellite's user doesn't exist".to_string()
+            })?;
+        if !user.roles.contains(&Role::Helper) {
+            return Err(format!("{} can't use the helper", user.username));
+        }
+        let session_id = self.session(&satellite.name, &user).await.map_err(|e| {
+            info!(
+                tool_use = false,
+                endpoint = "wyoming",
+                message = format!("Failed to create a session for {}: {}", satellite.name, e)
+            );
+            "Something went wrong, please try again".to_string()
+        })?;
+        info!(
+            tool_use = false,
+            endpoint = "wyoming",
+            message = format!(
+                "request from satellite {} as {}",
+                satellite.name, user.username
+            )
+        );
+        chat_turn(
+            &self.bots.helper_bot,
+            &mut DiscardTokens,
+            event.text(),
+            session_id,
+            &user,
+            &self.pool,
+            &self.servers,
+        )
+        .await
+        .map(|full_message| full_message.message.trim().to_string())
+        .map_err(|e| {
+            info!(
+                tool_use = false,
+                endpoint = "wyoming",
+                message = format!("Failed to answer satellite {}: {}", satellite.name, e)
+            );
+            "Something went wrong, please try again".to_string()
+        })
+    }
+
+    //None for events that don't need a reply, eg audio chunks
+    pub async fn respond(&self, event: &WyomingEvent, peer: IpAddr) -> Option<WyomingEvent> {
+        match event.event_type.as_str() {
+            "describe" => Some(info(&self.config)),
+            "ping" => Some(WyomingEvent::new(
+                "pong",
+                json!({ "text": event.data.get("text") }),
+            )),
+            "transcript" => Some(match self.answer(event, peer).await {
+                Ok(text) => WyomingEvent::new("handled", json!({ "text": text })),
+                Err(text) => WyomingEvent::new("not-handled", json!({ "text": text })),
+            }),
+            //the helper carries out the request itself, the answer is what to say
+            "recognize" => Some(match self.answer(event, peer).await {
+                Ok(text) => WyomingEvent::new(
+                    "intent",
+                    json!({ "name": "draid", "entities": [], "text": text }),
+                ),
+                Err(text) => WyomingEvent::new("not-recognized", json!({ "text": text })),
+            }),
+            _ => None,
+        }
+    }
+
+    async fn handle_connection(&self, stream: TcpStream, peer: SocketAddr) -> anyhow::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        while let Some(event) = read_event(&mut reader).await? {
+            if let Some(response) = self.respond(&event, peer.ip()).await {
+                write_event(&mut writer, &response).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub async fn serve(listener: TcpListener, server: Arc<WyomingServer>) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                info!(
+                    tool_use = false,
+                    endpoint = "wyoming",
+                    message = format!("Failed to accept Wyoming connection: {}", e)
+                );
+                continue;
+            }
+        };
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = server.handle_connection(stream, peer).await {
+                info!(
+                    tool_use = false,
+                    endpoint = "wyoming",
+                    message = format!("Wyoming connection from {} failed: {}", peer, e)
+                );
+            }
+        });
+    }
+}
+
+pub fn run_wyoming_server(
+    config: Wyoming,
+    pool: &PgPool,
+    bots: &Arc<Bots>,
+    servers: &Arc<McpServers>,
+) -> JoinHandle<()> {
+    let address = format!("{}:{}", config.address, config.port);
+    let server = Arc::new(WyomingServer::new(config, pool, bots, servers));
+    tokio::spawn(async move {
+        match TcpListener::bind(&address).await {
+            Ok(listener) => serve(listener, server).await,
+            Err(e) => info!(
+                tool_use = false,
+                endpoint = "wyoming",
+                message = format!("Failed to start the Wyoming server on {}: {}", address, e)
+            ),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WyomingEvent, WyomingServer, find_satellite, read_event, serve, write_event};
+    use crate::config::Wyoming;
+    use crate::llm::ModelParameters;
+    use crate::mcp_tools::McpServers;
+    use crate::models::get_bots;
+    use crate::tools::{ToolPermissions, ToolSet};
+    use serde_json::json;
+    use sqlx::postgres::PgPoolOptions;
+    use std::net::IpAddr;
+    use std::sync::Arc;
+    use tokio::io::BufReader;
+    use tokio::net::{TcpListener, TcpStream};
+
+    fn config() -> Wyoming {
+        serde_json::from_value(json!({
+            "satellites": [
+                {"name": "kitchen", "address": "192.168.1.20", "username": "household"},
+                {"name": "bedroom", "username": "sam"}
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn peer(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_reads_events_with_data_and_payload() {
+        let bytes = b"{\"type\":\"transcript\",\"data\":{\"language\":\"en\"},\"data_length\":25,\"payload_length\":4}\n{\"text\":\"turn on lights\"}\x00\x01\x02\x03{\"type\":\"describe\"}\n";
+        let mut reader = BufReader::new(&bytes[..]);
+        let event = read_event(&mut reader).await.unwrap().unwrap();
+        assert_eq!(
+            event,
+            WyomingEvent::new(
+                "transcript",
+                json!({"language": "en", "text": "turn on lights"})
+            )
+        );
+        let event = read_event(&mut reader).await.unwrap().unwrap();
+        assert_eq!(event, WyomingEvent::new("describe", json!({})));
+        assert!(read_event(&mut reader).await.unwrap().is_none());
+        let oversized = b"{\"type\":\"audio-chunk\",\"payload_length\":1073741824}\n";
+        assert!(
+            read_event(&mut BufReader::new(&oversized[..]))
+                .await
+                .is_err()
+        );
+        let long_line = vec![b' '; 2 * 1024 * 1024];
+        assert!(
+            read_event(&mut BufReader::new(&long_line[..]))
+                .await
+                .is_err()
+        );
+
+        let mut written = vec![];
+        let handled = WyomingEvent::new("handled", json!({"text": "Done"}));
+        write_event(&mut written, &handled).await.unwrap();
+        let mut reader = BufReader::new(&written[..]);
+        assert_eq!(read_event(&mut reader).await.unwrap().unwrap(), handled);
+    }
+
+    #[test]
+    fn it_maps_satellites_to_users() {
+        let config = config();
+        let from_context = WyomingEvent::new(
+            "transcript",
+            json!({"text": "hi", "context": {"satellite_id": "bedroom"}}),
+        );
+        let plain = WyomingEvent::new("transcript", json!({"text": "hi"}));
+        assert_eq!(
+            find_satellite(&from_context, peer("192.168.1.20"), &config)
+                .unwrap()
+                .username,
+            "sam"
+        );
+        assert_eq!(
+            find_satellite(&plain, peer("192.168.1.20"), &config)
+                .unwrap()
+                .name,
+            "kitchen"
+        );
+        assert!(find_satellite(&plain, peer("192.168.1.99"), &config).is_none());
+        //the kitchen satellite only connects from its own address
+        let impostor = WyomingEvent::new(
+            "transcript",
+            json!({"text": "hi", "context": {"satellite_id": "kitchen"}}),
+        );
+        assert!(find_satellite(&impostor, peer("192.168.1.99"), &config).is_none());
+        let config = Wyoming {
+            default_username: Some("guest".to_string()),
+            ..config
+        };
+        let satellite = find_satellite(&plain, peer("192.168.1.99"), &config).unwrap();
+        assert_eq!(satellite.name, "192.168.1.99");
+        assert_eq!(satellite.username, "guest");
+    }
+
+    #[tokio::test]
+    async fn it_serves_satellites() {
+        //nothing is asked of the database or the model here
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/draid")
+            .unwrap();
+        let bots = Arc::new(get_bots(
+            "model".to_string(),
+            "http://localhost:1".to_string(),
+            ToolSet::new(vec![], ToolPermissions::default()),
+            ModelParameters {
+                temperature: None,
+                presence_penalty: None,
+                top_p: None,
+            },
+        ));
+        let server = Arc::new(WyomingServer::new(
+            config(),
+            &pool,
+            &bots,
+            &Arc::new(McpServers::new()),
+        ));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, server));
+
+        let stream = TcpStream::connect(address).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        write_event(&mut writer, &WyomingEvent::new("describe", json!({})))
+            .await
+            .unwrap();
+        let info = read_event(&mut reader).await.unwrap().unwrap();
+        assert_eq!(info.event_type, "info");
+        assert_eq!(
+            info.data["handle"][0]["models"][0]["languages"],
+            json!(["en"])
+        );
+        assert_eq!(info.data["intent"][0]["name"], "draid");
+        //audio is skipped without a reply
+        write_event(&mut writer, &WyomingEvent::new("audio-stop", json!({})))
+            .await
+            .unwrap();
+        write_event(
+            &mut writer,
+            &WyomingEvent::new("transcript", json!({"text": "what's for dinner?"})),
+        )
+        .await
+        .unwrap();
+        let response = read_event(&mut reader).await.unwrap().unwrap();
+        assert_eq!(response.event_type, "not-handled");
+        write_event(
+            &mut writer,
+            &WyomingEvent::new("recognize", json!({"text": "what's for dinner?"})),
+        )
+        .await
+        .unwrap();
+        let response = read_event(&mut reader).await.unwrap().unwrap();
+        assert_eq!(response.event_type, "not-recognized");
+    }
+}
@@ -182,6 +182,8 @@ export async function invokeAgent(
     `/ws/${selectedAgent}?${new URLSearchParams({
       session_id: sessionId,
       token: jwt,
+      //a prompt per connection rather than the envelope protocol
+      v: "1",
     })} `,
     window.location.href,
   );